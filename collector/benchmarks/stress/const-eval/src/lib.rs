@@ -0,0 +1,50 @@
+// Constants whose evaluation copies large arrays around in the const
+// evaluator, and a long chain of associated constants, each computed from the
+// one before it: stresses const evaluation.
+
+const ROW: [u64; 1024] = [0x9e37_79b9_7f4a_7c15; 1024];
+const GRID: [[u64; 1024]; 256] = [ROW; 256];
+
+// Every use of `GRID` copies all 2 MiB of it.
+macro_rules! diagonals {
+    ($($i:expr)*) => {
+        pub static DIAGONALS: [u64; 64] = [
+            $( GRID[$i][$i * 16] ^ GRID[255 - $i][1023 - $i * 16], )*
+        ];
+    };
+}
+
+diagonals!(
+    0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31
+    32 33 34 35 36 37 38 39 40 41 42 43 44 45 46 47 48 49 50 51 52 53 54 55 56 57 58 59 60 61
+    62 63
+);
+
+pub trait Value {
+    const VALUE: u64;
+}
+
+pub struct Seed;
+pub struct Step<T>(T);
+
+impl Value for Seed {
+    const VALUE: u64 = 0x2545_f491_4f6c_dd1d;
+}
+
+// One round of xorshift64.
+impl<T: Value> Value for Step<T> {
+    const VALUE: u64 = (T::VALUE ^ (T::VALUE << 13))
+        ^ ((T::VALUE ^ (T::VALUE << 13)) >> 7)
+        ^ (((T::VALUE ^ (T::VALUE << 13)) ^ ((T::VALUE ^ (T::VALUE << 13)) >> 7)) << 17);
+}
+
+type S4<T> = Step<Step<Step<Step<T>>>>;
+type S16<T> = S4<S4<S4<S4<T>>>>;
+type S64<T> = S16<S16<S16<S16<T>>>>;
+
+pub const VALUES: [u64; 4] = [
+    <S16<Seed> as Value>::VALUE,
+    <S64<Seed> as Value>::VALUE,
+    <S64<S16<Seed>> as Value>::VALUE,
+    <S64<S64<Seed>> as Value>::VALUE,
+];
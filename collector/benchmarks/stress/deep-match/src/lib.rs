@@ -0,0 +1,71 @@
+// Deeply nested matches, and a wide one over a large enum: stresses match
+// checking (exhaustiveness and usefulness) and the lowering of matches to MIR.
+
+// Every level of nesting has four arms, each of which nests the next level, so
+// the innermost matches are repeated 4^6 times.
+macro_rules! nest {
+    ($x:expr;) => { $x };
+    ($x:expr; $level:tt $($rest:tt)*) => {{
+        let y = $x;
+        match y & 3 {
+            0 => nest!((y >> 2) ^ $level; $($rest)*),
+            1 => nest!((y >> 2).wrapping_add($level); $($rest)*),
+            2 => nest!((y >> 2).rotate_left($level); $($rest)*),
+            _ => nest!(y >> 2; $($rest)*),
+        }
+    }};
+}
+
+pub fn deep(x: u64) -> u64 {
+    nest!(x; 1 2 3 4 5 6)
+}
+
+#[derive(Clone, Copy)]
+pub enum Op {
+    Nop, Push, Pop, Dup, Swap, Over, Rot, Add, Sub, Mul, Div, Rem, Neg, And, Or, Xor, Not,
+    Shl, Shr, Eq, Ne, Lt, Le, Gt, Ge, Jump, JumpIf, JumpUnless, Call, Return, Load, Store,
+}
+
+// A match over pairs of `Op`s with overlapping arms, so that every arm has to
+// be checked against all the ones before it.
+pub fn fuse(a: Op, b: Op, c: Option<Option<Op>>) -> u32 {
+    use Op::*;
+    match (a, b, c) {
+        (Nop, Nop, None) => 0,
+        (Nop, _, _) | (_, Nop, _) => 1,
+        (Push, Pop, _) | (Dup, Pop, _) => 2,
+        (Push, Push, Some(None)) => 3,
+        (Push, Dup, Some(Some(Add))) | (Push, Dup, Some(Some(Mul))) => 4,
+        (Swap, Swap, _) | (Rot, Rot, Some(Some(Rot))) => 5,
+        (Add, Neg, _) | (Sub, Neg, _) => 6,
+        (Neg, Neg, _) | (Not, Not, _) => 7,
+        (Eq, Not, _) => 8,
+        (Ne, Not, _) => 9,
+        (Lt, Not, _) => 10,
+        (Le, Not, _) => 11,
+        (Gt, Not, _) => 12,
+        (Ge, Not, _) => 13,
+        (Eq, JumpIf, Some(Some(Jump))) | (Ne, JumpUnless, Some(Some(Jump))) => 14,
+        (Eq, JumpIf, _) | (Ne, JumpUnless, _) => 15,
+        (Lt, JumpIf, _) | (Ge, JumpUnless, _) => 16,
+        (Le, JumpIf, _) | (Gt, JumpUnless, _) => 17,
+        (Gt, JumpIf, _) | (Le, JumpUnless, _) => 18,
+        (Ge, JumpIf, _) | (Lt, JumpUnless, _) => 19,
+        (Call, Return, None) => 20,
+        (Call, Return, Some(_)) => 21,
+        (Load, Store, Some(Some(Load))) | (Store, Load, Some(Some(Store))) => 22,
+        (Load, Store, _) | (Store, Load, _) => 23,
+        (Load, Pop, _) | (Dup, Store, _) => 24,
+        (Shl, Shr, _) | (Shr, Shl, _) => 25,
+        (And, And, _) | (Or, Or, _) | (Xor, Xor, Some(None)) => 26,
+        (Add, Add, Some(Some(Add))) | (Mul, Mul, Some(Some(Mul))) => 27,
+        (Add, Sub, _) | (Sub, Add, _) | (Mul, Div, _) | (Div, Mul, _) => 28,
+        (Rem, _, Some(Some(Rem))) | (_, Rem, Some(Some(Rem))) => 29,
+        (Jump, _, _) | (Return, _, _) => 30,
+        (_, Jump, Some(Some(_))) | (_, Return, Some(Some(_))) => 31,
+        (Over, Over, _) | (Over, Swap, _) | (Swap, Over, _) => 32,
+        (_, _, None) => 33,
+        (_, _, Some(None)) => 34,
+        (_, _, Some(Some(_))) => 35,
+    }
+}
@@ -0,0 +1,14 @@
+// A macro that doubles the tokens it's given at every step of its recursion,
+// expanding to an array of 2^16 * 4 elements: stresses macro expansion and
+// the parser with a token stream of half a million tokens.
+
+macro_rules! double {
+    (; $($t:tt)*) => { [$($t)*] };
+    (x $($rest:ident)*; $($t:tt)*) => { double!($($rest)*; $($t)* $($t)*) };
+}
+
+pub static DATA: &[u8] = &double!(x x x x x x x x x x x x x x x x; 1, 2, 3, 4,);
+
+pub fn checksum() -> u64 {
+    DATA.iter().fold(0, |sum, &byte| sum.wrapping_mul(31).wrapping_add(u64::from(byte)))
+}
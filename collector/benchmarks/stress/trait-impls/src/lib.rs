@@ -0,0 +1,55 @@
+// Hundreds of impls of the same traits for distinct types: stresses coherence
+// checking, which compares the impls with each other, and trait selection,
+// which has to pick among them at every use.
+
+pub trait Size {
+    const SIZE: usize;
+
+    fn size(&self) -> usize {
+        Self::SIZE
+    }
+}
+
+pub trait Describe {
+    fn describe(&self) -> usize;
+}
+
+pub struct Wrap<T>(pub T);
+
+// Invokes `$m!(H T U)` for every number `HTU` from 0 to 399.
+macro_rules! for_each_number {
+    ($m:ident) => {
+        for_each_number!(@hundreds $m 0 1 2 3);
+    };
+    (@hundreds $m:ident $($h:tt)*) => {
+        $( for_each_number!(@tens $m $h 0 1 2 3 4 5 6 7 8 9); )*
+    };
+    (@tens $m:ident $h:tt $($t:tt)*) => {
+        $( for_each_number!(@ones $m $h $t 0 1 2 3 4 5 6 7 8 9); )*
+    };
+    (@ones $m:ident $h:tt $t:tt $($u:tt)*) => {
+        $( $m!($h $t $u); )*
+    };
+}
+
+macro_rules! impls {
+    ($h:tt $t:tt $u:tt) => {
+        impl Size for [u8; $h * 100 + $t * 10 + $u] {
+            const SIZE: usize = $h * 100 + $t * 10 + $u;
+        }
+
+        impl Size for Wrap<[u16; $h * 100 + $t * 10 + $u]> {
+            const SIZE: usize = 2 * ($h * 100 + $t * 10 + $u);
+        }
+
+        impl Describe for [u32; $h * 100 + $t * 10 + $u] {
+            fn describe(&self) -> usize {
+                [0u8; $h * 100 + $t * 10 + $u].size()
+                    + Wrap([0u16; $h * 100 + $t * 10 + $u]).size()
+                    + self.len()
+            }
+        }
+    };
+}
+
+for_each_number!(impls);
@@ -0,0 +1,17 @@
+// Exercises function calls and integer arithmetic.
+
+use std::env;
+
+fn fib(n: u64) -> u64 {
+    if n < 2 {
+        n
+    } else {
+        fib(n - 1) + fib(n - 2)
+    }
+}
+
+fn main() {
+    // Take the input from the environment so it can't be constant-folded.
+    let n = env::args().nth(1).and_then(|s| s.parse().ok()).unwrap_or(32);
+    eprintln!("{}", fib(n));
+}
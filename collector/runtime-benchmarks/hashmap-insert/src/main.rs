@@ -0,0 +1,17 @@
+// Exercises hashing, HashMap insertion/lookup and allocation.
+
+use std::collections::HashMap;
+use std::env;
+
+fn main() {
+    let len = env::args().nth(1).and_then(|s| s.parse().ok()).unwrap_or(1_000_000u64);
+
+    let mut map = HashMap::new();
+    for i in 0..len {
+        *map.entry(i % 100_003).or_insert(0u64) += i;
+        map.insert(i.wrapping_mul(2654435761), i.to_string().len() as u64);
+    }
+
+    let hits = (0..len).filter(|i| map.contains_key(i)).count();
+    eprintln!("{} {}", map.len(), hits);
+}
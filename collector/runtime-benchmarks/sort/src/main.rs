@@ -0,0 +1,23 @@
+// Exercises slice sorting, bounds checks and iterator chains.
+
+use std::env;
+
+fn main() {
+    let len = env::args().nth(1).and_then(|s| s.parse().ok()).unwrap_or(2_000_000);
+
+    // A simple LCG gives us reproducible pseudo-random input.
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut v: Vec<u64> = (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            state >> 16
+        })
+        .collect();
+
+    v.sort();
+    let mut w = v.iter().map(|x| x ^ 0xff).collect::<Vec<_>>();
+    w.sort_unstable();
+
+    let checksum = v.iter().zip(w.iter()).fold(0u64, |acc, (a, b)| acc.wrapping_add(a ^ b));
+    eprintln!("{}", checksum);
+}
@@ -1,3 +1,56 @@
+pub mod next_commit {
+    use {ArtifactMetadata, Shard};
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Response {
+        /// The next commit to benchmark, if any.
+        pub commit: Option<String>,
+        /// The benchmarks to run first, in this order. The others are run
+        /// after them, in the usual order.
+        #[serde(default)]
+        pub priority: Vec<String>,
+        /// The part of the benchmarks the collector that asked should run,
+        /// if the commit is split across several collectors.
+        #[serde(default)]
+        pub shard: Option<Shard>,
+        /// What the commit is, e.g. which PR a try build is of, which the
+        /// collector can't tell from the sha alone.
+        #[serde(default)]
+        pub metadata: Option<ArtifactMetadata>,
+    }
+}
+
+pub mod upload {
+    use {ArtifactData, CommitData};
+
+    /// The results uploaded by a collector.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub enum Data {
+        Commit(CommitData),
+        Artifact(ArtifactData),
+    }
+
+    /// One chunk of the JSON of a `Data`. The site limits the size of
+    /// requests, so the results are uploaded in chunks, which the site puts
+    /// back together once it has all of them.
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Request {
+        /// Identifies the upload. A chunk sent again with the same id
+        /// replaces the one sent before, so failed requests can be retried.
+        pub id: String,
+        pub index: usize,
+        pub count: usize,
+        pub data: String,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Response {
+        /// The chunks the site hasn't received yet. Empty once the upload is
+        /// complete and the results have been stored.
+        pub missing: Vec<usize>,
+    }
+}
+
 pub mod collected {
     use Commit;
 
@@ -1,11 +1,32 @@
 use std::env;
-use std::process::Command;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
 use std::time::{Duration, Instant};
 
 fn main() {
     let mut args = env::args_os().skip(1).collect::<Vec<_>>();
+
+    // rustc runs rustc-fake as its linker when link time is measured, see
+    // `wrap_linker`.
+    if let Some(linker) = env::var_os("RUSTC_PERF_REAL_LINKER") {
+        link(&linker, &args);
+    }
+
     let rustc = env::var_os("RUSTC_REAL").unwrap();
 
+    // The collector can't pass arguments to the rustcs of the members of a
+    // workspace benchmark only, as `cargo rustc` does for single crates, so
+    // they're added here to those of the rustcs cargo runs for its primary
+    // packages, i.e. the workspace's members.
+    if env::var_os("CARGO_PRIMARY_PACKAGE").is_some() {
+        if let Ok(member_args) = env::var("RUSTC_PERF_MEMBER_ARGS") {
+            args.extend(member_args.split('\x1f').filter(|a| !a.is_empty()).map(OsString::from));
+        }
+    }
+
     if let Some(pos) = args.iter().position(|arg| arg == "--wrap-rustc-with") {
         // Strip out the flag and its argument, and run rustc under the wrapper
         // program named by the argument.
@@ -16,24 +37,99 @@ fn main() {
         raise_priority();
 
         match wrapper {
+            #[cfg(target_os = "linux")]
             "perf-stat" => {
+                let (results_fd, mut results) = results();
+
                 let mut cmd = Command::new("perf");
                 let has_perf = cmd.output().is_ok();
                 assert!(has_perf);
                 cmd.arg("stat")
                     .arg("-x;")
                     .arg("-e")
-                    .arg("instructions:u,cycles:u,task-clock,cpu-clock,faults")
+                    .arg(perf_events())
                     .arg("--log-fd")
-                    .arg("1")
+                    .arg(results_fd.to_string())
                     .arg(&rustc)
                     .args(&args);
+                let link_times = wrap_linker(&mut cmd, &args);
 
+                let energy = read_energy();
                 let start = Instant::now();
-                assert!(cmd.status().expect("failed to spawn").success());
+                let child = cmd.spawn().expect("failed to spawn");
+                let (success, usage) = wait_with_usage(child);
+                let dur = start.elapsed();
+                assert!(success);
+                print_energy(&mut *results, &energy, &read_energy());
+                if let Some(usage) = usage {
+                    print_usage(&mut *results, &usage);
+                }
+                print_time(&mut *results, dur);
+                print_link_time(&mut *results, &link_times);
+                print_artifact_sizes(&mut *results, &args);
+                print_incremental_cache_size(&mut *results, &args);
+            }
+
+            // There's no `perf` on macOS (or the BSDs), so only what the OS
+            // keeps track of for rustc is reported.
+            #[cfg(all(unix, not(target_os = "linux")))]
+            "perf-stat" => {
+                let (_, mut results) = results();
+
+                let mut cmd = Command::new(&rustc);
+                cmd.args(&args);
+                let link_times = wrap_linker(&mut cmd, &args);
+
+                let start = Instant::now();
+                let child = cmd.spawn().expect("failed to spawn");
+                let (success, usage) = wait_with_usage(child);
                 let dur = start.elapsed();
-                print_memory();
-                print_time(dur);
+                assert!(success);
+                if let Some(usage) = usage {
+                    let faults = usage.faults.map_or(0, |(minor, major)| minor + major);
+                    print_usage(&mut *results, &usage);
+                    print_cpu_time_and_faults(&mut *results, usage.cpu_time, faults as u64);
+                }
+                print_time(&mut *results, dur);
+                print_link_time(&mut *results, &link_times);
+                print_artifact_sizes(&mut *results, &args);
+                print_incremental_cache_size(&mut *results, &args);
+            }
+
+            // There's no `perf` on Windows either, so only what the OS keeps
+            // track of is reported, through a job object that rustc (and the
+            // linker it starts) ends up in.
+            #[cfg(windows)]
+            "perf-stat" => {
+                let (_, mut results) = results();
+
+                let job = Job::enter();
+                let mut cmd = Command::new(&rustc);
+                cmd.args(&args);
+                let link_times = wrap_linker(&mut cmd, &args);
+
+                // `Instant` is based on `QueryPerformanceCounter` here.
+                let start = Instant::now();
+                let status = cmd.status().expect("failed to spawn");
+                let dur = start.elapsed();
+                assert!(status.success());
+                let (usage, faults) = job.usage();
+                print_usage(&mut *results, &usage);
+                print_cpu_time_and_faults(&mut *results, usage.cpu_time, faults);
+                print_time(&mut *results, dur);
+                print_link_time(&mut *results, &link_times);
+                print_artifact_sizes(&mut *results, &args);
+                print_incremental_cache_size(&mut *results, &args);
+            }
+
+            "self-profile" => {
+                let mut cmd = Command::new(&rustc);
+                if !args.iter().any(|arg| arg == "-Zself-profile") {
+                    cmd.arg("-Zself-profile");
+                }
+                cmd.args(&args);
+
+                assert!(cmd.status().expect("failed to spawn").success());
             }
 
             "time-passes" => {
@@ -58,6 +154,20 @@ fn main() {
                 assert!(cmd.status().expect("failed to spawn").success());
             }
 
+            "flamegraph" => {
+                let mut cmd = Command::new("perf");
+                let has_perf = cmd.output().is_ok();
+                assert!(has_perf);
+                cmd.arg("record")
+                    .arg("-g")
+                    .arg("--output=perf")
+                    .arg("--freq=999")
+                    .arg(&rustc)
+                    .args(&args);
+
+                assert!(cmd.status().expect("failed to spawn").success());
+            }
+
             "cachegrind" => {
                 let mut cmd = Command::new("valgrind");
                 let has_valgrind = cmd.output().is_ok();
@@ -122,6 +232,63 @@ fn main() {
                 assert!(cmd.status().expect("failed to spawn").success());
             }
 
+            "llvm-trace" => {
+                let mut cmd = Command::new(&rustc);
+                cmd.arg("-Zllvm-time-trace")
+                    .args(&args);
+
+                assert!(cmd.status().expect("failed to spawn").success());
+            }
+
+            "strace" => {
+                let has_strace = Command::new("strace").arg("-V").output().is_ok();
+                assert!(has_strace);
+
+                // Count the syscalls of rustc and all of its threads (and
+                // children, e.g. the linker), rather than tracing each one.
+                // The rustcs of the members of a workspace all run in the same
+                // directory, so each writes a summary of its own, which the
+                // collector merges.
+                let crate_name = arg_value(&args, "--crate-name")
+                    .unwrap_or_else(|| String::from("rustc"));
+                let mut cmd = Command::new("strace");
+                cmd.arg("-f")
+                    .arg("-c")
+                    .arg("-o")
+                    .arg(format!("strace-summary-{}-{}", crate_name, process::id()))
+                    .arg(&rustc)
+                    .args(&args);
+
+                assert!(cmd.status().expect("failed to spawn").success());
+            }
+
+            "xctrace" => {
+                let has_xctrace = Command::new("xcrun").arg("xctrace").arg("version").output()
+                    .map_or(false, |output| output.status.success());
+                assert!(has_xctrace);
+
+                // Instruments' Time Profiler samples the call stacks of all
+                // threads. xctrace doesn't pass its environment on to the
+                // process it launches, so it's passed explicitly (rustc needs
+                // the `CARGO_*` variables cargo sets for `env!`).
+                let mut cmd = Command::new("xcrun");
+                cmd.arg("xctrace")
+                    .arg("record")
+                    .arg("--template")
+                    .arg("Time Profiler")
+                    .arg("--output")
+                    .arg("xctrace.trace");
+                for (key, value) in env::vars() {
+                    cmd.arg("--env").arg(format!("{}={}", key, value));
+                }
+                cmd.arg("--launch")
+                    .arg("--")
+                    .arg(&rustc)
+                    .args(&args);
+
+                assert!(cmd.status().expect("failed to spawn").success());
+            }
+
             "eprintln" => {
                 let mut cmd = Command::new(&rustc);
                 cmd.args(&args);
@@ -148,6 +315,24 @@ fn exec(cmd: &mut Command) -> ! {
     panic!("failed to exec: {}", error);
 }
 
+/// The events `perf stat` counts: the ones in `RUSTC_PERF_EVENTS`, if set,
+/// or else the default for the architecture.
+#[cfg(target_os = "linux")]
+fn perf_events() -> String {
+    if let Ok(events) = env::var("RUSTC_PERF_EVENTS") {
+        return events;
+    }
+    if cfg!(target_arch = "aarch64") {
+        // The generic `cache-misses` counts L1 data cache refills on ARM,
+        // which has little to do with what it counts on x86 (last-level
+        // cache misses), so it isn't recorded under the same name.
+        "instructions:u,cycles:u,branch-misses:u,task-clock,cpu-clock,faults".to_string()
+    } else {
+        "instructions:u,cycles:u,branch-misses:u,cache-misses:u,task-clock,cpu-clock,faults"
+            .to_string()
+    }
+}
+
 #[cfg(unix)]
 fn raise_priority() {
     extern crate libc;
@@ -164,38 +349,606 @@ fn raise_priority() {
     }
 }
 
+/// The resources used by a process, as far as the OS keeps track of them.
+struct Usage {
+    /// Peak resident set size, in kilobytes.
+    max_rss: i64,
+    /// User and system CPU time, which `perf` reports where it's used.
+    #[cfg_attr(target_os = "linux", allow(dead_code))]
+    cpu_time: Duration,
+    /// Minor and major page faults, if the OS tells them apart.
+    faults: Option<(i64, i64)>,
+    /// Voluntary and involuntary context switches, if known.
+    context_switches: Option<(i64, i64)>,
+    /// Bytes read from and written to storage (rather than the page cache),
+    /// if known.
+    io: Option<(u64, u64)>,
+}
+
+/// Waits for `child` to exit, returning whether it succeeded and the
+/// resources it used. `wait4` is used rather than
+/// `getrusage(RUSAGE_CHILDREN)` so that only the given child (and the
+/// processes it waited for, i.e. rustc when the child is `perf`) is accounted
+/// for.
 #[cfg(unix)]
-fn print_memory() {
+fn wait_with_usage(child: ::std::process::Child) -> (bool, Option<Usage>) {
     extern crate libc;
 
     use std::mem;
 
+    let pid = child.id() as libc::pid_t;
+    // The I/O of the child can only be read from `/proc` (and its peak memory
+    // from Mach, on macOS) after it exited, but before it's reaped, so it's
+    // first waited for without reaping it.
+    let exited = wait_without_reaping(pid).is_some();
+    let io = if exited { read_io(pid) } else { None };
+    let footprint = if exited { peak_footprint(pid) } else { None };
+
     unsafe {
-        let mut usage = mem::zeroed();
-        let r = libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage);
-        if r == 0 {
-            // for explanation of all the semicolons, see `print_time` below
-            println!("{};;max-rss;3;100.00", usage.ru_maxrss);
+        let mut status = 0;
+        let mut usage: libc::rusage = mem::zeroed();
+        let r = libc::wait4(pid, &mut status, 0, &mut usage);
+        if r == -1 {
+            panic!("failed to wait for child: {}", ::std::io::Error::last_os_error());
         }
+        let success = libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0;
+        let timeval = |tv: libc::timeval| {
+            Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000)
+        };
+        // `ru_maxrss` is in bytes on macOS, where it's only the fallback.
+        let max_rss = footprint.unwrap_or_else(|| if cfg!(target_os = "macos") {
+            usage.ru_maxrss as i64 / 1024
+        } else {
+            usage.ru_maxrss as i64
+        });
+        (success, Some(Usage {
+            max_rss,
+            cpu_time: timeval(usage.ru_utime) + timeval(usage.ru_stime),
+            faults: Some((usage.ru_minflt as i64, usage.ru_majflt as i64)),
+            context_switches: Some((usage.ru_nvcsw as i64, usage.ru_nivcsw as i64)),
+            io,
+        }))
     }
 }
 
-fn print_time(dur: Duration) {
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn wait_without_reaping(pid: i32) -> Option<()> {
+    extern crate libc;
+
+    use std::mem;
+
+    unsafe {
+        let mut info: libc::siginfo_t = mem::zeroed();
+        let r = libc::waitid(libc::P_PID, pid as libc::id_t, &mut info,
+                             libc::WEXITED | libc::WNOWAIT);
+        if r == -1 { None } else { Some(()) }
+    }
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
+fn wait_without_reaping(_pid: i32) -> Option<()> {
+    None
+}
+
+/// The peak physical footprint, in kilobytes, of the process `pid`, which
+/// exited but wasn't reaped yet. That's the peak Mach keeps track of for the
+/// task, which `task_info` reports as `ledger_phys_footprint_peak`. It's read
+/// with `proc_pid_rusage`, as calling `task_info` on rustc would take its task
+/// port, which `task_for_pid` only hands out with privileges.
+#[cfg(target_os = "macos")]
+fn peak_footprint(pid: i32) -> Option<i64> {
+    extern crate libc;
+
+    use std::mem;
+
+    /// `rusage_info_v4`, of which only the 64-bit fields are spelled out,
+    /// in order.
+    #[repr(C)]
+    struct RusageInfoV4 {
+        uuid: [u8; 16],
+        fields: [u64; 35],
+    }
+    const RUSAGE_INFO_V4: libc::c_int = 4;
+    /// The index of `ri_lifetime_max_phys_footprint` in `fields`.
+    const LIFETIME_MAX_PHYS_FOOTPRINT: usize = 28;
+
+    extern "C" {
+        fn proc_pid_rusage(pid: libc::c_int, flavor: libc::c_int, buffer: *mut RusageInfoV4)
+                           -> libc::c_int;
+    }
+
+    unsafe {
+        let mut info: RusageInfoV4 = mem::zeroed();
+        if proc_pid_rusage(pid, RUSAGE_INFO_V4, &mut info) != 0 {
+            return None;
+        }
+        Some((info.fields[LIFETIME_MAX_PHYS_FOOTPRINT] / 1024) as i64)
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn peak_footprint(_pid: i32) -> Option<i64> {
+    None
+}
+
+/// The bytes the process `pid`, including the children it reaped, read from
+/// and wrote to storage.
+#[cfg(unix)]
+fn read_io(pid: i32) -> Option<(u64, u64)> {
+    let io = fs::read_to_string(format!("/proc/{}/io", pid)).ok()?;
+    let field = |name: &str| {
+        io.lines()
+            .find(|line| line.starts_with(name))
+            .and_then(|line| line[name.len()..].trim().parse::<u64>().ok())
+    };
+    Some((field("read_bytes:")?, field("write_bytes:")?))
+}
+
+/// Where to report measurements, both as a file descriptor (for `perf`) and
+/// as a `Write`: the pipe the collector passed in `RUSTC_PERF_RESULTS_FD`, so
+/// that they don't get mixed up with the output of cargo and rustc, or else
+/// stdout.
+#[cfg(unix)]
+fn results() -> (i32, Box<dyn Write>) {
+    use std::fs::File;
+    use std::os::unix::io::FromRawFd;
+
+    match env::var("RUSTC_PERF_RESULTS_FD").ok().and_then(|fd| fd.parse().ok()) {
+        Some(fd) => (fd, Box::new(unsafe { File::from_raw_fd(fd) })),
+        None => (1, Box::new(io::stdout())),
+    }
+}
+
+#[cfg(windows)]
+fn results() -> (i32, Box<dyn Write>) {
+    (1, Box::new(io::stdout()))
+}
+
+fn print_usage(out: &mut dyn Write, usage: &Usage) {
+    // for explanation of all the semicolons, see `print_time` below
+    writeln!(out, "{};;max-rss;3;100.00", usage.max_rss).unwrap();
+    // These are mostly useful to tell why a result is noisy, e.g. because
+    // the machine was short on memory or busy with something else.
+    if let Some((minor, major)) = usage.faults {
+        writeln!(out, "{};;minor-faults;0;100.00", minor).unwrap();
+        writeln!(out, "{};;major-faults;0;100.00", major).unwrap();
+    }
+    if let Some((voluntary, involuntary)) = usage.context_switches {
+        writeln!(out, "{};;voluntary-context-switches;0;100.00", voluntary).unwrap();
+        writeln!(out, "{};;involuntary-context-switches;0;100.00", involuntary).unwrap();
+    }
+    if let Some((read, written)) = usage.io {
+        writeln!(out, "{};;read-bytes;0;100.00", read).unwrap();
+        writeln!(out, "{};;written-bytes;0;100.00", written).unwrap();
+    }
+}
+
+fn print_time(out: &mut dyn Write, dur: Duration) {
     // Format output the same as `perf stat` in CSV mode, explained at
     // http://man7.org/linux/man-pages/man1/perf-stat.1.html#CSV_FORMAT
     //
     // tl;dr; it's:
     //
     //      $value ; $unit ; $name ; $runtime ; $pct
-    println!(
+    writeln!(
+        out,
         "{}.{:09};;wall-time;4;100.00",
         dur.as_secs(),
         dur.subsec_nanos()
-    );
+    ).unwrap();
+}
+
+/// The energy counters of the CPU packages, in microjoules, with the value
+/// each wraps around at, from RAPL through the powercap interface. Only read
+/// if the collector asks for them with `RUSTC_PERF_ENERGY`; empty if it
+/// doesn't, or they can't be read.
+#[cfg(target_os = "linux")]
+fn read_energy() -> Vec<(u64, u64)> {
+    if env::var_os("RUSTC_PERF_ENERGY").is_none() {
+        return Vec::new();
+    }
+    let mut zones = match fs::read_dir("/sys/class/powercap") {
+        Ok(zones) => zones.filter_map(|zone| zone.ok()).map(|zone| zone.path()).collect::<Vec<_>>(),
+        Err(_) => return Vec::new(),
+    };
+    // Some CPUs expose their packages through MMIO (`intel-rapl-mmio`) as
+    // well as through MSRs (`intel-rapl`), under the same name; only one of
+    // each is counted, preferably the latter.
+    zones.sort_by_key(|zone| (zone.to_string_lossy().contains("-mmio"), zone.clone()));
+    let read = |zone: &Path, file: &str| {
+        fs::read_to_string(zone.join(file)).ok().map(|s| s.trim().to_string())
+    };
+    let mut names = ::std::collections::HashSet::new();
+    zones.iter()
+        // The subzones of a package (its cores, uncore and DRAM) are part of
+        // it, and `psys` covers the whole platform.
+        .filter(|zone| match read(zone, "name") {
+            Some(name) => name.starts_with("package") && names.insert(name),
+            None => false,
+        })
+        .filter_map(|zone| {
+            let energy = read(zone, "energy_uj")?.parse().ok()?;
+            let range = read(zone, "max_energy_range_uj")?.parse().ok()?;
+            Some((energy, range))
+        })
+        .collect()
+}
+
+/// Reports the energy the CPU packages consumed between two readings of
+/// `read_energy` as `energy`, in joules. That's the energy of everything
+/// that ran on the machine meanwhile, not just of rustc.
+#[cfg(target_os = "linux")]
+fn print_energy(out: &mut dyn Write, before: &[(u64, u64)], after: &[(u64, u64)]) {
+    if before.is_empty() || before.len() != after.len() {
+        return;
+    }
+    let microjoules = before.iter().zip(after)
+        .map(|(&(before, range), &(after, _))| {
+            if after >= before { after - before } else { after + range - before }
+        })
+        .sum::<u64>();
+    // for explanation of all the semicolons, see `print_time` above
+    writeln!(out, "{:.6};;energy;0;100.00", microjoules as f64 / 1e6).unwrap();
 }
 
+/// The linker rustc runs for the host unless it's told otherwise with
+/// `-C linker`.
+#[cfg(all(windows, target_env = "msvc"))]
+const DEFAULT_LINKER: &str = "link.exe";
+#[cfg(all(windows, target_env = "gnu"))]
+const DEFAULT_LINKER: &str = "gcc";
+#[cfg(not(windows))]
+const DEFAULT_LINKER: &str = "cc";
+
+/// If the collector asked for the link time (in `RUSTC_PERF_LINK_TIME`),
+/// makes rustc run rustc-fake as its linker, which runs the actual linker and
+/// records how long it took (see `link`). That's the linker configured for the
+/// target, which cargo passes on with `-C linker`, or else the host's default
+/// one; the default linker of other targets isn't known, so their link time
+/// isn't measured. Returns the file the link times are written to.
+fn wrap_linker(cmd: &mut Command, args: &[OsString]) -> Option<PathBuf> {
+    if env::var_os("RUSTC_PERF_LINK_TIME").is_none() {
+        return None;
+    }
+    let linker = match codegen_opt(args, "linker") {
+        Some(linker) => linker,
+        None if arg_value(args, "--target").is_none() => DEFAULT_LINKER.to_string(),
+        None => return None,
+    };
+    let link_times = env::temp_dir().join(format!("rustc-fake-link-times-{}", process::id()));
+    let _ = fs::remove_file(&link_times);
+
+    // If `-C linker` is given more than once, the last one wins.
+    let mut opt = OsString::from("linker=");
+    opt.push(env::current_exe().expect("no path to rustc-fake"));
+    cmd.arg("-C").arg(opt)
+        .env("RUSTC_PERF_REAL_LINKER", linker)
+        .env("RUSTC_PERF_LINK_TIMES", &link_times);
+    Some(link_times)
+}
+
+/// Runs `linker` on behalf of rustc, and appends the time it took to the file
+/// in `RUSTC_PERF_LINK_TIMES`. rustc may run the linker more than once, e.g.
+/// to retry without flags the linker didn't understand.
+fn link(linker: &OsString, args: &[OsString]) -> ! {
+    let start = Instant::now();
+    let status = Command::new(linker).args(args).status().expect("failed to spawn linker");
+    let dur = start.elapsed();
+    if let Some(link_times) = env::var_os("RUSTC_PERF_LINK_TIMES") {
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(link_times)
+            .expect("failed to open the link times");
+        writeln!(file, "{}.{:09}", dur.as_secs(), dur.subsec_nanos()).unwrap();
+    }
+    process::exit(status.code().unwrap_or(1));
+}
+
+/// Reports the time rustc spent waiting for the linker as `link-time`, if it
+/// linked anything (which e.g. check builds and libraries don't).
+fn print_link_time(out: &mut dyn Write, link_times: &Option<PathBuf>) {
+    let link_times = match *link_times {
+        Some(ref link_times) => link_times,
+        None => return,
+    };
+    let times = match fs::read_to_string(link_times) {
+        Ok(times) => times,
+        Err(_) => return,
+    };
+    let _ = fs::remove_file(link_times);
+    let total = times.lines().filter_map(|line| line.trim().parse::<f64>().ok()).sum::<f64>();
+    // for explanation of all the semicolons, see `print_time` above
+    writeln!(out, "{:.9};;link-time;4;100.00", total).unwrap();
+}
+
+/// Returns the value of a rustc flag given either as `--flag value` or as
+/// `--flag=value`.
+fn arg_value(args: &[OsString], flag: &str) -> Option<String> {
+    let prefix = format!("{}=", flag);
+    let mut args = args.iter().filter_map(|arg| arg.to_str());
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next().map(|v| v.to_string());
+        }
+        if arg.starts_with(&prefix) {
+            return Some(arg[prefix.len()..].to_string());
+        }
+    }
+    None
+}
+
+/// Returns the value of a `-C $name=...` codegen option, if present.
+fn codegen_opt(args: &[OsString], name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    let mut args = args.iter().filter_map(|arg| arg.to_str());
+    while let Some(arg) = args.next() {
+        let opt = if arg == "-C" {
+            match args.next() {
+                Some(opt) => opt,
+                None => break,
+            }
+        } else if arg.starts_with("-C") {
+            &arg[2..]
+        } else {
+            continue;
+        };
+        if opt.starts_with(&prefix) {
+            return Some(opt[prefix.len()..].to_string());
+        }
+    }
+    None
+}
+
+/// Finds the artifacts (rlib, rmeta, binary, ...) that rustc just produced
+/// for the crate being compiled, based on the `--crate-name`, `--out-dir` and
+/// `-C extra-filename` arguments cargo passes.
+fn artifacts(args: &[OsString]) -> Vec<PathBuf> {
+    let crate_name = match arg_value(args, "--crate-name") {
+        Some(name) => name,
+        None => return Vec::new(),
+    };
+    let out_dir = arg_value(args, "--out-dir").unwrap_or_else(|| ".".to_string());
+    let extra = codegen_opt(args, "extra-filename").unwrap_or_default();
+    let stem = format!("{}{}", crate_name, extra);
+    let lib_stem = format!("lib{}", stem);
+
+    let entries = match fs::read_dir(&out_dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut artifacts = Vec::new();
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let matches = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => {
+                let file_stem = name.split('.').next().unwrap();
+                (file_stem == stem || file_stem == lib_stem) && !name.ends_with(".d")
+            }
+            None => false,
+        };
+        if matches && path.is_file() {
+            artifacts.push(path);
+        }
+    }
+    artifacts
+}
+
+/// Prints the total size of the produced artifacts, plus the size of the
+/// code and debuginfo sections as reported by `size -A`. rmeta files have no
+/// sections, so they only count towards the total.
+fn print_artifact_sizes(out: &mut dyn Write, args: &[OsString]) {
+    let artifacts = artifacts(args);
+    if artifacts.is_empty() {
+        return;
+    }
+
+    let mut total = 0;
+    let mut text = 0;
+    let mut debug = 0;
+    let mut has_sections = false;
+    for artifact in &artifacts {
+        total += fs::metadata(artifact).map(|m| m.len()).unwrap_or(0);
+        if artifact.extension().map_or(false, |ext| ext == "rmeta") {
+            continue;
+        }
+
+        // `size` complains about archive members that aren't object files
+        // (like the metadata in an rlib), but still prints the others, so
+        // its exit status is ignored.
+        let output = match Command::new("size").arg("-A").arg(artifact).output() {
+            Ok(output) => output,
+            Err(_) => continue,
+        };
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let mut parts = line.split_whitespace();
+            let (section, size) = match (parts.next(), parts.next()) {
+                (Some(section), Some(size)) => (section, size),
+                _ => continue,
+            };
+            let size = match size.parse::<u64>() {
+                Ok(size) => size,
+                Err(_) => continue,
+            };
+            if section == ".text" || section.starts_with(".text.") {
+                text += size;
+                has_sections = true;
+            } else if section.starts_with(".debug") {
+                debug += size;
+                has_sections = true;
+            }
+        }
+    }
+
+    // for explanation of all the semicolons, see `print_time` above
+    writeln!(out, "{};;artifact-size;0;100.00", total).unwrap();
+    if has_sections {
+        writeln!(out, "{};;artifact-size:text;0;100.00", text).unwrap();
+        writeln!(out, "{};;artifact-size:debug;0;100.00", debug).unwrap();
+    }
+}
+
+/// Prints the total size of the crate's incremental compilation cache, i.e.
+/// of its session directories in the `-C incremental` directory, if any.
+fn print_incremental_cache_size(out: &mut dyn Write, args: &[OsString]) {
+    fn dir_size(path: &Path) -> u64 {
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(_) => return 0,
+        };
+        entries.filter_map(|e| e.ok())
+            .map(|entry| match entry.metadata() {
+                Ok(ref metadata) if metadata.is_dir() => dir_size(&entry.path()),
+                Ok(metadata) => metadata.len(),
+                Err(_) => 0,
+            })
+            .sum()
+    }
+
+    let (crate_name, incr_dir) = match (arg_value(args, "--crate-name"),
+                                        codegen_opt(args, "incremental")) {
+        (Some(crate_name), Some(incr_dir)) => (crate_name, incr_dir),
+        _ => return,
+    };
+    // The session directories of a crate are in `$CRATE_NAME-$HASH`.
+    let prefix = format!("{}-", crate_name);
+    let entries = match fs::read_dir(&incr_dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    let size: u64 = entries.filter_map(|e| e.ok())
+        .filter(|entry| entry.file_name().to_str().map_or(false, |n| n.starts_with(&prefix)))
+        .map(|entry| dir_size(&entry.path()))
+        .sum();
+
+    // for explanation of all the semicolons, see `print_time` above
+    writeln!(out, "{};;incr-cache-size;0;100.00", size).unwrap();
+}
+
+#[cfg(windows)]
+fn exec(cmd: &mut Command) -> ! {
+    // Windows can't replace the current process, so rustc-fake waits for
+    // the command and exits the same way.
+    let status = cmd.status().expect("failed to spawn");
+    ::std::process::exit(status.code().unwrap_or(1));
+}
+
+#[cfg(windows)]
+fn raise_priority() {
+    extern crate winapi;
+
+    use winapi::um::processthreadsapi::{GetCurrentProcess, SetPriorityClass};
+    use winapi::um::winbase::HIGH_PRIORITY_CLASS;
+
+    unsafe {
+        // Like on Unix, to reduce jitter in wall time. The real-time class
+        // would starve the rest of the system.
+        SetPriorityClass(GetCurrentProcess(), HIGH_PRIORITY_CLASS);
+    }
+}
+
+/// A job object, which accounts for the resources used by all the processes
+/// in it, including the ones they start.
 #[cfg(windows)]
-fn raise_priority() {}
+struct Job(*mut std::os::raw::c_void);
 
 #[cfg(windows)]
-fn print_memory() {}
+impl Job {
+    /// Creates a job and puts rustc-fake in it, so that everything it starts
+    /// from now on is in it as well. (Putting the child in the job once it's
+    /// started would miss whatever it starts in the meantime.)
+    fn enter() -> Job {
+        extern crate winapi;
+
+        use std::ptr;
+        use winapi::um::jobapi2::{AssignProcessToJobObject, CreateJobObjectW};
+        use winapi::um::processthreadsapi::GetCurrentProcess;
+
+        unsafe {
+            let job = CreateJobObjectW(ptr::null_mut(), ptr::null());
+            if job.is_null() {
+                panic!("failed to create a job object: {}", io::Error::last_os_error());
+            }
+            if AssignProcessToJobObject(job, GetCurrentProcess()) == 0 {
+                panic!("failed to enter a job object: {}", io::Error::last_os_error());
+            }
+            Job(job as *mut _)
+        }
+    }
+
+    /// The resources used by the processes in the job, along with the page
+    /// faults they caused, which `perf` reports elsewhere. The peak memory is
+    /// the peak commit charge of any one process, which is what Windows keeps
+    /// track of per job, rather than its peak working set. The I/O Windows
+    /// accounts for includes reads and writes served by the cache, so it's
+    /// left out.
+    fn usage(&self) -> (Usage, u64) {
+        extern crate winapi;
+
+        use std::mem;
+        use std::ptr;
+        use winapi::um::jobapi2::QueryInformationJobObject;
+        use winapi::um::winnt::{JobObjectBasicAndIoAccountingInformation,
+                                JobObjectExtendedLimitInformation,
+                                JOBOBJECT_BASIC_AND_IO_ACCOUNTING_INFORMATION,
+                                JOBOBJECT_EXTENDED_LIMIT_INFORMATION};
+
+        unsafe {
+            let mut accounting: JOBOBJECT_BASIC_AND_IO_ACCOUNTING_INFORMATION = mem::zeroed();
+            let r = QueryInformationJobObject(
+                self.0 as *mut _,
+                JobObjectBasicAndIoAccountingInformation,
+                &mut accounting as *mut _ as *mut _,
+                mem::size_of_val(&accounting) as u32,
+                ptr::null_mut(),
+            );
+            if r == 0 {
+                panic!("failed to query the job object: {}", io::Error::last_os_error());
+            }
+            let mut limits: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = mem::zeroed();
+            let r = QueryInformationJobObject(
+                self.0 as *mut _,
+                JobObjectExtendedLimitInformation,
+                &mut limits as *mut _ as *mut _,
+                mem::size_of_val(&limits) as u32,
+                ptr::null_mut(),
+            );
+            if r == 0 {
+                panic!("failed to query the job object: {}", io::Error::last_os_error());
+            }
+
+            let basic = &accounting.BasicInfo;
+            // In units of 100ns.
+            let cpu_time = (*basic.TotalUserTime.QuadPart() +
+                            *basic.TotalKernelTime.QuadPart()) as u64;
+            let usage = Usage {
+                max_rss: (limits.PeakProcessMemoryUsed / 1024) as i64,
+                cpu_time: Duration::new(cpu_time / 10_000_000,
+                                        (cpu_time % 10_000_000) as u32 * 100),
+                faults: None,
+                context_switches: None,
+                io: None,
+            };
+            (usage, u64::from(basic.TotalPageFaultCount))
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Job {
+    fn drop(&mut self) {
+        extern crate winapi;
+
+        unsafe {
+            winapi::um::handleapi::CloseHandle(self.0 as *mut _);
+        }
+    }
+}
+
+/// Prints what `perf` would report as `task-clock` and `faults`, where it
+/// isn't used.
+#[cfg(not(target_os = "linux"))]
+fn print_cpu_time_and_faults(out: &mut dyn Write, cpu_time: Duration, faults: u64) {
+    // for explanation of all the semicolons, see `print_time` above;
+    // `task-clock` is in milliseconds, like `perf` reports it.
+    writeln!(out, "{:.6};;task-clock;0;100.00",
+             cpu_time.as_secs() as f64 * 1e3 + f64::from(cpu_time.subsec_nanos()) / 1e6).unwrap();
+    writeln!(out, "{};;faults;0;100.00", faults).unwrap();
+}
@@ -0,0 +1,122 @@
+//! Turns a crate from crates.io into a benchmark: downloads it, vendors its
+//! dependencies so that it can be built offline, and writes a
+//! `perf-config.json` for it.
+
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::Command;
+
+use failure::{Error, ResultExt};
+use reqwest;
+use serde_json;
+use tempfile::TempDir;
+
+use execute::command_output;
+
+const CRATES_URL: &str = "https://static.crates.io/crates";
+
+/// Directories that aren't needed to build a crate's library or binaries,
+/// each removed unless the crate's `Cargo.toml` mentions the corresponding
+/// kind of target explicitly (which would make cargo look for it).
+const OPTIONAL_DIRS: &[(&str, &str)] = &[
+    ("tests", "[[test]]"),
+    ("benches", "[[bench]]"),
+    ("examples", "[[example]]"),
+];
+
+/// CI configuration and the like, which is never needed.
+const NON_ESSENTIAL_FILES: &[&str] = &[
+    ".github", ".travis.yml", "appveyor.yml", ".appveyor.yml", ".gitlab-ci.yml",
+    "azure-pipelines.yml", ".cargo_vcs_info.json", "Cargo.toml.orig",
+];
+
+fn download(name: &str, version: &str, into: &Path) -> Result<(), Error> {
+    let url = format!("{}/{}/{}-{}.crate", CRATES_URL, name, name, version);
+    info!("downloading {}", url);
+    let mut resp = reqwest::get(&url)?;
+    if !resp.status().is_success() {
+        bail!("failed to download {}: {}", url, resp.status());
+    }
+    let archive = into.join("crate.tar.gz");
+    resp.copy_to(&mut File::create(&archive)?)?;
+
+    let mut cmd = Command::new("tar");
+    cmd.current_dir(into).arg("-xzf").arg(&archive);
+    command_output(&mut cmd).with_context(|_| format!("failed to extract {}", url))?;
+    Ok(())
+}
+
+/// Removes the files of the crate at `path` that aren't needed to build it.
+fn strip(path: &Path) -> Result<(), Error> {
+    let cargo_toml = fs::read_to_string(path.join("Cargo.toml"))?;
+    for &(dir, target) in OPTIONAL_DIRS {
+        if !cargo_toml.contains(target) && path.join(dir).is_dir() {
+            fs::remove_dir_all(path.join(dir))?;
+        }
+    }
+    for file in NON_ESSENTIAL_FILES {
+        let file = path.join(file);
+        if file.is_dir() {
+            fs::remove_dir_all(&file)?;
+        } else if file.exists() {
+            fs::remove_file(&file)?;
+        }
+    }
+    Ok(())
+}
+
+/// Generates a `Cargo.lock` if the crate doesn't come with one, and vendors
+/// all dependencies into `vendor`, with a `.cargo/config` that makes cargo use
+/// them instead of crates.io.
+fn vendor(path: &Path) -> Result<(), Error> {
+    if !path.join("Cargo.lock").exists() {
+        let mut cmd = Command::new("cargo");
+        cmd.current_dir(path).arg("generate-lockfile");
+        command_output(&mut cmd).context("failed to generate Cargo.lock")?;
+    }
+
+    // `cargo vendor` prints the configuration needed to use the vendored
+    // sources.
+    let mut cmd = Command::new("cargo");
+    cmd.current_dir(path).arg("vendor").arg("--locked").arg("vendor");
+    let output = command_output(&mut cmd).context("failed to vendor the dependencies")?;
+    fs::create_dir_all(path.join(".cargo"))?;
+    fs::write(path.join(".cargo").join("config"), &output.stdout)?;
+    Ok(())
+}
+
+fn write_config(path: &Path) -> Result<(), Error> {
+    let mut config = json!({ "category": "primary" });
+    // Extra arguments to rustc can only be passed to a single target, so a
+    // crate with a library and binaries has to be limited to the former.
+    if path.join("src").join("lib.rs").exists() {
+        config["cargo_opts"] = json!("--lib");
+    }
+    let mut json = serde_json::to_string_pretty(&config)?;
+    json.push('\n');
+    fs::write(path.join("perf-config.json"), json)?;
+    Ok(())
+}
+
+/// Adds version `version` of the crates.io crate `name` as the benchmark at
+/// `path`, which must not exist yet.
+pub fn add_benchmark(name: &str, version: &str, path: &Path) -> Result<(), Error> {
+    if path.exists() {
+        bail!("{:?} already exists", path);
+    }
+
+    let tmp_dir = TempDir::new()?;
+    download(name, version, tmp_dir.path())?;
+    let extracted = tmp_dir.path().join(format!("{}-{}", name, version));
+    strip(&extracted)?;
+    vendor(&extracted)?;
+    write_config(&extracted)?;
+
+    // The temporary directory may be on a different file system, so the
+    // crate is copied rather than renamed.
+    let mut cmd = Command::new("cp");
+    cmd.arg("-R").arg(&extracted).arg(path);
+    command_output(&mut cmd)
+        .with_context(|_| format!("failed to copy the benchmark to {:?}", path))?;
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! Measures how large the benchmarked compiler itself is on disk.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str;
+
+use failure::{Error, ResultExt};
+
+use execute::command_output;
+
+/// The size, in bytes, of all files in `dir`, recursively.
+pub fn dir_size(dir: &Path) -> Result<u64, Error> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir).with_context(|_| format!("failed to list {:?}", dir))? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
+
+/// The size of the dynamic library in `dir` named `$name-$hash.so`, if there
+/// is one.
+fn dylib_size(dir: &Path, name: &str) -> Result<Option<u64>, Error> {
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    let prefix = format!("{}-", name);
+    for entry in fs::read_dir(dir).with_context(|_| format!("failed to list {:?}", dir))? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if file_name.starts_with(&prefix) && file_name.ends_with(".so") {
+            return Ok(Some(entry.metadata()?.len()));
+        }
+    }
+    Ok(None)
+}
+
+fn sysroot(rustc: &Path) -> Result<PathBuf, Error> {
+    let mut cmd = Command::new(rustc);
+    cmd.arg("--print").arg("sysroot");
+    let output = command_output(&mut cmd).context("failed to find the sysroot")?;
+    Ok(PathBuf::from(str::from_utf8(&output.stdout)?.trim()))
+}
+
+/// The sizes, in bytes, of `librustc_driver`, of `libstd` for `triple`, and
+/// of the whole sysroot of `rustc`. Libraries that the sysroot doesn't
+/// contain, such as a `librustc_driver` in compilers too old to have one, are
+/// left out.
+pub fn measure(rustc: &Path, triple: &str) -> Result<BTreeMap<String, u64>, Error> {
+    let sysroot = sysroot(rustc)?;
+    let mut sizes = BTreeMap::new();
+    if let Some(size) = dylib_size(&sysroot.join("lib"), "librustc_driver")? {
+        sizes.insert(String::from("librustc_driver"), size);
+    }
+    let target_libs = sysroot.join("lib").join("rustlib").join(triple).join("lib");
+    if let Some(size) = dylib_size(&target_libs, "libstd")? {
+        sizes.insert(String::from("libstd"), size);
+    }
+    sizes.insert(String::from("sysroot"), dir_size(&sysroot)?);
+    Ok(sizes)
+}
@@ -0,0 +1,208 @@
+//! Benchmarks building the compiler itself: a stage 1 build of the rustc
+//! source the artifact under test was built from, using that artifact as the
+//! bootstrap compiler.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::iter;
+use std::path::Path;
+use std::process::Command;
+use std::time::Instant;
+
+use collector::{Benchmark as CollectedBenchmark, BenchmarkState, Category, CodegenBackend,
+                LayoutControl, Linker, Run, Stat, TargetDirPolicy};
+use collector::self_profile::SelfProfile;
+
+use failure::{Error, ResultExt};
+use reqwest;
+
+use execute::command_output;
+use layout;
+use Compiler;
+
+const RUST_REPO: &str = "https://github.com/rust-lang/rust.git";
+
+/// Where CI uploads the `build/metrics.json` written by rustbuild.
+const CI_METRICS_URL: &str = "https://s3-us-west-1.amazonaws.com/rust-lang-ci2/rustc-builds";
+
+/// The name the bootstrap benchmark is recorded under.
+pub const BENCHMARK_NAME: &str = "rustc";
+
+fn git(src_dir: &Path, args: &[&str]) -> Result<(), Error> {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(src_dir).args(args);
+    command_output(&mut cmd).with_context(|_| format!("git {:?} failed", args))?;
+    Ok(())
+}
+
+/// Makes `src_dir` a checkout of rust-lang/rust at `sha`, cloning it first if
+/// needed.
+fn checkout(src_dir: &Path, sha: &str) -> Result<(), Error> {
+    if !src_dir.join(".git").exists() {
+        info!("cloning {} into {}", RUST_REPO, src_dir.display());
+        fs::create_dir_all(src_dir)?;
+        git(src_dir, &["clone", RUST_REPO, "."])?;
+    }
+    git(src_dir, &["fetch", "origin", sha])?;
+    git(src_dir, &["checkout", "--force", sha])?;
+    git(src_dir, &["clean", "-fdx", "--exclude=build", "--exclude=config.toml"])?;
+    Ok(())
+}
+
+/// Points rustbuild at the artifact under test instead of the beta compiler
+/// it would otherwise download.
+fn write_config(src_dir: &Path, compiler: Compiler) -> Result<(), Error> {
+    let config = format!(
+        "[build]\n\
+         rustc = {:?}\n\
+         cargo = {:?}\n\
+         \n\
+         [rust]\n\
+         incremental = false\n",
+        compiler.rustc, compiler.cargo);
+    fs::write(src_dir.join("config.toml"), config)?;
+    Ok(())
+}
+
+fn x_py(src_dir: &Path) -> Command {
+    let mut cmd = Command::new("python");
+    cmd.current_dir(src_dir)
+        .env_clear()
+        .env("PATH", env::var_os("PATH").unwrap_or_default())
+        .env("HOME", env::var_os("HOME").unwrap_or_default())
+        .arg("x.py")
+        .arg("build")
+        .arg("--stage")
+        .arg("0")
+        .arg("src/rustc");
+    cmd
+}
+
+/// Checks out the compiler source at `sha` into `src_dir` and measures a
+/// build of the stage 1 compiler with `compiler` as the stage 0 compiler.
+///
+/// A first, unmeasured, build takes care of downloading dependencies and
+/// building LLVM. Only the Rust parts are then rebuilt, `iterations` times,
+/// and the shortest build is recorded.
+pub fn measure(
+    compiler: Compiler,
+    src_dir: &Path,
+    sha: &str,
+    triple: &str,
+    iterations: usize,
+) -> Result<CollectedBenchmark, Error> {
+    checkout(src_dir, sha)?;
+    write_config(src_dir, compiler)?;
+
+    info!("preparing bootstrap build of {}", sha);
+    command_output(&mut x_py(src_dir)).context("preparatory build failed")?;
+
+    let mut wall_time = ::std::f64::INFINITY;
+    let mut wall_times = Vec::with_capacity(iterations);
+    for i in 0..iterations {
+        debug!("Bootstrap iteration {}/{}", i + 1, iterations);
+
+        let build_dir = src_dir.join("build").join(triple);
+        for dir in &["stage0-std", "stage0-rustc", "stage1"] {
+            let dir = build_dir.join(dir);
+            if dir.exists() {
+                fs::remove_dir_all(&dir)
+                    .with_context(|_| format!("failed to remove {}", dir.display()))?;
+            }
+        }
+
+        let start = Instant::now();
+        command_output(&mut x_py(src_dir)).context("bootstrap build failed")?;
+        let dur = start.elapsed();
+        let secs = dur.as_secs() as f64 + dur.subsec_nanos() as f64 / 1e9;
+        wall_time = wall_time.min(secs);
+        wall_times.push(secs);
+    }
+
+    Ok(CollectedBenchmark {
+        name: BENCHMARK_NAME.to_string(),
+        runs: vec![Run {
+            stats: vec![Stat { name: String::from("wall-time"), cnt: wall_time }],
+            samples: iter::once((String::from("wall-time"), wall_times)).collect(),
+            self_profile: SelfProfile::default(),
+            check: false,
+            release: true,
+            doc: false,
+            debuginfo: false,
+            lto: false,
+            target: None,
+            backend: CodegenBackend::Llvm,
+            linker: Linker::Default,
+            threads: None,
+            iterations: Some(iterations),
+            warmup: None,
+            target_dir: TargetDirPolicy::Reuse,
+            state: BenchmarkState::Clean,
+            // The build isn't run in a temporary directory, nor with a
+            // padded environment.
+            layout: LayoutControl {
+                aslr_disabled: layout::control().aslr_disabled,
+                ..LayoutControl::default()
+            },
+        }],
+        category: Category::Primary,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct Metrics {
+    invocations: Vec<Invocation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Invocation {
+    children: Vec<Node>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Node {
+    RustbuildStep {
+        debug_repr: String,
+        duration_excluding_children_sec: f64,
+        children: Vec<Node>,
+    },
+    TestSuite {},
+}
+
+fn add_step_timings(nodes: &[Node], timings: &mut BTreeMap<String, f64>) {
+    for node in nodes {
+        if let Node::RustbuildStep { debug_repr, duration_excluding_children_sec, children } = node {
+            *timings.entry(debug_repr.clone()).or_insert(0.0) += *duration_excluding_children_sec;
+            add_step_timings(children, timings);
+        }
+    }
+}
+
+/// Fetches the rustbuild metrics CI recorded while building `sha` for
+/// `triple`, and returns how long each step took, excluding the time spent
+/// in the steps it depends on. Steps that ran several times (e.g. once per
+/// `x.py` invocation) have their durations summed.
+///
+/// Returns an empty map if CI didn't upload metrics for the commit, which is
+/// the case for all commits that predate rustbuild's metrics support.
+pub fn ci_step_timings(sha: &str, triple: &str) -> Result<BTreeMap<String, f64>, Error> {
+    let url = format!("{}/{}/metrics-{}.json", CI_METRICS_URL, sha, triple);
+    let mut resp = reqwest::get(&url)?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        info!("no CI metrics for {}", sha);
+        return Ok(BTreeMap::new());
+    }
+    if !resp.status().is_success() {
+        bail!("failed to fetch {}: {}", url, resp.status());
+    }
+    let metrics: Metrics = resp.json()
+        .with_context(|_| format!("failed to parse {}", url))?;
+
+    let mut timings = BTreeMap::new();
+    for invocation in &metrics.invocations {
+        add_step_timings(&invocation.children, &mut timings);
+    }
+    Ok(timings)
+}
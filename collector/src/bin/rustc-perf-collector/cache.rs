@@ -0,0 +1,293 @@
+//! A cache of the compilers downloaded from CI, so that benchmarking a commit
+//! again (e.g. with another profiler, or after a failed run) doesn't download
+//! it again, and so that the downloads don't pile up without bound.
+//!
+//! The cache holds the tarballs rather than the unpacked sysroots, which are
+//! several times larger; a sysroot is unpacked from them for each use and
+//! removed afterwards. Every tarball is checked against the SHA-256 recorded
+//! when it was downloaded before it's used, and the least recently used ones
+//! are evicted once the cache grows past its size limit.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use chrono::Utc;
+use failure::{Error, ResultExt};
+use hex;
+use reqwest;
+use rust_sysroot::git::Commit as GitCommit;
+use serde_json;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+
+use collector::Date;
+use execute::command_output;
+
+const CI_URL: &str = "https://rust-lang-ci2.s3.amazonaws.com";
+
+/// The size the cache is kept under, unless `--cache-size` says otherwise:
+/// room for the compilers of a few dozen commits.
+pub const DEFAULT_MAX_BYTES: u64 = 20 << 30;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub url: String,
+    /// Hex-encoded SHA-256 of the file, as downloaded.
+    pub sha256: String,
+    pub bytes: u64,
+    pub last_used: Date,
+}
+
+pub struct Cache {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// By the name of the file in `files`.
+    entries: BTreeMap<String, Entry>,
+}
+
+/// A sysroot unpacked from the cache, which is removed when dropped.
+pub struct Sysroot {
+    pub rustc: PathBuf,
+    pub cargo: PathBuf,
+    pub triple: String,
+    _dir: TempDir,
+}
+
+impl Cache {
+    /// Opens the cache at `dir`, creating it if needed. Entries whose files
+    /// have gone missing are forgotten.
+    pub fn open(dir: &Path, max_bytes: u64) -> Result<Cache, Error> {
+        fs::create_dir_all(dir.join("files"))
+            .with_context(|_| format!("failed to create the cache at {:?}", dir))?;
+        let index = dir.join("index.json");
+        let mut entries: BTreeMap<String, Entry> = if index.exists() {
+            serde_json::from_reader(File::open(&index)?)
+                .with_context(|_| format!("failed to read {:?}", index))?
+        } else {
+            BTreeMap::new()
+        };
+        entries.retain(|name, _| dir.join("files").join(name).exists());
+        Ok(Cache {
+            dir: dir.to_path_buf(),
+            max_bytes,
+            entries,
+        })
+    }
+
+    /// The entries, most recently used first.
+    pub fn entries(&self) -> Vec<(&str, &Entry)> {
+        let mut entries = self.entries.iter()
+            .map(|(name, entry)| (&**name, entry))
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| b.1.last_used.cmp(&a.1.last_used));
+        entries
+    }
+
+    pub fn size(&self) -> u64 {
+        self.entries.values().map(|entry| entry.bytes).sum()
+    }
+
+    /// Evicts the least recently used entries until the cache is no larger
+    /// than `max_bytes`, and, with `verify`, the entries whose files no
+    /// longer match their checksum. Returns the evicted entries.
+    pub fn prune(&mut self, max_bytes: u64, verify: bool) -> Result<Vec<(String, Entry)>, Error> {
+        let mut evicted = Vec::new();
+        if verify {
+            let names = self.entries.keys().cloned().collect::<Vec<_>>();
+            for name in names {
+                if sha256(&self.file(&name))? != self.entries[&name].sha256 {
+                    warn!("{} doesn't match its checksum", name);
+                    evicted.push(self.remove(&name)?);
+                }
+            }
+        }
+        evicted.extend(self.evict(max_bytes, None)?);
+        self.save()?;
+        Ok(evicted)
+    }
+
+    /// Downloads the rustc, standard library and cargo CI built for
+    /// `commit`, or the alternative build with `alt`, unless they're already
+    /// in the cache, and unpacks them into a temporary sysroot.
+    pub fn install_sysroot(
+        &mut self,
+        commit: &GitCommit,
+        triple: &str,
+        alt: bool,
+    ) -> Result<Sysroot, Error> {
+        let builds = if alt { "rustc-builds-alt" } else { "rustc-builds" };
+        let dir = TempDir::new_in(&self.dir)?;
+        for component in &["rustc", "rust-std", "cargo"] {
+            let tarball = format!("{}-nightly-{}.tar.xz", component, triple);
+            let url = format!("{}/{}/{}/{}", CI_URL, builds, commit.sha, tarball);
+            let name = format!("{}-{}-{}", builds, commit.sha, tarball);
+            let file = self.fetch(&name, &url)?;
+
+            // The tarballs hold `<tarball>/<component>/{bin,lib,...}`.
+            let mut cmd = Command::new("tar");
+            cmd.arg("-xJf").arg(&file)
+                .arg("--strip-components=2")
+                .arg("-C").arg(dir.path());
+            command_output(&mut cmd).with_context(|_| format!("failed to unpack {}", url))?;
+        }
+        Ok(Sysroot {
+            rustc: dir.path().join("bin").join(format!("rustc{}", env::consts::EXE_SUFFIX)),
+            cargo: dir.path().join("bin").join(format!("cargo{}", env::consts::EXE_SUFFIX)),
+            triple: triple.to_string(),
+            _dir: dir,
+        })
+    }
+
+    /// The path of the cached copy of `url`, downloading it first if it isn't
+    /// in the cache or doesn't match its checksum.
+    fn fetch(&mut self, name: &str, url: &str) -> Result<PathBuf, Error> {
+        let file = self.file(name);
+        let cached = match self.entries.get(name) {
+            Some(entry) => {
+                let matches = sha256(&file)? == entry.sha256;
+                if !matches {
+                    warn!("cached {} doesn't match its checksum, downloading it again", url);
+                }
+                matches
+            }
+            None => false,
+        };
+        if cached {
+            debug!("using cached {}", url);
+            self.entries.get_mut(name).unwrap().last_used = Date(Utc::now());
+        } else {
+            let (sha256, bytes) = download(url, &file)?;
+            self.entries.insert(name.to_string(), Entry {
+                url: url.to_string(),
+                sha256,
+                bytes,
+                last_used: Date(Utc::now()),
+            });
+            let max_bytes = self.max_bytes;
+            self.evict(max_bytes, Some(name))?;
+        }
+        self.save()?;
+        Ok(file)
+    }
+
+    /// Evicts the least recently used entries other than `keep` until the
+    /// cache is no larger than `max_bytes`.
+    fn evict(&mut self, max_bytes: u64, keep: Option<&str>) -> Result<Vec<(String, Entry)>, Error> {
+        let mut by_age = self.entries.iter()
+            .filter(|&(name, _)| Some(&**name) != keep)
+            .map(|(name, entry)| (entry.last_used, name.clone()))
+            .collect::<Vec<_>>();
+        by_age.sort();
+
+        let mut size = self.size();
+        let mut evicted = Vec::new();
+        for (_, name) in by_age {
+            if size <= max_bytes {
+                break;
+            }
+            let (name, entry) = self.remove(&name)?;
+            info!("evicting {} from the cache", name);
+            size -= entry.bytes;
+            evicted.push((name, entry));
+        }
+        Ok(evicted)
+    }
+
+    fn remove(&mut self, name: &str) -> Result<(String, Entry), Error> {
+        match fs::remove_file(self.file(name)) {
+            Err(ref e) if e.kind() != io::ErrorKind::NotFound => {
+                bail!("failed to remove {} from the cache: {}", name, e)
+            }
+            _ => {}
+        }
+        let entry = self.entries.remove(name).unwrap();
+        Ok((name.to_string(), entry))
+    }
+
+    fn file(&self, name: &str) -> PathBuf {
+        self.dir.join("files").join(name)
+    }
+
+    /// Writes the index, via a temporary file so that an interrupted write
+    /// doesn't lose it.
+    fn save(&self) -> Result<(), Error> {
+        let index = self.dir.join("index.json");
+        let tmp = self.dir.join("index.json.tmp");
+        serde_json::to_writer(File::create(&tmp)?, &self.entries)?;
+        fs::rename(&tmp, &index)?;
+        Ok(())
+    }
+}
+
+/// Downloads `url` to `file`, and returns its SHA-256 and size. A download
+/// that's cut short, or that doesn't match the checksum published next to it
+/// (if there is one), is an error.
+fn download(url: &str, file: &Path) -> Result<(String, u64), Error> {
+    info!("downloading {}", url);
+    let mut resp = reqwest::get(url)?;
+    if !resp.status().is_success() {
+        bail!("failed to download {}: {}", url, resp.status());
+    }
+    let expected_bytes = resp.content_length();
+
+    let partial = file.with_extension("part");
+    let mut out = File::create(&partial)?;
+    let mut hasher = Sha256::default();
+    let mut bytes = 0;
+    let mut buf = vec![0; 1 << 16];
+    loop {
+        let n = resp.read(&mut buf).with_context(|_| format!("failed to download {}", url))?;
+        if n == 0 {
+            break;
+        }
+        hasher.input(&buf[..n]);
+        out.write_all(&buf[..n])?;
+        bytes += n as u64;
+    }
+    let sha256 = hex::encode(hasher.result());
+
+    if let Some(expected_bytes) = expected_bytes {
+        if bytes != expected_bytes {
+            fs::remove_file(&partial)?;
+            bail!("download of {} was cut short: {} of {} bytes", url, bytes, expected_bytes);
+        }
+    }
+    if let Some(published) = published_sha256(url)? {
+        if published != sha256 {
+            fs::remove_file(&partial)?;
+            bail!("{} doesn't match its published SHA-256 ({} vs. {})", url, sha256, published);
+        }
+    }
+    fs::rename(&partial, file)?;
+    Ok((sha256, bytes))
+}
+
+/// The SHA-256 in `<url>.sha256`, as written by `sha256sum`, if there is
+/// such a file.
+fn published_sha256(url: &str) -> Result<Option<String>, Error> {
+    let url = format!("{}.sha256", url);
+    let mut resp = reqwest::get(&url)?;
+    if !resp.status().is_success() {
+        return Ok(None);
+    }
+    let text = resp.text()?;
+    Ok(text.split_whitespace().next().map(|sha256| sha256.to_lowercase()))
+}
+
+fn sha256(file: &Path) -> Result<String, Error> {
+    let mut file = File::open(file).with_context(|_| format!("failed to open {:?}", file))?;
+    let mut hasher = Sha256::default();
+    let mut buf = vec![0; 1 << 16];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.input(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.result()))
+}
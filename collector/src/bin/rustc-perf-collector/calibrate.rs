@@ -0,0 +1,138 @@
+//! Measures how noisy the machine is, by timing a synthetic workload that does
+//! exactly the same work every time it runs. The spread of its times is the
+//! noise floor of the wall time measurements; `calibrate` stores it as the
+//! machine's baseline, and before every collection a shorter run is compared
+//! against it, to catch a machine that's noisier than usual (e.g. because
+//! something else is running on it).
+
+use std::fs::{self, File};
+use std::path::Path;
+use std::time::Instant;
+
+use chrono::Utc;
+use failure::{Error, ResultExt};
+use serde_json;
+
+use collector::Date;
+use collection;
+
+/// How many times `calibrate` runs the workload, unless told otherwise.
+pub const DEFAULT_RUNS: usize = 20;
+
+/// How many times the workload is run before a collection.
+const CHECK_RUNS: usize = 5;
+
+/// The coefficient of variation above which the machine is too noisy,
+/// unless told otherwise.
+pub const DEFAULT_MAX_CV: f64 = 0.01;
+
+/// The noise statistics of the machine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Baseline {
+    pub hostname: Option<String>,
+    pub date: Date,
+    pub runs: usize,
+    /// The mean, smallest and largest wall time of a run of the workload, in
+    /// seconds.
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    /// The coefficient of variation (standard deviation divided by mean) of
+    /// the wall time.
+    pub cv: f64,
+}
+
+/// Sorts and hashes a few million pseudo-random numbers, which takes a few
+/// hundred milliseconds and exercises the caches and branch predictors a bit.
+/// The result is the same every time, which `measure` checks, so that the
+/// work can't be optimized away.
+fn workload() -> u64 {
+    // xorshift64
+    let mut state: u64 = 0x2545f4914f6cdd1d;
+    let mut numbers = (0..1 << 22).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    }).collect::<Vec<_>>();
+    numbers.sort();
+    numbers.iter().fold(0xcbf29ce484222325, |hash, &n| (hash ^ n).wrapping_mul(0x100000001b3))
+}
+
+/// Runs the workload once without timing it, to warm up, then `runs` times.
+pub fn measure(runs: usize) -> Result<Baseline, Error> {
+    if runs < 2 {
+        bail!("at least 2 runs are needed to measure the noise, not {}", runs);
+    }
+    let expected = workload();
+    let mut times = Vec::with_capacity(runs);
+    for i in 0..runs {
+        let start = Instant::now();
+        let result = workload();
+        let elapsed = start.elapsed();
+        if result != expected {
+            bail!("the calibration workload gave a different result in run {}", i + 1);
+        }
+        times.push(elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9);
+    }
+
+    let n = times.len() as f64;
+    let mean = times.iter().sum::<f64>() / n;
+    let variance = times.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    Ok(Baseline {
+        hostname: collection::hostname(),
+        date: Date(Utc::now()),
+        runs,
+        mean,
+        min: times.iter().cloned().fold(mean, f64::min),
+        max: times.iter().cloned().fold(mean, f64::max),
+        cv: variance.sqrt() / mean,
+    })
+}
+
+/// The baseline stored at `path`, if there is one.
+pub fn load(path: &Path) -> Result<Option<Baseline>, Error> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let baseline = serde_json::from_reader(File::open(path)?)
+        .with_context(|_| format!("failed to read the calibration {:?}", path))?;
+    Ok(Some(baseline))
+}
+
+pub fn save(baseline: &Baseline, path: &Path) -> Result<(), Error> {
+    fs::write(path, serde_json::to_string_pretty(baseline)?)
+        .with_context(|_| format!("failed to write the calibration {:?}", path))?;
+    Ok(())
+}
+
+/// Runs the workload a few times, and describes how the machine is noisier
+/// than the baseline at `path` allows, if it is: the noise is above both
+/// twice that of the baseline and `DEFAULT_MAX_CV`, or the workload is much
+/// slower than it was. Nothing is checked if the machine was never
+/// calibrated.
+pub fn check(path: &Path) -> Result<Option<String>, Error> {
+    let baseline = match load(path)? {
+        Some(baseline) => baseline,
+        None => return Ok(None),
+    };
+    if baseline.hostname.is_some() && baseline.hostname != collection::hostname() {
+        warn!("the calibration {:?} is of another machine ({}), ignoring it",
+              path, baseline.hostname.as_ref().unwrap());
+        return Ok(None);
+    }
+
+    let current = measure(CHECK_RUNS)?;
+    let max_cv = (2.0 * baseline.cv).max(DEFAULT_MAX_CV);
+    if current.cv > max_cv {
+        return Ok(Some(format!(
+            "the calibration workload varies by {:.2}%, not at most {:.2}% (the baseline is {:.2}%)",
+            current.cv * 100.0, max_cv * 100.0, baseline.cv * 100.0)));
+    }
+    if current.mean > baseline.mean * (1.0 + max_cv * 5.0) {
+        return Ok(Some(format!(
+            "the calibration workload takes {:.3}s, not {:.3}s like the baseline",
+            current.mean, baseline.mean)));
+    }
+    Ok(None)
+}
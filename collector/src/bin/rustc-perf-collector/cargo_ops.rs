@@ -0,0 +1,154 @@
+//! Benchmarks of cargo itself, rather than of the compiler: how long the
+//! cargo shipped with an artifact takes for operations that don't involve
+//! rustc, like resolving dependencies or finding out that nothing needs to be
+//! rebuilt. The compile-time benchmarks only measure rustc, so regressions in
+//! cargo's own overhead would otherwise go unnoticed.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::iter;
+use std::path::Path;
+use std::process::Command;
+use std::time::Instant;
+
+use collector::{Benchmark as CollectedBenchmark, BenchmarkState, Category, CodegenBackend,
+                LayoutControl, Linker, Run, Stat, TargetDirPolicy};
+use collector::self_profile::SelfProfile;
+
+use failure::{Error, ResultExt};
+
+use execute::{command_output, Benchmark};
+use isolation;
+use layout;
+use Compiler;
+
+struct Operation {
+    /// The name the operation is recorded under.
+    name: &'static str,
+    /// The benchmark whose crate the operation is done on.
+    benchmark: &'static str,
+    /// The cargo subcommand and its arguments.
+    subcommand: &'static [&'static str],
+    /// Whether the benchmark's own cargo options (e.g. its features) apply.
+    build_args: bool,
+}
+
+const OPERATIONS: &[Operation] = &[
+    // Resolving the dependency graph of a crate with many dependencies.
+    // Every cargo command does this, even with a `Cargo.lock`.
+    Operation {
+        name: "cargo-resolve",
+        benchmark: "cargo",
+        subcommand: &["metadata", "--format-version", "1"],
+        build_args: false,
+    },
+    // The fixed overhead of a build that has nothing to do.
+    Operation {
+        name: "cargo-noop-check",
+        benchmark: "helloworld",
+        subcommand: &["check"],
+        build_args: true,
+    },
+    // Checking the fingerprints of a large workspace and all of its
+    // dependencies, none of which need to be rebuilt.
+    Operation {
+        name: "cargo-fingerprint",
+        benchmark: "style-servo",
+        subcommand: &["check"],
+        build_args: true,
+    },
+];
+
+fn cargo_command(compiler: Compiler, cwd: &Path, benchmark: &Benchmark, op: &Operation)
+                 -> Command {
+    let mut cmd = isolation::sandboxed_command(compiler.cargo);
+    cmd.env_clear()
+        .env("SHELL", env::var_os("SHELL").unwrap_or_default())
+        .env("PATH", env::var_os("PATH").unwrap_or_default())
+        // Unlike for the other benchmarks, rustc isn't measured, so there's
+        // no need for rustc-fake.
+        .env("RUSTC", compiler.rustc)
+        .env("CARGO_INCREMENTAL", "0")
+        .current_dir(cwd)
+        .args(op.subcommand)
+        .arg("--manifest-path").arg(benchmark.manifest_path());
+    if op.build_args {
+        cmd.args(benchmark.cargo_args());
+    }
+    cmd
+}
+
+/// Measures `op` on a copy of `benchmark`. A first, unmeasured, run fetches
+/// the dependencies and does any building. `iterations` more runs are then
+/// timed, and the shortest one is recorded.
+fn measure_operation(
+    compiler: Compiler,
+    benchmark: &Benchmark,
+    op: &Operation,
+    iterations: usize,
+) -> Result<CollectedBenchmark, Error> {
+    let dir = benchmark.make_temp_dir(&benchmark.path, false)?;
+
+    info!("preparing {}", op.name);
+    command_output(&mut cargo_command(compiler, dir.path(), benchmark, op))
+        .context("preparatory run failed")?;
+
+    let mut wall_time = ::std::f64::INFINITY;
+    let mut wall_times = Vec::with_capacity(iterations);
+    for i in 0..iterations {
+        debug!("{} iteration {}/{}", op.name, i + 1, iterations);
+        let start = Instant::now();
+        command_output(&mut cargo_command(compiler, dir.path(), benchmark, op))
+            .context("measured run failed")?;
+        let dur = start.elapsed();
+        let secs = dur.as_secs() as f64 + dur.subsec_nanos() as f64 / 1e9;
+        wall_time = wall_time.min(secs);
+        wall_times.push(secs);
+    }
+
+    Ok(CollectedBenchmark {
+        name: op.name.to_string(),
+        runs: vec![Run {
+            stats: vec![Stat { name: String::from("wall-time"), cnt: wall_time }],
+            samples: iter::once((String::from("wall-time"), wall_times)).collect(),
+            self_profile: SelfProfile::default(),
+            check: op.subcommand[0] == "check",
+            release: false,
+            doc: false,
+            debuginfo: false,
+            lto: false,
+            target: None,
+            backend: CodegenBackend::Llvm,
+            linker: Linker::Default,
+            threads: None,
+            iterations: Some(iterations),
+            warmup: None,
+            target_dir: TargetDirPolicy::Reuse,
+            state: BenchmarkState::Clean,
+            // Cargo is run with an unpadded environment.
+            layout: LayoutControl { randomized_environment: false, ..layout::control() },
+        }],
+        category: Category::Primary,
+    })
+}
+
+/// Measures each of the cargo operations with `compiler`'s cargo, on the
+/// benchmarks in `benchmark_dir`. Each operation is recorded as a benchmark of
+/// its own, with a failure recorded like that of any other benchmark.
+pub fn measure(
+    compiler: Compiler,
+    benchmark_dir: &Path,
+    iterations: usize,
+) -> BTreeMap<String, Result<CollectedBenchmark, String>> {
+    let mut results = BTreeMap::new();
+    for op in OPERATIONS {
+        let result = Benchmark::new(op.benchmark.to_string(), benchmark_dir.join(op.benchmark))
+            .and_then(|benchmark| measure_operation(compiler, &benchmark, op, iterations))
+            .map_err(|e| {
+                info!("failed to benchmark {}, recorded: {}", op.name, e);
+                format!("{:?}", e)
+            });
+        results.insert(op.name.to_string(), result);
+    }
+    results
+}
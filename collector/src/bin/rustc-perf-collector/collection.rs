@@ -0,0 +1,122 @@
+//! Describes the machine and software that collected a set of results.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use collector::Collection;
+
+fn read_sys(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+/// Runs `cmd`, returning its stdout if it succeeded.
+fn output_of(cmd: &mut Command) -> Option<String> {
+    let output = cmd.output().ok()?;
+    if output.status.success() {
+        String::from_utf8(output.stdout).ok()
+    } else {
+        None
+    }
+}
+
+/// The value of the first `key: value` line for `key` in a file like
+/// `/proc/cpuinfo`.
+fn proc_field(contents: &str, key: &str) -> Option<String> {
+    contents.lines()
+        .filter(|line| line.split(':').next().map(str::trim) == Some(key))
+        .filter_map(|line| line.splitn(2, ':').nth(1))
+        .map(|value| value.trim().to_string())
+        .next()
+}
+
+/// ARM's `/proc/cpuinfo` has no model name, only the numbers of the
+/// implementer and the part, e.g. `0x41` (Arm) and `0xd0c` (Neoverse N1).
+fn arm_cpu_model(cpuinfo: &str) -> Option<String> {
+    let implementer = proc_field(cpuinfo, "CPU implementer")?;
+    let part = proc_field(cpuinfo, "CPU part")?;
+    Some(format!("implementer {} part {}", implementer, part))
+}
+
+/// The value of the sysctl `name`, for where there's no `/proc` (e.g.
+/// macOS).
+fn sysctl(name: &str) -> Option<String> {
+    output_of(Command::new("sysctl").arg("-n").arg(name)).map(|s| s.trim().to_string())
+}
+
+fn os() -> String {
+    // e.g. `10.14.3`; the Darwin version doesn't tell much.
+    let version = if cfg!(target_os = "macos") {
+        output_of(Command::new("sw_vers").arg("-productVersion"))
+    } else {
+        None
+    };
+    match version {
+        Some(version) => format!("{} {}", env::consts::OS, version.trim()),
+        None => env::consts::OS.to_string(),
+    }
+}
+
+fn memory() -> Option<u64> {
+    let meminfo = match read_sys("/proc/meminfo") {
+        Some(meminfo) => meminfo,
+        // In bytes.
+        None => return sysctl("hw.memsize")?.parse::<u64>().ok().map(|bytes| bytes / 1024),
+    };
+    // e.g. `MemTotal:       16314888 kB`
+    proc_field(&meminfo, "MemTotal")?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+fn glibc() -> Option<String> {
+    // The first line is e.g. `ldd (Ubuntu GLIBC 2.27-3ubuntu1) 2.27`.
+    let version = output_of(Command::new("ldd").arg("--version"))?;
+    version.lines().next()?.split_whitespace().last().map(String::from)
+}
+
+/// The LLVM version `rustc` was built against.
+pub fn llvm_version(rustc: &Path) -> Option<String> {
+    let version = output_of(Command::new(rustc).arg("-vV"))?;
+    proc_field(&version, "LLVM version")
+}
+
+fn collector_sha() -> Option<String> {
+    // The collector is run from the root of its checkout.
+    let sha = output_of(Command::new("git").args(&["rev-parse", "HEAD"]))?;
+    Some(sha.trim().to_string())
+}
+
+pub fn hostname() -> Option<String> {
+    read_sys("/proc/sys/kernel/hostname").or_else(|| sysctl("kern.hostname"))
+}
+
+/// Describes the current machine, and the collector, benchmarking `rustc`.
+/// Anything that can't be determined is left out. The suite version and the
+/// durations are left to the caller, as they depend on the benchmarks.
+pub fn current(rustc: &Path) -> Collection {
+    let cpuinfo = read_sys("/proc/cpuinfo").unwrap_or_default();
+    Collection {
+        hostname: hostname(),
+        os: Some(os()),
+        arch: Some(env::consts::ARCH.to_string()),
+        kernel: read_sys("/proc/sys/kernel/osrelease").or_else(|| sysctl("kern.osrelease")),
+        cpu_model: proc_field(&cpuinfo, "model name")
+            .or_else(|| arm_cpu_model(&cpuinfo))
+            .or_else(|| sysctl("machdep.cpu.brand_string")),
+        microcode: proc_field(&cpuinfo, "microcode"),
+        memory: memory(),
+        glibc: glibc(),
+        llvm_version: llvm_version(rustc),
+        collector_sha: collector_sha(),
+        suite_version: None,
+        duration: None,
+        interrupted: false,
+        benchmark_durations: BTreeMap::new(),
+        shard: None,
+    }
+}
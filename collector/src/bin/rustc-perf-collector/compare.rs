@@ -0,0 +1,98 @@
+//! Compares the results of benchmarking two compilers locally.
+
+use std::io::{self, Write};
+
+use collector::CommitData;
+
+/// Changes smaller than this many percent are considered noise, and are not
+/// highlighted.
+const SIGNIFICANCE_THRESHOLD: f64 = 1.0;
+
+/// The change in one stat of one run of a benchmark.
+#[derive(Debug, Clone, Serialize)]
+pub struct Delta {
+    pub benchmark: String,
+    pub run: String,
+    pub stat: String,
+    pub base: f64,
+    pub new: f64,
+    /// The change from `base` to `new`, in percent.
+    pub pct: f64,
+}
+
+impl Delta {
+    pub fn is_significant(&self) -> bool {
+        self.pct.abs() >= SIGNIFICANCE_THRESHOLD
+    }
+}
+
+/// Compares `stat` in every run that succeeded for both `base` and `new`.
+pub fn compare(base: &CommitData, new: &CommitData, stat: &str) -> Vec<Delta> {
+    let mut deltas = Vec::new();
+    for (name, base_result) in &base.benchmarks {
+        let (base_benchmark, new_benchmark) = match (base_result, new.benchmarks.get(name)) {
+            (&Ok(ref base), Some(&Ok(ref new))) => (base, new),
+            _ => continue,
+        };
+        for base_run in &base_benchmark.runs {
+            let new_run = match new_benchmark.runs.iter().find(|r| *r == base_run) {
+                Some(run) => run,
+                None => continue,
+            };
+            if let (Some(base), Some(new)) = (base_run.get_stat(stat), new_run.get_stat(stat)) {
+                let pct = if base == 0.0 { 0.0 } else { (new - base) / base * 100.0 };
+                deltas.push(Delta {
+                    benchmark: name.clone(),
+                    run: base_run.name(),
+                    stat: stat.to_string(),
+                    base,
+                    new,
+                    pct,
+                });
+            }
+        }
+    }
+    deltas
+}
+
+/// Prints `deltas` as a table, highlighting significant improvements in
+/// green and regressions in red if `color` is set.
+pub fn print_table(out: &mut dyn Write, deltas: &[Delta], color: bool) -> io::Result<()> {
+    let benchmark_width = deltas.iter().map(|d| d.benchmark.len()).max().unwrap_or(0).max(9);
+    let run_width = deltas.iter().map(|d| d.run.len()).max().unwrap_or(0).max(3);
+
+    writeln!(out, "{:<bw$}  {:<rw$}  {:>15}  {:>15}  {:>8}",
+             "benchmark", "run", "base", "new", "change",
+             bw = benchmark_width, rw = run_width)?;
+    for delta in deltas {
+        let change = format!("{:+.2}%", delta.pct);
+        let change = match (color && delta.is_significant(), delta.pct > 0.0) {
+            (true, true) => format!("\x1b[31m{:>8}\x1b[0m", change),
+            (true, false) => format!("\x1b[32m{:>8}\x1b[0m", change),
+            (false, _) => format!("{:>8}", change),
+        };
+        writeln!(out, "{:<bw$}  {:<rw$}  {:>15.0}  {:>15.0}  {}",
+                 delta.benchmark, delta.run, delta.base, delta.new, change,
+                 bw = benchmark_width, rw = run_width)?;
+    }
+
+    let regressions = deltas.iter().filter(|d| d.is_significant() && d.pct > 0.0).count();
+    let improvements = deltas.iter().filter(|d| d.is_significant() && d.pct < 0.0).count();
+    writeln!(out)?;
+    writeln!(out, "{} runs compared: {} regressed and {} improved by {}% or more",
+             deltas.len(), regressions, improvements, SIGNIFICANCE_THRESHOLD)?;
+    Ok(())
+}
+
+/// Whether stdout is a terminal, i.e. whether to use colors.
+#[cfg(unix)]
+pub fn stdout_is_terminal() -> bool {
+    extern crate libc;
+
+    unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+pub fn stdout_is_terminal() -> bool {
+    false
+}
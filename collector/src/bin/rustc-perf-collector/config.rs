@@ -0,0 +1,84 @@
+//! The collector's configuration file, `collector.toml`, which holds defaults
+//! for the flags and environment variables a collector is usually run with,
+//! e.g.
+//!
+//!     output-repo = "rustc-timing"
+//!     site-url = "https://perf.example.org"
+//!     cpu-set = "2-7"
+//!     jobs = 2
+//!     warmup = 1
+//!
+//!     [env]
+//!     PERF_UPLOAD_TOKEN = "..."
+//!
+//! The flags given on the command line, and the variables already set in the
+//! environment, override it.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use failure::{Error, ResultExt};
+use toml;
+
+use retention::Policy;
+
+/// Where the configuration is read from, unless told otherwise. It's fine
+/// for there to be no file there.
+pub const DEFAULT_PATH: &str = "collector.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// `--output-repo`.
+    pub output_repo: Option<PathBuf>,
+    /// The URL of the site, `$SITE_URL` (and `daemon --site-url`).
+    pub site_url: Option<String>,
+    /// Where the benchmarks are, instead of `collector/benchmarks`.
+    pub benchmark_dir: Option<PathBuf>,
+    /// `--cpu-set`, `--jobs` and `--cgroup`.
+    pub cpu_set: Option<String>,
+    pub jobs: Option<usize>,
+    pub cgroup: Option<String>,
+    /// `bench_local --warmup`, `--max-iterations` and `--max-cv`.
+    pub warmup: Option<usize>,
+    pub max_iterations: Option<usize>,
+    pub max_cv: Option<f64>,
+    /// `bench_local --profiler`.
+    pub profiler: Option<String>,
+    /// `process --name` and `daemon --name`.
+    pub name: Option<String>,
+    /// Environment variables, e.g. `PERF_UPLOAD_TOKEN`.
+    pub env: BTreeMap<String, String>,
+    /// What `prune_db` prunes.
+    pub retention: Policy,
+}
+
+impl Config {
+    /// Sets the environment variables of the configuration (and `$SITE_URL`)
+    /// that aren't set already.
+    pub fn apply_env(&self) {
+        let site_url = self.site_url.as_ref().map(|url| ("SITE_URL", url));
+        let vars = self.env.iter().map(|(name, value)| (name.as_str(), value));
+        for (name, value) in site_url.into_iter().chain(vars) {
+            if env::var_os(name).is_none() {
+                env::set_var(name, value);
+            }
+        }
+    }
+}
+
+/// The configuration at `path`, or at `DEFAULT_PATH` if there is one.
+pub fn load(path: Option<&Path>) -> Result<Config, Error> {
+    let path = match path {
+        Some(path) => path,
+        None if Path::new(DEFAULT_PATH).exists() => Path::new(DEFAULT_PATH),
+        None => return Ok(Config::default()),
+    };
+    let config = fs::read_to_string(path)
+        .with_context(|_| format!("failed to read the configuration {:?}", path))?;
+    let config = toml::from_str(&config)
+        .with_context(|_| format!("invalid configuration {:?}", path))?;
+    Ok(config)
+}
@@ -0,0 +1,276 @@
+//! Exports the results in a database as one row per value of a stat, as CSV
+//! or Parquet, for analyzing them in other tools (e.g. pandas) rather than
+//! through the site's API. The columns, which are documented in the README,
+//! are the same in both formats.
+
+use std::fs::File;
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use csv;
+use failure::{Error, ResultExt};
+use serde::Serializer;
+
+use collector::db::{self, Results};
+use collector::Benchmark;
+
+/// How many rows go in a row group of a Parquet file.
+#[cfg(feature = "parquet")]
+const ROW_GROUP_ROWS: usize = 1 << 20;
+
+#[cfg(feature = "parquet")]
+const PARQUET_SCHEMA: &str = "
+    message rustc_perf_results {
+        REQUIRED BYTE_ARRAY kind (UTF8);
+        REQUIRED BYTE_ARRAY artifact (UTF8);
+        OPTIONAL INT64 date (TIMESTAMP_MILLIS);
+        REQUIRED BYTE_ARRAY triple (UTF8);
+        REQUIRED BYTE_ARRAY flavor (UTF8);
+        REQUIRED BYTE_ARRAY collector (UTF8);
+        REQUIRED BYTE_ARRAY benchmark (UTF8);
+        REQUIRED BYTE_ARRAY category (UTF8);
+        REQUIRED BYTE_ARRAY run (UTF8);
+        REQUIRED BYTE_ARRAY metric (UTF8);
+        REQUIRED DOUBLE value;
+    }
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Parquet,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Result<Format, Error> {
+        match name {
+            "csv" => Ok(Format::Csv),
+            "parquet" if cfg!(feature = "parquet") => Ok(Format::Parquet),
+            "parquet" => bail!("the collector was built without Parquet support; rebuild \
+                                it with `--features parquet`"),
+            _ => bail!("'{}' is not a known export format", name),
+        }
+    }
+}
+
+/// Which of the results to export; `None` is all of them.
+#[derive(Debug, Default)]
+pub struct Filter {
+    pub benchmarks: Option<Vec<String>>,
+    pub metrics: Option<Vec<String>>,
+    /// Only the commits merged on or after this date, and no artifacts.
+    pub since: Option<NaiveDate>,
+}
+
+impl Filter {
+    fn benchmark(&self, name: &str) -> bool {
+        self.benchmarks.as_ref().map_or(true, |names| names.iter().any(|n| n == name))
+    }
+
+    fn metric(&self, name: &str) -> bool {
+        self.metrics.as_ref().map_or(true, |names| names.iter().any(|n| n == name))
+    }
+
+    fn date(&self, date: Option<DateTime<Utc>>) -> bool {
+        match (self.since, date) {
+            (Some(since), Some(date)) => date.naive_utc().date() >= since,
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Row<'a> {
+    kind: &'static str,
+    artifact: &'a str,
+    #[serde(serialize_with = "rfc3339")]
+    date: Option<DateTime<Utc>>,
+    triple: &'a str,
+    flavor: &'static str,
+    collector: &'a str,
+    benchmark: &'a str,
+    category: &'static str,
+    run: String,
+    metric: &'a str,
+    value: f64,
+}
+
+fn rfc3339<S: Serializer>(date: &Option<DateTime<Utc>>, s: S) -> Result<S::Ok, S::Error> {
+    match *date {
+        Some(date) => s.serialize_str(&date.to_rfc3339()),
+        None => s.serialize_none(),
+    }
+}
+
+/// What the rows of the benchmarks of an artifact have in common.
+struct Artifact<'a> {
+    kind: &'static str,
+    name: &'a str,
+    date: Option<DateTime<Utc>>,
+    triple: &'a str,
+    flavor: &'static str,
+}
+
+fn push_rows<'a, F>(
+    rows: &mut Vec<Row<'a>>,
+    filter: &Filter,
+    artifact: &Artifact<'a>,
+    benchmark: &'a Benchmark,
+    collector: F,
+) where F: Fn(&str) -> &'a str
+{
+    if !filter.benchmark(&benchmark.name) {
+        return;
+    }
+    for run in &benchmark.runs {
+        for stat in run.stats.iter().filter(|stat| filter.metric(&stat.name)) {
+            rows.push(Row {
+                kind: artifact.kind,
+                artifact: artifact.name,
+                date: artifact.date,
+                triple: artifact.triple,
+                flavor: artifact.flavor,
+                collector: collector(&benchmark.name),
+                benchmark: &benchmark.name,
+                category: benchmark.category.name(),
+                run: run.name(),
+                metric: &stat.name,
+                value: stat.cnt,
+            });
+        }
+    }
+}
+
+/// The rows of `results` that pass `filter`: the commits in the order they
+/// were merged, and then the artifacts.
+fn rows<'a>(results: &'a Results, filter: &Filter) -> Vec<Row<'a>> {
+    let mut commits = results.commits.iter()
+        .filter(|commit| filter.date(Some(commit.commit.date.0)))
+        .collect::<Vec<_>>();
+    commits.sort_by_key(|commit| commit.commit.date);
+
+    let mut rows = Vec::new();
+    for commit in commits {
+        let artifact = Artifact {
+            kind: db::COMMIT,
+            name: &commit.commit.sha,
+            date: Some(commit.commit.date.0),
+            triple: &commit.triple,
+            flavor: commit.flavor.name(),
+        };
+        for benchmark in commit.benchmarks.values().filter_map(|b| b.as_ref().ok()) {
+            push_rows(&mut rows, filter, &artifact, benchmark, |name| {
+                commit.collected_by.get(name).map_or("", |c| c.as_str())
+            });
+        }
+    }
+    if filter.since.is_none() {
+        for data in &results.artifacts {
+            let artifact = Artifact {
+                kind: db::ARTIFACT,
+                name: &data.id,
+                date: None,
+                triple: "",
+                flavor: "",
+            };
+            for benchmark in data.benchmarks.values().filter_map(|b| b.as_ref().ok()) {
+                push_rows(&mut rows, filter, &artifact, benchmark, |_| "");
+            }
+        }
+    }
+    rows
+}
+
+/// Writes the results in the database at `url` that pass `filter` to the file
+/// `output`, returning how many rows were written.
+pub fn export(url: &str, filter: &Filter, format: Format, output: &Path)
+              -> Result<usize, Error> {
+    let results = db::open(url)?.load()?;
+    let rows = rows(&results, filter);
+    let file = File::create(output)
+        .with_context(|_| format!("failed to create {:?}", output))?;
+    match format {
+        Format::Csv => write_csv(&rows, file)?,
+        Format::Parquet => write_parquet(&rows, file)?,
+    }
+    Ok(rows.len())
+}
+
+fn write_csv(rows: &[Row], file: File) -> Result<(), Error> {
+    let mut writer = csv::Writer::from_writer(file);
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet(rows: &[Row], file: File) -> Result<(), Error> {
+    use std::rc::Rc;
+
+    use parquet::column::writer::ColumnWriter;
+    use parquet::data_type::ByteArray;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::{FileWriter, RowGroupWriter, SerializedFileWriter};
+    use parquet::schema::parser::parse_message_type;
+
+    let schema = Rc::new(parse_message_type(PARQUET_SCHEMA)?);
+    let properties = Rc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(file, schema, properties)?;
+    for rows in rows.chunks(ROW_GROUP_ROWS) {
+        let mut group = writer.next_row_group()?;
+        let mut column = 0;
+        while let Some(mut column_writer) = group.next_column()? {
+            match column_writer {
+                ColumnWriter::ByteArrayColumnWriter(ref mut w) => {
+                    let values = rows.iter()
+                        .map(|row| ByteArray::from(text_column(row, column)))
+                        .collect::<Vec<_>>();
+                    w.write_batch(&values, None, None)?;
+                }
+                ColumnWriter::Int64ColumnWriter(ref mut w) => {
+                    let dates = rows.iter()
+                        .filter_map(|row| row.date.map(|date| date.timestamp_millis()))
+                        .collect::<Vec<_>>();
+                    let defined = rows.iter().map(|row| row.date.is_some() as i16)
+                        .collect::<Vec<_>>();
+                    w.write_batch(&dates, Some(&defined), None)?;
+                }
+                ColumnWriter::DoubleColumnWriter(ref mut w) => {
+                    let values = rows.iter().map(|row| row.value).collect::<Vec<_>>();
+                    w.write_batch(&values, None, None)?;
+                }
+                _ => unreachable!("column {} isn't in the schema", column),
+            }
+            group.close_column(column_writer)?;
+            column += 1;
+        }
+        writer.close_row_group(group)?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+/// The value of the text column `column` (its index in the schema) of `row`.
+#[cfg(feature = "parquet")]
+fn text_column<'a>(row: &'a Row, column: usize) -> &'a str {
+    match column {
+        0 => row.kind,
+        1 => row.artifact,
+        3 => row.triple,
+        4 => row.flavor,
+        5 => row.collector,
+        6 => row.benchmark,
+        7 => row.category,
+        8 => &row.run,
+        9 => row.metric,
+        _ => unreachable!("column {} isn't text", column),
+    }
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet(_rows: &[Row], _file: File) -> Result<(), Error> {
+    unreachable!("Format::from_name doesn't accept parquet without Parquet support")
+}
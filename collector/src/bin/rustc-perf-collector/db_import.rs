@@ -0,0 +1,61 @@
+//! Imports the results in another instance's database (e.g. the SQLite file
+//! of a self-hosted collector) into a database, so that their history can be
+//! contributed upstream.
+//!
+//! Benchmarks and metrics are matched by name and get the ids of the database
+//! they're imported into, like any stored results. An artifact that's already
+//! in the database, i.e. with the same `ArtifactKey` (the same commit, triple,
+//! flavor and shard, or the same artifact id), is kept rather than imported,
+//! unless the imported ones are to replace it.
+
+use std::collections::HashSet;
+
+use failure::Error;
+
+use collector::db::{self, ArtifactKey};
+
+pub struct Options {
+    /// Replace the artifacts that are already in the database.
+    pub replace: bool,
+    /// Prepended to the ids of the imported artifacts (and of their runtime
+    /// results), e.g. `alice/`, so that they don't collide with the ones of
+    /// the same name, which are usually different local compilers.
+    pub artifact_prefix: Option<String>,
+}
+
+pub struct Summary {
+    pub imported: usize,
+    pub skipped: usize,
+}
+
+/// Imports the results in the database at `from` into the one at `to`.
+pub fn import(from: &str, to: &str, options: &Options) -> Result<Summary, Error> {
+    if from == to {
+        bail!("can't import a database into itself");
+    }
+    let mut results = db::open(from)?.load()?;
+    let total = results.commits.len() + results.artifacts.len() + results.runtime.len();
+
+    if let Some(ref prefix) = options.artifact_prefix {
+        let renamed = results.artifacts.iter().map(|a| a.id.clone()).collect::<HashSet<_>>();
+        for artifact in &mut results.artifacts {
+            artifact.id = format!("{}{}", prefix, artifact.id);
+        }
+        for runtime in results.runtime.iter_mut().filter(|r| renamed.contains(&r.id)) {
+            runtime.id = format!("{}{}", prefix, runtime.id);
+        }
+    }
+
+    let mut db = db::open(to)?;
+    if !options.replace {
+        let existing = ArtifactKey::all(&db.load()?).into_iter().collect::<HashSet<_>>();
+        results.commits.retain(|c| !existing.contains(&ArtifactKey::of_commit(c)));
+        results.artifacts.retain(|a| !existing.contains(&ArtifactKey::of_artifact(a)));
+        results.runtime.retain(|r| !existing.contains(&ArtifactKey::of_runtime(r)));
+    }
+    let imported = results.commits.len() + results.artifacts.len() + results.runtime.len();
+    if !results.is_empty() {
+        db.store(&results)?;
+    }
+    Ok(Summary { imported, skipped: total - imported })
+}
@@ -0,0 +1,95 @@
+//! Checks that there's enough free disk space to benchmark, before a
+//! collection and before every benchmark, so that a full disk is reported as
+//! such rather than as builds failing in mysterious ways halfway through.
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use failure::Error;
+
+/// How much space the builds of a benchmark are assumed to take until one
+/// has been measured: the target directory of its unmeasured build, and the
+/// copy of it that each iteration is built in.
+const DEFAULT_BENCHMARK_BYTES: u64 = 4 << 30;
+
+/// How much space a compiler that's downloaded into the cache takes.
+const ARTIFACT_BYTES: u64 = 1 << 30;
+
+lazy_static! {
+    /// The most space the builds of a benchmark took so far.
+    static ref LARGEST_BENCHMARK: Mutex<Option<u64>> = Mutex::new(None);
+}
+
+/// The free space, in bytes, of the file system `dir` is on (or would be,
+/// if it doesn't exist yet), if it can be determined.
+#[cfg(unix)]
+pub fn free_bytes(dir: &Path) -> Option<u64> {
+    extern crate libc;
+
+    use std::ffi::CString;
+    use std::mem;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = dir.ancestors()
+        .find(|dir| dir.exists())
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let path = CString::new(dir.as_os_str().as_bytes()).ok()?;
+    unsafe {
+        let mut stat: libc::statvfs = mem::zeroed();
+        if libc::statvfs(path.as_ptr(), &mut stat) != 0 {
+            return None;
+        }
+        Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+    }
+}
+
+#[cfg(windows)]
+pub fn free_bytes(_dir: &Path) -> Option<u64> {
+    None
+}
+
+fn gib(bytes: u64) -> f64 {
+    bytes as f64 / (1u64 << 30) as f64
+}
+
+/// Records that the builds of a benchmark took `bytes`, which makes the
+/// largest such size the estimate for the next ones.
+pub fn record_benchmark_size(bytes: u64) {
+    let mut largest = LARGEST_BENCHMARK.lock().unwrap();
+    if largest.map_or(true, |largest| bytes > largest) {
+        debug!("the builds of a benchmark took {:.2} GiB", gib(bytes));
+        *largest = Some(bytes);
+    }
+}
+
+fn benchmark_bytes() -> u64 {
+    LARGEST_BENCHMARK.lock().unwrap().unwrap_or(DEFAULT_BENCHMARK_BYTES)
+}
+
+fn check_free(dir: &Path, needed: u64, what: &str) -> Result<(), Error> {
+    match free_bytes(dir) {
+        Some(free) if free < needed => {
+            bail!("not enough free disk space in {:?} for {}: {:.1} GiB free, about {:.1} GiB \
+                   needed", dir, what, gib(free), gib(needed));
+        }
+        Some(_) => Ok(()),
+        None => {
+            debug!("can't tell how much free disk space there is in {:?}", dir);
+            Ok(())
+        }
+    }
+}
+
+/// Checks, before a collection, that there's room in `build_dir` for the
+/// builds of `jobs` benchmarks at a time, and in `cache_dir` for a compiler.
+pub fn preflight(build_dir: &Path, jobs: usize, cache_dir: &Path) -> Result<(), Error> {
+    check_free(build_dir, benchmark_bytes() * jobs as u64, "building the benchmarks")?;
+    check_free(cache_dir, ARTIFACT_BYTES, "downloading a compiler")
+}
+
+/// Checks that there's room in `build_dir` for the builds of the benchmark
+/// `name`.
+pub fn check(build_dir: &Path, name: &str) -> Result<(), Error> {
+    check_free(build_dir, benchmark_bytes(), &format!("building {}", name))
+}
@@ -0,0 +1,147 @@
+//! Checks whether the machine is configured for stable measurements.
+//!
+//! Turbo boost, frequency scaling, SMT and ASLR all add noise to the
+//! measurements, so we record how they are set up alongside the results, and
+//! optionally refuse to benchmark at all if they aren't disabled.
+
+use std::fs;
+
+use collector::Environment;
+
+use layout;
+
+fn read_sys(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+fn turbo_boost() -> Option<bool> {
+    // intel_pstate has its own knob; acpi-cpufreq uses the generic one.
+    if let Some(no_turbo) = read_sys("/sys/devices/system/cpu/intel_pstate/no_turbo") {
+        return Some(no_turbo == "0");
+    }
+    read_sys("/sys/devices/system/cpu/cpufreq/boost").map(|boost| boost == "1")
+}
+
+fn cpu_mhz() -> Option<f64> {
+    let cpuinfo = read_sys("/proc/cpuinfo")?;
+    let mhz = cpuinfo.lines()
+        .filter(|line| line.starts_with("cpu MHz"))
+        .filter_map(|line| line.split(':').nth(1))
+        .filter_map(|mhz| mhz.trim().parse::<f64>().ok())
+        .collect::<Vec<_>>();
+    if mhz.is_empty() {
+        None
+    } else {
+        Some(mhz.iter().sum::<f64>() / mhz.len() as f64)
+    }
+}
+
+fn max_temperature() -> Option<f64> {
+    let zones = fs::read_dir("/sys/class/thermal").ok()?;
+    zones.filter_map(|zone| zone.ok())
+        .filter(|zone| zone.file_name().to_string_lossy().starts_with("thermal_zone"))
+        .filter_map(|zone| fs::read_to_string(zone.path().join("temp")).ok())
+        .filter_map(|temp| temp.trim().parse::<f64>().ok())
+        // The kernel reports millidegrees Celsius.
+        .map(|temp| temp / 1000.0)
+        .fold(None, |max, temp| Some(max.map_or(temp, |max: f64| max.max(temp))))
+}
+
+/// Why `perf` can't count the hardware events of user space, if it can't:
+/// `perf_event_paranoid` forbids it, or the kernel exposes no CPU PMU, as in
+/// many VMs and on ARM machines whose PMU isn't described to the kernel (in
+/// which case `perf` silently reports the events as not supported).
+pub fn pmu_problem() -> Option<String> {
+    // Root isn't subject to `perf_event_paranoid`. The effective uid is the
+    // second one, e.g. `Uid:	1000	1000	1000	1000`.
+    let is_root = read_sys("/proc/self/status").map_or(false, |status| {
+        status.lines()
+            .find(|line| line.starts_with("Uid:"))
+            .and_then(|line| line.split_whitespace().nth(2).map(|uid| uid == "0"))
+            .unwrap_or(false)
+    });
+    if let Some(paranoid) = read_sys("/proc/sys/kernel/perf_event_paranoid") {
+        if !is_root && paranoid.parse::<i32>().map_or(false, |paranoid| paranoid > 2) {
+            return Some(format!("kernel.perf_event_paranoid is {}, not at most 2", paranoid));
+        }
+    }
+    let devices = fs::read_dir("/sys/bus/event_source/devices").ok()?;
+    let has_cpu_pmu = devices.filter_map(|device| device.ok()).any(|device| {
+        let name = device.file_name().to_string_lossy().into_owned();
+        // `cpu` (or `cpu_core` and `cpu_atom`) on x86, and e.g.
+        // `armv8_pmuv3_0` or `armv8_cortex_a72` on ARM.
+        name.starts_with("cpu") || name.starts_with("armv")
+    });
+    if has_cpu_pmu {
+        None
+    } else {
+        Some(String::from("the kernel exposes no CPU PMU in /sys/bus/event_source/devices"))
+    }
+}
+
+/// Why the energy the CPU packages consume can't be measured with RAPL (see
+/// `--energy`), if it can't: there are no package zones in the powercap
+/// interface, as on most non-x86 machines and in VMs, or their counters can't
+/// be read, which takes root on recent kernels.
+pub fn rapl_problem() -> Option<String> {
+    let zones = match fs::read_dir("/sys/class/powercap") {
+        Ok(zones) => zones,
+        Err(_) => return Some(String::from("there is no /sys/class/powercap")),
+    };
+    let packages = zones.filter_map(|zone| zone.ok())
+        .map(|zone| zone.path())
+        .filter(|zone| {
+            fs::read_to_string(zone.join("name")).map_or(false, |name| name.starts_with("package"))
+        })
+        .collect::<Vec<_>>();
+    if packages.is_empty() {
+        return Some(String::from("there are no RAPL package zones in /sys/class/powercap"));
+    }
+    for package in packages {
+        let energy = package.join("energy_uj");
+        if let Err(e) = fs::read_to_string(&energy) {
+            return Some(format!("{} can't be read: {}", energy.display(), e));
+        }
+    }
+    None
+}
+
+/// Inspects the current state of the machine.
+pub fn current() -> Environment {
+    let turbo_boost = turbo_boost();
+    let cpu_governor = read_sys("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor");
+    let smt = read_sys("/sys/devices/system/cpu/smt/active").map(|active| active == "1");
+    // ASLR disabled for the collector and its children (`--disable-aslr`)
+    // counts, whatever the machine's setting.
+    let aslr = if layout::control().aslr_disabled {
+        Some(false)
+    } else {
+        read_sys("/proc/sys/kernel/randomize_va_space").map(|aslr| aslr != "0")
+    };
+
+    let mut issues = Vec::new();
+    if turbo_boost == Some(true) {
+        issues.push(String::from("turbo boost is enabled"));
+    }
+    if let Some(ref governor) = cpu_governor {
+        if governor != "performance" {
+            issues.push(format!("the CPU governor is `{}`, not `performance`", governor));
+        }
+    }
+    if smt == Some(true) {
+        issues.push(String::from("SMT (hyper-threading) is enabled"));
+    }
+    if aslr == Some(true) {
+        issues.push(String::from("ASLR is enabled"));
+    }
+
+    Environment {
+        turbo_boost,
+        cpu_governor,
+        smt,
+        aslr,
+        cpu_mhz: cpu_mhz(),
+        max_temperature: max_temperature(),
+        issues,
+    }
+}
@@ -2,25 +2,40 @@
 
 use std::env;
 use std::path::{Path, PathBuf};
-use std::process::{self, Command};
+use std::process::{self, Command, Stdio};
 use std::str;
 use std::f64;
-use std::io::Write;
+use std::io::{self, Read, Write};
 use std::fs::{self, File};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::cmp;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use tempfile::TempDir;
 
-use collector::{Benchmark as CollectedBenchmark, BenchmarkState, Patch, Run, Stat};
-use collector::self_profile::SelfProfile;
+use collector::{Benchmark as CollectedBenchmark, BenchmarkState, Category, CodegenBackend,
+                LayoutControl, Linker, Patch, Run, Stat, TargetDirPolicy};
+use collector::self_profile::{QueryData, SelfProfile};
 
 use failure::{err_msg, Error, ResultExt};
 use serde_json;
 
-use {Compiler, BuildKind, RunKind};
+use artifact_size;
+use disk;
+use environment;
+use isolation;
+use layout;
+use log::Level;
 
-fn command_output(cmd: &mut Command) -> Result<process::Output, Error> {
+use logging;
+use progress::Progress;
+use shutdown;
+use {build_kinds_from_arg, run_kinds_from_arg, Compiler, BuildKind, RunKind};
+
+pub fn command_output(cmd: &mut Command) -> Result<process::Output, Error> {
     trace!("running: {:?}", cmd);
     let output = cmd.output()?;
     if !output.status.success() {
@@ -34,6 +49,185 @@ fn command_output(cmd: &mut Command) -> Result<process::Output, Error> {
     Ok(output)
 }
 
+/// Like `command_output`, but kills the command (and everything it started)
+/// if it takes longer than `timeout`. The command must have been set up with
+/// `new_process_group`.
+fn command_output_with_timeout(cmd: &mut Command, timeout: Duration)
+                               -> Result<process::Output, Error> {
+    trace!("running: {:?}", cmd);
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    // The output has to be read while waiting, or the child blocks once the
+    // pipes are full.
+    let stdout = read_in_background(child.stdout.take().unwrap());
+    let stderr = read_in_background(child.stderr.take().unwrap());
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed() > timeout {
+            kill_process_group(&child);
+            let _ = child.wait();
+            bail!("timed out after {} seconds", timeout.as_secs());
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+
+    let output = process::Output {
+        status,
+        stdout: stdout.join().unwrap(),
+        stderr: stderr.join().unwrap(),
+    };
+    if !output.status.success() {
+        bail!(
+            "expected success, got {}\n\nstderr={}\n\n stdout={}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr),
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+    Ok(output)
+}
+
+fn read_in_background<R: Read + Send + 'static>(mut r: R) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = r.read_to_end(&mut buf);
+        buf
+    })
+}
+
+/// A pipe that rustc-fake reports its measurements over, instead of mixing
+/// them into the output of cargo and rustc. Its write end is inherited by
+/// everything the command it's passed to starts, and its number is passed to
+/// rustc-fake in `RUSTC_PERF_RESULTS_FD`.
+#[cfg(unix)]
+struct ResultsPipe {
+    write_fd: i32,
+    contents: thread::JoinHandle<Vec<u8>>,
+}
+
+#[cfg(unix)]
+impl ResultsPipe {
+    fn new(cmd: &mut Command) -> Result<ResultsPipe, Error> {
+        extern crate libc;
+
+        use std::os::unix::io::FromRawFd;
+        use std::os::unix::process::CommandExt;
+
+        let mut fds = [0; 2];
+        unsafe {
+            // Only `cmd` is meant to inherit the write end, and not whatever
+            // other threads happen to start in the meantime (with `--jobs`),
+            // which would keep the pipe open until they exit.
+            if cloexec_pipe(&mut fds) != 0 {
+                bail!("failed to create a pipe: {}", io::Error::last_os_error());
+            }
+            let write_fd = fds[1];
+            cmd.pre_exec(move || {
+                libc::fcntl(write_fd, libc::F_SETFD, 0);
+                Ok(())
+            });
+        }
+        cmd.env("RUSTC_PERF_RESULTS_FD", fds[1].to_string());
+        // The pipe is read while the command runs, so that rustc-fake never
+        // blocks on a full pipe.
+        let read = unsafe { File::from_raw_fd(fds[0]) };
+        Ok(ResultsPipe {
+            write_fd: fds[1],
+            contents: read_in_background(read),
+        })
+    }
+
+    /// Returns everything reported over the pipe. Must only be called once
+    /// the command has exited, as the reading only ends once every write end
+    /// is closed.
+    fn finish(self) -> Vec<u8> {
+        extern crate libc;
+
+        unsafe {
+            libc::close(self.write_fd);
+        }
+        self.contents.join().unwrap()
+    }
+}
+
+/// Creates a pipe whose ends are closed on exec, atomically where the OS
+/// can, so that no other thread's child inherits them in between.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn cloexec_pipe(fds: &mut [i32; 2]) -> i32 {
+    extern crate libc;
+
+    libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC)
+}
+
+/// macOS has no `pipe2`, so the ends are only made close-on-exec once the
+/// pipe exists.
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+unsafe fn cloexec_pipe(fds: &mut [i32; 2]) -> i32 {
+    extern crate libc;
+
+    if libc::pipe(fds.as_mut_ptr()) != 0 {
+        return -1;
+    }
+    libc::fcntl(fds[0], libc::F_SETFD, libc::FD_CLOEXEC);
+    libc::fcntl(fds[1], libc::F_SETFD, libc::FD_CLOEXEC);
+    0
+}
+
+/// Without a pipe, rustc-fake reports to its stdout, which cargo passes on.
+#[cfg(windows)]
+struct ResultsPipe;
+
+#[cfg(windows)]
+impl ResultsPipe {
+    fn new(_cmd: &mut Command) -> Result<ResultsPipe, Error> {
+        Ok(ResultsPipe)
+    }
+
+    fn finish(self) -> Vec<u8> {
+        Vec::new()
+    }
+}
+
+/// Makes `cmd` start a new process group, so that it can be killed along
+/// with all of its descendants (cargo doesn't kill the rustcs it started when
+/// it is killed itself).
+#[cfg(unix)]
+fn new_process_group(cmd: &mut Command) {
+    extern crate libc;
+
+    use std::os::unix::process::CommandExt;
+
+    unsafe {
+        cmd.pre_exec(|| {
+            libc::setpgid(0, 0);
+            Ok(())
+        });
+    }
+}
+
+#[cfg(unix)]
+fn kill_process_group(child: &process::Child) {
+    extern crate libc;
+
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+#[cfg(windows)]
+fn new_process_group(_cmd: &mut Command) {}
+
+#[cfg(windows)]
+fn kill_process_group(child: &process::Child) {
+    // There are no process groups to kill here; make do with killing
+    // the child itself.
+    let _ = Command::new("taskkill").arg("/F").arg("/T").arg("/PID")
+        .arg(child.id().to_string()).status();
+}
+
 fn touch_all(path: &Path) -> Result<(), Error> {
     let mut cmd = Command::new("bash");
     cmd.current_dir(path)
@@ -42,10 +236,48 @@ fn touch_all(path: &Path) -> Result<(), Error> {
     Ok(())
 }
 
+fn touch(file: &Path) -> Result<(), Error> {
+    let mut cmd = Command::new("touch");
+    cmd.arg(file);
+    command_output(&mut cmd)?;
+    Ok(())
+}
+
+/// How many times each benchmark is run.
+#[derive(Debug, Clone, Copy)]
+pub struct Iterations<'a> {
+    /// How many times each benchmark is run without measuring it before the
+    /// measured iterations, e.g. to get the files it reads into the page
+    /// cache and the CPU out of its power-saving states.
+    pub warmup: usize,
+    pub limit: IterationLimit<'a>,
+}
+
+impl<'a> Iterations<'a> {
+    pub fn fixed(n: usize) -> Iterations<'a> {
+        Iterations { warmup: 0, limit: IterationLimit::Fixed(n) }
+    }
+}
+
+/// How many measured iterations are done.
+#[derive(Debug, Clone, Copy)]
+pub enum IterationLimit<'a> {
+    /// Run this many times, or as often as the benchmark's `runs` config
+    /// allows, whichever is less.
+    Fixed(usize),
+    /// Keep running until the coefficient of variation of `stat` is at most
+    /// `max_cv` for every run kind, or until `max` iterations have been done.
+    Adaptive { stat: &'a str, max_cv: f64, max: usize },
+}
+
 fn default_runs() -> usize {
     3
 }
 
+fn default_timeout() -> u64 {
+    60 * 60
+}
+
 /// This is the internal representation of an individual benchmark's
 /// perf-config.json file.
 #[derive(Debug, Clone, Deserialize)]
@@ -59,6 +291,43 @@ struct BenchmarkConfig {
     runs: usize,
     #[serde(default)]
     supports_stable: bool,
+    /// Cargo features to enable.
+    #[serde(default)]
+    features: Vec<String>,
+    /// Build kinds (as accepted by `--builds`) this benchmark isn't run with.
+    #[serde(default)]
+    excluded_profiles: Vec<String>,
+    /// Run kinds (as accepted by `--runs`) this benchmark isn't run with.
+    #[serde(default)]
+    excluded_scenarios: Vec<String>,
+    /// Extra build kinds (`OptDebuginfo`, `DebugLto`) this benchmark is run
+    /// with, which no benchmark is run with by default.
+    #[serde(default)]
+    extra_profiles: Vec<String>,
+    /// The file to touch before each build to make cargo rebuild the crate,
+    /// relative to the benchmark directory. By default all `.rs` files are
+    /// touched.
+    touch_file: Option<String>,
+    #[serde(default)]
+    category: Category,
+    /// How long, in seconds, a single build may take before it is killed.
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+    /// How often a failed (or timed out) build is retried before the whole
+    /// benchmark is recorded as failed.
+    #[serde(default)]
+    retries: usize,
+    /// Whether each patch is measured on its own, against the unpatched
+    /// source, rather than on top of the patches before it.
+    #[serde(default)]
+    independent_patches: bool,
+    /// How the target directory is prepared for the measured builds, unless
+    /// `--target-dir` says otherwise.
+    target_dir: Option<TargetDirPolicy>,
+    /// Whether the benchmark is a workspace, all of whose members are built
+    /// (with `cargo check` or `cargo build`) and measured together.
+    #[serde(default)]
+    workspace: bool,
 }
 
 impl Default for BenchmarkConfig {
@@ -70,27 +339,138 @@ impl Default for BenchmarkConfig {
             disabled: false,
             runs: default_runs(),
             supports_stable: false,
+            features: Vec::new(),
+            excluded_profiles: Vec::new(),
+            excluded_scenarios: Vec::new(),
+            extra_profiles: Vec::new(),
+            touch_file: None,
+            category: Category::default(),
+            timeout: default_timeout(),
+            retries: 0,
+            independent_patches: false,
+            target_dir: None,
+            workspace: false,
         }
     }
 }
 
+/// One of the builds `Benchmark::measure` does.
+pub struct PlannedBuild {
+    pub benchmark: String,
+    /// The name of the run this build is recorded as, e.g. `clean-check`.
+    pub run_name: String,
+    pub iteration: usize,
+    pub iterations: usize,
+    /// Whether this is one of the unmeasured warmup iterations, which are
+    /// numbered separately.
+    pub warmup: bool,
+    /// Whether this is the unmeasured rebuild after reverting the patch of
+    /// the run (with `independent_patches`).
+    pub revert: bool,
+}
+
+/// The builds `Benchmark::measure` does for a build kind, which
+/// `Benchmark::plan` lists.
+struct BuildKindSteps<'a> {
+    build_kind: BuildKind,
+    run_kinds: Vec<RunKind>,
+    /// Whether the build kind is skipped, as it was measured already.
+    skipped: bool,
+    /// The builds of each iteration, in order.
+    steps: Vec<Step<'a>>,
+}
+
+/// One of the builds of an iteration.
+enum Step<'a> {
+    /// A build measured as the run `run_kind_str` in `state`, after applying
+    /// `patch`, if any.
+    Measure {
+        run_kind: RunKind,
+        run_kind_str: String,
+        state: BenchmarkState,
+        patch: Option<&'a Patch>,
+    },
+    /// Reverting `patch` and rebuilding, without measuring, so that the next
+    /// patch starts from the same state.
+    Revert(&'a Patch),
+}
+
+impl<'a> Step<'a> {
+    fn measure(run_kind: RunKind, run_kind_str: &str, state: BenchmarkState,
+               patch: Option<&'a Patch>) -> Step<'a> {
+        Step::Measure { run_kind, run_kind_str: run_kind_str.to_string(), state, patch }
+    }
+}
+
+/// Whether any of `run_kinds` is incremental, which needs an incremental
+/// build from scratch first.
+fn has_incremental(run_kinds: &[RunKind]) -> bool {
+    run_kinds.contains(&RunKind::BaseIncr) ||
+        run_kinds.contains(&RunKind::CleanIncr) ||
+        run_kinds.contains(&RunKind::PatchedIncrs)
+}
+
+/// rustdoc has no incremental mode and NLL doesn't affect it, so doc builds
+/// only do clean runs.
+fn run_kinds_for_build(build_kind: BuildKind, run_kinds: &[RunKind]) -> &[RunKind] {
+    if build_kind != BuildKind::Doc {
+        run_kinds
+    } else if run_kinds.contains(&RunKind::Clean) {
+        &[RunKind::Clean]
+    } else {
+        &[]
+    }
+}
+
+/// The name under which a build of kind `build_kind` in `state` is recorded.
+fn run_name(build_kind: BuildKind, state: BenchmarkState, compiler: Compiler) -> String {
+    Run {
+        stats: Vec::new(),
+        samples: BTreeMap::new(),
+        self_profile: SelfProfile::default(),
+        check: build_kind == BuildKind::Check,
+        release: build_kind == BuildKind::Opt || build_kind == BuildKind::OptDebuginfo,
+        doc: build_kind == BuildKind::Doc,
+        debuginfo: build_kind == BuildKind::OptDebuginfo,
+        lto: build_kind == BuildKind::DebugLto,
+        target: compiler.target.map(String::from),
+        backend: compiler.backend,
+        linker: compiler.linker,
+        threads: compiler.threads,
+        iterations: None,
+        warmup: None,
+        state,
+        layout: LayoutControl::default(),
+        target_dir: TargetDirPolicy::Reuse,
+    }.name()
+}
+
+#[derive(Clone)]
 pub struct Benchmark {
     pub name: String,
     pub path: PathBuf,
     patches: Vec<Patch>,
     config: BenchmarkConfig,
+    excluded_build_kinds: Vec<BuildKind>,
+    excluded_run_kinds: Vec<RunKind>,
+    extra_build_kinds: Vec<BuildKind>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Profiler {
     PerfStat,
+    SelfProfile,
     TimePasses,
     PerfRecord,
+    Flamegraph,
     Cachegrind,
     Callgrind,
     DHAT,
     Massif,
     Eprintln,
+    LlvmTimeTrace,
+    Strace,
+    Xctrace,
 }
 
 #[derive(Fail, PartialEq, Eq, Debug)]
@@ -99,6 +479,8 @@ pub enum FromNameError {
     PerfStat,
     #[fail(display = "'{:?}' is not a known profiler", _0)]
     UnknownProfiler(String),
+    #[fail(display = "'{:?}' cannot be used to collect measurements", _0)]
+    NotMeasurable(String),
 }
 
 impl Profiler {
@@ -108,27 +490,52 @@ impl Profiler {
             // is rejected because it can't be used with the `profiler`
             // subcommand. (It's used with `bench_local` instead.)
             "perf-stat" => Err(FromNameError::PerfStat),
+            "self-profile" => Ok(Profiler::SelfProfile),
             "time-passes" => Ok(Profiler::TimePasses),
             "perf-record" => Ok(Profiler::PerfRecord),
+            "flamegraph" => Ok(Profiler::Flamegraph),
             "cachegrind" => Ok(Profiler::Cachegrind),
             "callgrind" => Ok(Profiler::Callgrind),
             "dhat" => Ok(Profiler::DHAT),
             "massif" => Ok(Profiler::Massif),
             "eprintln" => Ok(Profiler::Eprintln),
+            "llvm-trace" => Ok(Profiler::LlvmTimeTrace),
+            "strace" => Ok(Profiler::Strace),
+            "xctrace" => Ok(Profiler::Xctrace),
             _ => Err(FromNameError::UnknownProfiler(name.to_string())),
         }
     }
 
+    /// Like `from_name`, but for the profilers whose output can be turned into
+    /// stats by `MeasureProcessor`, i.e. the ones accepted by `bench_local`.
+    pub fn measure_from_name(name: &str) -> Result<Profiler, FromNameError> {
+        match name {
+            "perf-stat" => Ok(Profiler::PerfStat),
+            "time-passes" => Ok(Profiler::TimePasses),
+            "dhat" => Ok(Profiler::DHAT),
+            "strace" => Ok(Profiler::Strace),
+            _ => match Profiler::from_name(name) {
+                Ok(_) => Err(FromNameError::NotMeasurable(name.to_string())),
+                Err(e) => Err(e),
+            },
+        }
+    }
+
     fn name(&self) -> &'static str {
         match self {
             Profiler::PerfStat => "perf-stat",
+            Profiler::SelfProfile => "self-profile",
             Profiler::TimePasses => "time-passes",
             Profiler::PerfRecord => "perf-record",
+            Profiler::Flamegraph => "flamegraph",
             Profiler::Cachegrind => "cachegrind",
             Profiler::Callgrind => "callgrind",
             Profiler::DHAT => "dhat",
             Profiler::Massif => "massif",
             Profiler::Eprintln => "eprintln",
+            Profiler::LlvmTimeTrace => "llvm-trace",
+            Profiler::Strace => "strace",
+            Profiler::Xctrace => "xctrace",
         }
     }
 }
@@ -140,9 +547,32 @@ struct CargoProcess<'a> {
     incremental: bool,
     nll: bool,
     processor_etc: Option<(&'a mut dyn Processor, &'a str, RunKind, &'a str, Option<&'a Patch>)>,
+    /// Whether this is a warmup build, see `warmup`.
+    warmup: bool,
     manifest_path: String,
     cargo_args: Vec<String>,
     rustc_args: Vec<String>,
+    touch_file: Option<String>,
+    timeout: Duration,
+    retries: usize,
+    /// What a retried build restores the sources in `cwd` from, see
+    /// `sources`.
+    sources: Option<(&'a Path, Vec<&'a Patch>)>,
+    /// Where the stats of the codegen units of the crate are written, see
+    /// `cgu_stats`.
+    cgu_stats: Option<&'a mut Vec<Stat>>,
+    /// Where the build times of the units cargo builds are written, see
+    /// `unit_timings`.
+    unit_timings: Option<&'a mut Vec<Stat>>,
+    /// Where the sizes of the crate's AST and HIR are written, see
+    /// `ir_stats`.
+    ir_stats: Option<&'a mut Vec<Stat>>,
+    /// Where the size of the dep-graph and the reuse of the incremental
+    /// cache are written, see `incr_stats`.
+    incr_stats: Option<&'a mut Vec<Stat>>,
+    /// Whether all the members of the workspace are built and measured,
+    /// rather than a single crate.
+    workspace: bool,
 }
 
 impl<'a> CargoProcess<'a> {
@@ -162,8 +592,61 @@ impl<'a> CargoProcess<'a> {
         self
     }
 
+    /// Makes rustc print the items of each codegen unit, and writes their
+    /// stats (see `process_mono_items_output`) to `stats` after the build.
+    /// Only nightly compilers can do that, and only for the build kinds that
+    /// do codegen; otherwise `stats` is left empty.
+    fn cgu_stats(mut self, stats: &'a mut Vec<Stat>) -> Self {
+        self.cgu_stats = Some(stats);
+        self
+    }
+
+    /// Makes cargo report how long it took to build each unit (each crate,
+    /// and each build script and its run), and writes their times (see
+    /// `process_unit_timings_output`) to `stats` after the build. Only
+    /// nightly cargo can do that; otherwise `stats` is left empty.
+    fn unit_timings(mut self, stats: &'a mut Vec<Stat>) -> Self {
+        self.unit_timings = Some(stats);
+        self
+    }
+
+    /// Makes rustc print the number and size of the nodes of the crate's AST
+    /// and HIR, and writes their stats (see `process_hir_stats_output`) to
+    /// `stats` after the build. Only nightly compilers can do that;
+    /// otherwise `stats` is left empty.
+    fn ir_stats(mut self, stats: &'a mut Vec<Stat>) -> Self {
+        self.ir_stats = Some(stats);
+        self
+    }
+
+    /// Makes rustc print the size of the dep-graph and how many codegen
+    /// units it reused from the incremental cache, and writes their stats
+    /// (see `process_incremental_info_output`) to `stats` after the build.
+    /// Only incremental builds of nightly compilers do that; otherwise
+    /// `stats` is left empty.
+    fn incr_stats(mut self, stats: &'a mut Vec<Stat>) -> Self {
+        self.incr_stats = Some(stats);
+        self
+    }
+
+    /// With `warmup`, the build is done just like a measured one, profiler
+    /// and all, but its output isn't handed to the processor.
+    fn warmup(mut self, warmup: bool) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Makes a retried build first restore the sources in `cwd` (everything
+    /// but the target directory) from the benchmark's own directory `dir`,
+    /// and apply `patches` to them again, in case the failed attempt left
+    /// them changed.
+    fn sources(mut self, dir: &'a Path, patches: Vec<&'a Patch>) -> Self {
+        self.sources = Some((dir, patches));
+        self
+    }
+
     fn base_command(&self, cwd: &Path, subcommand: &str) -> Command {
-        let mut cmd = Command::new(Path::new("cargo"));
+        let mut cmd = isolation::sandboxed_command(Path::new("cargo"));
         cmd
             // Not all cargo invocations (e.g. `cargo clean`) need all of these
             // env vars set, but it doesn't hurt to have them.
@@ -174,6 +657,8 @@ impl<'a> CargoProcess<'a> {
             .env("PATH", env::var_os("PATH").unwrap_or_default())
             .env("RUSTC", &*FAKE_RUSTC)
             .env("RUSTC_REAL", &self.compiler.rustc)
+            .env("RUSTDOC", &*FAKE_RUSTDOC)
+            .env("RUSTDOC_REAL", self.compiler.rustdoc())
             .env("CARGO", &self.compiler.cargo)
             .env(
                 "CARGO_INCREMENTAL",
@@ -182,6 +667,7 @@ impl<'a> CargoProcess<'a> {
             .current_dir(cwd)
             .arg(subcommand)
             .arg("--manifest-path").arg(&self.manifest_path);
+        layout::pad_environment(&mut cmd);
         cmd
     }
 
@@ -197,45 +683,101 @@ impl<'a> CargoProcess<'a> {
     }
 
     fn run_rustc(&mut self) -> Result<(), Error> {
+        // Once the collector is asked to shut down, the builds in progress
+        // are finished, but no new ones are started.
+        shutdown::check()?;
+        let _span = self.processor_etc.as_ref().map(|&(_, _, _, run_kind_str, patch)| {
+            let run_kind = match patch {
+                Some(patch) => format!("{} ({})", run_kind_str, patch.name),
+                None => run_kind_str.to_string(),
+            };
+            logging::span("run_kind", run_kind, Level::Debug)
+        });
         loop {
-            let mut cmd = self.base_command(self.cwd, "rustc");
-            cmd.arg("-p").arg(self.get_pkgid(self.cwd));
-            match self.build_kind {
-                BuildKind::Check => { cmd.arg("--profile").arg("check"); }
-                BuildKind::Debug => {}
-                BuildKind::Opt => { cmd.arg("--release"); }
-            }
-            cmd.args(&self.cargo_args);
-            cmd.arg("--");
-            if self.nll {
-                cmd.arg("-Zborrowck=mir");
-                cmd.arg("-Ztwo-phase-borrows");
-            }
-            if self.compiler.is_nightly {
-                cmd.arg("-Zself-profile");
-                cmd.arg("-Zprofile-json");
-            }
-            // --wrap-rustc-with is not a valid rustc flag. But rustc-fake
-            // recognizes it, strips it (and its argument) out, and uses it as an
-            // indicator that the rustc invocation should be profiled. This works
-            // out nicely because `cargo rustc` only passes arguments after '--'
-            // onto rustc for the final crate, which is exactly the crate for which
-            // we want to wrap rustc.
-            if let Some((ref mut processor, ..)) = self.processor_etc {
-                cmd.arg("--wrap-rustc-with");
-                cmd.arg(processor.profiler().name());
-                cmd.args(&self.rustc_args);
-            }
-
-            debug!("{:?}", cmd);
-
-            touch_all(&self.cwd)?;
-
-            let output = command_output(&mut cmd)?;
+            let mut attempt = 0;
+            let (output, cargo_wall_time) = loop {
+                // A build that's retried starts over from the sources the
+                // first attempt started from, touched again. The target
+                // directory is left as the failed attempt left it: cargo only
+                // records the units that were built completely, and rustc
+                // only keeps the incremental sessions that finished, so what
+                // the failed attempt didn't finish is built again.
+                if attempt > 0 {
+                    if let Some((dir, ref patches)) = self.sources {
+                        restore_sources(dir, self.cwd, patches)?;
+                    }
+                }
+                match self.touch_file {
+                    Some(ref file) => touch(&self.cwd.join(file))?,
+                    None => touch_all(&self.cwd)?,
+                }
+
+                // Every attempt gets a command of its own, as the results
+                // pipe of an earlier one is closed.
+                let mut cmd = if self.build_kind == BuildKind::Doc {
+                    self.doc_command()
+                } else {
+                    self.rustc_command()
+                };
+                new_process_group(&mut cmd);
+                debug!("{:?}", cmd);
+
+                let results = ResultsPipe::new(&mut cmd)?;
+                // Only the attempt that succeeds is timed, not the ones that
+                // failed before it.
+                let start = Instant::now();
+                let output = command_output_with_timeout(&mut cmd, self.timeout);
+                let cargo_wall_time = start.elapsed();
+                let reported = results.finish();
+                match output {
+                    Ok(mut output) => {
+                        // The processors expect the measurements where
+                        // rustc-fake used to print them.
+                        if !reported.is_empty() {
+                            output.stdout = reported;
+                        }
+                        break (output, cargo_wall_time);
+                    }
+                    Err(e) => {
+                        if attempt == self.retries {
+                            return Err(e);
+                        }
+                        attempt += 1;
+                        warn!("build failed, retrying ({}/{}): {}", attempt, self.retries, e);
+                    }
+                }
+            };
+            if let Some(ref mut cgu_stats) = self.cgu_stats {
+                **cgu_stats = process_mono_items_output(&output.stdout);
+            }
+            if let Some(ref mut unit_timings) = self.unit_timings {
+                **unit_timings = process_unit_timings_output(&output.stdout);
+            }
+            if let Some(ref mut ir_stats) = self.ir_stats {
+                **ir_stats = process_hir_stats_output(&output.stdout);
+            }
+            if let Some(ref mut incr_stats) = self.incr_stats {
+                **incr_stats = process_incremental_info_output(&output.stderr);
+            }
             let self_profile_file = self.cwd.join("self_profiler_results.json");
             let self_profile_json = fs::read_to_string(&self_profile_file);
+            if self.warmup {
+                return Ok(());
+            }
             if let Some((ref mut processor, name, run_kind, run_kind_str, patch)) =
                     self.processor_etc {
+                let mut self_profile = self_profile_json.as_ref()
+                    .map(|s| serde_json::from_str(&s).unwrap())
+                    .unwrap_or_else(|_| {
+                        warn!("self profile results: {:?} from {:?}",
+                              self_profile_json, self_profile_file);
+                        SelfProfile::default()
+                    });
+                // When profiling, the self-profile data is the profile, and
+                // is processed as such.
+                if processor.profiler() != Profiler::SelfProfile {
+                    self_profile.query_data = summarize_queries(self.cwd);
+                }
                 let data = ProcessOutputData {
                     name,
                     cwd: self.cwd,
@@ -243,13 +785,8 @@ impl<'a> CargoProcess<'a> {
                     run_kind,
                     run_kind_str,
                     patch,
-                    self_profile: self_profile_json.as_ref()
-                        .map(|s| serde_json::from_str(&s).unwrap())
-                        .unwrap_or_else(|_| {
-                            eprintln!("self profile results: {:?} from {:?}",
-                                self_profile_json, self_profile_file);
-                            SelfProfile::default()
-                        }),
+                    cargo_wall_time,
+                    self_profile,
                 };
                 match processor.process_output(&data, output) {
                     Ok(Retry::No) => return Ok(()),
@@ -261,17 +798,365 @@ impl<'a> CargoProcess<'a> {
             }
         }
     }
+
+    fn rustc_command(&self) -> Command {
+        let mut cmd = if self.workspace {
+            let subcommand = if self.build_kind == BuildKind::Check { "check" } else { "build" };
+            let mut cmd = self.base_command(self.cwd, subcommand);
+            cmd.arg("--workspace");
+            cmd
+        } else {
+            let mut cmd = self.base_command(self.cwd, "rustc");
+            cmd.arg("-p").arg(self.get_pkgid(self.cwd));
+            if self.build_kind == BuildKind::Check {
+                cmd.arg("--profile").arg("check");
+            }
+            cmd
+        };
+        match self.build_kind {
+            BuildKind::Check | BuildKind::Debug | BuildKind::DebugLto => {}
+            BuildKind::Opt | BuildKind::OptDebuginfo => { cmd.arg("--release"); }
+            BuildKind::Doc => unreachable!(),
+        }
+        if let Some(target) = self.compiler.target {
+            cmd.arg("--target").arg(target);
+        }
+        // Check builds don't link, so there's no link time to measure (see
+        // `wrap_linker` in rustc-fake).
+        if self.build_kind != BuildKind::Check {
+            cmd.env("RUSTC_PERF_LINK_TIME", "1");
+        }
+        cmd.args(&self.cargo_args);
+        if self.unit_timings.is_some() && self.compiler.is_nightly {
+            cmd.arg("-Zunstable-options").arg("-Ztimings=json");
+        }
+
+        // The arguments for the rustc of the benchmarked crate(s) only.
+        let mut args = Vec::new();
+        if self.nll {
+            args.push("-Zborrowck=mir".to_string());
+            args.push("-Ztwo-phase-borrows".to_string());
+        }
+        match self.build_kind {
+            BuildKind::OptDebuginfo => { args.push("-Cdebuginfo=2".to_string()); }
+            BuildKind::DebugLto => { args.push("-Clto".to_string()); }
+            _ => {}
+        }
+        if !self.compiler.backend.is_llvm() {
+            args.push(format!("-Zcodegen-backend={}", self.compiler.backend.name()));
+        }
+        match self.compiler.linker {
+            Linker::Default => {}
+            // Through `cc`, which is what rustc links with by default on
+            // Unix.
+            linker => { args.push(format!("-Clink-arg=-fuse-ld={}", linker.name())); }
+        }
+        if let Some(threads) = self.compiler.threads {
+            args.push(format!("-Zthreads={}", threads));
+        }
+        if self.compiler.is_nightly {
+            args.push("-Zself-profile".to_string());
+            args.push("-Zprofile-json".to_string());
+            if self.incremental && self.incr_stats.is_some() {
+                args.push("-Zincremental-info".to_string());
+            }
+            if self.cgu_stats.is_some() {
+                args.push("-Zprint-mono-items=lazy".to_string());
+            }
+            if self.ir_stats.is_some() {
+                args.push("-Zhir-stats".to_string());
+            }
+        }
+        // --wrap-rustc-with is not a valid rustc flag. But rustc-fake
+        // recognizes it, strips it (and its argument) out, and uses it as an
+        // indicator that the rustc invocation should be profiled. This works
+        // out nicely because `cargo rustc` only passes arguments after '--'
+        // onto rustc for the final crate, which is exactly the crate for which
+        // we want to wrap rustc.
+        if let Some((ref processor, ..)) = self.processor_etc {
+            args.push("--wrap-rustc-with".to_string());
+            args.push(processor.profiler().name().to_string());
+            args.extend(self.rustc_args.iter().cloned());
+        }
+
+        if self.workspace {
+            // `cargo check` and `cargo build` can't pass arguments to the
+            // rustcs of the workspace's members only, so rustc-fake adds
+            // these to the rustcs cargo runs for its primary packages.
+            cmd.env("RUSTC_PERF_MEMBER_ARGS", args.join(MEMBER_ARGS_SEPARATOR));
+        } else {
+            cmd.arg("--").args(&args);
+        }
+        cmd
+    }
+
+    fn doc_command(&self) -> Command {
+        let mut cmd = self.base_command(self.cwd, "doc");
+        cmd.arg("--no-deps");
+        if self.workspace {
+            cmd.arg("--workspace");
+        } else {
+            cmd.arg("-p").arg(self.get_pkgid(self.cwd));
+        }
+        if let Some(target) = self.compiler.target {
+            cmd.arg("--target").arg(target);
+        }
+        cmd.args(&self.cargo_args);
+        // With --no-deps rustdoc is only run on the benchmark crate itself, so
+        // passing --wrap-rustc-with through RUSTDOCFLAGS reaches exactly the
+        // invocation we want to profile. rustdoc-fake hands it on to
+        // rustc-fake, which wraps rustdoc instead of rustc.
+        if let Some((ref processor, ..)) = self.processor_etc {
+            cmd.env("RUSTDOCFLAGS",
+                    format!("--wrap-rustc-with {}", processor.profiler().name()));
+        }
+        cmd
+    }
+}
+
+/// Makes all benchmarks use `policy` for their target directories, whatever
+/// their `perf-config.json` says.
+pub fn set_target_dir_policy(policy: TargetDirPolicy) -> Result<(), Error> {
+    let tmpfs_dir = TMPFS_DIR.lock().unwrap();
+    if policy == TargetDirPolicy::Tmpfs && !tmpfs_dir.is_dir() {
+        bail!("the tmpfs target directory policy needs a tmpfs at {:?}", *tmpfs_dir);
+    }
+    *TARGET_DIR_POLICY.lock().unwrap() = Some(policy);
+    Ok(())
+}
+
+/// Makes every benchmark record how long cargo took to build each of its
+/// units in the first (unmeasured) build of each build kind, as the
+/// `unit-time:$UNIT` stats of its `Clean` run. This shows whether a change in
+/// the build time of a benchmark is due to the benchmarked crate or to one of
+/// its dependencies.
+pub fn record_unit_timings() {
+    *UNIT_TIMINGS.lock().unwrap() = true;
+}
+
+/// Makes every benchmark record the number and size of the nodes of its AST
+/// and HIR in the first (unmeasured) build of each build kind, as stats of
+/// its `Clean` run (see `process_hir_stats_output`). This shows a change
+/// that makes the front-end's data structures grow even if it doesn't make
+/// the build much slower.
+pub fn record_ir_stats() {
+    *IR_STATS.lock().unwrap() = true;
+}
+
+/// Makes every benchmark record the dep-graph and reuse stats
+/// `-Zincremental-info` prints for its incremental runs (see
+/// `measure_incr_stats`). This costs an extra, unmeasured round of the
+/// incremental builds of each build kind.
+pub fn record_incr_stats() {
+    *INCR_STATS.lock().unwrap() = true;
+}
+
+/// Makes the `tmpfs` policy use the tmpfs at `dir` rather than `/dev/shm`.
+pub fn set_tmpfs_dir(dir: &Path) {
+    *TMPFS_DIR.lock().unwrap() = dir.to_path_buf();
+}
+
+/// The directory benchmarks are built in, on a tmpfs with `tmpfs`.
+fn build_dir(tmpfs: bool) -> PathBuf {
+    if tmpfs {
+        TMPFS_DIR.lock().unwrap().clone()
+    } else {
+        layout::work_dir()
+    }
+}
+
+/// The directory benchmarks are built in, unless their `perf-config.json`
+/// asks for a tmpfs.
+pub fn default_build_dir() -> PathBuf {
+    build_dir(*TARGET_DIR_POLICY.lock().unwrap() == Some(TargetDirPolicy::Tmpfs))
+}
+
+/// Removes a directory a benchmark was built in as soon as it's done with.
+/// Unlike dropping it, this reports a failure to, as every directory left
+/// behind takes up space until the disk is full.
+fn remove_temp_dir(dir: TempDir) {
+    let path = dir.path().to_path_buf();
+    if let Err(e) = dir.close() {
+        warn!("failed to remove {:?}: {}", path, e);
+    }
+}
+
+/// Replaces everything in `dir` but its target directory with the contents
+/// of the benchmark directory `sources`, and applies `patches` to them.
+fn restore_sources(sources: &Path, dir: &Path, patches: &[&Patch]) -> Result<(), Error> {
+    for entry in fs::read_dir(dir).with_context(|_| format!("failed to list {:?}", dir))? {
+        let entry = entry?;
+        if entry.file_name() == "target" {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(entry.path())?;
+        } else {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    for entry in fs::read_dir(sources)
+            .with_context(|_| format!("failed to list {:?}", sources))? {
+        let entry = entry?;
+        if entry.file_name() == "target" {
+            continue;
+        }
+        let mut cmd = Command::new("cp");
+        cmd.arg("-R").arg(entry.path()).arg(dir);
+        command_output(&mut cmd)
+            .with_context(|_| format!("copying {:?} to {:?}", entry.path(), dir))?;
+    }
+    for patch in patches {
+        patch.apply(dir).map_err(|s| err_msg(s))?;
+    }
+    Ok(())
+}
+
+/// Evicts the files in `dir` from the page cache, after writing them back,
+/// so that the next build has to read them from disk.
+#[cfg(target_os = "linux")]
+fn evict_from_page_cache(dir: &Path) -> Result<(), Error> {
+    extern crate libc;
+
+    use std::os::unix::io::AsRawFd;
+
+    unsafe {
+        libc::sync();
+    }
+    for entry in fs::read_dir(dir).with_context(|_| format!("failed to list {:?}", dir))? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            evict_from_page_cache(&entry.path())?;
+        } else if file_type.is_file() {
+            let file = File::open(entry.path())?;
+            unsafe {
+                libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn evict_from_page_cache(_dir: &Path) -> Result<(), Error> {
+    bail!("the wipe target directory policy is only supported on Linux");
+}
+
+/// The extensions of the files written by `-Zself-profile`: a single
+/// `.mm_profdata` file in recent compilers, separate event and string table
+/// files in older ones.
+const SELF_PROFILE_EXTENSIONS: &[&str] = &[
+    "mm_profdata", "events", "string_data", "string_index",
+];
+
+/// The part of the JSON written by `summarize summarize --json` that is
+/// recorded.
+#[derive(Deserialize)]
+struct Summary {
+    query_data: Vec<SummaryQueryData>,
+}
+
+#[derive(Deserialize)]
+struct SummaryQueryData {
+    label: String,
+    #[serde(default)]
+    invocation_count: u64,
+    #[serde(default)]
+    number_of_cache_hits: u64,
+    #[serde(default)]
+    incremental_load_time: Duration,
+    #[serde(default)]
+    self_time: Duration,
+}
+
+/// Summarizes the per-query data of the `-Zself-profile` files in `cwd`, and
+/// removes them so that they don't end up in the next iteration's. Returns
+/// nothing if there are none, or measureme's `summarize` isn't in `PATH`.
+fn summarize_queries(cwd: &Path) -> Vec<QueryData> {
+    let files = match fs::read_dir(cwd) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+                SELF_PROFILE_EXTENSIONS.contains(&extension)
+            })
+            .collect::<Vec<_>>(),
+        Err(_) => return Vec::new(),
+    };
+    let stem = match files.first() {
+        Some(file) => file.with_extension(""),
+        None => return Vec::new(),
+    };
+
+    let summary = Command::new("summarize")
+        .arg("summarize")
+        .arg("--json")
+        .arg(&stem)
+        .output();
+    let json_file = stem.with_extension("json");
+    let queries = match summary {
+        Ok(ref output) if output.status.success() => {
+            fs::read_to_string(&json_file).ok()
+                .and_then(|json| serde_json::from_str::<Summary>(&json).ok())
+                .map(|summary| summary.query_data)
+                .unwrap_or_default()
+        }
+        _ => {
+            debug!("failed to summarize the self-profile data {:?}", stem);
+            Vec::new()
+        }
+    };
+    for file in files.iter().chain(Some(&json_file)) {
+        let _ = fs::remove_file(file);
+    }
+
+    queries.into_iter()
+        .map(|q| QueryData {
+            label: q.label,
+            invocations: q.invocation_count,
+            cache_hits: q.number_of_cache_hits,
+            incremental_load_time_ms: q.incremental_load_time.as_secs() as f64 * 1e3
+                + f64::from(q.incremental_load_time.subsec_nanos()) / 1e6,
+            self_time_ms: q.self_time.as_secs() as f64 * 1e3
+                + f64::from(q.self_time.subsec_nanos()) / 1e6,
+        })
+        .collect()
 }
 
 lazy_static! {
+    /// The `--target-dir` policy, which overrides the benchmarks' own.
+    static ref TARGET_DIR_POLICY: Mutex<Option<TargetDirPolicy>> = Mutex::new(None);
+    /// Whether to record the build times of the units cargo builds, see
+    /// `record_unit_timings`.
+    static ref UNIT_TIMINGS: Mutex<bool> = Mutex::new(false);
+    /// Whether to record the sizes of the AST and HIR, see
+    /// `record_ir_stats`.
+    static ref IR_STATS: Mutex<bool> = Mutex::new(false);
+    /// Whether to record the stats of `-Zincremental-info`, see
+    /// `record_incr_stats`.
+    static ref INCR_STATS: Mutex<bool> = Mutex::new(false);
+    /// Where the directories of benchmarks with the `tmpfs` policy are
+    /// created.
+    static ref TMPFS_DIR: Mutex<PathBuf> = Mutex::new(PathBuf::from("/dev/shm"));
     static ref FAKE_RUSTC: PathBuf = {
         let mut fake_rustc = env::current_exe().unwrap();
         fake_rustc.pop();
-        fake_rustc.push("rustc-fake");
+        fake_rustc.push(format!("rustc-fake{}", env::consts::EXE_SUFFIX));
         fake_rustc
     };
+    static ref FAKE_RUSTDOC: PathBuf = {
+        let mut fake_rustdoc = env::current_exe().unwrap();
+        fake_rustdoc.pop();
+        fake_rustdoc.push(format!("rustdoc-fake{}", env::consts::EXE_SUFFIX));
+        fake_rustdoc
+    };
 }
 
+/// Separates the arguments in `RUSTC_PERF_MEMBER_ARGS`, which rustc-fake
+/// splits them at again.
+const MEMBER_ARGS_SEPARATOR: &str = "\x1f";
+
 /// Used to indicate if we need to retry a run.
 pub enum Retry {
     No,
@@ -286,6 +1171,9 @@ pub struct ProcessOutputData<'a> {
     run_kind_str: &'a str,
     self_profile: SelfProfile,
     patch: Option<&'a Patch>,
+    /// How long the whole cargo invocation took, including cargo itself and
+    /// checking that the dependencies are fresh.
+    cargo_wall_time: Duration,
 }
 
 /// Trait used by `Benchmark::measure()` to provide different kinds of
@@ -301,48 +1189,246 @@ pub trait Processor {
     /// Called when all the runs of a benchmark for a particular `BuildKind`
     /// have been completed. Can be used to process/reset accumulated state.
     fn finish_build_kind(&mut self, _build_kind: BuildKind) {}
-}
 
-pub struct MeasureProcessor {
-    clean_stats: Vec<(Vec<Stat>, SelfProfile)>,
-    nll_stats: Vec<(Vec<Stat>, SelfProfile)>,
-    base_incr_stats: Vec<(Vec<Stat>, SelfProfile)>,
-    clean_incr_stats: Vec<(Vec<Stat>, SelfProfile)>,
-    patched_incr_stats: Vec<(Patch, Vec<(Vec<Stat>, SelfProfile)>)>,
+    /// Whether `build_kind` was already measured, e.g. before the collection
+    /// was interrupted, and should be skipped.
+    fn skip_build_kind(&self, _build_kind: BuildKind) -> bool {
+        false
+    }
 
-    pub collected: CollectedBenchmark,
+    /// The largest coefficient of variation of `stat` across the run kinds
+    /// measured so far for the current `BuildKind`, if there is enough data
+    /// to tell. Used for `IterationLimit::Adaptive`.
+    fn variation(&self, _stat: &str) -> Option<f64> {
+        None
+    }
+
+    /// Called before a benchmark is built, with how its target directory is
+    /// prepared.
+    fn target_dir_policy(&mut self, _policy: TargetDirPolicy) {}
+
+    /// Called before `finish_build_kind`, with the stats of the codegen
+    /// units of a full non-incremental build of the current `BuildKind`.
+    /// Empty if they couldn't be determined.
+    fn cgu_stats(&mut self, _stats: Vec<Stat>) {}
+
+    /// Called before `finish_build_kind`, with the build times of the units
+    /// of a full non-incremental build of the current `BuildKind`, if they
+    /// were recorded (see `record_unit_timings`).
+    fn unit_timings(&mut self, _stats: Vec<Stat>) {}
+
+    /// Called before `finish_build_kind`, with the sizes of the AST and HIR
+    /// of the crate in the current `BuildKind`, if they were recorded (see
+    /// `record_ir_stats`).
+    fn ir_stats(&mut self, _stats: Vec<Stat>) {}
+
+    /// Called before `finish_build_kind`, with the dep-graph and cache reuse
+    /// stats of each incremental run kind of the current `BuildKind`, from an
+    /// unmeasured build. Empty if they couldn't be determined.
+    fn incr_stats(&mut self, _stats: Vec<(BenchmarkState, Vec<Stat>)>) {}
 }
 
+/// Returns the coefficient of variation (standard deviation divided by mean)
+/// of `stat` across `runs`, or `None` if there are fewer than two samples.
+fn coefficient_of_variation(stat: &str, runs: &[(Vec<Stat>, SelfProfile)]) -> Option<f64> {
+    let values = runs.iter()
+        .filter_map(|&(ref stats, _)| stats.iter().find(|s| s.name == stat))
+        .map(|s| s.cnt)
+        .collect::<Vec<_>>();
+    if values.len() < 2 {
+        return None;
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    if mean == 0.0 {
+        return Some(0.0);
+    }
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    Some(variance.sqrt() / mean)
+}
+
+/// Records how long macro expansion took, according to the self-profile, as
+/// `expansion-time`, and how much of that was spent expanding procedural
+/// macros (including derives) as `proc-macro-time`, both in seconds. This
+/// tells regressions in the proc-macro infrastructure (or in the proc-macro
+/// crates, e.g. `syn`, that the benchmark uses) apart from the rest of the
+/// front end.
+///
+/// They come from the per-query summary of `summarize` if there is one: the
+/// time spent in `expand_crate` itself, and in the activities expanding
+/// procedural macros (`expand_proc_macro` and the like), which it calls.
+/// Otherwise only `expansion-time` is recorded, from the `Expansion`
+/// category of older compilers. Without either, nothing is recorded.
+fn expansion_stats(self_profile: &SelfProfile) -> Vec<Stat> {
+    let ms_to_stat = |name: &str, ms: f64| Stat { name: name.to_string(), cnt: ms / 1e3 };
+    if !self_profile.query_data.is_empty() {
+        let is_proc_macro = |label: &str| {
+            label.starts_with("expand_") && label.contains("proc_macro")
+        };
+        let proc_macro_ms = self_profile.query_data.iter()
+            .filter(|q| is_proc_macro(&q.label))
+            .map(|q| q.self_time_ms)
+            .sum::<f64>();
+        let expand_crate_ms = self_profile.query_data.iter()
+            .filter(|q| q.label == "expand_crate")
+            .map(|q| q.self_time_ms)
+            .sum::<f64>();
+        return vec![
+            ms_to_stat("expansion-time", expand_crate_ms + proc_macro_ms),
+            ms_to_stat("proc-macro-time", proc_macro_ms),
+        ];
+    }
+    self_profile.category_data.iter()
+        .filter(|c| c.name == "Expansion")
+        .map(|c| ms_to_stat("expansion-time", c.time_ms as f64))
+        .collect()
+}
+
+/// Records the wall time of the whole cargo invocation as `cargo-wall-time`,
+/// and how much of it wasn't spent in the measured rustc as `cargo-overhead`.
+/// `wall-time` covers just the measured rustc, so it isn't affected by noise
+/// from cargo, which can dominate for small benchmarks.
+fn add_cargo_stats(stats: &mut Vec<Stat>, cargo_wall_time: Duration) {
+    let cargo_wall_time = cargo_wall_time.as_secs() as f64
+        + f64::from(cargo_wall_time.subsec_nanos()) / 1e9;
+    let rustc_wall_time = match stats.iter().find(|s| s.name == "wall-time") {
+        Some(stat) => stat.cnt,
+        // Only `perf-stat` measures the wall time of rustc.
+        None => return,
+    };
+    stats.push(Stat { name: "cargo-wall-time".to_string(), cnt: cargo_wall_time });
+    stats.push(Stat {
+        name: "cargo-overhead".to_string(),
+        cnt: (cargo_wall_time - rustc_wall_time).max(0.0),
+    });
+}
+
+pub struct MeasureProcessor {
+    profiler: Profiler,
+    target: Option<String>,
+    backend: CodegenBackend,
+    linker: Linker,
+    threads: Option<u32>,
+    warmup: Option<usize>,
+    target_dir: TargetDirPolicy,
+    /// Added to the stats of the `Clean` runs of the current build kind.
+    cgu_stats: Vec<Stat>,
+    unit_timings: Vec<Stat>,
+    ir_stats: Vec<Stat>,
+    /// Added to the stats of the incremental runs of the current build kind
+    /// with the same state.
+    incr_stats: Vec<(BenchmarkState, Vec<Stat>)>,
+
+    clean_stats: Vec<(Vec<Stat>, SelfProfile)>,
+    nll_stats: Vec<(Vec<Stat>, SelfProfile)>,
+    base_incr_stats: Vec<(Vec<Stat>, SelfProfile)>,
+    clean_incr_stats: Vec<(Vec<Stat>, SelfProfile)>,
+    patched_incr_stats: Vec<(Patch, Vec<(Vec<Stat>, SelfProfile)>)>,
+
+    /// Where each finished build kind is recorded, and the build kinds that
+    /// were already finished.
+    progress: Option<Arc<Progress>>,
+    done: Vec<BuildKind>,
+
+    pub collected: CollectedBenchmark,
+}
+
+/// Whether it's been pointed out that the hardware counters aren't recorded
+/// on Windows, which is done once rather than for every benchmark.
+static WINDOWS_COUNTERS_WARNED: AtomicBool = AtomicBool::new(false);
+
 impl MeasureProcessor {
-    pub fn new(name: &str) -> Self {
-        // Check we have `perf` available.
-        let has_perf = Command::new("perf").output().is_ok();
-        assert!(has_perf);
+    /// Fails if `profiler` can't be used on this machine, e.g. because `perf`
+    /// isn't allowed to count hardware events.
+    pub fn new(name: &str, profiler: Profiler, compiler: Compiler) -> Result<Self, Error> {
+        // Counting them would take ETW, which rustc-fake doesn't use.
+        if profiler == Profiler::PerfStat && cfg!(windows) &&
+            !WINDOWS_COUNTERS_WARNED.swap(true, Ordering::SeqCst) {
+            warn!("hardware counters such as instructions:u aren't recorded on Windows, \
+                   only task-clock, faults, max-rss and wall-time are");
+        }
+
+        // There's no `perf` on macOS and Windows, where rustc-fake makes do
+        // with what the OS keeps track of.
+        if profiler == Profiler::PerfStat && cfg!(target_os = "linux") {
+            if Command::new("perf").output().is_err() {
+                bail!("perf isn't installed");
+            }
+            // Custom events needn't be hardware events.
+            if env::var_os("RUSTC_PERF_EVENTS").is_none() {
+                if let Some(problem) = environment::pmu_problem() {
+                    bail!("perf can't count hardware events: {}", problem);
+                }
+            }
+        }
+
+        Ok(MeasureProcessor {
+            profiler,
+            target: compiler.target.map(String::from),
+            backend: compiler.backend,
+            linker: compiler.linker,
+            threads: compiler.threads,
+            warmup: None,
+            target_dir: TargetDirPolicy::default(),
+            cgu_stats: Vec::new(),
+            unit_timings: Vec::new(),
+            ir_stats: Vec::new(),
+            incr_stats: Vec::new(),
 
-        MeasureProcessor {
             clean_stats: Vec::new(),
             nll_stats: Vec::new(),
             base_incr_stats: Vec::new(),
             clean_incr_stats: Vec::new(),
             patched_incr_stats: Vec::new(),
 
+            progress: None,
+            done: Vec::new(),
+
             collected: CollectedBenchmark {
                 name: name.to_string(),
                 runs: Vec::new(),
+                category: Category::default(),
             },
+        })
+    }
+
+    /// Records that each benchmark was run `warmup` times before it was
+    /// measured.
+    pub fn with_warmup(mut self, warmup: usize) -> Self {
+        if warmup > 0 {
+            self.warmup = Some(warmup);
         }
+        self
+    }
+
+    /// Records the runs of every build kind in `progress` once it's
+    /// finished, and skips the build kinds recorded there already.
+    pub fn with_progress(mut self, progress: Arc<Progress>) -> Self {
+        let previous = progress.get(&self.collected.name);
+        self.done = previous.build_kinds;
+        self.collected.runs = previous.runs;
+        self.progress = Some(progress);
+        self
     }
 }
 
 impl Processor for MeasureProcessor {
     fn profiler(&self) -> Profiler {
-        Profiler::PerfStat
+        self.profiler
     }
 
     fn process_output(&mut self, data: &ProcessOutputData, output: process::Output)
                       -> Result<Retry, Error> {
-        match process_perf_stat_output(output) {
-            Ok(stats) => {
+        let stats = match self.profiler {
+            Profiler::TimePasses => process_time_passes_output(output),
+            Profiler::DHAT => process_dhat_output(output),
+            Profiler::Strace => process_strace_output(data.cwd, output),
+            _ => process_perf_stat_output(output).map(merge_stats),
+        };
+        match stats {
+            Ok(mut stats) => {
+                add_cargo_stats(&mut stats, data.cargo_wall_time);
+                stats.extend(expansion_stats(&data.self_profile));
                 let self_profile = data.self_profile.clone();
                 match data.run_kind {
                     RunKind::Clean => { self.clean_stats.push((stats, self_profile)); }
@@ -377,38 +1463,108 @@ impl Processor for MeasureProcessor {
 
     fn finish_build_kind(&mut self, build_kind: BuildKind) {
         if !self.clean_stats.is_empty() {
-            self.collected.runs.push(
-                process_stats(build_kind, BenchmarkState::Clean, &self.clean_stats));
+            let mut run = process_stats(build_kind, BenchmarkState::Clean, &self.clean_stats);
+            run.stats.extend(self.cgu_stats.drain(..));
+            run.stats.extend(self.unit_timings.drain(..));
+            run.stats.extend(self.ir_stats.drain(..));
+            self.collected.runs.push(run);
         }
         if !self.nll_stats.is_empty() {
             self.collected.runs.push(
                 process_stats(build_kind, BenchmarkState::Nll, &self.nll_stats));
         }
         if !self.base_incr_stats.is_empty() {
-            self.collected.runs.push(
-                process_stats(build_kind, BenchmarkState::IncrementalStart, &self.base_incr_stats));
+            let mut run =
+                process_stats(build_kind, BenchmarkState::IncrementalStart, &self.base_incr_stats);
+            run.stats.extend(take_incr_stats(&mut self.incr_stats, &run.state));
+            self.collected.runs.push(run);
         }
         if !self.clean_incr_stats.is_empty() {
-            self.collected.runs.push(
-                process_stats(build_kind, BenchmarkState::IncrementalClean,
-                              &self.clean_incr_stats));
+            let mut run = process_stats(build_kind, BenchmarkState::IncrementalClean,
+                                        &self.clean_incr_stats);
+            run.stats.extend(take_incr_stats(&mut self.incr_stats, &run.state));
+            self.collected.runs.push(run);
         }
         if !self.patched_incr_stats.is_empty() {
             for (patch, results) in self.patched_incr_stats.iter() {
-                self.collected.runs.push(process_stats(
+                let mut run = process_stats(
                     build_kind,
                     BenchmarkState::IncrementalPatched(patch.clone()),
                     &results,
-                ));
+                );
+                run.stats.extend(take_incr_stats(&mut self.incr_stats, &run.state));
+                self.collected.runs.push(run);
             }
         }
 
+        // All runs of a processor are for the same target, backend, linker
+        // and thread count, and had the same warmup and target directory
+        // policy.
+        for run in &mut self.collected.runs {
+            run.target = self.target.clone();
+            run.backend = self.backend;
+            run.linker = self.linker;
+            run.threads = self.threads;
+            run.warmup = self.warmup;
+            run.target_dir = self.target_dir;
+        }
+
         // Empty all the vectors.
         self.clean_stats.clear();
         self.nll_stats.clear();
         self.base_incr_stats.clear();
         self.clean_incr_stats.clear();
         self.patched_incr_stats.clear();
+        self.cgu_stats.clear();
+        self.unit_timings.clear();
+        self.ir_stats.clear();
+        self.incr_stats.clear();
+
+        if let Some(ref progress) = self.progress {
+            progress.record(&self.collected.name, build_kind, &self.collected.runs);
+        }
+    }
+
+    fn skip_build_kind(&self, build_kind: BuildKind) -> bool {
+        self.done.contains(&build_kind)
+    }
+
+    fn variation(&self, stat: &str) -> Option<f64> {
+        let mut all_runs = vec![
+            &self.clean_stats,
+            &self.nll_stats,
+            &self.base_incr_stats,
+            &self.clean_incr_stats,
+        ];
+        all_runs.extend(self.patched_incr_stats.iter().map(|&(_, ref runs)| runs));
+
+        let mut max = None;
+        for runs in all_runs.into_iter().filter(|runs| !runs.is_empty()) {
+            // Any run kind without enough data yet means we can't tell.
+            let cv = coefficient_of_variation(stat, runs)?;
+            max = Some(max.map_or(cv, |max: f64| max.max(cv)));
+        }
+        max
+    }
+
+    fn target_dir_policy(&mut self, policy: TargetDirPolicy) {
+        self.target_dir = policy;
+    }
+
+    fn cgu_stats(&mut self, stats: Vec<Stat>) {
+        self.cgu_stats = stats;
+    }
+
+    fn unit_timings(&mut self, stats: Vec<Stat>) {
+        self.unit_timings = stats;
+    }
+
+    fn ir_stats(&mut self, stats: Vec<Stat>) {
+        self.ir_stats = stats;
+    }
+
+    fn incr_stats(&mut self, stats: Vec<(BenchmarkState, Vec<Stat>)>) {
+        self.incr_stats = stats;
     }
 }
 
@@ -416,14 +1572,17 @@ pub struct ProfileProcessor<'a> {
     profiler: Profiler,
     output_dir: &'a Path,
     id: &'a str,
+    diff_id: Option<&'a str>,
 }
 
 impl<'a> ProfileProcessor<'a> {
-    pub fn new(profiler: Profiler, output_dir: &'a Path, id: &'a str) -> Self {
+    pub fn new(profiler: Profiler, output_dir: &'a Path, id: &'a str,
+               diff_id: Option<&'a str>) -> Self {
         ProfileProcessor {
             profiler,
             output_dir,
             id,
+            diff_id,
         }
     }
 }
@@ -436,9 +1595,12 @@ impl<'a> Processor for ProfileProcessor<'a> {
     fn process_output(&mut self, data: &ProcessOutputData, output: process::Output)
                       -> Result<Retry, Error> {
         // Produce a name of the form $PREFIX-$ID-$BENCHMARK-$BUILDKIND-$RUNKIND.
-        let out_file = |prefix: &str| -> String {
+        let out_file_for = |prefix: &str, id: &str| -> String {
             format!("{}-{}-{}-{:?}-{}",
-                    prefix, self.id, data.name, data.build_kind, data.run_kind_str)
+                    prefix, id, data.name, data.build_kind, data.run_kind_str)
+        };
+        let out_file = |prefix: &str| -> String {
+            out_file_for(prefix, self.id)
         };
 
         // Combine a dir and a file.
@@ -453,6 +1615,43 @@ impl<'a> Processor for ProfileProcessor<'a> {
                 panic!("unexpected profiler");
             }
 
+            // -Zself-profile produces (via rustc-fake) `measureme` data files
+            // named after the crate and the process id. We move them to the
+            // output dir, giving them a new name in the process, and then
+            // summarize the queries with `summarize`.
+            Profiler::SelfProfile => {
+                let sp_stem = out_file("sp");
+                let mut found = false;
+                for entry in fs::read_dir(data.cwd)? {
+                    let path = entry?.path();
+                    let extension = path.extension()
+                        .and_then(|e| e.to_str()).unwrap_or("").to_string();
+                    if !SELF_PROFILE_EXTENSIONS.contains(&&*extension) {
+                        continue;
+                    }
+                    found = true;
+                    let sp_file = filepath(self.output_dir,
+                                           &format!("{}.{}", sp_stem, extension));
+                    fs::rename(&path, &sp_file).or_else(|_| fs::copy(&path, &sp_file).map(|_| ()))?;
+                    // Don't let stale files end up in the next iteration.
+                    let _ = fs::remove_file(&path);
+                }
+                if !found {
+                    bail!("no self-profile data found in {:?}", data.cwd);
+                }
+
+                let sp_summary_file = filepath(self.output_dir, &out_file("spsummary"));
+                let mut summarize_cmd = Command::new("summarize");
+                summarize_cmd
+                    .arg("summarize")
+                    .arg(filepath(self.output_dir, &sp_stem));
+                let output = command_output(&mut summarize_cmd)?;
+
+                let mut f = File::create(sp_summary_file)?;
+                f.write_all(&output.stdout)?;
+                f.flush()?;
+            }
+
             // -Ztime-passes writes its output to stdout. We copy that output
             // into a file in the output dir.
             Profiler::TimePasses => {
@@ -473,10 +1672,44 @@ impl<'a> Processor for ProfileProcessor<'a> {
                 fs::copy(&tmp_perf_file, &perf_file)?;
             }
 
+            // Flamegraph produces (via rustc-fake) a perf data file called
+            // 'perf'. Its stacks are folded with `inferno-collapse-perf` and
+            // rendered as an interactive SVG with `inferno-flamegraph`; both
+            // the folded stacks and the SVG are written to the output dir.
+            Profiler::Flamegraph => {
+                let tmp_perf_file = filepath(data.cwd.as_ref(), "perf");
+                let tmp_script_file = filepath(data.cwd.as_ref(), "perf.script");
+                let folded_file = filepath(self.output_dir, &out_file("folded"));
+                let svg_file =
+                    filepath(self.output_dir, &format!("{}.svg", out_file("flamegraph")));
+
+                let mut perf_script_cmd = Command::new("perf");
+                perf_script_cmd
+                    .arg("script")
+                    .arg("--input").arg(&tmp_perf_file);
+                let output = command_output(&mut perf_script_cmd)?;
+                fs::write(&tmp_script_file, &output.stdout)?;
+
+                let mut collapse_cmd = Command::new("inferno-collapse-perf");
+                collapse_cmd.arg(&tmp_script_file);
+                let output = command_output(&mut collapse_cmd)?;
+                fs::write(&folded_file, &output.stdout)?;
+
+                let mut flamegraph_cmd = Command::new("inferno-flamegraph");
+                flamegraph_cmd
+                    .arg("--title")
+                    .arg(format!("{} {} {:?} {}",
+                                 self.id, data.name, data.build_kind, data.run_kind_str))
+                    .arg(&folded_file);
+                let output = command_output(&mut flamegraph_cmd)?;
+                fs::write(&svg_file, &output.stdout)?;
+            }
+
             // Cachegrind produces (via rustc-fake) a data file called 'cgout'.
             // We copy it from the temp dir to the output dir, giving it a new
             // name in the process, and then post-process it to produce another
-            // data file in the output dir.
+            // data file in the output dir. If a base id was given, the 'cgout'
+            // file of that earlier run is diffed against the new one.
             Profiler::Cachegrind => {
                 let tmp_cgout_file = filepath(data.cwd.as_ref(), "cgout");
                 let cgout_file = filepath(self.output_dir, &out_file("cgout"));
@@ -493,12 +1726,48 @@ impl<'a> Processor for ProfileProcessor<'a> {
                 let mut f = File::create(cgann_file)?;
                 f.write_all(&output.stdout)?;
                 f.flush()?;
+
+                if let Some(diff_id) = self.diff_id {
+                    let base_cgout_file =
+                        filepath(self.output_dir, &out_file_for("cgout", diff_id));
+                    if base_cgout_file.exists() {
+                        let cgdiff_file = filepath(self.output_dir, &out_file("cgdiff"));
+                        let cgdiffann_file = filepath(self.output_dir, &out_file("cgdiffann"));
+
+                        // The two toolchains usually live in different
+                        // directories, so strip everything before the source
+                        // root to make the file names line up.
+                        let mut cg_diff_cmd = Command::new("cg_diff");
+                        cg_diff_cmd
+                            .arg("--mod-filename=s/.*\\/src\\//src\\//")
+                            .arg(&base_cgout_file)
+                            .arg(&cgout_file);
+                        let output = command_output(&mut cg_diff_cmd)?;
+
+                        let mut f = File::create(&cgdiff_file)?;
+                        f.write_all(&output.stdout)?;
+                        f.flush()?;
+
+                        let mut cg_annotate_cmd = Command::new("cg_annotate");
+                        cg_annotate_cmd.arg(&cgdiff_file);
+                        let output = cg_annotate_cmd.output()?;
+
+                        let mut f = File::create(cgdiffann_file)?;
+                        f.write_all(&output.stdout)?;
+                        f.flush()?;
+                    } else {
+                        warn!("no cachegrind output for {} to diff against: {:?}",
+                              diff_id, base_cgout_file);
+                    }
+                }
             }
 
             // Callgrind produces (via rustc-fake) a data file called 'clgout'.
             // We copy it from the temp dir to the output dir, giving it a new
             // name in the process, and then post-process it to produce another
-            // data file in the output dir.
+            // data file in the output dir. If a base id was given, the
+            // per-function totals of both runs are compared and written as a
+            // delta table.
             Profiler::Callgrind => {
                 let tmp_clgout_file = filepath(data.cwd.as_ref(), "clgout");
                 let clgout_file = filepath(self.output_dir, &out_file("clgout"));
@@ -515,6 +1784,24 @@ impl<'a> Processor for ProfileProcessor<'a> {
                 let mut f = File::create(clgann_file)?;
                 f.write_all(&output.stdout)?;
                 f.flush()?;
+
+                if let Some(diff_id) = self.diff_id {
+                    let base_clgout_file =
+                        filepath(self.output_dir, &out_file_for("clgout", diff_id));
+                    if base_clgout_file.exists() {
+                        let clgdiff_file = filepath(self.output_dir, &out_file("clgdiff"));
+
+                        let base = callgrind_function_totals(&base_clgout_file)?;
+                        let new = callgrind_function_totals(&clgout_file)?;
+
+                        let mut f = File::create(clgdiff_file)?;
+                        write_function_deltas(&mut f, &base, &new)?;
+                        f.flush()?;
+                    } else {
+                        warn!("no callgrind output for {} to diff against: {:?}",
+                              diff_id, base_clgout_file);
+                    }
+                }
             }
 
             // DHAT writes its output to stderr. We copy that output into a
@@ -529,28 +1816,305 @@ impl<'a> Processor for ProfileProcessor<'a> {
 
             // Massif produces (via rustc-fake) a data file called 'msout'. We
             // copy it from the temp dir to the output dir, giving it a new
-            // name in the process.
+            // name in the process, and then write a summary of the peak
+            // snapshot to another file in the output dir.
             Profiler::Massif => {
                 let tmp_msout_file = filepath(data.cwd.as_ref(), "msout");
                 let msout_file = filepath(self.output_dir, &out_file("msout"));
+                let mspeak_file = filepath(self.output_dir, &out_file("mspeak"));
 
                 fs::copy(&tmp_msout_file, &msout_file)?;
+
+                let peak = MassifSnapshot::peak(&fs::read_to_string(&msout_file)?)
+                    .ok_or_else(|| err_msg("massif output contains no snapshots"))?;
+
+                let mut f = File::create(mspeak_file)?;
+                writeln!(f, "snapshot:   {}", peak.index)?;
+                writeln!(f, "time:       {}", peak.time)?;
+                writeln!(f, "heap:       {} B", peak.heap)?;
+                writeln!(f, "heap-extra: {} B", peak.heap_extra)?;
+                writeln!(f, "stacks:     {} B", peak.stacks)?;
+                writeln!(f, "total:      {} B", peak.total())?;
+                f.flush()?;
             }
 
             // eprintln! statements writes their output to stderr. We copy that
-            // output into a file in the output dir.
+            // output into a file in the output dir. If a base id was given,
+            // the output of both runs is diffed line by line.
             Profiler::Eprintln => {
                 let eprintln_file = filepath(self.output_dir, &out_file("eprintln"));
 
-                let mut f = File::create(eprintln_file)?;
+                let mut f = File::create(&eprintln_file)?;
                 f.write_all(&output.stderr)?;
                 f.flush()?;
+
+                if let Some(diff_id) = self.diff_id {
+                    let base_eprintln_file =
+                        filepath(self.output_dir, &out_file_for("eprintln", diff_id));
+                    if base_eprintln_file.exists() {
+                        let eprintln_diff_file =
+                            filepath(self.output_dir, &out_file("eprintln-diff"));
+
+                        // `diff` exits with 1 if the files differ, and with 2
+                        // if something went wrong.
+                        let mut diff_cmd = Command::new("diff");
+                        diff_cmd
+                            .arg("-u")
+                            .arg(&base_eprintln_file)
+                            .arg(&eprintln_file);
+                        let output = diff_cmd.output()?;
+                        if output.status.code() == Some(2) {
+                            bail!("failed to diff {:?} and {:?}: {}",
+                                  base_eprintln_file, eprintln_file,
+                                  String::from_utf8_lossy(&output.stderr));
+                        }
+
+                        let mut f = File::create(eprintln_diff_file)?;
+                        f.write_all(&output.stdout)?;
+                        f.flush()?;
+                    } else {
+                        warn!("no eprintln output for {} to diff against: {:?}",
+                              diff_id, base_eprintln_file);
+                    }
+                }
+            }
+
+            // -Zllvm-time-trace produces (via rustc-fake) one Chrome trace
+            // file per LLVM module. We merge them into a single trace in the
+            // output dir, giving each module its own process id so they show
+            // up as separate tracks in chrome://tracing or Perfetto.
+            Profiler::LlvmTimeTrace => {
+                let trace_file =
+                    filepath(self.output_dir, &format!("{}.json", out_file("llvm-trace")));
+
+                let mut trace_files = Vec::new();
+                for entry in fs::read_dir(data.cwd)? {
+                    let path = entry?.path();
+                    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("")
+                        .to_string();
+                    if name.ends_with(".json") &&
+                        (name.contains("llvm_timings") || name.ends_with(".time-trace.json")) {
+                        trace_files.push(path);
+                    }
+                }
+                if trace_files.is_empty() {
+                    bail!("no LLVM time trace found in {:?}", data.cwd);
+                }
+                trace_files.sort();
+
+                let mut events = Vec::new();
+                for (pid, path) in trace_files.iter().enumerate() {
+                    let trace: serde_json::Value = serde_json::from_reader(File::open(path)?)
+                        .with_context(|_| format!("failed to parse {:?}", path))?;
+                    let trace_events = match trace.get("traceEvents") {
+                        Some(serde_json::Value::Array(trace_events)) => trace_events.clone(),
+                        _ => bail!("{:?} is not a Chrome trace", path),
+                    };
+                    for mut event in trace_events {
+                        if let Some(event) = event.as_object_mut() {
+                            event.insert("pid".to_string(), pid.into());
+                        }
+                        events.push(event);
+                    }
+                    // Don't let stale traces end up in the next iteration.
+                    fs::remove_file(path)?;
+                }
+
+                let mut f = File::create(trace_file)?;
+                serde_json::to_writer(&mut f, &json!({ "traceEvents": events }))?;
+                f.flush()?;
+            }
+
+            // strace produces (via rustc-fake) a summary file per rustc
+            // invocation, called 'strace-summary-$CRATE-$PID'. We concatenate
+            // them into a single file in the output dir, each under the name
+            // of its crate and pid.
+            Profiler::Strace => {
+                let summary_file = filepath(self.output_dir, &out_file("strace"));
+
+                let summary_files = strace_summary_files(data.cwd)?;
+                if summary_files.is_empty() {
+                    bail!("no strace summary found in {:?}", data.cwd);
+                }
+                let mut f = File::create(summary_file)?;
+                for path in summary_files {
+                    let name = path.file_name().unwrap().to_string_lossy();
+                    writeln!(f, "{}:", &name[STRACE_SUMMARY_PREFIX.len()..])?;
+                    f.write_all(&fs::read(&path)?)?;
+                    writeln!(f)?;
+                    // Don't let stale summaries end up in the next iteration.
+                    fs::remove_file(&path)?;
+                }
+            }
+
+            // xctrace produces (via rustc-fake) a trace bundle (a directory)
+            // called 'xctrace.trace'. We move it from the temp dir to the
+            // output dir, giving it a new name in the process; xctrace won't
+            // record over an existing bundle anyway.
+            Profiler::Xctrace => {
+                let tmp_trace = filepath(data.cwd.as_ref(), "xctrace.trace");
+                let trace = filepath(self.output_dir,
+                                     &format!("{}.trace", out_file("xctrace")));
+
+                if fs::rename(&tmp_trace, &trace).is_err() {
+                    let mut cmd = Command::new("cp");
+                    cmd.arg("-R").arg(&tmp_trace).arg(&trace);
+                    command_output(&mut cmd)?;
+                    fs::remove_dir_all(&tmp_trace)?;
+                }
             }
         }
         Ok(Retry::No)
     }
 }
 
+/// A single snapshot from a Massif output file.
+#[derive(Debug, Default)]
+struct MassifSnapshot {
+    index: u64,
+    time: u64,
+    heap: u64,
+    heap_extra: u64,
+    stacks: u64,
+    is_peak: bool,
+}
+
+impl MassifSnapshot {
+    fn total(&self) -> u64 {
+        self.heap + self.heap_extra + self.stacks
+    }
+
+    /// Finds the peak snapshot in the contents of a Massif output file. Massif
+    /// marks the peak it detected with `heap_tree=peak`; if no snapshot is
+    /// marked (e.g. the program exited right at its peak), the snapshot with
+    /// the largest total is used instead.
+    fn peak(msout: &str) -> Option<MassifSnapshot> {
+        let mut snapshots: Vec<MassifSnapshot> = Vec::new();
+        for line in msout.lines() {
+            let mut parts = line.splitn(2, '=');
+            let (key, value) = match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => (key.trim(), value.trim()),
+                _ => continue,
+            };
+            if key == "snapshot" {
+                snapshots.push(MassifSnapshot {
+                    index: value.parse().unwrap_or(0),
+                    ..MassifSnapshot::default()
+                });
+                continue;
+            }
+            let snapshot = match snapshots.last_mut() {
+                Some(snapshot) => snapshot,
+                None => continue,
+            };
+            match key {
+                "time" => snapshot.time = value.parse().unwrap_or(0),
+                "mem_heap_B" => snapshot.heap = value.parse().unwrap_or(0),
+                "mem_heap_extra_B" => snapshot.heap_extra = value.parse().unwrap_or(0),
+                "mem_stacks_B" => snapshot.stacks = value.parse().unwrap_or(0),
+                "heap_tree" => snapshot.is_peak = value == "peak",
+                _ => {}
+            }
+        }
+
+        let marked_peak = snapshots.iter().position(|s| s.is_peak);
+        if let Some(pos) = marked_peak {
+            return Some(snapshots.swap_remove(pos));
+        }
+        snapshots.into_iter().max_by_key(|s| s.total())
+    }
+}
+
+/// Runs `callgrind_annotate` on a raw Callgrind output file and collects the
+/// instruction count of every function it lists.
+fn callgrind_function_totals(clgout_file: &Path) -> Result<HashMap<String, u64>, Error> {
+    let mut cmd = Command::new("callgrind_annotate");
+    cmd.arg("--threshold=100")
+        .arg("--inclusive=no")
+        .arg(clgout_file);
+    let output = command_output(&mut cmd)?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let mut totals = HashMap::new();
+    for line in stdout.lines() {
+        // Function lines look like `1,234 (0.12%)  file:function [object]`; the
+        // percentage is only printed by newer versions of Valgrind.
+        let line = line.trim();
+        let mut parts = line.splitn(2, char::is_whitespace);
+        let count = match parts.next().map(|c| c.replace(",", "").parse::<u64>()) {
+            Some(Ok(count)) => count,
+            _ => continue,
+        };
+        let mut function = parts.next().unwrap_or("").trim();
+        if function.starts_with('(') {
+            function = function.splitn(2, ')').nth(1).unwrap_or("").trim();
+        }
+        if function.is_empty() || function.starts_with("PROGRAM TOTALS") {
+            continue;
+        }
+        *totals.entry(normalize_function_name(function)).or_insert(0) += count;
+    }
+    Ok(totals)
+}
+
+/// Strips the object file and the toolchain-specific part of the source path,
+/// so that the same function profiled with two toolchains gets the same name.
+fn normalize_function_name(function: &str) -> String {
+    let function = match function.rfind(" [") {
+        Some(pos) => &function[..pos],
+        None => function,
+    };
+    match function.find(':') {
+        Some(colon) => {
+            let (file, name) = function.split_at(colon);
+            let file = match file.rfind("/src/") {
+                Some(pos) => &file[pos + 1..],
+                None => file,
+            };
+            format!("{}{}", file, name)
+        }
+        None => function.to_string(),
+    }
+}
+
+/// Writes a table of per-function instruction count changes, largest absolute
+/// change first.
+fn write_function_deltas(
+    out: &mut dyn Write,
+    base: &HashMap<String, u64>,
+    new: &HashMap<String, u64>,
+) -> Result<(), Error> {
+    let mut functions = base.keys().chain(new.keys()).collect::<HashSet<_>>()
+        .into_iter()
+        .map(|function| {
+            let base = base.get(function).cloned().unwrap_or(0) as i64;
+            let new = new.get(function).cloned().unwrap_or(0) as i64;
+            (function, base, new, new - base)
+        })
+        .filter(|&(_, _, _, delta)| delta != 0)
+        .collect::<Vec<_>>();
+    functions.sort_by_key(|&(function, _, _, delta)| (cmp::Reverse(delta.abs()), function));
+
+    let base_total = base.values().sum::<u64>() as i64;
+    let new_total = new.values().sum::<u64>() as i64;
+    writeln!(out, "{:>15} {:>15} {:>15} {:>8}  {}", "base", "new", "delta", "%", "function")?;
+    writeln!(out, "{:>15} {:>15} {:>15} {:>8}  {}", base_total, new_total,
+             new_total - base_total, percent_change(base_total, new_total), "TOTAL")?;
+    for (function, base, new, delta) in functions {
+        writeln!(out, "{:>15} {:>15} {:>15} {:>8}  {}", base, new, delta,
+                 percent_change(base, new), function)?;
+    }
+    Ok(())
+}
+
+fn percent_change(base: i64, new: i64) -> String {
+    if base == 0 {
+        String::from("new")
+    } else {
+        format!("{:+.2}", (new - base) as f64 / base as f64 * 100.0)
+    }
+}
+
 impl Benchmark {
     pub fn new(name: String, path: PathBuf) -> Result<Self, Error> {
         let mut patches = vec![];
@@ -579,24 +2143,69 @@ impl Benchmark {
             BenchmarkConfig::default()
         };
 
+        // Parse the exclusions up front, so that typos are reported even if
+        // the benchmark is never run with the misspelled kind.
+        let excluded_build_kinds = if config.excluded_profiles.is_empty() {
+            Vec::new()
+        } else {
+            build_kinds_from_arg(&Some(&config.excluded_profiles.join(",")))
+                .with_context(|_| format!("invalid excluded_profiles in {:?}", config_path))?
+        };
+        let excluded_run_kinds = if config.excluded_scenarios.is_empty() {
+            Vec::new()
+        } else {
+            run_kinds_from_arg(&Some(&config.excluded_scenarios.join(",")))
+                .with_context(|_| format!("invalid excluded_scenarios in {:?}", config_path))?
+        };
+        let extra_build_kinds = if config.extra_profiles.is_empty() {
+            Vec::new()
+        } else {
+            build_kinds_from_arg(&Some(&config.extra_profiles.join(",")))
+                .with_context(|_| format!("invalid extra_profiles in {:?}", config_path))?
+        };
+        if let Some(kind) = extra_build_kinds.iter().find(|k| !k.is_extra()) {
+            bail!("invalid extra_profiles in {:?}: {:?} is always run", config_path, kind);
+        }
+
         Ok(Benchmark {
             name,
             path,
             patches,
             config,
+            excluded_build_kinds,
+            excluded_run_kinds,
+            extra_build_kinds,
         })
     }
 
     pub fn supports_stable(&self) -> bool {
-        self.config.supports_stable
+        self.config.supports_stable || self.config.category == Category::Stable
     }
 
-    fn make_temp_dir(&self, base: &Path) -> Result<TempDir, Error> {
+    pub fn category(&self) -> Category {
+        self.config.category
+    }
+
+    /// How the target directory is prepared for the measured builds.
+    pub fn target_dir_policy(&self) -> TargetDirPolicy {
+        TARGET_DIR_POLICY.lock().unwrap()
+            .or(self.config.target_dir)
+            .unwrap_or_default()
+    }
+
+    /// Copies `base` to a new temporary directory, on a tmpfs with `tmpfs`.
+    pub fn make_temp_dir(&self, base: &Path, tmpfs: bool) -> Result<TempDir, Error> {
         // Appending `.` means we copy just the contents of `base` into
         // `tmp_dir`, rather than `base` itself.
         let mut base_dot = base.to_path_buf();
         base_dot.push(".");
-        let tmp_dir = TempDir::new()?;
+        let tmp_dir = if tmpfs {
+            let tmpfs_dir = TMPFS_DIR.lock().unwrap().clone();
+            TempDir::new_in(&tmpfs_dir)
+                .with_context(|_| format!("failed to create a directory in {:?}", tmpfs_dir))?
+        } else {
+            layout::temp_dir()?
+        };
         let mut cmd = Command::new("cp");
         cmd.arg("-R")
             .arg(base_dot)
@@ -605,6 +2214,28 @@ impl Benchmark {
         Ok(tmp_dir)
     }
 
+    /// The extra arguments cargo is run with to build this benchmark.
+    pub fn cargo_args(&self) -> Vec<String> {
+        let mut cargo_args: Vec<String> = self.config
+            .cargo_opts
+            .clone()
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        if !self.config.features.is_empty() {
+            cargo_args.push(String::from("--features"));
+            cargo_args.push(self.config.features.join(","));
+        }
+        cargo_args
+    }
+
+    /// The path of the manifest to build, relative to the benchmark
+    /// directory.
+    pub fn manifest_path(&self) -> String {
+        self.config.cargo_toml.clone().unwrap_or_else(|| String::from("Cargo.toml"))
+    }
+
     fn mk_cargo_process<'a>(
         &self,
         compiler: Compiler<'a>,
@@ -618,17 +2249,9 @@ impl Benchmark {
             incremental: false,
             nll: false,
             processor_etc: None,
-            manifest_path: self.config
-                .cargo_toml
-                .clone()
-                .unwrap_or_else(|| String::from("Cargo.toml")),
-            cargo_args: self.config
-                .cargo_opts
-                .clone()
-                .unwrap_or_default()
-                .split_whitespace()
-                .map(String::from)
-                .collect(),
+            warmup: false,
+            manifest_path: self.manifest_path(),
+            cargo_args: self.cargo_args(),
             rustc_args: self.config
                 .cargo_rustc_opts
                 .clone()
@@ -636,7 +2259,193 @@ impl Benchmark {
                 .split_whitespace()
                 .map(String::from)
                 .collect(),
+            touch_file: self.config.touch_file.clone(),
+            timeout: Duration::from_secs(self.config.timeout),
+            retries: self.config.retries,
+            sources: None,
+            cgu_stats: None,
+            unit_timings: None,
+            ir_stats: None,
+            incr_stats: None,
+            workspace: self.config.workspace,
+        }
+    }
+
+    /// Does the incremental builds of `run_kinds` once more, without measuring
+    /// them, in a copy of `prep_dir`, to record the stats `-Zincremental-info`
+    /// prints for each, which the measured builds don't pass as that would
+    /// affect the measurements.
+    fn measure_incr_stats(&self, compiler: Compiler, prep_dir: &Path, build_kind: BuildKind,
+                          run_kinds: &[RunKind])
+                          -> Result<Vec<(BenchmarkState, Vec<Stat>)>, Error> {
+        let tmpfs = self.target_dir_policy() == TargetDirPolicy::Tmpfs;
+        let dir = self.make_temp_dir(prep_dir, tmpfs)?;
+        let cwd = dir.path();
+        if self.target_dir_policy() == TargetDirPolicy::Wipe {
+            self.mk_cargo_process(compiler, cwd, build_kind).run_rustc()?;
+        }
+
+        let mut all_stats = Vec::new();
+        let mut stats = Vec::new();
+        self.mk_cargo_process(compiler, cwd, build_kind)
+            .incremental(true)
+            .incr_stats(&mut stats)
+            .run_rustc()?;
+        all_stats.push((BenchmarkState::IncrementalStart, stats));
+
+        if run_kinds.contains(&RunKind::CleanIncr) {
+            let mut stats = Vec::new();
+            self.mk_cargo_process(compiler, cwd, build_kind)
+                .incremental(true)
+                .incr_stats(&mut stats)
+                .run_rustc()?;
+            all_stats.push((BenchmarkState::IncrementalClean, stats));
+        }
+
+        if run_kinds.contains(&RunKind::PatchedIncrs) {
+            for patch in &self.patches {
+                patch.apply(cwd).map_err(|s| err_msg(s))?;
+                let mut stats = Vec::new();
+                self.mk_cargo_process(compiler, cwd, build_kind)
+                    .incremental(true)
+                    .incr_stats(&mut stats)
+                    .run_rustc()?;
+                all_stats.push((BenchmarkState::IncrementalPatched(patch.clone()), stats));
+                if self.config.independent_patches {
+                    patch.revert(cwd).map_err(|s| err_msg(s))?;
+                    self.mk_cargo_process(compiler, cwd, build_kind)
+                        .incremental(true)
+                        .run_rustc()?;
+                }
+            }
+        }
+        remove_temp_dir(dir);
+        Ok(all_stats)
+    }
+
+    fn max_iterations(&self, iterations: Iterations) -> usize {
+        match iterations.limit {
+            IterationLimit::Fixed(n) => cmp::min(n, self.config.runs),
+            IterationLimit::Adaptive { max, .. } => max,
+        }
+    }
+
+    /// Removes the kinds this benchmark's config excludes.
+    fn kinds_to_run(&self, build_kinds: &[BuildKind], run_kinds: &[RunKind])
+                    -> (Vec<BuildKind>, Vec<RunKind>) {
+        let build_kinds = build_kinds.iter()
+            .filter(|k| !self.excluded_build_kinds.contains(*k))
+            .filter(|k| !k.is_extra() || self.extra_build_kinds.contains(*k))
+            .cloned()
+            .collect();
+        let run_kinds = run_kinds.iter()
+            .filter(|k| !self.excluded_run_kinds.contains(*k))
+            .cloned()
+            .collect();
+        (build_kinds, run_kinds)
+    }
+
+    /// The builds `measure` does for each of `build_kinds` that this
+    /// benchmark is run with, unless `skip` says it was measured already.
+    fn steps(&self, build_kinds: &[BuildKind], run_kinds: &[RunKind],
+             skip: &dyn Fn(BuildKind) -> bool) -> Vec<BuildKindSteps> {
+        let (build_kinds, run_kinds) = self.kinds_to_run(build_kinds, run_kinds);
+        let mut all_steps = Vec::new();
+        for &build_kind in &build_kinds {
+            let run_kinds = run_kinds_for_build(build_kind, &run_kinds).to_vec();
+            let mut steps = Vec::new();
+
+            // A full non-incremental build.
+            if run_kinds.contains(&RunKind::Clean) {
+                steps.push(Step::measure(RunKind::Clean, "Clean", BenchmarkState::Clean, None));
+            }
+
+            // A full non-incremental build with NLL enabled.
+            // These are only collected on check builds to save time.
+            let has_check = build_kinds.contains(&BuildKind::Check);
+            let is_check = build_kind == BuildKind::Check;
+            if run_kinds.contains(&RunKind::Nll) && ((has_check && is_check) || !has_check) {
+                steps.push(Step::measure(RunKind::Nll, "Nll", BenchmarkState::Nll, None));
+            }
+
+            // An incremental build from scratch (slowest incremental case).
+            // This is required for any subsequent incremental builds.
+            if has_incremental(&run_kinds) {
+                steps.push(Step::measure(RunKind::BaseIncr, "BaseIncr",
+                                         BenchmarkState::IncrementalStart, None));
+            }
+
+            // An incremental build with no changes (fastest incremental case).
+            if run_kinds.contains(&RunKind::CleanIncr) {
+                steps.push(Step::measure(RunKind::CleanIncr, "CleanIncr",
+                                         BenchmarkState::IncrementalClean, None));
+            }
+
+            // An incremental build with some changes (realistic incremental
+            // case) for each patch.
+            if run_kinds.contains(&RunKind::PatchedIncrs) {
+                for (i, patch) in self.patches.iter().enumerate() {
+                    steps.push(Step::measure(RunKind::PatchedIncrs, &format!("PatchedIncr{}", i),
+                                             BenchmarkState::IncrementalPatched(patch.clone()),
+                                             Some(patch)));
+                    if self.config.independent_patches {
+                        steps.push(Step::Revert(patch));
+                    }
+                }
+            }
+
+            all_steps.push(BuildKindSteps {
+                build_kind,
+                run_kinds,
+                skipped: skip(build_kind),
+                steps,
+            });
         }
+        all_steps
+    }
+
+    /// Lists the builds that `measure` would do with the same arguments,
+    /// without doing any of them. `skip` is the `skip_build_kind` of the
+    /// processor `measure` would be run with.
+    pub fn plan(
+        &self,
+        build_kinds: &[BuildKind],
+        run_kinds: &[RunKind],
+        compiler: Compiler,
+        iterations: Iterations,
+        skip: &dyn Fn(BuildKind) -> bool,
+    ) -> Vec<PlannedBuild> {
+        let mut plan = Vec::new();
+        if self.config.disabled {
+            return plan;
+        }
+
+        let max_iterations = self.max_iterations(iterations);
+        for kind_steps in self.steps(build_kinds, run_kinds, skip) {
+            if kind_steps.skipped {
+                continue;
+            }
+            for i in 0..iterations.warmup + max_iterations {
+                let warmup = i < iterations.warmup;
+                for step in &kind_steps.steps {
+                    let (state, revert) = match *step {
+                        Step::Measure { ref state, .. } => (state.clone(), false),
+                        Step::Revert(patch) => {
+                            (BenchmarkState::IncrementalPatched(patch.clone()), true)
+                        }
+                    };
+                    plan.push(PlannedBuild {
+                        benchmark: self.name.clone(),
+                        run_name: run_name(kind_steps.build_kind, state, compiler),
+                        iteration: if warmup { i + 1 } else { i - iterations.warmup + 1 },
+                        iterations: if warmup { iterations.warmup } else { max_iterations },
+                        warmup,
+                        revert,
+                    });
+                }
+            }
+        }
+        plan
     }
 
     /// Run a specific benchmark under a processor + profiler combination.
@@ -646,87 +2455,148 @@ impl Benchmark {
         build_kinds: &[BuildKind],
         run_kinds: &[RunKind],
         compiler: Compiler,
-        iterations: usize,
+        iterations: Iterations,
     ) -> Result<(), Error> {
 
-        let iterations = cmp::min(iterations, self.config.runs);
+        let max_iterations = self.max_iterations(iterations);
 
         if self.config.disabled {
-            eprintln!("skipping {}: disabled", self.name);
+            warn!("skipping {}: disabled", self.name);
             bail!("disabled benchmark");
         }
 
-        for &build_kind in build_kinds {
+        let all_steps = self.steps(build_kinds, run_kinds,
+                                   &|build_kind| processor.skip_build_kind(build_kind));
+        for BuildKindSteps { build_kind, run_kinds, skipped, steps } in all_steps {
+            if skipped {
+                info!("Skipping {}: {:?}, which was measured already", self.name, build_kind);
+                continue;
+            }
+            let _span = logging::span("build_kind", format!("{:?}", build_kind), Level::Info);
             info!("Running {}: {:?} + {:?}", self.name, build_kind, run_kinds);
 
             // Build everything, including all dependent crates, in a temp dir.
             // We do this before the iterations so that dependent crates aren't
-            // built on every iteration. A different temp dir is used for the
-            // timing builds.
-            let prep_dir = self.make_temp_dir(&self.path)?;
-            self.mk_cargo_process(compiler, prep_dir.path(), build_kind)
-                .run_rustc()?;
-
-            for i in 0..iterations {
-                debug!("Benchmark iteration {}/{}", i + 1, iterations);
-                let timing_dir = self.make_temp_dir(prep_dir.path())?;
-                let cwd = timing_dir.path();
-
-                // A full non-incremental build.
-                if run_kinds.contains(&RunKind::Clean) {
-                    self.mk_cargo_process(compiler, cwd, build_kind)
-                        .processor(processor, &self.name, RunKind::Clean, "Clean", None)
-                        .run_rustc()?;
+            // built on every iteration (unless the target directory is to be
+            // wiped). A different temp dir is used for the timing builds.
+            let policy = self.target_dir_policy();
+            processor.target_dir_policy(policy);
+            let tmpfs = policy == TargetDirPolicy::Tmpfs;
+            disk::check(&build_dir(tmpfs), &self.name)?;
+            let prep_dir = self.make_temp_dir(&self.path, tmpfs)?;
+            // How the crate is split into codegen units, how long each of
+            // the units cargo builds takes, and how large the crate's AST and
+            // HIR are, is recorded from the first full non-incremental build,
+            // which isn't measured, so that printing them doesn't affect the
+            // measurements. It's also the only build of the dependencies.
+            let mut cgu_stats = Vec::new();
+            let mut unit_timings = Vec::new();
+            let mut ir_stats = Vec::new();
+            let has_codegen = build_kind != BuildKind::Check && build_kind != BuildKind::Doc;
+            let has_incremental = has_incremental(&run_kinds);
+            let record_unit_timings = *UNIT_TIMINGS.lock().unwrap();
+            // rustdoc doesn't take `-Zhir-stats`.
+            let record_ir_stats = *IR_STATS.lock().unwrap() && build_kind != BuildKind::Doc;
+            if policy != TargetDirPolicy::Wipe {
+                let mut cargo = self.mk_cargo_process(compiler, prep_dir.path(), build_kind);
+                if has_codegen {
+                    cargo = cargo.cgu_stats(&mut cgu_stats);
                 }
-
-                // A full non-incremental build with NLL enabled.
-                // These are only collected on check builds to save time.
-                let has_check = build_kinds.contains(&BuildKind::Check);
-                let is_check = build_kind == BuildKind::Check;
-                if run_kinds.contains(&RunKind::Nll) && ((has_check && is_check) || !has_check)
-                {
-                    self.mk_cargo_process(compiler, cwd, build_kind)
-                        .nll(true)
-                        .processor(processor, &self.name, RunKind::Nll, "Nll", None)
-                        .run_rustc()?;
+                if record_unit_timings {
+                    cargo = cargo.unit_timings(&mut unit_timings);
                 }
-
-                // An incremental build from scratch (slowest incremental case).
-                // This is required for any subsequent incremental builds.
-                if run_kinds.contains(&RunKind::BaseIncr) ||
-                   run_kinds.contains(&RunKind::CleanIncr) ||
-                   run_kinds.contains(&RunKind::PatchedIncrs) {
-                    self.mk_cargo_process(compiler, cwd, build_kind)
-                        .incremental(true)
-                        .processor(processor, &self.name, RunKind::BaseIncr, "BaseIncr", None)
-                        .run_rustc()?;
+                if record_ir_stats {
+                    cargo = cargo.ir_stats(&mut ir_stats);
                 }
+                cargo.run_rustc()?;
+            }
 
-                // An incremental build with no changes (fastest incremental case).
-                if run_kinds.contains(&RunKind::CleanIncr) {
-                    self.mk_cargo_process(compiler, cwd, build_kind)
-                        .incremental(true)
-                        .processor(processor, &self.name, RunKind::CleanIncr, "CleanIncr", None)
-                        .run_rustc()?;
+            for i in 0..iterations.warmup + max_iterations {
+                // The warmup iterations do the same builds as the measured
+                // ones, but without measuring them.
+                let warmup = i < iterations.warmup;
+                let _span = if warmup {
+                    debug!("Warmup iteration {}/{}", i + 1, iterations.warmup);
+                    logging::span("iteration", format!("warmup {}", i + 1), Level::Debug)
+                } else {
+                    debug!("Benchmark iteration {}/{}", i - iterations.warmup + 1, max_iterations);
+                    logging::span("iteration", i - iterations.warmup + 1, Level::Debug)
+                };
+                let timing_dir = self.make_temp_dir(prep_dir.path(), tmpfs)?;
+                let cwd = timing_dir.path();
+                if policy == TargetDirPolicy::Wipe {
+                    let mut cargo = self.mk_cargo_process(compiler, cwd, build_kind);
+                    if has_codegen && i == 0 {
+                        cargo = cargo.cgu_stats(&mut cgu_stats);
+                    }
+                    if record_unit_timings && i == 0 {
+                        cargo = cargo.unit_timings(&mut unit_timings);
+                    }
+                    if record_ir_stats && i == 0 {
+                        cargo = cargo.ir_stats(&mut ir_stats);
+                    }
+                    cargo.run_rustc()?;
+                    evict_from_page_cache(cwd)?;
+                }
+                if i == 0 {
+                    disk::record_benchmark_size(
+                        artifact_size::dir_size(prep_dir.path())? + artifact_size::dir_size(cwd)?);
                 }
 
-                if run_kinds.contains(&RunKind::PatchedIncrs) {
-                    for (i, patch) in self.patches.iter().enumerate() {
-                        debug!("applying patch {}", patch.name);
-                        patch.apply(cwd).map_err(|s| err_msg(s))?;
+                // The patches applied to `cwd` so far, which a retried build
+                // applies again.
+                let mut applied = Vec::new();
+                for step in &steps {
+                    match *step {
+                        Step::Measure { run_kind, ref run_kind_str, patch, .. } => {
+                            if let Some(patch) = patch {
+                                debug!("applying patch {}", patch.name);
+                                patch.apply(cwd).map_err(|s| err_msg(s))?;
+                                applied.push(patch);
+                            }
+                            self.mk_cargo_process(compiler, cwd, build_kind)
+                                .incremental(run_kind != RunKind::Clean && run_kind != RunKind::Nll)
+                                .nll(run_kind == RunKind::Nll)
+                                .processor(processor, &self.name, run_kind, run_kind_str, patch)
+                                .warmup(warmup)
+                                .sources(&self.path, applied.clone())
+                                .run_rustc()?;
+                        }
+                        Step::Revert(patch) => {
+                            patch.revert(cwd).map_err(|s| err_msg(s))?;
+                            applied.retain(|&applied| applied != patch);
+                            self.mk_cargo_process(compiler, cwd, build_kind)
+                                .incremental(true)
+                                .sources(&self.path, applied.clone())
+                                .run_rustc()?;
+                        }
+                    }
+                }
 
-                        // An incremental build with some changes (realistic
-                        // incremental case).
-                        let run_kind_str = format!("PatchedIncr{}", i);
-                        self.mk_cargo_process(compiler, cwd, build_kind)
-                            .incremental(true)
-                            .processor(processor, &self.name, RunKind::PatchedIncrs, &run_kind_str,
-                                       Some(&patch))
-                            .run_rustc()?;
+                if let IterationLimit::Adaptive { stat, max_cv, .. } = iterations.limit {
+                    match processor.variation(stat) {
+                        Some(cv) if !warmup && cv <= max_cv => {
+                            debug!("{} is stable after {} iterations (cv = {:.4})",
+                                   stat, i - iterations.warmup + 1, cv);
+                            break;
+                        }
+                        _ => {}
                     }
                 }
+                remove_temp_dir(timing_dir);
             }
+            let record_incr_stats = *INCR_STATS.lock().unwrap();
+            let incr_stats = if record_incr_stats && compiler.is_nightly && has_incremental {
+                self.measure_incr_stats(compiler, prep_dir.path(), build_kind, &run_kinds)?
+            } else {
+                Vec::new()
+            };
+            remove_temp_dir(prep_dir);
 
+            processor.cgu_stats(cgu_stats);
+            processor.unit_timings(unit_timings);
+            processor.ir_stats(ir_stats);
+            processor.incr_stats(incr_stats);
             processor.finish_build_kind(build_kind);
         }
 
@@ -735,14 +2605,29 @@ impl Benchmark {
 }
 
 #[derive(Fail, PartialEq, Eq, Debug)]
-enum DeserializeStatError {
+pub enum DeserializeStatError {
     #[fail(display = "could not deserialize empty output to stats, output: {:?}", _0)]
     NoOutput(process::Output),
     #[fail(display = "could not parse `{}` as a float", _0)]
     ParseError(String, #[fail(cause)] ::std::num::ParseFloatError),
 }
 
-fn process_perf_stat_output(output: process::Output) -> Result<Vec<Stat>, DeserializeStatError> {
+/// Merges the stats that were reported more than once, by the rustcs of the
+/// members of a workspace: `max-rss` is the largest of them, and every other
+/// stat is their sum.
+fn merge_stats(stats: Vec<Stat>) -> Vec<Stat> {
+    let mut merged: Vec<Stat> = Vec::with_capacity(stats.len());
+    for stat in stats {
+        match merged.iter().position(|s| s.name == stat.name) {
+            Some(i) if stat.name == "max-rss" => merged[i].cnt = merged[i].cnt.max(stat.cnt),
+            Some(i) => merged[i].cnt += stat.cnt,
+            None => merged.push(stat),
+        }
+    }
+    merged
+}
+
+pub fn process_perf_stat_output(output: process::Output) -> Result<Vec<Stat>, DeserializeStatError> {
     let stdout = String::from_utf8(output.stdout.clone()).expect("utf8 output");
     let mut stats = Vec::new();
 
@@ -765,7 +2650,7 @@ fn process_perf_stat_output(output: process::Output) -> Result<Vec<Stat>, Deseri
         let name = get!(parts.next());
         let _time = get!(parts.next());
         let pct = get!(parts.next());
-        if cnt == "<not supported>" {
+        if cnt == "<not supported>" || cnt == "<not counted>" {
             continue;
         }
         if !pct.starts_with("100.") {
@@ -788,6 +2673,411 @@ fn process_perf_stat_output(output: process::Output) -> Result<Vec<Stat>, Deseri
     Ok(stats)
 }
 
+/// Extracts how the crate was split into codegen units from the output of
+/// `-Zprint-mono-items` (on stdout), which lists the units each item was
+/// placed in, e.g.
+///
+///     MONO_ITEM fn foo::bar @@ foo.1a2b3c-cgu.0[External] foo.1a2b3c-cgu.3[Internal]
+///
+/// The number of units is `cgu-count`. Their sizes, estimated by the number
+/// of items in each (an item that's inlined into several units counts towards
+/// each), are `cgu-items:min`, `cgu-items:median` and `cgu-items:max`: a
+/// large maximum compared to the median means a unit that holds up the
+/// parallel codegen of the others. Compilers that don't print the items give
+/// no stats.
+fn process_mono_items_output(stdout: &[u8]) -> Vec<Stat> {
+    let stdout = String::from_utf8_lossy(stdout);
+    let mut items = HashMap::new();
+    for line in stdout.lines().filter(|line| line.starts_with("MONO_ITEM ")) {
+        let cgus = match line.find(" @@ ") {
+            Some(pos) => &line[pos + " @@ ".len()..],
+            None => continue,
+        };
+        for cgu in cgus.split_whitespace() {
+            let name = cgu.split('[').next().unwrap();
+            *items.entry(name.to_string()).or_insert(0u64) += 1;
+        }
+    }
+    if items.is_empty() {
+        return Vec::new();
+    }
+
+    let mut sizes = items.values().cloned().collect::<Vec<_>>();
+    sizes.sort();
+    vec![
+        Stat { name: "cgu-count".to_string(), cnt: sizes.len() as f64 },
+        Stat { name: "cgu-items:min".to_string(), cnt: sizes[0] as f64 },
+        Stat { name: "cgu-items:median".to_string(), cnt: sizes[sizes.len() / 2] as f64 },
+        Stat { name: "cgu-items:max".to_string(), cnt: sizes[sizes.len() - 1] as f64 },
+    ]
+}
+
+/// Extracts the build time of each unit from the timing messages that cargo
+/// prints (on stdout) with `-Ztimings=json`, e.g.
+///
+///     {"reason":"timing-info","package_id":"syn 0.15.22 (registry+...)",
+///      "target":{"name":"syn",...},"mode":"build","duration":9.87,...}
+///
+/// as `unit-time:$UNIT`, in seconds. The unit is named after its target, or
+/// for build scripts after its package, e.g. `unit-time:syn` and
+/// `unit-time:proc-macro2 (build script)`, or `(build script run)` for
+/// running it. Units built more than once (e.g. for the host and the target)
+/// are added up. Cargos that don't print the messages give no stats.
+fn process_unit_timings_output(stdout: &[u8]) -> Vec<Stat> {
+    let stdout = String::from_utf8_lossy(stdout);
+    let mut times = BTreeMap::new();
+    for line in stdout.lines().filter(|line| line.starts_with('{')) {
+        let message = match serde_json::from_str::<serde_json::Value>(line) {
+            Ok(message) => message,
+            Err(_) => continue,
+        };
+        if message["reason"] != "timing-info" {
+            continue;
+        }
+        let duration = match message["duration"].as_f64() {
+            Some(duration) => duration,
+            None => continue,
+        };
+        let package = message["package_id"].as_str().map(package_name).unwrap_or("?");
+        let target = message["target"]["name"].as_str().unwrap_or(package);
+        let unit = if message["mode"] == "run-custom-build" {
+            format!("{} (build script run)", package)
+        } else if target.starts_with("build-script-") {
+            format!("{} (build script)", package)
+        } else {
+            target.to_string()
+        };
+        *times.entry(unit).or_insert(0.0) += duration;
+    }
+    times.into_iter()
+        .map(|(unit, duration)| Stat { name: format!("unit-time:{}", unit), cnt: duration })
+        .collect()
+}
+
+/// Extracts the sizes of the crate's AST (after macro expansion) and HIR
+/// from the tables that `-Zhir-stats` prints (on stdout), e.g.
+///
+///     HIR STATS
+///
+///     Name                Accumulated Size         Count     Item Size
+///     ----------------------------------------------------------------
+///     Expr                           36432           506            72
+///     ...
+///     ----------------------------------------------------------------
+///     Total                         102336
+///
+/// For each of `ast` and `hir`, the number of nodes of each kind is
+/// `$IR-nodes:$KIND` (e.g. `hir-nodes:Expr`), and the total number and size
+/// (in bytes) of the nodes are `$IR-nodes` and `$IR-bytes`. The tables of
+/// several crates (of a workspace) are added up. The AST before expansion is
+/// left out, it's mostly what the source says.
+fn process_hir_stats_output(stdout: &[u8]) -> Vec<Stat> {
+    let stdout = String::from_utf8_lossy(stdout);
+    let mut sizes = BTreeMap::new();
+    let mut ir = None;
+    for line in stdout.lines() {
+        match line.trim() {
+            "PRE EXPANSION AST STATS" => { ir = None; continue; }
+            "POST EXPANSION AST STATS" => { ir = Some("ast"); continue; }
+            "HIR STATS" => { ir = Some("hir"); continue; }
+            _ => {}
+        }
+        let ir = match ir {
+            Some(ir) => ir,
+            None => continue,
+        };
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        let (kind, bytes, count) = match fields[..] {
+            [kind, bytes, count, _] if kind != "Name" => {
+                match (bytes.parse::<u64>(), count.parse::<u64>()) {
+                    (Ok(bytes), Ok(count)) => (kind, bytes, count),
+                    _ => continue,
+                }
+            }
+            _ => continue,
+        };
+        *sizes.entry(format!("{}-nodes:{}", ir, kind)).or_insert(0) += count;
+        *sizes.entry(format!("{}-nodes", ir)).or_insert(0) += count;
+        *sizes.entry(format!("{}-bytes", ir)).or_insert(0) += bytes;
+    }
+    sizes.into_iter().map(|(name, cnt)| Stat { name, cnt: cnt as f64 }).collect()
+}
+
+/// The name of the package with the id `id`, which is `$NAME $VERSION
+/// ($SOURCE)` for older cargos and `$SOURCE#$NAME@$VERSION` for newer ones,
+/// where `$NAME@` is left out if it's the last component of the source path.
+fn package_name(id: &str) -> &str {
+    match id.rfind('#') {
+        Some(pos) => {
+            let fragment = &id[pos + 1..];
+            match fragment.find('@') {
+                Some(at) => &fragment[..at],
+                None => id[..pos].trim_end_matches('/').rsplit('/').next().unwrap(),
+            }
+        }
+        None => id.split_whitespace().next().unwrap_or(id),
+    }
+}
+
+/// Extracts the size of the dep-graph from the output of `-Zincremental-info`
+/// (on stderr) as `dep-graph-nodes` and `dep-graph-edges`, and the share of
+/// the codegen units that were reused from the incremental cache, in
+/// percent, as `incr-module-reuse`, and their number as `incr-cgu-count`
+/// (apart from the `cgu-count` of the non-incremental build), e.g.
+///
+///     [incremental] Re-using 12 out of 16 modules
+///     [incremental] Total Node Count: 44393
+///     [incremental] Total Edge Count: 268471
+///
+/// Compilers that don't print these give no stats.
+fn process_incremental_info_output(stderr: &[u8]) -> Vec<Stat> {
+    let stderr = String::from_utf8_lossy(stderr);
+    let mut stats = Vec::new();
+    for line in stderr.lines() {
+        let line = line.trim();
+        if line.starts_with("[incremental] Re-using ") {
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            // `[incremental]`, `Re-using`, N, `out`, `of`, M, `modules`
+            if let (Some(Ok(reused)), Some(Ok(total))) =
+                    (words.get(2).map(|w| w.parse::<f64>()), words.get(5).map(|w| w.parse::<f64>())) {
+                if total > 0.0 {
+                    stats.push(Stat {
+                        name: "incr-module-reuse".to_string(),
+                        cnt: reused / total * 100.0,
+                    });
+                }
+                stats.push(Stat { name: "incr-cgu-count".to_string(), cnt: total });
+            }
+            continue;
+        }
+        let (name, count) = if line.starts_with("[incremental] Total Node Count:") {
+            ("dep-graph-nodes", &line["[incremental] Total Node Count:".len()..])
+        } else if line.starts_with("[incremental] Total Edge Count:") {
+            ("dep-graph-edges", &line["[incremental] Total Edge Count:".len()..])
+        } else {
+            continue;
+        };
+        if let Ok(count) = count.trim().parse() {
+            stats.push(Stat { name: name.to_string(), cnt: count });
+        }
+    }
+    stats
+}
+
+/// The categories syscalls are grouped into for the `syscalls:$CATEGORY`
+/// stats; anything not listed is counted as `other`.
+const SYSCALL_CATEGORIES: &[(&str, &[&str])] = &[
+    ("file", &[
+        "open", "openat", "close", "read", "write", "pread64", "pwrite64", "readv", "writev",
+        "stat", "fstat", "lstat", "newfstatat", "statx", "access", "faccessat", "lseek",
+        "getdents64", "readlink", "readlinkat", "unlink", "unlinkat", "rename", "renameat",
+        "renameat2", "mkdir", "mkdirat", "rmdir", "fcntl", "ioctl", "ftruncate", "fsync",
+        "fdatasync", "dup", "dup2", "dup3", "flock", "chmod", "fchmod", "utimensat", "link",
+        "linkat", "symlink", "copy_file_range", "fallocate", "getcwd", "chdir",
+    ]),
+    ("memory", &["mmap", "munmap", "mremap", "mprotect", "brk", "madvise"]),
+    ("process", &[
+        "clone", "clone3", "fork", "vfork", "execve", "wait4", "exit", "exit_group", "kill",
+        "tgkill", "set_tid_address", "set_robust_list", "futex", "sched_yield",
+        "sched_getaffinity", "prctl", "rt_sigaction", "rt_sigprocmask", "sigaltstack",
+        "getpid", "gettid",
+    ]),
+];
+
+/// Reads the number of calls of each syscall from the summary written by
+/// `strace -c`, which looks like:
+///
+///     % time     seconds  usecs/call     calls    errors syscall
+///     ------ ----------- ----------- --------- --------- ----------------
+///      41.03    0.000407          12        33           mmap
+///      18.95    0.000188          11        17         3 openat
+///     ...
+///     ------ ----------- ----------- --------- --------- ----------------
+///     100.00    0.000992                   124         5 total
+fn strace_syscall_counts(summary: &str) -> BTreeMap<String, u64> {
+    let mut counts = BTreeMap::new();
+    for line in summary.lines() {
+        let parts = line.split_whitespace().collect::<Vec<_>>();
+        if parts.len() < 5 || parts[0] == "%" || parts[0].starts_with('-') {
+            continue;
+        }
+        let syscall = parts[parts.len() - 1];
+        if syscall == "total" {
+            continue;
+        }
+        if let Ok(calls) = parts[3].parse::<u64>() {
+            *counts.entry(syscall.to_string()).or_insert(0) += calls;
+        }
+    }
+    counts
+}
+
+/// The start of the names of the summaries rustc-fake writes for strace, which
+/// end in the crate name and pid of the rustc.
+const STRACE_SUMMARY_PREFIX: &str = "strace-summary-";
+
+/// The summaries written by `strace -c` (via rustc-fake) in `cwd`, one per
+/// rustc, sorted by name.
+fn strace_summary_files(cwd: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(cwd)? {
+        let path = entry?.path();
+        if path.file_name().and_then(|n| n.to_str())
+            .map_or(false, |n| n.starts_with(STRACE_SUMMARY_PREFIX)) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Turns the summaries written by `strace -c` (via rustc-fake), one per rustc,
+/// into the total number of syscalls, `syscalls`, and the number per
+/// category, `syscalls:$CATEGORY`.
+fn process_strace_output(cwd: &Path, output: process::Output)
+                         -> Result<Vec<Stat>, DeserializeStatError> {
+    let mut counts = BTreeMap::new();
+    for summary_file in strace_summary_files(cwd).unwrap_or_default() {
+        if let Ok(summary) = fs::read_to_string(&summary_file) {
+            for (syscall, calls) in strace_syscall_counts(&summary) {
+                *counts.entry(syscall).or_insert(0) += calls;
+            }
+        }
+        // Don't let a stale summary end up in the next iteration.
+        let _ = fs::remove_file(&summary_file);
+    }
+    if counts.is_empty() {
+        return Err(DeserializeStatError::NoOutput(output));
+    }
+
+    let mut by_category = BTreeMap::new();
+    for &(category, _) in SYSCALL_CATEGORIES {
+        by_category.insert(category, 0);
+    }
+    by_category.insert("other", 0);
+    for (syscall, &calls) in &counts {
+        let category = SYSCALL_CATEGORIES.iter()
+            .find(|&&(_, syscalls)| syscalls.contains(&&**syscall))
+            .map_or("other", |&(category, _)| category);
+        *by_category.get_mut(category).unwrap() += calls;
+    }
+
+    let mut stats = vec![Stat {
+        name: "syscalls".to_string(),
+        cnt: counts.values().sum::<u64>() as f64,
+    }];
+    for (category, calls) in by_category {
+        stats.push(Stat { name: format!("syscalls:{}", category), cnt: calls as f64 });
+    }
+    Ok(stats)
+}
+
+/// Turns the output of `-Ztime-passes` into one stat per pass, named
+/// `time-passes:$PASS` and measured in seconds. Depending on the compiler
+/// version the output goes to stdout or stderr, and looks like one of:
+///
+///     time: 0.012; rss: 45MB	parsing
+///     time:   0.012; rss:   45MB ->   47MB (   +2MB)	parse_crate
+///
+/// Passes that are executed more than once (e.g. per codegen unit) are summed.
+fn process_time_passes_output(output: process::Output)
+                              -> Result<Vec<Stat>, DeserializeStatError> {
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+
+    let mut stats: Vec<Stat> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if !line.starts_with("time:") {
+            continue;
+        }
+        let rest = line["time:".len()..].trim_left();
+        let time = rest.split(|c: char| c == ';' || c.is_whitespace()).next().unwrap_or("");
+        let pass = match line.rfind('\t') {
+            Some(pos) => line[pos + 1..].trim(),
+            None => line.rsplit(char::is_whitespace).next().unwrap_or(""),
+        };
+        if pass.is_empty() {
+            continue;
+        }
+        let time: f64 = time.parse()
+            .map_err(|e| DeserializeStatError::ParseError(time.to_string(), e))?;
+        let name = format!("time-passes:{}", pass);
+        if let Some(stat) = stats.iter_mut().find(|s| s.name == name) {
+            stat.cnt += time;
+            continue;
+        }
+        stats.push(Stat { name, cnt: time });
+    }
+
+    if stats.is_empty() {
+        return Err(DeserializeStatError::NoOutput(output));
+    }
+
+    Ok(stats)
+}
+
+/// Extracts the summary statistics from the output of DHAT, which is written
+/// to stderr. Both the output of `exp-dhat` and of the newer `dhat` tool are
+/// understood:
+///
+///     ==1234== max_live:     63,669 in 61 blocks
+///     ==1234== tot_alloc:    1,209,536 in 3,007 blocks
+///
+///     ==1234== Total:     1,209,536 bytes in 3,007 blocks
+///     ==1234== At t-gmax: 63,669 bytes in 61 blocks
+fn process_dhat_output(output: process::Output) -> Result<Vec<Stat>, DeserializeStatError> {
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    let mut stats = Vec::new();
+
+    for line in stderr.lines() {
+        // Strip the `==PID==` prefix.
+        let line = match line.rfind("== ") {
+            Some(pos) => line[pos + 3..].trim(),
+            None => continue,
+        };
+        let (prefix, rest) = match line.find(':') {
+            Some(pos) => (&line[..pos], line[pos + 1..].trim()),
+            None => continue,
+        };
+        let name = match prefix {
+            "tot_alloc" | "Total" => "total",
+            "max_live" | "At t-gmax" => "peak",
+            _ => continue,
+        };
+        let numbers = rest.split_whitespace()
+            .map(|s| s.replace(",", ""))
+            .filter(|s| s.chars().all(|c| c.is_digit(10)) && !s.is_empty())
+            .collect::<Vec<_>>();
+        if numbers.len() != 2 {
+            continue;
+        }
+        for (value, unit) in numbers.iter().zip(&["bytes", "blocks"]) {
+            stats.push(Stat {
+                name: format!("dhat-{}-{}", name, unit),
+                cnt: value.parse()
+                    .map_err(|e| DeserializeStatError::ParseError(value.to_string(), e))?,
+            });
+        }
+    }
+
+    if stats.is_empty() {
+        return Err(DeserializeStatError::NoOutput(output));
+    }
+
+    Ok(stats)
+}
+
+/// Removes the stats of the runs in `state` from `incr_stats`, see
+/// `Processor::incr_stats`.
+fn take_incr_stats(incr_stats: &mut Vec<(BenchmarkState, Vec<Stat>)>, state: &BenchmarkState)
+                   -> Vec<Stat> {
+    match incr_stats.iter().position(|&(ref s, _)| s == state) {
+        Some(pos) => incr_stats.remove(pos).1,
+        None => Vec::new(),
+    }
+}
+
 fn process_stats(
     build_kind: BuildKind,
     state: BenchmarkState,
@@ -805,28 +3095,41 @@ fn process_stats(
     // all stats should be present in all runs
     let map = stats.values().map(|v| v.len()).collect::<HashSet<_>>();
     if map.len() != 1 {
-        eprintln!("build_kind: {:?}", build_kind);
-        eprintln!("state: {:?}", state);
-        eprintln!("lengths: {:?}", map);
-        eprintln!("runs: {:?}", runs);
-        eprintln!("stats: {:?}", stats);
+        error!("build_kind: {:?}", build_kind);
+        error!("state: {:?}", state);
+        error!("lengths: {:?}", map);
+        error!("runs: {:?}", runs);
+        error!("stats: {:?}", stats);
         panic!("expected all stats to be present in all runs");
     }
-    let stats = stats
-        .into_iter()
+    let samples = stats.into_iter().collect::<BTreeMap<_, _>>();
+    let stats = samples
+        .iter()
         .map(|(stat, counts)| Stat {
-            name: stat,
+            name: stat.clone(),
             cnt: counts
-                .into_iter()
-                .fold(f64::INFINITY, |acc, v| f64::min(acc, v)),
+                .iter()
+                .fold(f64::INFINITY, |acc, &v| f64::min(acc, v)),
         })
         .collect();
 
     Run {
         stats,
+        samples,
         check: build_kind == BuildKind::Check,
-        release: build_kind == BuildKind::Opt,
+        release: build_kind == BuildKind::Opt || build_kind == BuildKind::OptDebuginfo,
+        doc: build_kind == BuildKind::Doc,
+        debuginfo: build_kind == BuildKind::OptDebuginfo,
+        lto: build_kind == BuildKind::DebugLto,
+        target: None,
+        backend: CodegenBackend::Llvm,
+        linker: Linker::Default,
+        threads: None,
+        iterations: Some(runs.len()),
+        warmup: None,
         state: state,
+        layout: layout::control(),
+        target_dir: TargetDirPolicy::Reuse,
         // TODO: Aggregate self profiles.
         self_profile: runs[0].1.clone(),
     }
@@ -0,0 +1,139 @@
+//! Exports local benchmark results for use outside of the site: as JSON with
+//! a stable schema for other tools, or as Markdown for pasting into PR
+//! descriptions.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use failure::Error;
+use serde_json;
+
+use collector::CommitData;
+
+use compare::Delta;
+
+/// Bumped whenever the JSON output changes incompatibly.
+const SCHEMA_VERSION: u32 = 1;
+
+/// The stats shown in Markdown summaries; the JSON output has all of them.
+const MARKDOWN_STATS: &[&str] = &["instructions:u", "wall-time", "max-rss"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Json,
+    Markdown,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Result<OutputFormat, Error> {
+        match name {
+            "json" => Ok(OutputFormat::Json),
+            "markdown" => Ok(OutputFormat::Markdown),
+            _ => bail!("'{}' is not a known output format", name),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Results<'a> {
+    schema_version: u32,
+    id: &'a str,
+    runs: Vec<RunResult<'a>>,
+    /// Why each benchmark that failed did so.
+    errors: BTreeMap<&'a str, &'a str>,
+}
+
+#[derive(Serialize)]
+struct RunResult<'a> {
+    benchmark: &'a str,
+    run: String,
+    iterations: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warmup: Option<usize>,
+    stats: BTreeMap<&'a str, f64>,
+}
+
+#[derive(Serialize)]
+struct Comparison<'a> {
+    schema_version: u32,
+    deltas: &'a [Delta],
+}
+
+/// Writes the results of benchmarking one compiler.
+pub fn write_results(out: &mut dyn Write, data: &CommitData, format: OutputFormat)
+                     -> Result<(), Error> {
+    let mut results = Results {
+        schema_version: SCHEMA_VERSION,
+        id: &data.commit.sha,
+        runs: Vec::new(),
+        errors: BTreeMap::new(),
+    };
+    for (name, result) in &data.benchmarks {
+        match *result {
+            Ok(ref benchmark) => {
+                for run in &benchmark.runs {
+                    results.runs.push(RunResult {
+                        benchmark: name,
+                        run: run.name(),
+                        iterations: run.iterations,
+                        warmup: run.warmup,
+                        stats: run.stats.iter().map(|s| (&*s.name, s.cnt)).collect(),
+                    });
+                }
+            }
+            Err(ref e) => {
+                results.errors.insert(name, e);
+            }
+        }
+    }
+
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, &results)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Markdown => {
+            writeln!(out, "| Benchmark | Run | {} |", MARKDOWN_STATS.join(" | "))?;
+            writeln!(out, "|---|---|{}", "---:|".repeat(MARKDOWN_STATS.len()))?;
+            for run in &results.runs {
+                let stats = MARKDOWN_STATS.iter()
+                    .map(|stat| run.stats.get(stat).map_or(String::new(), |v| format!("{}", v)))
+                    .collect::<Vec<_>>();
+                writeln!(out, "| {} | {} | {} |", run.benchmark, run.run, stats.join(" | "))?;
+            }
+            if !results.errors.is_empty() {
+                writeln!(out)?;
+                writeln!(out, "Failed: {}",
+                         results.errors.keys().cloned().collect::<Vec<_>>().join(", "))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the comparison of two compilers.
+pub fn write_comparison(out: &mut dyn Write, deltas: &[Delta], format: OutputFormat)
+                        -> Result<(), Error> {
+    match format {
+        OutputFormat::Json => {
+            let comparison = Comparison { schema_version: SCHEMA_VERSION, deltas };
+            serde_json::to_writer_pretty(&mut *out, &comparison)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Markdown => {
+            writeln!(out, "| Benchmark | Run | Base | New | Change |")?;
+            writeln!(out, "|---|---|---:|---:|---:|")?;
+            for delta in deltas {
+                // Significant changes are in bold, so that they stand out.
+                let change = if delta.is_significant() {
+                    format!("**{:+.2}%**", delta.pct)
+                } else {
+                    format!("{:+.2}%", delta.pct)
+                };
+                writeln!(out, "| {} | {} | {} | {} | {} |",
+                         delta.benchmark, delta.run, delta.base, delta.new, change)?;
+            }
+        }
+    }
+    Ok(())
+}
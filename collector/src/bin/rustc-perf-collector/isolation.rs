@@ -0,0 +1,238 @@
+//! Isolates the collector (and so every cargo and rustc it spawns) from the
+//! rest of the machine, to reduce run-to-run variance on shared machines.
+//!
+//! CPU affinity and cgroup membership are both inherited by child processes,
+//! so it's enough to set them up once for the collector itself. With
+//! `--jobs`, each thread that runs benchmarks is then pinned to its own share
+//! of those CPUs.
+//!
+//! The sandbox is different: the collector itself needs the network (to
+//! download compilers and push results), so only the cargo invocations that
+//! build benchmarks are run in it.
+
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use failure::{Error, ResultExt};
+
+/// Where the cgroup v2 hierarchy is mounted.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// The number of CPUs a `cpu_set_t` can hold (`CPU_SETSIZE`), and so the
+/// number of CPUs the collector can be pinned to.
+const MAX_CPUS: usize = 1024;
+
+/// Parses a list of CPUs in the format `taskset -c` accepts, e.g. `2,4-7`.
+pub fn parse_cpu_set(s: &str) -> Result<Vec<usize>, Error> {
+    let mut cpus = Vec::new();
+    for part in s.split(',') {
+        let part = part.trim();
+        let mut range = part.splitn(2, '-');
+        let start = range.next().unwrap().parse::<usize>()
+            .with_context(|_| format!("invalid CPU: {}", part))?;
+        let end = match range.next() {
+            Some(end) => end.parse::<usize>().with_context(|_| format!("invalid CPU: {}", part))?,
+            None => start,
+        };
+        if end < start {
+            bail!("invalid CPU range: {}", part);
+        }
+        if end >= MAX_CPUS {
+            bail!("invalid CPU: {} (CPUs are numbered below {})", part, MAX_CPUS);
+        }
+        cpus.extend(start..=end);
+    }
+    cpus.sort();
+    cpus.dedup();
+    Ok(cpus)
+}
+
+/// The CPUs of the machine that are online.
+pub fn online_cpus() -> Result<Vec<usize>, Error> {
+    let online = fs::read_to_string("/sys/devices/system/cpu/online")
+        .context("failed to find the online CPUs")?;
+    parse_cpu_set(online.trim())
+}
+
+/// Restricts the calling thread, and every thread and process it starts from
+/// then on, to the given CPUs. Called before any other threads are started,
+/// that's the collector and all of its children.
+#[cfg(target_os = "linux")]
+pub fn pin_to_cpus(cpus: &[usize]) -> Result<(), Error> {
+    extern crate libc;
+
+    use std::io;
+    use std::mem;
+
+    // `CPU_SET` panics for CPUs the set can't hold.
+    if let Some(&cpu) = cpus.iter().find(|&&cpu| cpu >= libc::CPU_SETSIZE as usize) {
+        bail!("failed to pin to CPU {}: CPUs are numbered below {}", cpu, libc::CPU_SETSIZE);
+    }
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        for &cpu in cpus {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        if libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            bail!("failed to pin to CPUs {:?}: {}", cpus, io::Error::last_os_error());
+        }
+    }
+    info!("pinned to CPUs {:?}", cpus);
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_to_cpus(_cpus: &[usize]) -> Result<(), Error> {
+    bail!("--cpu-set is only supported on Linux");
+}
+
+/// Moves the collector and its children into the cgroup `name`, creating it
+/// if needed, and optionally limits the memory it may use.
+pub fn enter_cgroup(name: &str, memory_limit: Option<u64>) -> Result<(), Error> {
+    let path = PathBuf::from(CGROUP_ROOT).join(name);
+    fs::create_dir_all(&path)
+        .with_context(|_| format!("failed to create cgroup {:?}", path))?;
+    if let Some(limit) = memory_limit {
+        fs::write(path.join("memory.max"), limit.to_string())
+            .with_context(|_| format!("failed to set the memory limit of cgroup {:?}", path))?;
+    }
+    fs::write(path.join("cgroup.procs"), process::id().to_string())
+        .with_context(|_| format!("failed to enter cgroup {:?}", path))?;
+    info!("entered cgroup {:?}", path);
+    Ok(())
+}
+
+/// A tmpfs the collector mounted, which is unmounted again when dropped.
+pub struct Tmpfs {
+    pub path: PathBuf,
+}
+
+impl Drop for Tmpfs {
+    fn drop(&mut self) {
+        match Command::new("umount").arg(&self.path).status() {
+            Ok(ref status) if status.success() => info!("unmounted the tmpfs at {:?}", self.path),
+            _ => warn!("failed to unmount the tmpfs at {:?}", self.path),
+        }
+    }
+}
+
+/// Mounts a tmpfs to build the benchmarks in, so that disk I/O doesn't add
+/// noise to the measurements. Its size is limited to half of the memory that
+/// is available now, which leaves the other half to the compiler: a build
+/// that fills it up fails, rather than making the machine swap. (A tmpfs only
+/// takes up memory for what's written to it, so the limit costs nothing.)
+/// This usually requires root.
+#[cfg(target_os = "linux")]
+pub fn mount_tmpfs() -> Result<Tmpfs, Error> {
+    let meminfo = fs::read_to_string("/proc/meminfo")
+        .with_context(|_| "failed to read /proc/meminfo")?;
+    // e.g. `MemAvailable:   12345678 kB`
+    let available_kb = meminfo.lines()
+        .find(|line| line.starts_with("MemAvailable:"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|kb| kb.parse::<u64>().ok())
+        .ok_or_else(|| format_err!("no MemAvailable in /proc/meminfo"))?;
+    let size = available_kb * 1024 / 2;
+
+    let path = env::temp_dir().join("rustc-perf-tmpfs");
+    fs::create_dir_all(&path).with_context(|_| format!("failed to create {:?}", path))?;
+    let status = Command::new("mount")
+        .arg("-t").arg("tmpfs")
+        .arg("-o").arg(format!("size={},mode=1777", size))
+        .arg("tmpfs")
+        .arg(&path)
+        .status()
+        .with_context(|_| "failed to run mount")?;
+    if !status.success() {
+        bail!("failed to mount a tmpfs at {:?}; --work-dir-tmpfs usually requires root", path);
+    }
+    info!("building benchmarks in a tmpfs of {} MiB at {:?}", size >> 20, path);
+    Ok(Tmpfs { path })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn mount_tmpfs() -> Result<Tmpfs, Error> {
+    bail!("--work-dir-tmpfs is only supported on Linux");
+}
+
+lazy_static! {
+    static ref CPU_PARTITIONS: Mutex<Vec<Vec<usize>>> = Mutex::new(Vec::new());
+}
+
+/// Makes `bench_commit` run `jobs` benchmarks at a time, each pinned to its
+/// own CPUs. `cpus` is split into `jobs` disjoint sets of equal size; any
+/// CPUs left over aren't used.
+pub fn set_jobs(jobs: usize, cpus: &[usize]) -> Result<(), Error> {
+    if jobs == 0 {
+        bail!("--jobs must be at least 1");
+    }
+    if cpus.len() < jobs {
+        bail!("can't run {} jobs on only {} CPUs", jobs, cpus.len());
+    }
+    if jobs == 1 {
+        return Ok(());
+    }
+    if cfg!(not(target_os = "linux")) {
+        bail!("--jobs is only supported on Linux");
+    }
+    let partitions = cpus.chunks(cpus.len() / jobs)
+        .take(jobs)
+        .map(|cpus| cpus.to_vec())
+        .collect::<Vec<_>>();
+    info!("running benchmarks concurrently on CPUs {:?}", partitions);
+    *CPU_PARTITIONS.lock().unwrap() = partitions;
+    Ok(())
+}
+
+/// The CPUs of each of the benchmarks run at a time, or nothing if they're
+/// run one after the other.
+pub fn cpu_partitions() -> Vec<Vec<usize>> {
+    CPU_PARTITIONS.lock().unwrap().clone()
+}
+
+static SANDBOX: AtomicBool = AtomicBool::new(false);
+
+/// Makes every subsequent `sandboxed_command` run in a sandbox, after
+/// checking that sandboxes can be created on this machine.
+#[cfg(target_os = "linux")]
+pub fn enable_sandbox() -> Result<(), Error> {
+    let status = sandbox_command("true").status()
+        .with_context(|_| "failed to run unshare, which --sandbox requires")?;
+    if !status.success() {
+        bail!("failed to create a sandbox; are unprivileged user namespaces enabled?");
+    }
+    SANDBOX.store(true, Ordering::SeqCst);
+    info!("running benchmarks in a sandbox");
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn enable_sandbox() -> Result<(), Error> {
+    bail!("--sandbox is only supported on Linux");
+}
+
+/// Runs `program` in new user and network namespaces, so that it has no
+/// network access (not even loopback) and can't affect anything outside of
+/// them but the files the collector's user can write.
+fn sandbox_command<S: AsRef<OsStr>>(program: S) -> Command {
+    let mut cmd = Command::new("unshare");
+    cmd.arg("--user").arg("--map-root-user").arg("--net").arg("--").arg(program);
+    cmd
+}
+
+/// A `Command` for `program` that runs it in the sandbox if `--sandbox` was
+/// given. Meant for anything that runs code from the benchmarks, like build
+/// scripts and procedural macros.
+pub fn sandboxed_command<S: AsRef<OsStr>>(program: S) -> Command {
+    if SANDBOX.load(Ordering::SeqCst) {
+        sandbox_command(program)
+    } else {
+        Command::new(program)
+    }
+}
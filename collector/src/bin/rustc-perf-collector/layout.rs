@@ -0,0 +1,155 @@
+//! Controls the things that change the memory layout of the measured
+//! compiler from one run to the next, and so add noise to the measurements
+//! that has nothing to do with the compiler itself: the addresses ASLR picks,
+//! the length of the path of the directory benchmarks are built in, and the
+//! size of the environment, both of which move the stack.
+//!
+//! The compilers are prebuilt, so their link order can't be changed. To
+//! estimate how much the results depend on layout, the size of the
+//! environment can be randomized instead, which shifts the stack by a
+//! different amount for every run.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use collector::{stable_hash, LayoutControl};
+use failure::{Error, ResultExt};
+use tempfile::{Builder, TempDir};
+
+/// The name the directories benchmarks are built in get under the padded
+/// directory: a prefix and random characters, plus a separator.
+const TEMP_DIR_PREFIX: &str = "bench-";
+const TEMP_DIR_RAND_BYTES: usize = 8;
+
+/// How long a directory a benchmark was built in must have been left alone
+/// before it's taken to be left behind by a killed collector.
+const STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The most bytes the environment is padded with.
+const MAX_PADDING: usize = 4096;
+
+lazy_static! {
+    static ref CONTROL: Mutex<LayoutControl> = Mutex::new(LayoutControl::default());
+    static ref WORK_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+}
+
+/// Disables ASLR for the collector and everything it runs from then on.
+#[cfg(target_os = "linux")]
+pub fn disable_aslr() -> Result<(), Error> {
+    extern crate libc;
+
+    use std::io;
+
+    // From `<sys/personality.h>`.
+    const ADDR_NO_RANDOMIZE: libc::c_ulong = 0x0040000;
+    // Queries the personality without changing it.
+    const QUERY: libc::c_ulong = 0xffffffff;
+
+    unsafe {
+        let personality = libc::personality(QUERY);
+        if personality == -1 ||
+            libc::personality(personality as libc::c_ulong | ADDR_NO_RANDOMIZE) == -1 {
+            bail!("failed to disable ASLR: {}", io::Error::last_os_error());
+        }
+    }
+    CONTROL.lock().unwrap().aslr_disabled = true;
+    info!("disabled ASLR");
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn disable_aslr() -> Result<(), Error> {
+    bail!("--disable-aslr is only supported on Linux");
+}
+
+/// Makes benchmarks be built in directories whose paths are `length` bytes
+/// long, whatever the temporary directory of the machine is.
+pub fn set_path_length(length: usize) -> Result<(), Error> {
+    let temp_dir = env::temp_dir();
+    let unpadded = temp_dir.join("rustc-perf");
+    let unpadded_length = unpadded.as_os_str().len()
+        + 1 + TEMP_DIR_PREFIX.len() + TEMP_DIR_RAND_BYTES;
+    if length < unpadded_length {
+        bail!("--path-length must be at least {} in {:?}", unpadded_length, temp_dir);
+    }
+    let mut padded = unpadded.into_os_string();
+    padded.push("_".repeat(length - unpadded_length));
+    let padded = PathBuf::from(padded);
+    fs::create_dir_all(&padded).with_context(|_| format!("failed to create {:?}", padded))?;
+    info!("building benchmarks in {:?}", padded);
+    *WORK_DIR.lock().unwrap() = Some(padded);
+    CONTROL.lock().unwrap().path_length = Some(length);
+    Ok(())
+}
+
+/// Makes the environment of every rustc that is measured a different,
+/// random, size.
+pub fn randomize_environment() {
+    CONTROL.lock().unwrap().randomized_environment = true;
+    info!("randomizing the size of the environment");
+}
+
+/// How the layout is controlled, as recorded with every run.
+pub fn control() -> LayoutControl {
+    CONTROL.lock().unwrap().clone()
+}
+
+/// The directory benchmarks are built in, unless they're built on a tmpfs.
+pub fn work_dir() -> PathBuf {
+    WORK_DIR.lock().unwrap().clone().unwrap_or_else(env::temp_dir)
+}
+
+/// Removes the directories that benchmarks were built in by collectors that
+/// were killed, and that weren't touched for `STALE_AFTER`.
+pub fn remove_stale_temp_dirs() {
+    let work_dir = work_dir();
+    let entries = match fs::read_dir(&work_dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("failed to list {:?}: {}", work_dir, e);
+            return;
+        }
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let is_temp_dir = entry.file_name().to_str()
+            .map_or(false, |name| name.starts_with(TEMP_DIR_PREFIX));
+        let is_stale = entry.metadata().ok()
+            .filter(|metadata| metadata.is_dir())
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| modified.elapsed().ok())
+            .map_or(false, |age| age > STALE_AFTER);
+        if is_temp_dir && is_stale {
+            info!("removing the stale {:?}", entry.path());
+            if let Err(e) = fs::remove_dir_all(entry.path()) {
+                warn!("failed to remove {:?}: {}", entry.path(), e);
+            }
+        }
+    }
+}
+
+/// Creates a temporary directory to build a benchmark in.
+pub fn temp_dir() -> Result<TempDir, Error> {
+    let mut builder = Builder::new();
+    builder.prefix(TEMP_DIR_PREFIX).rand_bytes(TEMP_DIR_RAND_BYTES);
+    let dir = match *WORK_DIR.lock().unwrap() {
+        Some(ref work_dir) => builder.tempdir_in(work_dir)?,
+        None => builder.tempdir()?,
+    };
+    Ok(dir)
+}
+
+/// Pads the environment of `cmd` by a random number of bytes, if enabled.
+pub fn pad_environment(cmd: &mut Command) {
+    if !CONTROL.lock().unwrap().randomized_environment {
+        return;
+    }
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let padding = stable_hash(nanos.to_string().as_bytes()) as usize % (MAX_PADDING + 1);
+    cmd.env("RUSTC_PERF_LAYOUT_PADDING", "x".repeat(padding));
+}
@@ -0,0 +1,89 @@
+//! The collector's log, which is either the usual text of `env_logger`, or
+//! with `--log-json` one JSON object per line, for the monitoring of long
+//! unattended runs, e.g.
+//!
+//!     {"time":"2019-01-20T12:34:56.789+00:00","level":"INFO","target":"collector::execute",
+//!      "message":"...","span":{"benchmark":"syn","build_kind":"Debug","iteration":"2"}}
+//!
+//! What the collector is working on is tracked in spans (the benchmark, its
+//! build kind, the iteration, the run kind), which are part of every JSON
+//! line logged while they're open. When a span closes, how long it was open
+//! is logged, as `elapsed_secs` in JSON.
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::time::Instant;
+
+use chrono::Utc;
+use env_logger;
+use log::Level;
+use serde_json;
+
+thread_local! {
+    /// The spans open on this thread, outermost first.
+    static SPANS: RefCell<Vec<(&'static str, String)>> = RefCell::new(Vec::new());
+    /// The duration of the span being closed, while that's logged.
+    static ELAPSED: Cell<Option<f64>> = Cell::new(None);
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    time: String,
+    level: String,
+    target: &'a str,
+    message: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    span: BTreeMap<&'static str, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    elapsed_secs: Option<f64>,
+}
+
+/// Sets up the log, which is filtered by `$RUST_LOG` either way.
+pub fn init(json: bool) {
+    let mut builder = env_logger::Builder::from_default_env();
+    if json {
+        builder.format(|buf, record| {
+            let line = Line {
+                time: Utc::now().to_rfc3339(),
+                level: record.level().to_string(),
+                target: record.target(),
+                message: record.args().to_string(),
+                span: SPANS.with(|spans| spans.borrow().iter().cloned().collect()),
+                elapsed_secs: ELAPSED.with(|elapsed| elapsed.get()),
+            };
+            writeln!(buf, "{}", serde_json::to_string(&line).unwrap())
+        });
+    }
+    builder.init();
+}
+
+/// An open span, which is closed when it's dropped.
+pub struct Span {
+    key: &'static str,
+    level: Level,
+    start: Instant,
+}
+
+/// Opens the span `key` = `value` on this thread, e.g. `benchmark` = `syn`.
+/// Its closing is logged at `level`.
+pub fn span<V: ToString>(key: &'static str, value: V, level: Level) -> Span {
+    SPANS.with(|spans| spans.borrow_mut().push((key, value.to_string())));
+    Span { key, level, start: Instant::now() }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+        ELAPSED.with(|cell| cell.set(Some(elapsed)));
+        log!(self.level, "{} done in {:.2}s", self.key, elapsed);
+        ELAPSED.with(|cell| cell.set(None));
+        SPANS.with(|spans| {
+            let mut spans = spans.borrow_mut();
+            if let Some(pos) = spans.iter().rposition(|&(key, _)| key == self.key) {
+                spans.remove(pos);
+            }
+        });
+    }
+}
@@ -5,6 +5,8 @@ extern crate chrono;
 #[macro_use]
 extern crate clap;
 extern crate collector;
+extern crate crossbeam_utils;
+extern crate csv;
 extern crate env_logger;
 #[macro_use]
 extern crate failure;
@@ -16,52 +18,138 @@ extern crate rust_sysroot;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
 extern crate serde_json;
 extern crate tempfile;
 extern crate rustup;
 extern crate semver;
 extern crate reqwest;
+extern crate sha2;
+extern crate hex;
 extern crate futures;
+extern crate toml;
+#[cfg(feature = "parquet")]
+extern crate parquet;
 
 use failure::{Error, ResultExt, SyncFailure};
 
-use std::collections::HashSet;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::env;
+use std::panic::{self, AssertUnwindSafe};
 use std::process;
 use std::str;
 use std::path::{Path, PathBuf};
-use std::io::{stderr, Write};
+use std::io::{stderr, stdout, Write};
 use std::collections::BTreeMap;
-use std::sync::Arc;
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-use chrono::{Timelike, Utc};
+use chrono::{Datelike, NaiveDate, Timelike, Utc};
 use futures::sync::mpsc::{unbounded as unbounded_channel, UnboundedSender, UnboundedReceiver};
 use futures::stream::Stream;
+use log::Level;
 
-use collector::{Commit, ArtifactData, CommitData, Date};
-use collector::api::collected;
+use collector::{Commit, ArtifactData, ArtifactMetadata, ArtifactType, Category, CodegenBackend,
+                Collection, CommitData, Date, Flavor, Linker, Release, RuntimeData, Shard,
+                TargetDirPolicy, DEFAULT_STAT, ice_message};
+use collector::api::{collected, next_commit};
+use collector::db;
 use rust_sysroot::git::Commit as GitCommit;
-use rust_sysroot::sysroot::Sysroot;
 
+mod add_benchmark;
+mod artifact_size;
+mod bootstrap;
+mod cache;
+mod calibrate;
+mod cargo_ops;
+mod collection;
+mod compare;
+mod config;
+mod db_export;
+mod db_import;
+mod disk;
+mod environment;
 mod execute;
+mod export;
+mod isolation;
+mod layout;
+mod logging;
+mod outliers;
 mod outrepo;
+mod plan;
+mod progress;
+mod published;
+mod retention;
+mod runtime;
+mod sensitivity;
+mod shutdown;
+mod spool;
+mod suite;
+mod upload;
 
-use execute::{Benchmark, Profiler};
+use execute::{Benchmark, IterationLimit, Iterations, Profiler};
 
 #[derive(Debug, Copy, Clone)]
 pub struct Compiler<'a> {
     pub rustc: &'a Path,
     pub cargo: &'a Path,
     pub is_nightly: bool,
+    /// The target to build for, if not the host.
+    pub target: Option<&'a str>,
+    pub backend: CodegenBackend,
+    pub linker: Linker,
+    /// The number of frontend threads to use (`-Zthreads`), if set.
+    pub threads: Option<u32>,
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+impl<'a> Compiler<'a> {
+    /// The rustdoc that is shipped alongside `rustc`.
+    pub fn rustdoc(&self) -> PathBuf {
+        self.rustc.with_file_name(format!("rustdoc{}", env::consts::EXE_SUFFIX))
+    }
+}
+
+/// The triple of the machine the collector runs on, which results are
+/// recorded under, and which the compilers downloaded from CI are built for.
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+pub const HOST_TRIPLE: &str = "x86_64-unknown-linux-gnu";
+#[cfg(all(target_arch = "x86_64", target_os = "windows", target_env = "msvc"))]
+pub const HOST_TRIPLE: &str = "x86_64-pc-windows-msvc";
+#[cfg(all(target_arch = "x86_64", target_os = "windows", target_env = "gnu"))]
+pub const HOST_TRIPLE: &str = "x86_64-pc-windows-gnu";
+#[cfg(all(target_arch = "x86_64", target_os = "macos"))]
+pub const HOST_TRIPLE: &str = "x86_64-apple-darwin";
+#[cfg(all(target_arch = "aarch64", target_os = "linux"))]
+pub const HOST_TRIPLE: &str = "aarch64-unknown-linux-gnu";
+#[cfg(all(target_arch = "aarch64", target_os = "macos"))]
+pub const HOST_TRIPLE: &str = "aarch64-apple-darwin";
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub enum BuildKind {
     Check,
     Debug,
-    Opt
+    Opt,
+    Doc,
+    /// An optimized build with full debuginfo.
+    OptDebuginfo,
+    /// A debug build with LTO.
+    DebugLto,
+}
+
+impl BuildKind {
+    fn all() -> Vec<BuildKind> {
+        vec![BuildKind::Check, BuildKind::Debug, BuildKind::Opt, BuildKind::Doc,
+             BuildKind::OptDebuginfo, BuildKind::DebugLto]
+    }
+
+    /// Whether this build kind is only run for benchmarks that ask for it in
+    /// their `extra_profiles`.
+    pub fn is_extra(self) -> bool {
+        self == BuildKind::OptDebuginfo || self == BuildKind::DebugLto
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -99,6 +187,9 @@ const STRINGS_AND_BUILD_KINDS: &[(&str, BuildKind)] = &[
     ("Check", BuildKind::Check),
     ("Debug", BuildKind::Debug),
     ("Opt", BuildKind::Opt),
+    ("Doc", BuildKind::Doc),
+    ("OptDebuginfo", BuildKind::OptDebuginfo),
+    ("DebugLto", BuildKind::DebugLto),
 ];
 
 // How the --runs arg maps to RunKinds.
@@ -108,13 +199,80 @@ const STRINGS_AND_RUN_KINDS: &[(&str, RunKind)] = &[
     ("BaseIncr", RunKind::BaseIncr),
     ("CleanIncr", RunKind::CleanIncr),
     ("PatchedIncrs", RunKind::PatchedIncrs),
+    // Aliases for the incremental scenarios, named after what they measure: a
+    // full incremental build, a rebuild with no changes, and a rebuild after
+    // applying each of the benchmark's patches.
+    ("IncrFull", RunKind::BaseIncr),
+    ("IncrUnchanged", RunKind::CleanIncr),
+    ("IncrPatched", RunKind::PatchedIncrs),
+    ("Full", RunKind::Clean),
 ];
 
 pub fn build_kinds_from_arg(arg: &Option<&str>) -> Result<Vec<BuildKind>, KindError> {
     if let Some(arg) = arg {
         kinds_from_arg(STRINGS_AND_BUILD_KINDS, arg)
     } else {
-        Ok(vec![BuildKind::Check, BuildKind::Debug, BuildKind::Opt])
+        // rustdoc is only benchmarked when asked for.
+        Ok(BuildKind::all().into_iter().filter(|&kind| kind != BuildKind::Doc).collect())
+    }
+}
+
+pub fn backend_from_arg(arg: &Option<&str>) -> Result<CodegenBackend, KindError> {
+    if let Some(arg) = arg {
+        CodegenBackend::from_name(arg)
+            .ok_or_else(|| KindError::UnknownKind("backend", arg.to_string()))
+    } else {
+        Ok(CodegenBackend::Llvm)
+    }
+}
+
+pub fn linker_from_arg(arg: &Option<&str>) -> Result<Linker, KindError> {
+    if let Some(arg) = arg {
+        Linker::from_name(arg).ok_or_else(|| KindError::UnknownKind("linker", arg.to_string()))
+    } else {
+        Ok(Linker::Default)
+    }
+}
+
+pub fn flavor_from_arg(arg: &Option<&str>) -> Result<Flavor, KindError> {
+    if let Some(arg) = arg {
+        Flavor::from_name(arg).ok_or_else(|| KindError::UnknownKind("flavor", arg.to_string()))
+    } else {
+        Ok(Flavor::Plain)
+    }
+}
+
+/// Parses a size in GiB, which may be fractional, into bytes.
+fn gib_from_arg(arg: &str) -> Result<u64, Error> {
+    let gib = arg.parse::<f64>().with_context(|_| format!("invalid size: {}", arg))?;
+    if gib < 0.0 {
+        bail!("invalid size: {}", arg);
+    }
+    Ok((gib * (1u64 << 30) as f64) as u64)
+}
+
+/// The URL of the database given with `--db`, or else by `$DATABASE_URL`.
+fn database_url(arg: Option<&str>) -> Result<String, Error> {
+    match arg {
+        Some(url) => Ok(url.to_string()),
+        None => env::var("DATABASE_URL")
+            .map_err(|_| format_err!("no database given with --db or $DATABASE_URL")),
+    }
+}
+
+/// Parses a comma-separated list of `-Zthreads` values. No list means that
+/// `-Zthreads` isn't passed at all.
+pub fn threads_from_arg(arg: &Option<&str>) -> Result<Vec<Option<u32>>, Error> {
+    if let Some(arg) = arg {
+        arg.split(',')
+            .map(|s| -> Result<_, Error> {
+                let threads = s.parse::<u32>()
+                    .with_context(|_| format!("invalid thread count: {}", s))?;
+                Ok(Some(threads))
+            })
+            .collect()
+    } else {
+        Ok(vec![None])
     }
 }
 
@@ -126,8 +284,15 @@ pub fn run_kinds_from_arg(arg: &Option<&str>) -> Result<Vec<RunKind>, KindError>
     }
 }
 
+// Normalizes a kind name so that e.g. `incr-patched` and `IncrPatched` are
+// treated the same.
+fn normalize_kind_name(s: &str) -> String {
+    s.chars().filter(|&c| c != '-' && c != '_').flat_map(char::to_lowercase).collect()
+}
+
 // Converts a comma-separated list of kind names to a vector of kinds with no
-// duplicates.
+// duplicates. Names are matched case-insensitively, ignoring dashes and
+// underscores.
 fn kinds_from_arg<K>(strings_and_kinds: &[(&str, K)], arg: &str)
                      -> Result<Vec<K>, KindError>
     where K: Copy + Eq + ::std::hash::Hash
@@ -135,9 +300,12 @@ fn kinds_from_arg<K>(strings_and_kinds: &[(&str, K)], arg: &str)
     let mut kind_set = HashSet::new();
 
     for s in arg.split(',') {
-        if let Some((_s, k)) = strings_and_kinds.iter().find(|(str, _k)| s == *str) {
+        let name = normalize_kind_name(s);
+        if let Some((_s, k)) = strings_and_kinds.iter()
+            .find(|(str, _k)| normalize_kind_name(str) == name)
+        {
             kind_set.insert(k);
-        } else if s == "All" {
+        } else if name == "all" {
             for (_, k) in strings_and_kinds.iter() {
                 kind_set.insert(k);
             }
@@ -147,9 +315,11 @@ fn kinds_from_arg<K>(strings_and_kinds: &[(&str, K)], arg: &str)
     }
 
     // Nb: the element order of `v` must match that of `strings_and_kinds`.
+    // Aliases map to kinds that already appear earlier in the list, so
+    // `remove` ensures each kind is only pushed once.
     let mut v = vec![];
     for (_s, k) in strings_and_kinds.iter() {
-        if kind_set.contains(k) {
+        if kind_set.remove(k) {
             v.push(*k);
         }
     }
@@ -204,18 +374,23 @@ fn send_home(d: &collected::Request) {
 
 fn bench_commit(
     repo: Option<&outrepo::Repo>,
+    history: &[CommitData],
     commit: &GitCommit,
+    metadata: Option<&ArtifactMetadata>,
     triple: &str,
+    flavor: Flavor,
     build_kinds: &[BuildKind],
     run_kinds: &[RunKind],
     compiler: Compiler,
     benchmarks: &[Benchmark],
-    iterations: usize,
+    shard: Option<&Shard>,
+    iterations: Iterations,
     call_home: bool,
-) -> CommitData {
+    profiler: Profiler,
+) -> Result<CommitData, Error> {
     info!(
-        "benchmarking commit {} ({}) for triple {}",
-        commit.sha, commit.date, triple
+        "benchmarking commit {} ({}) for triple {} ({} build)",
+        commit.sha, commit.date, triple, flavor.name()
     );
 
     if call_home {
@@ -227,7 +402,33 @@ fn bench_commit(
             benchmarks: benchmarks.iter().map(|b| b.name.clone()).collect(),
         });
     }
-    let existing_data = repo.and_then(|r| r.load_commit_data(&commit, &triple).ok());
+    let start = Instant::now();
+    let is_try = match metadata {
+        Some(metadata) => metadata.kind == ArtifactType::Try,
+        None => Commit { sha: commit.sha.clone(), date: Date(commit.date) }.is_try(),
+    };
+    let existing_data = repo.and_then(|r| r.load_commit_data(&commit, &triple, flavor).ok());
+    let llvm_version = collection::llvm_version(compiler.rustc);
+    let environment = environment::current();
+    let mut collection = collection::current(compiler.rustc);
+    collection.suite_version = suite::version(benchmarks);
+    collection.shard = shard.cloned();
+    // Which collector produced each of the results, if there are several.
+    let collected_by = |results: &BTreeMap<String, _>| -> BTreeMap<String, String> {
+        match shard {
+            Some(shard) => results.keys()
+                .map(|name: &String| (name.clone(), shard.collector.clone()))
+                .collect(),
+            None => BTreeMap::new(),
+        }
+    };
+    // The time spent on reused results counts towards that of this
+    // collection.
+    let mut previous_duration = 0.0;
+    if let Some(previous) = existing_data.as_ref().and_then(|d| d.collection.as_ref()) {
+        previous_duration = previous.duration.unwrap_or(0.0);
+        collection.benchmark_durations = previous.benchmark_durations.clone();
+    }
 
     let mut results = BTreeMap::new();
     if let Some(ref data) = existing_data {
@@ -246,54 +447,844 @@ fn bench_commit(
             }
         }
     }
+    // The ICEs of the failed benchmarks, which are also kept with the results
+    // of a resumed collection.
+    let ices = Mutex::new(existing_data.as_ref().map_or_else(BTreeMap::new, |data| {
+        data.ices.iter()
+            .filter(|&(name, _)| results.contains_key(name))
+            .map(|(name, ice)| (name.clone(), ice.clone()))
+            .collect()
+    }));
 
-    for benchmark in benchmarks {
-        if results.contains_key(&benchmark.name) {
-            continue;
+    // What was measured of the benchmarks that were in progress when the
+    // collection was interrupted.
+    let progress = repo.map(|r| {
+        Arc::new(progress::Progress::load(r.path(), &commit.sha, triple, flavor))
+    });
+
+    // The bootstrap isn't one of `benchmarks`, but measured by `bench_rustc`,
+    // so what it recorded for the commit is kept when the commit is
+    // benchmarked again.
+    let bootstrap_result = existing_data.as_ref()
+        .and_then(|data| data.benchmarks.get(bootstrap::BENCHMARK_NAME).cloned());
+    let with_bootstrap_result = |mut results: BTreeMap<String, _>| {
+        if let Some(ref result) = bootstrap_result {
+            results.insert(bootstrap::BENCHMARK_NAME.to_string(), result.clone());
+        }
+        results
+    };
+
+    // The results so far, which are saved after every benchmark, and when the
+    // collector is shut down.
+    let partial_data = |results: &BTreeMap<String, _>, collection: &Collection| {
+        CommitData {
+            commit: Commit {
+                sha: commit.sha.clone(),
+                date: Date(commit.date),
+            },
+            triple: triple.to_string(),
+            flavor,
+            llvm_version: llvm_version.clone(),
+            benchmarks: with_bootstrap_result(results.clone()),
+            bootstrap: BTreeMap::new(),
+            environment: Some(environment.clone()),
+            collection: Some(collection.clone()),
+            artifact_sizes: BTreeMap::new(),
+            collected_by: collected_by(results),
+            shards: Vec::new(),
+            outliers: Vec::new(),
+            ices: ices.lock().unwrap().clone(),
+            metadata: metadata.cloned(),
+        }
+    };
+    // Reviewers are waiting on the results of try builds, so those are
+    // pushed as well.
+    let save_partial = |repo: &outrepo::Repo, data: &CommitData| {
+        if is_try {
+            repo.success(data)
+        } else {
+            repo.add_commit_data(data)
         }
+    };
 
-        let mut processor = execute::MeasureProcessor::new(&benchmark.name);
-        let result = benchmark.measure(
-            &mut processor, build_kinds, run_kinds, compiler, iterations);
-        let result = match result {
-            Ok(()) => Ok(processor.collected),
+    let todo = benchmarks.iter()
+        .filter(|benchmark| !results.contains_key(&benchmark.name))
+        .collect::<Vec<_>>();
+    let measure = |benchmark: &Benchmark| {
+        let _span = logging::span("benchmark", &benchmark.name, Level::Info);
+        // A panic while benchmarking (e.g. on unexpected output) is recorded
+        // as a failure of this benchmark, like any other error, rather than
+        // aborting the whole collection.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut processor =
+                execute::MeasureProcessor::new(&benchmark.name, profiler, compiler)?;
+            processor = processor.with_warmup(iterations.warmup);
+            if let Some(ref progress) = progress {
+                processor = processor.with_progress(progress.clone());
+            }
+            benchmark.measure(&mut processor, build_kinds, run_kinds, compiler, iterations)
+                .map(|()| {
+                    let mut collected = processor.collected;
+                    collected.category = benchmark.category();
+                    collected
+                })
+        })).unwrap_or_else(|payload| {
+            let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| String::from("unknown panic"));
+            Err(format_err!("panicked: {}", message))
+        });
+        match result {
+            Ok(collected) => Ok(collected),
             Err(ref s) => {
                 info!("failed to benchmark {}, recorded: {}", benchmark.name, s);
+                // The output of the failed build is in one of the causes.
+                let output = s.iter_chain().map(|c| c.to_string()).collect::<Vec<_>>().join("\n");
+                if let Some(ice) = ice_message(&output) {
+                    warn!("{} ran into an internal compiler error", benchmark.name);
+                    ices.lock().unwrap().insert(benchmark.name.clone(), ice);
+                }
                 Err(format!("{:?}", s))
             }
-        };
+        }
+    };
 
-        if call_home {
-            send_home(&collected::Request::BenchmarkDone {
-                benchmark: benchmark.name.clone(),
-                commit: Commit {
-                    sha: commit.sha.clone(),
-                    date: Date(commit.date),
-                },
-            });
+    // The benchmarks are measured by a single thread, or with `--jobs` by one
+    // thread per CPU partition, each pinned to its partition. Every benchmark
+    // is built in its own temporary directory, so they don't share any
+    // target directories either way.
+    let partitions = isolation::cpu_partitions();
+    let workers = if partitions.is_empty() {
+        vec![None]
+    } else {
+        partitions.into_iter().map(Some).collect()
+    };
+    let queue = Mutex::new(todo.into_iter());
+    let (sender, receiver) = mpsc::channel();
+    // A worker that can't be pinned to its partition measures nothing, and
+    // fails the collection once the other workers are done.
+    crossbeam_utils::thread::scope(|scope| -> Result<(), Error> {
+        let mut handles = Vec::new();
+        for cpus in workers {
+            let sender = sender.clone();
+            let queue = &queue;
+            let measure = &measure;
+            handles.push(scope.spawn(move |_| -> Result<(), Error> {
+                if let Some(cpus) = cpus {
+                    isolation::pin_to_cpus(&cpus)
+                        .context("failed to pin a benchmark thread")?;
+                }
+                while !shutdown::requested() {
+                    let benchmark = match queue.lock().unwrap().next() {
+                        Some(benchmark) => benchmark,
+                        None => break,
+                    };
+                    let start = Instant::now();
+                    let result = measure(benchmark);
+                    sender.send((benchmark, result, start.elapsed())).unwrap();
+                }
+                Ok(())
+            }));
+        }
+        drop(sender);
+
+        for (benchmark, result, duration) in receiver {
+            // A benchmark that failed because the collector is shutting down
+            // is resumed, from the build kinds that were done, by the next
+            // run of the collector.
+            if result.is_err() && shutdown::requested() {
+                info!("{} was interrupted", benchmark.name);
+                continue;
+            }
+            collection.benchmark_durations.insert(benchmark.name.clone(), as_secs_f64(duration));
+            if call_home {
+                send_home(&collected::Request::BenchmarkDone {
+                    benchmark: benchmark.name.clone(),
+                    commit: Commit {
+                        sha: commit.sha.clone(),
+                        date: Date(commit.date),
+                    },
+                });
+            }
+
+            results.insert(benchmark.name.clone(), result);
+            info!("{} benchmarks left", benchmarks.len() - results.len());
+
+            // The results so far are saved after every benchmark, so that an
+            // interrupted collection can be resumed.
+            if let Some(repo) = repo {
+                match save_partial(repo, &partial_data(&results, &collection)) {
+                    Ok(()) => if let Some(ref progress) = progress {
+                        progress.finish(&benchmark.name);
+                    },
+                    Err(e) => warn!("failed to save the results so far: {:?}", e),
+                }
+            }
+        }
+
+        for handle in handles {
+            handle.join().unwrap_or_else(|_| Err(format_err!("a benchmark thread panicked")))?;
         }
+        Ok(())
+    }).unwrap_or_else(|_| Err(format_err!("a benchmark thread panicked")))?;
 
-        results.insert(benchmark.name.clone(), result);
-        info!("{} benchmarks left", benchmarks.len() - results.len());
+    // When the collector is shut down, the rest of the collection is left to
+    // the run that resumes it, which also counts the time spent so far.
+    if shutdown::requested() {
+        collection.duration = Some(previous_duration + as_secs_f64(start.elapsed()));
+        collection.interrupted = true;
+        let data = partial_data(&results, &collection);
+        if let Some(repo) = repo {
+            if let Err(e) = save_partial(repo, &data) {
+                warn!("failed to save the results so far: {:?}", e);
+            }
+        }
+        return Ok(data);
     }
 
-    CommitData {
+    // Results that are far off from those of the previous commits are
+    // checked by rerunning their benchmarks, to tell real changes from
+    // noise.
+    let history = history.iter()
+        .filter(|data| {
+            data.triple == triple && data.flavor == flavor && data.commit.date < commit.date
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    let outliers = outliers::check(&results, &history, DEFAULT_STAT, |name| {
+        let benchmark = benchmarks.iter().find(|b| b.name == name)?;
+        // A rerun starts from scratch, rather than from the progress of the
+        // first run.
+        if let Some(ref progress) = progress {
+            progress.finish(name);
+        }
+        measure(benchmark).ok()
+    });
+
+    // Only CI artifacts are benchmarked with an output repo, so that's when
+    // the rustbuild timings CI recorded while building them are collected.
+    // CI only publishes those of the regular build.
+    let bootstrap = match existing_data {
+        Some(ref data) if !data.bootstrap.is_empty() => data.bootstrap.clone(),
+        _ if repo.is_some() && flavor.is_plain() => {
+            bootstrap::ci_step_timings(&commit.sha, triple).unwrap_or_else(|e| {
+                warn!("failed to fetch CI metrics for {}: {:?}", commit.sha, e);
+                BTreeMap::new()
+            })
+        }
+        _ => BTreeMap::new(),
+    };
+
+    let artifact_sizes = match existing_data {
+        Some(ref data) if !data.artifact_sizes.is_empty() => data.artifact_sizes.clone(),
+        _ => artifact_size::measure(compiler.rustc, triple).unwrap_or_else(|e| {
+            warn!("failed to measure the size of {}: {:?}", commit.sha, e);
+            BTreeMap::new()
+        }),
+    };
+
+    collection.duration = Some(previous_duration + as_secs_f64(start.elapsed()));
+    if let Some(ref progress) = progress {
+        progress.clear();
+    }
+
+    Ok(CommitData {
         commit: Commit {
             sha: commit.sha.clone(),
             date: Date(commit.date),
         },
         triple: triple.to_string(),
-        benchmarks: results,
+        flavor,
+        llvm_version: llvm_version.clone(),
+        collected_by: collected_by(&results),
+        benchmarks: with_bootstrap_result(results),
+        bootstrap,
+        environment: Some(environment.clone()),
+        collection: Some(collection),
+        artifact_sizes,
+        shards: Vec::new(),
+        outliers,
+        ices: ices.lock().unwrap().clone(),
+        metadata: metadata.cloned(),
+    })
+}
+
+/// The metadata of `commit` if it's one of the bors merges `commits` (which
+/// are oldest first), whose parent is the merge before it.
+fn master_metadata(commits: &[GitCommit], commit: &GitCommit) -> Option<ArtifactMetadata> {
+    let index = commits.iter().position(|c| c.sha == commit.sha)?;
+    let parent = index.checked_sub(1).map(|i| commits[i].sha.clone());
+    Some(ArtifactMetadata::master(Date(commit.date), &commit.summary, parent))
+}
+
+fn as_secs_f64(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1e9
+}
+
+/// Asks the site for the next commit it wants results for, and if there is
+/// one, benchmarks it and pushes the results. Returns whether there was one.
+/// With a `collector` name, the site assigns the collector a shard of the
+/// benchmarks, and only those are run. With `alt`, the alternative build of
+/// the commit is benchmarked as well.
+///
+/// If the commit's sysroot can't be installed, the commit is skipped, unless
+/// `install_failures` keeps count of the failed attempts of each commit: then
+/// the attempt fails, to be retried, until `SYSROOT_ATTEMPTS` of them failed
+/// and the commit is recorded as failed, so that the site stops asking for it.
+fn process_next_commit(
+    out_repo: &outrepo::Repo,
+    cache: &mut cache::Cache,
+    collector: Option<&str>,
+    benchmarks: &[Benchmark],
+    commits: &[GitCommit],
+    alt: bool,
+    history: &mut Vec<CommitData>,
+    install_failures: Option<&mut HashMap<String, usize>>,
+) -> Result<bool, Error> {
+    let client = reqwest::Client::new();
+    let url = format!(
+        "{}/perf/next_commit",
+        env::var("SITE_URL").expect("SITE_URL defined")
+    );
+    let mut request = client.get(&url);
+    if let Some(collector) = collector {
+        // Percent-encoded, as the name may be anything.
+        request = request.query(&[("collector", collector)]);
+    }
+    let next: next_commit::Response = request.send()?.json()?;
+    let commit = if let Some(c) = next.commit {
+        c
+    } else {
+        // no missing commits
+        return Ok(false);
+    };
+    // The sort is stable, so the benchmarks without a priority stay in their
+    // usual order.
+    let mut benchmarks = benchmarks.to_vec();
+    if let Some(ref shard) = next.shard {
+        info!("collecting shard {} of {}", shard.index + 1, shard.count);
+        benchmarks.retain(|b| shard.contains(&b.name));
+    }
+    let priority = &next.priority;
+    benchmarks.sort_by_key(|b| {
+        priority.iter().position(|name| *name == b.name).unwrap_or(priority.len())
+    });
+
+    let commit = commits.iter()
+        .find(|c| c.sha == commit)
+        .cloned()
+        .unwrap_or_else(|| {
+            warn!("utilizing fake commit!");
+            rust_sysroot::git::Commit {
+                sha: commit.to_string(),
+                date: Date::ymd_hms(2000, 01, 01, 0, 0, 0).0,
+                summary: String::new(),
+            }
+        });
+    let metadata = next.metadata.clone().or_else(|| master_metadata(commits, &commit));
+    let sysroot = match cache.install_sysroot(&commit, HOST_TRIPLE, false) {
+        Ok(sysroot) => sysroot,
+        Err(e) => {
+            let e = format_err!("failed to install sysroot for {}: {}", commit.sha, e);
+            let failures = match install_failures {
+                Some(failures) => failures,
+                None => {
+                    error!("{}", e);
+                    return Ok(true);
+                }
+            };
+            let attempts = {
+                let attempts = failures.entry(commit.sha.clone()).or_insert(0);
+                *attempts += 1;
+                *attempts
+            };
+            if attempts < SYSROOT_ATTEMPTS {
+                return Err(e);
+            }
+            error!("giving up on {} after {} attempts: {}", commit.sha, attempts, e);
+            failures.remove(&commit.sha);
+            out_repo.success(&failed_commit_data(&commit, metadata, &benchmarks, &e))?;
+            return Ok(true);
+        }
+    };
+    let data = bench_commit(
+        Some(out_repo),
+        &history[..],
+        &commit,
+        metadata.as_ref(),
+        &sysroot.triple,
+        Flavor::Plain,
+        &BuildKind::all(),
+        &RunKind::all(),
+        Compiler {
+            rustc: &sysroot.rustc,
+            cargo: &sysroot.cargo,
+            is_nightly: true,
+            target: None,
+            backend: CodegenBackend::Llvm,
+            linker: Linker::Default,
+            threads: None,
+        },
+        &benchmarks,
+        next.shard.as_ref(),
+        Iterations::fixed(3),
+        true,
+        Profiler::PerfStat,
+    )?;
+    // The results so far were saved by `bench_commit`.
+    shutdown::check()?;
+    let result = out_repo.success(&data);
+    if let Err(err) = result {
+        out_repo.write_broken_commit(&commit, err)?;
+        return Ok(true);
+    }
+    history.push(data);
+
+    // The alternative build (e.g. with the compiler's debug assertions
+    // enabled) is benchmarked right after the plain one, so that what it
+    // costs is tracked commit by commit. The site only waits for the plain
+    // build, so it isn't told about this one.
+    if alt {
+        let sysroot = match cache.install_sysroot(&commit, HOST_TRIPLE, true) {
+            Ok(sysroot) => sysroot,
+            Err(e) => {
+                warn!("skipping the alternative build of {}: {:?}", commit.sha, e);
+                return Ok(true);
+            }
+        };
+        let data = bench_commit(
+            Some(out_repo),
+            &history[..],
+            &commit,
+            metadata.as_ref(),
+            &sysroot.triple,
+            Flavor::Alt,
+            &BuildKind::all(),
+            &RunKind::all(),
+            Compiler {
+                rustc: &sysroot.rustc,
+                cargo: &sysroot.cargo,
+                is_nightly: true,
+                target: None,
+                backend: CodegenBackend::Llvm,
+                linker: Linker::Default,
+                threads: None,
+            },
+            &benchmarks,
+            next.shard.as_ref(),
+            Iterations::fixed(3),
+            false,
+            Profiler::PerfStat,
+        )?;
+        shutdown::check()?;
+        out_repo.success(&data)?;
+        history.push(data);
+    }
+    Ok(true)
+}
+
+/// The longest the daemon waits after a failure before trying again.
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// How many times the daemon tries to install the sysroot of a commit before it
+/// gives up on the commit.
+const SYSROOT_ATTEMPTS: usize = 3;
+
+/// The results of `commit` when it couldn't be benchmarked at all: every
+/// benchmark failed with `error`.
+fn failed_commit_data(
+    commit: &GitCommit,
+    metadata: Option<ArtifactMetadata>,
+    benchmarks: &[Benchmark],
+    error: &Error,
+) -> CommitData {
+    CommitData {
+        commit: Commit {
+            sha: commit.sha.clone(),
+            date: Date(commit.date),
+        },
+        triple: HOST_TRIPLE.to_string(),
+        flavor: Flavor::Plain,
+        llvm_version: None,
+        benchmarks: benchmarks.iter()
+            .map(|b| (b.name.clone(), Err(error.to_string())))
+            .collect(),
+        bootstrap: BTreeMap::new(),
+        environment: None,
+        collection: None,
+        artifact_sizes: BTreeMap::new(),
+        collected_by: BTreeMap::new(),
+        shards: Vec::new(),
+        outliers: Vec::new(),
+        ices: BTreeMap::new(),
+        metadata,
+    }
+}
+
+/// Processes commits for as long as the site has any, and then polls it every
+/// `poll_interval` for more, until the collector is shut down. The results in
+/// the output repo, the history outliers are told by, are loaded once for
+/// every such pass, and extended with those collected during it. Failures
+/// (including panics) are logged and retried after a delay that doubles with
+/// every consecutive failure. Nothing is lost if the daemon dies nonetheless:
+/// once restarted, it resumes the commit it was working on from the results
+/// it saved along the way.
+fn daemon<F>(
+    out_repo: &outrepo::Repo,
+    cache: &mut cache::Cache,
+    collector: Option<&str>,
+    benchmarks: &[Benchmark],
+    alt: bool,
+    poll_interval: Duration,
+    get_commits: &F,
+) -> Result<i32, Error>
+where
+    F: Fn() -> Result<Vec<GitCommit>, Error>,
+{
+    let mut backoff = poll_interval;
+    let mut install_failures = HashMap::new();
+    let mut history = None;
+    loop {
+        // Once the site is back, what couldn't be uploaded while it was down.
+        if let Err(e) = out_repo.replay_uploads() {
+            debug!("failed to upload the queued results: {:?}", e);
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| -> Result<bool, Error> {
+            let history = history.get_or_insert_with(|| {
+                outrepo::load_all_commit_data(out_repo.path())
+            });
+            process_next_commit(out_repo, cache, collector, benchmarks, &get_commits()?, alt,
+                                history, Some(&mut install_failures))
+        })).unwrap_or_else(|_| Err(format_err!("panicked")));
+        shutdown::check()?;
+        match result {
+            Ok(true) => backoff = poll_interval,
+            Ok(false) => {
+                backoff = poll_interval;
+                history = None;
+                shutdown::sleep(poll_interval);
+            }
+            Err(e) => {
+                error!("failed to process the next commit, retrying in {:?}: {:?}",
+                       backoff, e);
+                shutdown::sleep(backoff);
+                backoff = cmp::min(backoff * 2, MAX_BACKOFF);
+            }
+        }
+        shutdown::check()?;
+    }
+}
+
+/// Benchmarks the published toolchain `toolchain` (anything rustup can
+/// install), and records the results under `id`.
+fn bench_published(
+    repo: &outrepo::Repo,
+    id: &str,
+    toolchain: &str,
+    release: Option<Release>,
+    benchmarks: &[Benchmark],
+) -> Result<(), Error> {
+    let commit = rust_sysroot::git::Commit {
+        sha: String::from("<none>"),
+        date: Date::ymd_hms(2010, 01, 01, 0, 0, 0).0,
+        summary: String::new(),
+    };
+    let cfg = rustup::Cfg::from_env(Arc::new(|_| {}))
+        .map_err(SyncFailure::new)?;
+    let toolchain = rustup::Toolchain::from(&cfg, toolchain)
+        .map_err(SyncFailure::new)
+        .with_context(|_| format!("creating toolchain for id: {}", id))?;
+    toolchain.install_from_dist_if_not_installed().map_err(SyncFailure::new)?;
+
+    // No NLL runs when testing stable builds.
+    let run_kinds = if collector::version_supports_incremental(id) {
+        RunKind::all_except_nll()
+    } else {
+        RunKind::all_non_incr_except_nll()
+    };
+    let data = bench_commit(
+        None,
+        &[],
+        &commit,
+        None,
+        HOST_TRIPLE,
+        Flavor::Plain,
+        &[BuildKind::Check, BuildKind::Debug, BuildKind::Opt, BuildKind::Doc],
+        &run_kinds,
+        Compiler {
+            rustc: &toolchain.binary_file("rustc"),
+            cargo: &toolchain.binary_file("cargo"),
+            is_nightly: false,
+            target: None,
+            backend: CodegenBackend::Llvm,
+            linker: Linker::Default,
+            threads: None,
+        },
+        benchmarks,
+        None,
+        Iterations::fixed(3),
+        false,
+        Profiler::PerfStat,
+    )?;
+    // Published toolchains aren't resumed, they're benchmarked again.
+    shutdown::check()?;
+    repo.success_artifact(&ArtifactData {
+        id: id.to_string(),
+        benchmarks: data.benchmarks,
+        collection: data.collection,
+        artifact_sizes: data.artifact_sizes,
+        llvm_version: data.llvm_version,
+        release,
+        ices: data.ices,
+    })
+}
+
+/// How many times a release is benchmarked before it's given up on.
+const RELEASE_ATTEMPTS: usize = 3;
+
+/// Checks the channels for new releases every `poll_interval`, and
+/// benchmarks those that haven't been yet. All of them are benchmarked with
+/// the same (stable) benchmarks, so that their results can be compared. A
+/// release that fails `RELEASE_ATTEMPTS` times is recorded as failed, and the
+/// dated toolchains of nightlies and betas are removed once they're done
+/// with. Stops when the collector is shut down.
+fn watch_published(repo: &outrepo::Repo, benchmarks: &[Benchmark], poll_interval: Duration)
+                   -> Result<i32, Error> {
+    let mut failures = HashMap::new();
+    loop {
+        for channel in published::CHANNELS {
+            let release = match published::current(channel) {
+                Ok(release) => release,
+                Err(e) => {
+                    warn!("failed to check for a new {} release: {:?}", channel, e);
+                    continue;
+                }
+            };
+            if !published::is_new(repo.path(), &release) {
+                continue;
+            }
+            let id = published::artifact_id(&release);
+            info!("benchmarking the new {} release {} ({})", channel, id, release.version);
+            let toolchain = published::toolchain(&release);
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                bench_published(repo, &id, &toolchain, Some(release.clone()), benchmarks)
+            })).unwrap_or_else(|_| Err(format_err!("panicked")));
+            shutdown::check()?;
+            if let Err(e) = result {
+                let attempts = {
+                    let attempts = failures.entry(toolchain.clone()).or_insert(0);
+                    *attempts += 1;
+                    *attempts
+                };
+                if attempts < RELEASE_ATTEMPTS {
+                    error!("failed to benchmark {} ({}/{}): {:?}",
+                           id, attempts, RELEASE_ATTEMPTS, e);
+                    continue;
+                }
+                error!("giving up on {} after {} attempts: {:?}", id, attempts, e);
+                failures.remove(&toolchain);
+                let failed = ArtifactData {
+                    id: id.clone(),
+                    benchmarks: benchmarks.iter()
+                        .map(|b| (b.name.clone(), Err(e.to_string())))
+                        .collect(),
+                    collection: None,
+                    artifact_sizes: BTreeMap::new(),
+                    llvm_version: None,
+                    release: Some(release.clone()),
+                    ices: BTreeMap::new(),
+                };
+                if let Err(e) = repo.success_artifact(&failed) {
+                    error!("failed to record {} as failed: {:?}", id, e);
+                }
+            }
+            if release.channel != "stable" {
+                if let Err(e) = remove_toolchain(&toolchain) {
+                    warn!("failed to remove the toolchain {}: {:?}", toolchain, e);
+                }
+            }
+        }
+        shutdown::sleep(poll_interval);
+        shutdown::check()?;
+    }
+}
+
+/// Uninstalls the rustup toolchain `name`, if it's installed.
+fn remove_toolchain(name: &str) -> Result<(), Error> {
+    let cfg = rustup::Cfg::from_env(Arc::new(|_| {}))
+        .map_err(SyncFailure::new)?;
+    let toolchain = rustup::Toolchain::from(&cfg, name)
+        .map_err(SyncFailure::new)?;
+    if toolchain.exists() {
+        info!("removing the toolchain {}", name);
+        toolchain.remove().map_err(SyncFailure::new)?;
+    }
+    Ok(())
+}
+
+/// Whether `s` looks like the full hash of a commit.
+fn is_commit_hash(s: &str) -> bool {
+    s.len() == 40 && s.chars().all(|c| c.is_digit(16))
+}
+
+/// Checks that `linker` is installed, so that a missing one is reported once
+/// rather than as a failure of every benchmark.
+fn check_linker(linker: Linker) -> Result<(), Error> {
+    let binary = match linker {
+        Linker::Default => return Ok(()),
+        Linker::Lld => "ld.lld",
+        Linker::Mold => "mold",
+    };
+    let found = process::Command::new(binary).arg("--version").output()
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+    if !found {
+        bail!("the {} linker was requested, but `{}` couldn't be run", linker.name(), binary);
+    }
+    Ok(())
+}
+
+/// Resolves the `--rustc` and `--cargo` arguments of the local commands to
+/// paths. `--rustc` is either the path of a rustc binary or the name of a
+/// rustup toolchain, optionally prefixed with `+` (e.g. `+nightly` or
+/// `stage1`). Toolchains that aren't installed yet are installed with rustup,
+/// or with `rustup-toolchain-install-master` if they are named after a
+/// commit. Without `--cargo`, the toolchain's own cargo is used, or the
+/// default toolchain's if it has none (as is usual for linked toolchains).
+fn resolve_toolchain(rustc: &str, cargo: Option<&str>) -> Result<(PathBuf, PathBuf), Error> {
+    let toolchain_name = if rustc.starts_with('+') {
+        Some(&rustc[1..])
+    } else if Path::new(rustc).is_file() {
+        None
+    } else {
+        Some(rustc)
+    };
+
+    let (rustc_path, toolchain_cargo) = match toolchain_name {
+        None => (PathBuf::from(rustc).canonicalize()?, None),
+        Some(name) => {
+            let cfg = rustup::Cfg::from_env(Arc::new(|_| {}))
+                .map_err(SyncFailure::new)?;
+            let toolchain = rustup::Toolchain::from(&cfg, name)
+                .map_err(SyncFailure::new)
+                .with_context(|_| format!("creating toolchain for: {}", name))?;
+            if !toolchain.exists() {
+                if is_commit_hash(name) {
+                    let mut cmd = process::Command::new("rustup-toolchain-install-master");
+                    cmd.arg(name);
+                    execute::command_output(&mut cmd)
+                        .with_context(|_| format!("failed to install toolchain {}", name))?;
+                } else {
+                    toolchain.install_from_dist_if_not_installed().map_err(SyncFailure::new)?;
+                }
+            }
+            let cargo = toolchain.binary_file("cargo");
+            (toolchain.binary_file("rustc"), if cargo.is_file() { Some(cargo) } else { None })
+        }
+    };
+
+    let cargo_path = match (cargo, toolchain_cargo) {
+        (Some(cargo), _) => PathBuf::from(cargo).canonicalize()?,
+        (None, Some(cargo)) => cargo,
+        (None, None) => {
+            let mut cmd = process::Command::new("rustup");
+            cmd.arg("which").arg("cargo");
+            let output = execute::command_output(&mut cmd)
+                .context("failed to find the default toolchain's cargo")?;
+            PathBuf::from(String::from_utf8(output.stdout)?.trim())
+        }
+    };
+    debug!("using rustc {:?} and cargo {:?}", rustc_path, cargo_path);
+    Ok((rustc_path, cargo_path))
+}
+
+/// Adds the results in `from` to those in `into`, if there are any yet.
+fn accumulate_commit_data(into: &mut Option<CommitData>, from: CommitData) {
+    if let Some(ref mut into) = *into {
+        merge_commit_data(into, from);
+        return;
+    }
+    *into = Some(from);
+}
+
+/// Warns about machine settings that make the measurements noisy, or, in
+/// strict mode, refuses to continue.
+fn check_environment(strict: bool) -> Result<(), Error> {
+    let environment = environment::current();
+    if !environment.is_noisy() {
+        return Ok(());
+    }
+    let issues = environment.issues.join(", ");
+    if strict {
+        bail!("refusing to benchmark in a noisy environment: {}", issues);
+    }
+    warn!("results will be marked as noisy: {}", issues);
+    Ok(())
+}
+
+/// Warns if the machine is noisier than its calibration allows, or, in strict
+/// mode, refuses to continue.
+fn check_noise(strict: bool, calibration: &Path) -> Result<(), Error> {
+    let problem = match calibrate::check(calibration)? {
+        Some(problem) => problem,
+        None => return Ok(()),
+    };
+    if strict {
+        bail!("refusing to benchmark on a noisy machine: {}", problem);
+    }
+    warn!("results may be noisy: {}", problem);
+    Ok(())
+}
+
+/// Adds the results in `from` to `into`. Runs of benchmarks that are in both
+/// are combined; an error in either is kept, and so is the collection of
+/// `into` being marked as interrupted if that of `from` is.
+fn merge_commit_data(into: &mut CommitData, from: CommitData) {
+    let interrupted = from.collection.as_ref().map_or(false, |c| c.interrupted);
+    if let Some(ref mut collection) = into.collection {
+        collection.interrupted |= interrupted;
+    }
+    for (name, result) in from.benchmarks {
+        let merged = match (into.benchmarks.remove(&name), result) {
+            (Some(Ok(mut benchmark)), Ok(other)) => {
+                benchmark.runs.extend(other.runs);
+                Ok(benchmark)
+            }
+            (Some(Err(e)), _) | (_, Err(e)) => Err(e),
+            (None, result) => result,
+        };
+        into.benchmarks.insert(name, merged);
+    }
+}
+
+/// Whether the benchmark `name` matches `pattern`. Patterns containing `*` or
+/// `?` are globs that must match the whole name; other patterns match any
+/// name that contains them.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    fn glob(pattern: &[u8], name: &[u8]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some((b'*', rest)) => (0..=name.len()).any(|i| glob(rest, &name[i..])),
+            Some((b'?', rest)) => !name.is_empty() && glob(rest, &name[1..]),
+            Some((c, rest)) => name.first() == Some(c) && glob(rest, &name[1..]),
+        }
+    }
+
+    if pattern.contains('*') || pattern.contains('?') {
+        glob(pattern.as_bytes(), name.as_bytes())
+    } else {
+        name.contains(pattern)
     }
 }
 
 fn get_benchmarks(
     benchmark_dir: &Path,
     filter: Option<&str>,
+    include: Option<&str>,
     exclude: Option<&str>,
 ) -> Result<Vec<Benchmark>, Error> {
-    let mut benchmarks = Vec::new();
-    'outer: for entry in fs::read_dir(benchmark_dir).context("failed to list benchmarks")? {
+    let mut candidates = Vec::new();
+    for entry in fs::read_dir(benchmark_dir).context("failed to list benchmarks")? {
         let entry = entry?;
         let path = entry.path();
         let name = match entry.file_name().into_string() {
@@ -306,6 +1297,30 @@ fn get_benchmarks(
             continue;
         }
 
+        // A directory without a `Cargo.toml` is a group of benchmarks (e.g.
+        // `stress`), whose names are prefixed with that of the group, unless
+        // it has a `perf-config.json`, which may point to a `Cargo.toml`
+        // elsewhere in the benchmark.
+        if !path.join("Cargo.toml").exists() && !path.join("perf-config.json").exists() {
+            let group = fs::read_dir(&path)
+                .with_context(|_| format!("failed to list the benchmark group {}", name))?;
+            for entry in group {
+                let entry = entry?;
+                if !entry.file_type()?.is_dir() {
+                    continue;
+                }
+                match entry.file_name().into_string() {
+                    Ok(s) => candidates.push((format!("{}-{}", name, s), entry.path())),
+                    Err(e) => bail!("non-utf8 benchmark name: {:?}", e),
+                }
+            }
+            continue;
+        }
+        candidates.push((name, path));
+    }
+
+    let mut benchmarks = Vec::new();
+    'outer: for (name, path) in candidates {
         if let Some(filter) = filter {
             if !name.contains(filter) {
                 debug!("benchmark {} - doesn't match --filter argument, skipping", name);
@@ -313,9 +1328,16 @@ fn get_benchmarks(
             }
         }
 
+        if let Some(include) = include {
+            if !include.split(',').any(|inc| matches_pattern(inc, &name)) {
+                debug!("benchmark {} - doesn't match --include argument, skipping", name);
+                continue;
+            }
+        }
+
         if let Some(exclude) = exclude {
             for exc in exclude.split(',') {
-                if name.contains(exc) {
+                if matches_pattern(exc, &name) {
                     debug!("benchmark {} - matches --exclude argument, skipping", name);
                     continue 'outer;
                 }
@@ -332,6 +1354,10 @@ fn get_benchmarks(
 fn main() {
     match main_result() {
         Ok(code) => process::exit(code),
+        Err(ref err) if err.downcast_ref::<shutdown::Interrupted>().is_some() => {
+            eprintln!("{}", err);
+            process::exit(shutdown::EXIT_CODE);
+        }
         Err(err) => {
             eprintln!("{}", err);
             process::exit(1);
@@ -340,88 +1366,554 @@ fn main() {
 }
 
 fn main_result() -> Result<i32, Error> {
-    env_logger::init();
-
     let matches = clap_app!(rustc_perf_collector =>
        (version: "0.1")
        (author: "The Rust Compiler Team")
        (about: "Collects Rust performance data")
 
+       (@arg config: --config +takes_value
+        "The configuration file with the defaults for the flags below\n\
+        (default: collector.toml, if there is one)")
+       (@arg log_json: --("log-json")
+        "Log one JSON object per line, with the benchmark, build kind,\n\
+        iteration and run kind being measured, rather than text")
        (@arg filter: --filter +takes_value "Run only benchmarks that contain this")
-       (@arg exclude: --exclude +takes_value "Ignore all benchmarks that contain this")
+       (@arg include: --include +takes_value
+        "Run only benchmarks that match one of these (comma-separated)\n\
+        patterns, which may use '*' and '?' as wildcards")
+       (@arg exclude: --exclude +takes_value
+        "Ignore all benchmarks that match one of these (comma-separated)\n\
+        patterns, which may use '*' and '?' as wildcards")
+       (@arg category: --category +takes_value
+        "Run only benchmarks in one or more (comma-separated) of these\n\
+        categories: 'primary', 'secondary', 'stable'")
+       (@arg changed_files: --("changed-files") +takes_value
+        "Run only the benchmarks that the sensitivity matrix says are\n\
+        sensitive to a change to the rust files listed in this file (one\n\
+        per line, as printed by `git diff --name-only`)")
+       (@arg sensitivity: --sensitivity +takes_value
+        "Where `learn_sensitivity` stores the sensitivity matrix used by\n\
+        --changed-files (default: sensitivity.json)")
        (@arg sync_git: --("sync-git") "Synchronize repository with remote")
-       (@arg output_repo: --("output-repo") +required +takes_value "Output repository/directory")
+       (@arg output_repo: --("output-repo") +takes_value
+        "Output repository/directory (required, unless the configuration\n\
+        sets it)")
+       (@arg upload: --upload conflicts_with[sync_git]
+        "Upload the results to the site at $SITE_URL, authenticated by\n\
+        $PERF_UPLOAD_TOKEN, instead of committing them to the output repo")
+       (@arg strict: --strict
+        "Refuse to benchmark if the machine isn't set up for stable measurements")
+       (@arg cpu_set: --("cpu-set") +takes_value
+        "Only run on these CPUs, e.g. '2,4-7' (Linux only)")
+       (@arg jobs: --jobs +takes_value
+        "Run this many benchmarks at a time, each on its own share of the\n\
+        CPUs (Linux only)")
+       (@arg cgroup: --cgroup +takes_value
+        "Run in this cgroup (created under /sys/fs/cgroup if needed)")
+       (@arg memory_limit: --("memory-limit") +takes_value
+        "Limit the memory of the --cgroup to this many bytes")
+       (@arg sandbox: --sandbox
+        "Build benchmarks without network access, in a user namespace\n\
+        (Linux only)")
+       (@arg disable_aslr: --("disable-aslr")
+        "Disable address space layout randomization for the benchmarks\n\
+        (Linux only)")
+       (@arg path_length: --("path-length") +takes_value
+        "Build benchmarks in directories whose paths are this many bytes\n\
+        long, so the results don't depend on the temporary directory")
+       (@arg randomize_environment: --("randomize-environment")
+        "Give every measured rustc an environment of a random size, to\n\
+        estimate how much the results depend on memory layout")
+       (@arg target_dir: --("target-dir") +takes_value
+        "How to prepare the benchmarks' target directories, overriding\n\
+        their perf-config.json: 'reuse', 'wipe' or 'tmpfs'")
+       (@arg work_dir_tmpfs: --("work-dir-tmpfs") conflicts_with[target_dir]
+        "Build the benchmarks in a tmpfs mounted for the purpose, sized to\n\
+        half of the available memory (Linux only, usually requires root)")
+       (@arg perf_events: --("perf-events") +takes_value
+        "The events for perf stat to count, instead of the default ones for\n\
+        the architecture, e.g. 'instructions:u,armv8_pmuv3_0/l2d_cache_refill/u'")
+       (@arg unit_timings: --("unit-timings")
+        "Record how long cargo takes to build each crate (and build script)\n\
+        of the benchmarks, with nightly cargo")
+       (@arg ir_stats: --("ir-stats")
+        "Record the number and size of the nodes of the benchmarks' AST and\n\
+        HIR, with nightly compilers")
+       (@arg incr_stats: --("incr-stats")
+        "Record the dep-graph and reuse stats of the incremental builds, with\n\
+        nightly compilers, at the cost of another round of them")
+       (@arg energy: --energy
+        "Record the energy the CPU packages consume during every measured\n\
+        rustc, with RAPL (Linux on x86 only, usually requires root)")
+       (@arg calibration: --calibration +takes_value
+        "Where `calibrate` stores the machine's noise baseline, which it's\n\
+        checked against before benchmarking (default: calibration.json)")
+       (@arg cache_dir: --("cache-dir") +takes_value
+        "Where to keep the compilers downloaded from CI (default: artifact-cache)")
+       (@arg cache_size: --("cache-size") +takes_value
+        "Evict the least recently used compilers once the cache is larger\n\
+        than this many GiB (default: 20)")
 
        (@subcommand bench_commit =>
            (about: "benchmark a bors merge from AWS")
+           (@arg ALT: --alt
+            "Benchmark the alternative build of the commit (e.g. with debug\n\
+            assertions, or against a newer LLVM), kept apart from the results\n\
+            of the regular build")
+           (@arg COMMIT: +required +takes_value "Commit hash to bench")
+       )
+       (@subcommand bench_rustc =>
+           (about: "benchmark building the compiler itself with a bors merge from AWS")
+           (@arg RUST_SRC: --rust-src +takes_value
+            "The rust-lang/rust checkout to build in (default: ./rust)")
+           (@arg ITERATIONS: --iterations +takes_value
+            "How many times to build the compiler (default: 1)")
+           (@arg COMMIT: +required +takes_value "Commit hash to bench")
+       )
+       (@subcommand bench_cargo =>
+           (about: "benchmark cargo operations with the cargo of a bors merge from AWS")
            (@arg COMMIT: +required +takes_value "Commit hash to bench")
        )
        (@subcommand bench_local =>
            (about: "benchmark a local rustc")
-           (@arg RUSTC: --rustc +required +takes_value "The path to the local rustc to benchmark")
-           (@arg CARGO: --cargo +required +takes_value "The path to the local Cargo to use")
+           (@arg RUSTC: --rustc +required +takes_value
+            "The path to the local rustc to benchmark, or the name of a rustup\n\
+            toolchain (e.g. '+nightly' or 'stage1')")
+           (@arg CARGO: --cargo +takes_value
+            "The path to the local Cargo to use (default: the toolchain's)")
+           (@arg BUILDS: --builds +takes_value
+            "One or more (comma-separated) of: 'Check', 'Debug',\n\
+            'Opt', 'Doc', 'All'")
+           (@arg PROFILES: --profiles +takes_value "An alias for --builds")
+           (@arg RUNS: --runs +takes_value
+            "One or more (comma-separated) of: 'Clean' (or 'Full'), 'Nll',\n\
+            'BaseIncr' (or 'IncrFull'), 'CleanIncr' (or 'IncrUnchanged'),\n\
+            'PatchedIncrs' (or 'IncrPatched'), 'All'")
+           (@arg SCENARIOS: --scenarios +takes_value "An alias for --runs")
+           (@arg PROFILER: --profiler +takes_value
+            "One of: 'perf-stat' (the default), 'time-passes', 'dhat', 'strace'")
+           (@arg TARGET: --target +takes_value
+            "The target triple to build for, if not the host (e.g. wasm32-unknown-unknown)")
+           (@arg BACKEND: --backend +takes_value
+            "The codegen backend to use: 'llvm' (the default) or 'cranelift'")
+           (@arg LINKER: --linker +takes_value
+            "The linker to link the benchmarks with: 'default', 'lld' or 'mold'")
+           (@arg FLAVOR: --flavor +takes_value
+            "How the rustc was built, if it's a flavor of the build of ID\n\
+            (the commit it was built from): 'pgo', 'bolt' or 'lto'")
+           (@arg THREADS: --threads +takes_value
+            "One or more (comma-separated) frontend thread counts to\n\
+            measure with, passed as -Zthreads (e.g. '1,4,8')")
+           (@arg ADAPTIVE: --adaptive +takes_value
+            "Keep running each benchmark until the variation of this stat\n\
+            (e.g. 'wall-time') is low enough, instead of running it once")
+           (@arg MAX_CV: --("max-cv") +takes_value
+            "The coefficient of variation at which --adaptive stops (default: 0.01)")
+           (@arg MAX_ITERATIONS: --("max-iterations") +takes_value
+            "The most iterations --adaptive does (default: 10)")
+           (@arg WARMUP: --warmup +takes_value
+            "How many times to run each benchmark without measuring it,\n\
+            before the measured iterations (default: 0)")
+           (@arg OUTPUT_FORMAT: --("output-format") +takes_value
+            "Also print the results, as 'json' or 'markdown'")
+           (@arg DRY_RUN: --("dry-run")
+            "Only print the builds that would be done, and how long they are\n\
+            expected to take according to earlier results in the output directory")
+           (@arg DB: --db +takes_value
+            "Also store the results in the database at this URL, e.g.\n\
+            'sqlite://results.db', which the site can be run on")
+           (@arg ID: +required +takes_value "Identifier to associate benchmark results with")
+       )
+       (@subcommand bench_ci =>
+           (about: "download the rustc and cargo built by CI and benchmark them locally")
+           (@arg BUILDS: --builds +takes_value
+            "One or more (comma-separated) of: 'Check', 'Debug',\n\
+            'Opt', 'Doc', 'All'")
+           (@arg PROFILES: --profiles +takes_value "An alias for --builds")
+           (@arg RUNS: --runs +takes_value
+            "One or more (comma-separated) of: 'Clean' (or 'Full'), 'Nll',\n\
+            'BaseIncr' (or 'IncrFull'), 'CleanIncr' (or 'IncrUnchanged'),\n\
+            'PatchedIncrs' (or 'IncrPatched'), 'All'")
+           (@arg SCENARIOS: --scenarios +takes_value "An alias for --runs")
+           (@arg ID: +required +takes_value
+            "The bors merge commit hash, or 'nightly-YYYY-MM-DD', to benchmark")
+       )
+       (@subcommand bench_cmp =>
+           (about: "benchmark two local rustcs in interleaved order and compare them")
+           (@arg CARGO: --cargo +takes_value
+            "The path to the local Cargo to use (default: the toolchains')")
            (@arg BUILDS: --builds +takes_value
             "One or more (comma-separated) of: 'Check', 'Debug',\n\
-            'Opt', 'All'")
+            'Opt', 'Doc', 'All'")
+           (@arg PROFILES: --profiles +takes_value "An alias for --builds")
            (@arg RUNS: --runs +takes_value
-            "One or more (comma-separated) of: 'Clean', 'Nll',\n\
-            'BaseIncr', 'CleanIncr', 'PatchedIncrs', 'All'")
+            "One or more (comma-separated) of: 'Clean' (or 'Full'), 'Nll',\n\
+            'BaseIncr' (or 'IncrFull'), 'CleanIncr' (or 'IncrUnchanged'),\n\
+            'PatchedIncrs' (or 'IncrPatched'), 'All'")
+           (@arg SCENARIOS: --scenarios +takes_value "An alias for --runs")
+           (@arg STAT: --stat +takes_value "The stat to compare (default: instructions:u)")
+           (@arg JSON: --json +takes_value "Also write the comparison to this file, as JSON")
+           (@arg OUTPUT_FORMAT: --("output-format") +takes_value
+            "Print the comparison as 'json' or 'markdown' instead of a table")
+           (@arg BASE: +required +takes_value
+            "The baseline rustc: a path, or the name of a rustup toolchain")
+           (@arg NEW: +required +takes_value
+            "The rustc to compare: a path, or the name of a rustup toolchain")
+       )
+       (@subcommand bench_runtime_local =>
+           (about: "benchmark the code generated by a local rustc")
+           (@arg RUSTC: --rustc +required +takes_value
+            "The path to the local rustc to benchmark, or the name of a rustup\n\
+            toolchain (e.g. '+nightly' or 'stage1')")
+           (@arg CARGO: --cargo +takes_value
+            "The path to the local Cargo to use (default: the toolchain's)")
+           (@arg ITERATIONS: --iterations +takes_value
+            "How many times to run each benchmark (default: 3)")
+           (@arg BACKEND: --backend +takes_value
+            "The codegen backend to use: 'llvm' (the default) or 'cranelift'")
            (@arg ID: +required +takes_value "Identifier to associate benchmark results with")
        )
        (@subcommand bench_published =>
            (about: "bench an artifact from static.r-l.o")
-           (@arg ID: +required +takes_value "id to install (e.g., stable, beta, 1.26.0)")
+           (@arg ID: +takes_value required_unless[WATCH]
+            "id to install (e.g., stable, beta, 1.26.0)")
+           (@arg WATCH: --watch conflicts_with[ID]
+            "Keep benchmarking every new stable, beta and nightly release as\n\
+            it's published")
+           (@arg POLL_INTERVAL: --("poll-interval") +takes_value requires[WATCH]
+            "How many seconds to wait before checking for new releases again\n\
+            (default: 3600)")
        )
        (@subcommand process =>
            (about: "syncs to git and collects performance data for all versions")
+           (@arg ALT: --alt
+            "Also benchmark the alternative build of every commit (e.g. with\n\
+            debug assertions), kept apart from the results of the regular build")
+           (@arg NAME: --name +takes_value
+            "The name to register with the site under, to share the benchmarks\n\
+            of each commit with the other collectors")
+       )
+       (@subcommand daemon =>
+           (about: "keeps benchmarking the commits the site asks for")
+           (@arg SITE_URL: --("site-url") +takes_value
+            "The URL of the site to get work from and report to (default:\n\
+            $SITE_URL)")
+           (@arg POLL_INTERVAL: --("poll-interval") +takes_value
+            "How many seconds to wait before asking again when there's\n\
+            nothing to do (default: 60)")
+           (@arg ALT: --alt
+            "Also benchmark the alternative build of every commit (e.g. with\n\
+            debug assertions), kept apart from the results of the regular build")
+           (@arg NAME: --name +takes_value
+            "The name to register with the site under, to share the benchmarks\n\
+            of each commit with the other collectors")
        )
        (@subcommand profile =>
            (about: "profile a local rustc")
-           (@arg RUSTC: --rustc +required +takes_value "The path to the local rustc to benchmark")
-           (@arg CARGO: --cargo +required +takes_value "The path to the local Cargo to use")
+           (@arg RUSTC: --rustc +required +takes_value
+            "The path to the local rustc to benchmark, or the name of a rustup\n\
+            toolchain (e.g. '+nightly' or 'stage1')")
+           (@arg CARGO: --cargo +takes_value
+            "The path to the local Cargo to use (default: the toolchain's)")
            (@arg BUILDS: --builds +takes_value
             "One or more (comma-separated) of: 'Check', 'Debug',\n\
-            'Opt', 'All'")
+            'Opt', 'Doc', 'All'")
+           (@arg PROFILES: --profiles +takes_value "An alias for --builds")
            (@arg RUNS: --runs +takes_value
-            "One or more (comma-separated) of: 'Clean', 'Nll',\n\
-            'BaseIncr', 'CleanIncr', 'PatchedIncrs', 'All'")
+            "One or more (comma-separated) of: 'Clean' (or 'Full'), 'Nll',\n\
+            'BaseIncr' (or 'IncrFull'), 'CleanIncr' (or 'IncrUnchanged'),\n\
+            'PatchedIncrs' (or 'IncrPatched'), 'All'")
+           (@arg SCENARIOS: --scenarios +takes_value "An alias for --runs")
+           (@arg TARGET: --target +takes_value
+            "The target triple to build for, if not the host (e.g. wasm32-unknown-unknown)")
+           (@arg BACKEND: --backend +takes_value
+            "The codegen backend to use: 'llvm' (the default) or 'cranelift'")
+           (@arg LINKER: --linker +takes_value
+            "The linker to link the benchmarks with: 'default', 'lld' or 'mold'")
            (@arg PROFILER: +required +takes_value
-            "One of: 'time-passes', 'perf-record', 'cachegrind',\n\
-            'callgrind', 'dhat', 'massif', 'eprintln'")
+            "One of: 'self-profile', 'time-passes', 'perf-record',\n\
+            'flamegraph', 'cachegrind', 'callgrind', 'dhat', 'massif',\n\
+            'eprintln', 'llvm-trace', 'strace', 'xctrace'")
            (@arg ID: +required +takes_value "Identifier to associate benchmark results with")
+           (@arg DIFF: --diff +takes_value
+            "Identifier of an earlier profile run in the same output directory\n\
+            to diff against (cachegrind, callgrind and eprintln only)")
        )
        (@subcommand remove_benchmark =>
            (about: "remove data for a benchmark")
            (@arg BENCHMARK: --benchmark +required +takes_value "benchmark name to remove data for")
        )
+       (@subcommand add_benchmark =>
+           (about: "add a crate from crates.io as a benchmark")
+           (@arg NAME: +required +takes_value "The name of the crate")
+           (@arg VERSION: +required +takes_value "The version of the crate")
+       )
+       (@subcommand update_manifest =>
+           (about: "update the benchmark suite manifest, bumping its version if\n\
+            any of the benchmarks changed")
+       )
        (@subcommand remove_errs =>
            (about: "remove errored data")
        )
        (@subcommand test_benchmarks =>
            (about: "test benchmark the most recent commit")
        )
-    ).get_matches();
+       (@subcommand learn_sensitivity =>
+           (about: "learn which benchmarks move when which parts of the compiler\n\
+            change, from the results in the output repo, for --changed-files")
+           (@arg RUST_SRC: --rust-src +takes_value
+            "A rust-lang/rust checkout with the benchmarked commits (default: ./rust)")
+           (@arg STAT: --stat +takes_value
+            "The stat whose changes to look at (default: instructions:u)")
+           (@arg THRESHOLD: --threshold +takes_value
+            "How much the stat has to change, in percent, for a benchmark to\n\
+            count as moved (default: 1)")
+       )
+       (@subcommand calibrate =>
+           (about: "measure how noisy the machine is, and store it as the\n\
+            baseline the machine is checked against before benchmarking")
+           (@arg RUNS: --runs +takes_value
+            "How many times to run the calibration workload (default: 20)")
+           (@arg MAX_CV: --("max-cv") +takes_value
+            "Warn if the times of the workload vary by more than this\n\
+            coefficient of variation (default: 0.01)")
+       )
+       (@subcommand export_db =>
+           (about: "export the results in a database as CSV or Parquet, one row\n\
+            per value of a stat")
+           (@arg DB: --db +takes_value
+            "The URL of the database, e.g. 'sqlite://results.db'\n\
+            (default: $DATABASE_URL)")
+           (@arg FORMAT: --format +takes_value "'csv' (the default) or 'parquet'")
+           (@arg BENCHMARKS: --benchmarks +takes_value
+            "Only export these (comma-separated) benchmarks")
+           (@arg METRICS: --metrics +takes_value
+            "Only export these (comma-separated) stats, e.g.\n\
+            'instructions:u,max-rss'")
+           (@arg SINCE: --since +takes_value
+            "Only export the commits merged on or after this date\n\
+            (YYYY-MM-DD), and no artifacts")
+           (@arg OUTPUT: +required +takes_value "The file to write")
+       )
+       (@subcommand import_db =>
+           (about: "import the results in another database, e.g. the SQLite file\n\
+            of a self-hosted collector, into a database")
+           (@arg DB: --db +takes_value
+            "The URL of the database to import into (default: $DATABASE_URL)")
+           (@arg REPLACE: --replace
+            "Replace the results already in the database with the imported\n\
+            ones of the same commits and artifacts, rather than keep them")
+           (@arg ARTIFACT_PREFIX: --("artifact-prefix") +takes_value
+            "Prepend this to the ids of the imported artifacts (e.g.\n\
+            'alice/'), so that they don't collide with the ones there")
+           (@arg FROM: +required +takes_value
+            "The URL of the database to import, e.g. 'sqlite://theirs.db'")
+       )
+       (@subcommand flush_spool =>
+           (about: "send the results that couldn't be uploaded, or stored in a\n\
+            database, when they were collected")
+           (@arg DB: --db +takes_value
+            "The URL of the database to store the results queued for one in\n\
+            (default: $DATABASE_URL)")
+       )
+       (@subcommand prune_db =>
+           (about: "prune the old results in a database by the retention policy\n\
+            in the configuration")
+           (@arg DB: --db +takes_value
+            "The URL of the database to prune (default: $DATABASE_URL)")
+           (@arg DRY_RUN: --("dry-run") "Only report what would be pruned")
+       )
+       (@subcommand cache =>
+           (about: "inspect the cache of compilers downloaded from CI")
+           (@subcommand list =>
+               (about: "list the cached files, most recently used first")
+           )
+           (@subcommand prune =>
+               (about: "evict the least recently used files")
+               (@arg MAX_SIZE: --("max-size") +takes_value
+                "Evict files until the cache is no larger than this many GiB\n\
+                (default: --cache-size)")
+               (@arg VERIFY: --verify
+                "Also evict the files that no longer match their checksum")
+           )
+       )
+    ).get_matches();
+    logging::init(matches.is_present("log_json"));
 
-    let benchmark_dir = PathBuf::from("collector/benchmarks");
+    let config = config::load(matches.value_of("config").map(Path::new))?;
+    config.apply_env();
+    let output_repo = match matches.value_of_os("output_repo") {
+        Some(path) => PathBuf::from(path),
+        None => match config.output_repo {
+            Some(ref path) => path.clone(),
+            None => bail!("--output-repo is required, unless the configuration sets output-repo"),
+        },
+    };
+
+    let benchmark_dir = config.benchmark_dir.clone()
+        .unwrap_or_else(|| PathBuf::from("collector/benchmarks"));
     let filter = matches.value_of("filter");
+    let include = matches.value_of("include");
     let exclude = matches.value_of("exclude");
     let mut benchmarks = get_benchmarks(
         &benchmark_dir,
         filter,
+        include,
         exclude,
     )?;
+    if let Some(categories) = matches.value_of("category") {
+        let categories = categories.split(',')
+            .map(|name| Category::from_name(name)
+                 .ok_or_else(|| KindError::UnknownKind("category", name.to_string())))
+            .collect::<Result<Vec<_>, _>>()?;
+        benchmarks.retain(|b| categories.contains(&b.category()));
+    }
+    let sensitivity_path =
+        PathBuf::from(matches.value_of("sensitivity").unwrap_or("sensitivity.json"));
+    if let Some(changed_files) = matches.value_of("changed_files") {
+        let changed_files = sensitivity::load_changed_files(Path::new(changed_files))?;
+        if changed_files.is_empty() {
+            warn!("no changed files are listed, so every benchmark is run");
+        }
+        let sensitivity = sensitivity::load(&sensitivity_path)?;
+        let before = benchmarks.len();
+        benchmarks.retain(|b| sensitivity.selects(&b.name, &changed_files));
+        info!("running {} of {} benchmarks, as the others aren't sensitive to the changes",
+              benchmarks.len(), before);
+    }
     let use_remote = matches.is_present("sync_git");
+    // Read by rustc-fake.
+    if let Some(events) = matches.value_of("perf_events") {
+        env::set_var("RUSTC_PERF_EVENTS", events);
+    }
+
+    let calibration = PathBuf::from(matches.value_of("calibration").unwrap_or("calibration.json"));
+
+    let dry_run = matches.subcommand_matches("bench_local")
+        .map_or(false, |sub_m| sub_m.is_present("DRY_RUN"));
+
+    let cache_dir = PathBuf::from(matches.value_of("cache_dir").unwrap_or("artifact-cache"));
+    let cache_size = match matches.value_of("cache_size") {
+        Some(size) => gib_from_arg(size)?,
+        None => cache::DEFAULT_MAX_BYTES,
+    };
+    // Unmounted when the collector is done.
+    let mut _work_dir_tmpfs = None;
+    match matches.subcommand_name() {
+        // Nothing is benchmarked, so the machine's setup doesn't matter.
+        _ if dry_run => {}
+        Some("bench_commit") | Some("bench_rustc") | Some("bench_cargo") |
+        Some("bench_local") | Some("bench_ci") | Some("bench_cmp") |
+        Some("bench_runtime_local") | Some("bench_published") | Some("process") |
+        Some("daemon") | Some("calibrate") => {
+            // A collection that's shut down saves what it measured.
+            shutdown::install();
+            // ASLR disabled here doesn't show in the machine's settings, so
+            // the layout is controlled before the environment is checked.
+            if matches.is_present("disable_aslr") {
+                layout::disable_aslr()?;
+            }
+            if let Some(length) = matches.value_of("path_length") {
+                layout::set_path_length(length.parse::<usize>()
+                    .with_context(|_| format!("invalid path length: {}", length))?)?;
+            }
+            if matches.is_present("randomize_environment") {
+                layout::randomize_environment();
+            }
+            check_environment(matches.is_present("strict"))?;
+            let cpu_set = match matches.value_of("cpu_set")
+                    .or(config.cpu_set.as_ref().map(String::as_str)) {
+                Some(cpu_set) => Some(isolation::parse_cpu_set(cpu_set)?),
+                None => None,
+            };
+            if let Some(ref cpu_set) = cpu_set {
+                isolation::pin_to_cpus(cpu_set)?;
+            }
+            let jobs = match matches.value_of("jobs") {
+                Some(jobs) => Some(jobs.parse::<usize>()
+                    .with_context(|_| format!("invalid number of jobs: {}", jobs))?),
+                None => config.jobs,
+            };
+            if let Some(jobs) = jobs {
+                let cpus = match cpu_set {
+                    Some(cpu_set) => cpu_set,
+                    None => isolation::online_cpus()?,
+                };
+                isolation::set_jobs(jobs, &cpus)?;
+            }
+            let memory_limit = match matches.value_of("memory_limit") {
+                Some(limit) => Some(limit.parse::<u64>()
+                    .with_context(|_| format!("invalid memory limit: {}", limit))?),
+                None => None,
+            };
+            if let Some(cgroup) = matches.value_of("cgroup")
+                    .or(config.cgroup.as_ref().map(String::as_str)) {
+                isolation::enter_cgroup(cgroup, memory_limit)?;
+            } else if memory_limit.is_some() {
+                bail!("--memory-limit requires --cgroup");
+            }
+            if matches.is_present("sandbox") {
+                isolation::enable_sandbox()?;
+            }
+            if matches.is_present("work_dir_tmpfs") {
+                let tmpfs = isolation::mount_tmpfs()?;
+                execute::set_tmpfs_dir(&tmpfs.path);
+                execute::set_target_dir_policy(TargetDirPolicy::Tmpfs)?;
+                _work_dir_tmpfs = Some(tmpfs);
+            }
+            if let Some(policy) = matches.value_of("target_dir") {
+                execute::set_target_dir_policy(TargetDirPolicy::from_name(policy)
+                    .ok_or_else(|| KindError::UnknownKind("target directory policy",
+                                                          policy.to_string()))?)?;
+            }
+            if matches.is_present("unit_timings") {
+                execute::record_unit_timings();
+            }
+            if matches.is_present("ir_stats") {
+                execute::record_ir_stats();
+            }
+            if matches.is_present("incr_stats") {
+                execute::record_incr_stats();
+            }
+            if matches.is_present("energy") {
+                if let Some(problem) = environment::rapl_problem() {
+                    bail!("can't measure energy: {}", problem);
+                }
+                // Read by rustc-fake.
+                env::set_var("RUSTC_PERF_ENERGY", "1");
+            }
+            // The builds that a killed collector left behind would count
+            // against the free space.
+            layout::remove_stale_temp_dirs();
+            disk::preflight(&execute::default_build_dir(),
+                            cmp::max(isolation::cpu_partitions().len(), 1), &cache_dir)?;
+            // Last, so that the calibration workload runs in the same
+            // conditions as the benchmarks.
+            if matches.subcommand_name() != Some("calibrate") {
+                check_noise(matches.is_present("strict"), &calibration)?;
+            }
+        }
+        _ => {}
+    }
 
     let get_out_dir = || {
-        let path = PathBuf::from(matches.value_of_os("output_repo").unwrap());
-        fs::create_dir_all(&path).unwrap();
-        path
+        fs::create_dir_all(&output_repo).unwrap();
+        output_repo.clone()
     };
 
-    let get_out_repo = |allow_new_dir| {
-        outrepo::Repo::open(get_out_dir(), allow_new_dir, use_remote)
+    // When uploading, the output directory only holds a local copy of the
+    // results, so it needn't be a clone of the output repo.
+    let uploading = matches.is_present("upload");
+    let get_out_repo = |allow_new_dir| -> Result<outrepo::Repo, Error> {
+        let mut repo = outrepo::Repo::open(get_out_dir(), allow_new_dir || uploading, use_remote)?;
+        if uploading {
+            repo.set_uploader(upload::Uploader::from_env()?);
+        }
+        Ok(repo)
     };
 
+    let get_cache = || cache::Cache::open(&cache_dir, cache_size);
+
     let get_commits = || {
         rust_sysroot::get_commits(rust_sysroot::EPOCH_COMMIT, "master").map_err(SyncFailure::new)
     };
@@ -429,7 +1921,8 @@ fn main_result() -> Result<i32, Error> {
     let ret = match matches.subcommand() {
         ("bench_commit", Some(sub_m)) => {
             let commit = sub_m.value_of("COMMIT").unwrap();
-            let commit = get_commits()?
+            let commits = get_commits()?;
+            let commit = commits
                 .iter()
                 .find(|c| c.sha == commit)
                 .cloned()
@@ -441,34 +1934,205 @@ fn main_result() -> Result<i32, Error> {
                         summary: String::new(),
                     }
                 });
+            let flavor = if sub_m.is_present("ALT") { Flavor::Alt } else { Flavor::Plain };
             let out_repo = get_out_repo(false)?;
-            let sysroot = Sysroot::install(&commit, "x86_64-unknown-linux-gnu", false, false)
-                .map_err(SyncFailure::new)?;
-            let build_kinds = &[BuildKind::Check, BuildKind::Debug, BuildKind::Opt];
+            let sysroot = get_cache()?
+                .install_sysroot(&commit, HOST_TRIPLE, flavor == Flavor::Alt)?;
+            let build_kinds = BuildKind::all();
             let run_kinds = RunKind::all();
-            out_repo.success(&bench_commit(
+            let metadata = master_metadata(&commits, &commit);
+            let history = outrepo::load_all_commit_data(out_repo.path());
+            let data = bench_commit(
                 Some(&out_repo),
+                &history,
                 &commit,
+                metadata.as_ref(),
                 &sysroot.triple,
-                build_kinds,
+                flavor,
+                &build_kinds,
                 &run_kinds,
                 Compiler {
                     rustc: &sysroot.rustc,
                     cargo: &sysroot.cargo,
                     is_nightly: true,
+                    target: None,
+                    backend: CodegenBackend::Llvm,
+                    linker: Linker::Default,
+                    threads: None,
                 },
                 &benchmarks,
-                3,
+                None,
+                Iterations::fixed(3),
                 false,
-            ))?;
+                Profiler::PerfStat,
+            )?;
+            // The results so far were saved by `bench_commit`.
+            shutdown::check()?;
+            out_repo.success(&data)?;
+            Ok(0)
+        }
+
+        ("bench_rustc", Some(sub_m)) => {
+            let commit = sub_m.value_of("COMMIT").unwrap();
+            let commit = get_commits()?
+                .iter()
+                .find(|c| c.sha == commit)
+                .cloned()
+                .ok_or_else(|| format_err!("unknown commit {}", commit))?;
+            let rust_src = PathBuf::from(sub_m.value_of("RUST_SRC").unwrap_or("rust"));
+            let iterations = match sub_m.value_of("ITERATIONS") {
+                Some(iterations) => iterations.parse()
+                    .with_context(|_| format!("invalid --iterations: {}", iterations))?,
+                None => 1,
+            };
+            let out_repo = get_out_repo(false)?;
+            let sysroot = get_cache()?.install_sysroot(&commit, HOST_TRIPLE, false)?;
+
+            let result = bootstrap::measure(
+                Compiler {
+                    rustc: &sysroot.rustc,
+                    cargo: &sysroot.cargo,
+                    is_nightly: true,
+                    target: None,
+                    backend: CodegenBackend::Llvm,
+                    linker: Linker::Default,
+                    threads: None,
+                },
+                &rust_src,
+                &commit.sha,
+                &sysroot.triple,
+                iterations,
+            ).map_err(|e| {
+                info!("failed to benchmark the bootstrap, recorded: {}", e);
+                format!("{:?}", e)
+            });
+
+            // The bootstrap is recorded alongside the other benchmarks of the
+            // commit, as if it were just another (very large) crate. Results
+            // that can't be read aren't replaced, though.
+            let existing = out_repo.load_commit_data(&commit, &sysroot.triple, Flavor::Plain);
+            let not_found = existing.as_ref().err()
+                .and_then(|e| e.downcast_ref::<std::io::Error>())
+                .map_or(false, |e| e.kind() == std::io::ErrorKind::NotFound);
+            let mut data = match existing {
+                Ok(data) => data,
+                Err(_) if not_found => CommitData {
+                    commit: Commit {
+                        sha: commit.sha.clone(),
+                        date: Date(commit.date),
+                    },
+                    triple: sysroot.triple.clone(),
+                    flavor: Flavor::Plain,
+                    llvm_version: collection::llvm_version(&sysroot.rustc),
+                    benchmarks: BTreeMap::new(),
+                    bootstrap: BTreeMap::new(),
+                    environment: Some(environment::current()),
+                    collection: Some(collection::current(&sysroot.rustc)),
+                    artifact_sizes: BTreeMap::new(),
+                    collected_by: BTreeMap::new(),
+                    shards: Vec::new(),
+                    outliers: Vec::new(),
+                    ices: BTreeMap::new(),
+                    metadata: None,
+                },
+                Err(e) => return Err(e),
+            };
+            data.benchmarks.insert(bootstrap::BENCHMARK_NAME.to_string(), result);
+            out_repo.success(&data)?;
+            Ok(0)
+        }
+
+        ("bench_cargo", Some(sub_m)) => {
+            let commit = sub_m.value_of("COMMIT").unwrap();
+            let commit = get_commits()?
+                .iter()
+                .find(|c| c.sha == commit)
+                .cloned()
+                .ok_or_else(|| format_err!("unknown commit {}", commit))?;
+            let out_repo = get_out_repo(false)?;
+            let sysroot = get_cache()?.install_sysroot(&commit, HOST_TRIPLE, false)?;
+
+            let results = cargo_ops::measure(
+                Compiler {
+                    rustc: &sysroot.rustc,
+                    cargo: &sysroot.cargo,
+                    is_nightly: true,
+                    target: None,
+                    backend: CodegenBackend::Llvm,
+                    linker: Linker::Default,
+                    threads: None,
+                },
+                &benchmark_dir,
+                3,
+            );
+
+            // Like the bootstrap, the cargo operations are recorded alongside
+            // the other benchmarks of the commit.
+            let mut data = out_repo.load_commit_data(&commit, &sysroot.triple, Flavor::Plain)
+                .unwrap_or_else(|_| CommitData {
+                    commit: Commit {
+                        sha: commit.sha.clone(),
+                        date: Date(commit.date),
+                    },
+                    triple: sysroot.triple.clone(),
+                    flavor: Flavor::Plain,
+                    llvm_version: collection::llvm_version(&sysroot.rustc),
+                    benchmarks: BTreeMap::new(),
+                    bootstrap: BTreeMap::new(),
+                    environment: Some(environment::current()),
+                    collection: Some(collection::current(&sysroot.rustc)),
+                    artifact_sizes: BTreeMap::new(),
+                    collected_by: BTreeMap::new(),
+                    shards: Vec::new(),
+                    outliers: Vec::new(),
+                    ices: BTreeMap::new(),
+                    metadata: None,
+                });
+            data.benchmarks.extend(results);
+            out_repo.success(&data)?;
             Ok(0)
         }
 
         ("bench_local", Some(sub_m)) => {
             let rustc = sub_m.value_of("RUSTC").unwrap();
-            let cargo = sub_m.value_of("CARGO").unwrap();
-            let build_kinds = build_kinds_from_arg(&sub_m.value_of("BUILDS"))?;
-            let run_kinds = run_kinds_from_arg(&sub_m.value_of("RUNS"))?;
+            let cargo = sub_m.value_of("CARGO");
+            let build_kinds = build_kinds_from_arg(
+                &sub_m.value_of("BUILDS").or(sub_m.value_of("PROFILES")))?;
+            let run_kinds = run_kinds_from_arg(
+                &sub_m.value_of("RUNS").or(sub_m.value_of("SCENARIOS")))?;
+            let profiler = match sub_m.value_of("PROFILER")
+                    .or(config.profiler.as_ref().map(String::as_str)) {
+                Some(name) => Profiler::measure_from_name(name)?,
+                None => Profiler::PerfStat,
+            };
+            // Parsed up front, so that a typo doesn't only show up after the
+            // benchmarks have run.
+            let output_format = match sub_m.value_of("OUTPUT_FORMAT") {
+                Some(format) => Some(export::OutputFormat::from_name(format)?),
+                None => None,
+            };
+            let limit = match sub_m.value_of("ADAPTIVE") {
+                Some(stat) => IterationLimit::Adaptive {
+                    stat,
+                    max_cv: match sub_m.value_of("MAX_CV") {
+                        Some(max_cv) => max_cv.parse()
+                            .with_context(|_| format!("invalid --max-cv: {}", max_cv))?,
+                        None => config.max_cv.unwrap_or(0.01),
+                    },
+                    max: match sub_m.value_of("MAX_ITERATIONS") {
+                        Some(max) => max.parse()
+                            .with_context(|_| format!("invalid --max-iterations: {}", max))?,
+                        None => config.max_iterations.unwrap_or(10),
+                    },
+                },
+                None => IterationLimit::Fixed(1),
+            };
+            let warmup = match sub_m.value_of("WARMUP") {
+                Some(warmup) => warmup.parse()
+                    .with_context(|_| format!("invalid --warmup: {}", warmup))?,
+                None => config.warmup.unwrap_or(0),
+            };
+            let iterations = Iterations { warmup, limit };
             let id = sub_m.value_of("ID").unwrap();
 
             // This isn't a true representation of a commit, because `id` is an
@@ -481,144 +2145,384 @@ fn main_result() -> Result<i32, Error> {
                 date: Utc::now().with_nanosecond(0).unwrap(),
                 summary: String::new(),
             };
-            let rustc_path = PathBuf::from(rustc).canonicalize()?;
-            let cargo_path = PathBuf::from(cargo).canonicalize()?;
-            // We don't pass `out_repo` here. `commit` is unique because
-            // `commit.date` is unique, so there's no point even trying to load
-            // prior data.
-            let result = bench_commit(
-                None,
-                &commit,
-                "x86_64-unknown-linux-gnu",
-                &build_kinds,
-                &run_kinds,
-                Compiler {
-                    rustc: &rustc_path,
-                    cargo: &cargo_path,
-                    is_nightly: true,
-                },
-                &benchmarks,
-                1,
-                false,
-            );
-            get_out_repo(true)?.add_commit_data(&result)?;
-            Ok(0)
-        }
-
-        ("bench_published", Some(sub_m)) => {
-            let id = sub_m.value_of("ID").unwrap();
-            let repo = get_out_repo(false)?;
-            let commit = rust_sysroot::git::Commit {
-                sha: String::from("<none>"),
-                date: Date::ymd_hms(2010, 01, 01, 0, 0, 0).0,
-                summary: String::new(),
-            };
-            let cfg = rustup::Cfg::from_env(Arc::new(|_| {}))
-                .map_err(SyncFailure::new)?;
-            let toolchain = rustup::Toolchain::from(&cfg, id)
-                .map_err(SyncFailure::new)
-                .with_context(|_| format!("creating toolchain for id: {}", id))?;
-            toolchain.install_from_dist_if_not_installed().map_err(SyncFailure::new)?;
+            let backend = backend_from_arg(&sub_m.value_of("BACKEND"))?;
+            let linker = linker_from_arg(&sub_m.value_of("LINKER"))?;
+            let flavor = flavor_from_arg(&sub_m.value_of("FLAVOR"))?;
+            let thread_counts = threads_from_arg(&sub_m.value_of("THREADS"))?;
 
-            // Remove benchmarks that don't work with a stable compiler.
-            benchmarks.retain(|b| b.supports_stable());
+            if sub_m.is_present("DRY_RUN") {
+                // The plan doesn't depend on the compiler binaries, so don't
+                // bother resolving (and maybe installing) the toolchain.
+                let mut plan = Vec::new();
+                for &threads in &thread_counts {
+                    let compiler = Compiler {
+                        rustc: Path::new(rustc),
+                        cargo: Path::new(cargo.unwrap_or("cargo")),
+                        is_nightly: true,
+                        target: sub_m.value_of("TARGET"),
+                        backend,
+                        linker,
+                        threads,
+                    };
+                    // `bench_local` starts every benchmark from scratch, so no
+                    // build kind is skipped.
+                    for benchmark in &benchmarks {
+                        plan.extend(benchmark.plan(&build_kinds, &run_kinds, compiler, iterations,
+                                                   &|_| false));
+                    }
+                }
+                let history = outrepo::load_all_commit_data(&output_repo);
+                plan::print_plan(&plan, &plan::durations(&history));
+                return Ok(0);
+            }
 
-            // No NLL runs when testing stable builds.
-            let run_kinds = if collector::version_supports_incremental(id) {
-                RunKind::all_except_nll()
-            } else {
-                RunKind::all_non_incr_except_nll()
+            check_linker(linker)?;
+            let (rustc_path, cargo_path) = resolve_toolchain(rustc, cargo)?;
+            // Opened up front, so that a bad URL doesn't only show up after
+            // the benchmarks have run.
+            let db = match sub_m.value_of("DB") {
+                Some(url) => Some(db::open(url)?),
+                None => None,
             };
-            let CommitData { benchmarks: benchmark_data, .. } = bench_commit(
-                None,
-                &commit,
-                "x86_64-unknown-linux-gnu",
-                &[BuildKind::Check, BuildKind::Debug, BuildKind::Opt],
-                &run_kinds,
-                Compiler {
-                    rustc: &toolchain.binary_file("rustc"),
-                    cargo: &toolchain.binary_file("cargo"),
-                    is_nightly: false,
-                },
-                &benchmarks,
-                3,
-                false,
-            );
-            repo.success_artifact(&ArtifactData { id: id.to_string(), benchmarks: benchmark_data })?;
+
+            // Each thread count is measured separately, and all the runs are
+            // then recorded together; the thread count is part of the run's
+            // name, so they still show up as distinct series.
+            let mut result: Option<CommitData> = None;
+            for &threads in &thread_counts {
+                // We don't pass `out_repo` here. `commit` is unique because
+                // `commit.date` is unique, so there's no point even trying to
+                // load prior data.
+                let data = bench_commit(
+                    None,
+                    &[],
+                    &commit,
+                    None,
+                    HOST_TRIPLE,
+                    flavor,
+                    &build_kinds,
+                    &run_kinds,
+                    Compiler {
+                        rustc: &rustc_path,
+                        cargo: &cargo_path,
+                        is_nightly: true,
+                        target: sub_m.value_of("TARGET"),
+                        backend,
+                        linker,
+                        threads,
+                    },
+                    &benchmarks,
+                    None,
+                    iterations,
+                    false,
+                    profiler,
+                )?;
+                accumulate_commit_data(&mut result, data);
+                if shutdown::requested() {
+                    break;
+                }
+            }
+            // What was measured is saved even if the collector is shut down.
+            let result = result.unwrap();
+            get_out_repo(true)?.add_commit_data(&result)?;
+            if let Some(mut db) = db {
+                let data = collector::api::upload::Data::Commit(result.clone());
+                spool::stores(&get_out_dir()).send(&data, |data| {
+                    db.store(&db::Results::from(data.clone()))
+                })?;
+            }
+            shutdown::check()?;
+            if let Some(format) = output_format {
+                export::write_results(&mut stdout(), &result, format)?;
+            }
             Ok(0)
         }
 
-        ("process", Some(_)) => {
-            let out_repo = get_out_repo(false)?;
-            println!("processing commits");
-            let commits = get_commits()?;
-            let client = reqwest::Client::new();
-            let commit: Option<String> = client.get(&format!(
-                "{}/perf/next_commit",
-                env::var("SITE_URL").expect("SITE_URL defined")
-            )).send()?.json()?;
-            let commit = if let Some(c) = commit {
-                c
-            } else {
-                // no missing commits
-                return Ok(0);
-            };
+        ("bench_ci", Some(sub_m)) => {
+            let id = sub_m.value_of("ID").unwrap();
+            let build_kinds = build_kinds_from_arg(
+                &sub_m.value_of("BUILDS").or(sub_m.value_of("PROFILES")))?;
+            let run_kinds = run_kinds_from_arg(
+                &sub_m.value_of("RUNS").or(sub_m.value_of("SCENARIOS")))?;
+            let triple = HOST_TRIPLE;
 
-            let commit = commits.iter()
-                .find(|c| c.sha == commit)
-                .cloned()
-                .unwrap_or_else(|| {
-                    warn!("utilizing fake commit!");
-                    rust_sysroot::git::Commit {
-                        sha: commit.to_string(),
-                        date: Date::ymd_hms(2000, 01, 01, 0, 0, 0).0,
-                        summary: String::new(),
-                    }
-                });
-            if let Ok(sysroot) = Sysroot::install(&commit, "x86_64-unknown-linux-gnu", false, false) {
-                let result = out_repo.success(&bench_commit(
-                    Some(&out_repo),
+            // Nightlies are installed with rustup, and other artifacts are
+            // downloaded from CI's bucket, the same way `bench_commit` gets
+            // them. Either way, nothing needs to be built locally.
+            let data = if id.starts_with("nightly-") {
+                let date = NaiveDate::parse_from_str(&id["nightly-".len()..], "%Y-%m-%d")
+                    .with_context(|_| format!("invalid nightly: {}", id))?;
+                let commit = GitCommit {
+                    sha: id.to_string(),
+                    date: Date::ymd_hms(date.year(), date.month(), date.day(), 0, 0, 0).0,
+                    summary: String::new(),
+                };
+                let cfg = rustup::Cfg::from_env(Arc::new(|_| {}))
+                    .map_err(SyncFailure::new)?;
+                let toolchain = rustup::Toolchain::from(&cfg, id)
+                    .map_err(SyncFailure::new)
+                    .with_context(|_| format!("creating toolchain for id: {}", id))?;
+                toolchain.install_from_dist_if_not_installed().map_err(SyncFailure::new)?;
+                bench_commit(
+                    None,
+                    &[],
+                    &commit,
+                    None,
+                    triple,
+                    Flavor::Plain,
+                    &build_kinds,
+                    &run_kinds,
+                    Compiler {
+                        rustc: &toolchain.binary_file("rustc"),
+                        cargo: &toolchain.binary_file("cargo"),
+                        is_nightly: true,
+                        target: None,
+                        backend: CodegenBackend::Llvm,
+                        linker: Linker::Default,
+                        threads: None,
+                    },
+                    &benchmarks,
+                    None,
+                    Iterations::fixed(1),
+                    false,
+                    Profiler::PerfStat,
+                )?
+            } else {
+                let commits = get_commits()?;
+                let commit = commits
+                    .iter()
+                    .find(|c| c.sha == id)
+                    .cloned()
+                    .ok_or_else(|| format_err!("{} is not a known bors merge", id))?;
+                let sysroot = get_cache()?.install_sysroot(&commit, triple, false)?;
+                bench_commit(
+                    None,
+                    &[],
                     &commit,
+                    master_metadata(&commits, &commit).as_ref(),
                     &sysroot.triple,
-                    &[BuildKind::Check, BuildKind::Debug, BuildKind::Opt],
-                    &RunKind::all(),
+                    Flavor::Plain,
+                    &build_kinds,
+                    &run_kinds,
                     Compiler {
                         rustc: &sysroot.rustc,
                         cargo: &sysroot.cargo,
                         is_nightly: true,
+                        target: None,
+                        backend: CodegenBackend::Llvm,
+                        linker: Linker::Default,
+                        threads: None,
                     },
                     &benchmarks,
-                    3,
-                    true,
-                ));
-                if let Err(err) = result {
-                    out_repo.write_broken_commit(&commit, err)?;
-                }
-            } else {
-                error!("failed to install sysroot for {:?}", commit);
+                    None,
+                    Iterations::fixed(1),
+                    false,
+                    Profiler::PerfStat,
+                )?
+            };
+            // What was measured is saved even if the collector is shut down.
+            get_out_repo(true)?.add_commit_data(&data)?;
+            shutdown::check()?;
+            Ok(0)
+        }
+
+        ("bench_cmp", Some(sub_m)) => {
+            let cargo = sub_m.value_of("CARGO");
+            let (base_rustc, base_cargo) = resolve_toolchain(sub_m.value_of("BASE").unwrap(), cargo)?;
+            let (new_rustc, new_cargo) = resolve_toolchain(sub_m.value_of("NEW").unwrap(), cargo)?;
+            let build_kinds = build_kinds_from_arg(
+                &sub_m.value_of("BUILDS").or(sub_m.value_of("PROFILES")))?;
+            let run_kinds = run_kinds_from_arg(
+                &sub_m.value_of("RUNS").or(sub_m.value_of("SCENARIOS")))?;
+            let stat = sub_m.value_of("STAT").unwrap_or("instructions:u");
+
+            // Drop the nanoseconds; we don't want that level of precision.
+            let date = Utc::now().with_nanosecond(0).unwrap();
+            let commit = |id: &str| GitCommit {
+                sha: id.to_string(),
+                date,
+                summary: String::new(),
+            };
+            let (base_commit, new_commit) = (commit("base"), commit("new"));
+            let bench = |commit: &GitCommit, rustc: &Path, cargo: &Path, benchmarks: &[Benchmark]| {
+                bench_commit(
+                    None,
+                    &[],
+                    commit,
+                    None,
+                    HOST_TRIPLE,
+                    Flavor::Plain,
+                    &build_kinds,
+                    &run_kinds,
+                    Compiler {
+                        rustc,
+                        cargo,
+                        is_nightly: true,
+                        target: None,
+                        backend: CodegenBackend::Llvm,
+                        linker: Linker::Default,
+                        threads: None,
+                    },
+                    benchmarks,
+                    None,
+                    Iterations::fixed(1),
+                    false,
+                    Profiler::PerfStat,
+                )
+            };
+
+            // Alternate between the two compilers, one benchmark at a time, so
+            // that the machine's state drifting over time (e.g. it heating up)
+            // affects both about equally.
+            let mut base = None;
+            let mut new = None;
+            for benchmark in benchmarks.chunks(1) {
+                accumulate_commit_data(
+                    &mut base, bench(&base_commit, &base_rustc, &base_cargo, benchmark)?);
+                accumulate_commit_data(
+                    &mut new, bench(&new_commit, &new_rustc, &new_cargo, benchmark)?);
+                // Half a comparison is no comparison.
+                shutdown::check()?;
+            }
+            let (base, new) = match (base, new) {
+                (Some(base), Some(new)) => (base, new),
+                _ => bail!("no benchmarks to compare"),
+            };
+
+            let deltas = compare::compare(&base, &new, stat);
+            match sub_m.value_of("OUTPUT_FORMAT") {
+                Some(format) => export::write_comparison(
+                    &mut stdout(), &deltas, export::OutputFormat::from_name(format)?)?,
+                None => compare::print_table(
+                    &mut stdout(), &deltas, compare::stdout_is_terminal())?,
+            }
+            if let Some(json) = sub_m.value_of("JSON") {
+                fs::write(json, serde_json::to_string_pretty(&deltas)?)
+                    .with_context(|_| format!("failed to write {}", json))?;
             }
             Ok(0)
         }
 
+        ("bench_runtime_local", Some(sub_m)) => {
+            let rustc = sub_m.value_of("RUSTC").unwrap();
+            let cargo = sub_m.value_of("CARGO");
+            let iterations = match sub_m.value_of("ITERATIONS") {
+                Some(iterations) => iterations.parse()
+                    .with_context(|_| format!("invalid --iterations: {}", iterations))?,
+                None => 3,
+            };
+            let id = sub_m.value_of("ID").unwrap();
+
+            let (rustc_path, cargo_path) = resolve_toolchain(rustc, cargo)?;
+            let backend = backend_from_arg(&sub_m.value_of("BACKEND"))?;
+            let compiler = Compiler { rustc: &rustc_path, cargo: &cargo_path, is_nightly: true,
+                                      target: None, backend, linker: Linker::Default,
+                                      threads: None };
+
+            let runtime_benchmarks = runtime::get_runtime_benchmarks(
+                &PathBuf::from("collector/runtime-benchmarks"),
+                filter,
+            )?;
+            let mut results = BTreeMap::new();
+            for (i, benchmark) in runtime_benchmarks.iter().enumerate() {
+                let result = benchmark.measure(compiler, iterations).map_err(|e| {
+                    info!("failed to benchmark {}, recorded: {}", benchmark.name, e);
+                    format!("{:?}", e)
+                });
+                results.insert(benchmark.name.clone(), result);
+                info!("{} runtime benchmarks left", runtime_benchmarks.len() - i - 1);
+            }
+
+            get_out_repo(true)?.add_runtime_data(&RuntimeData {
+                id: id.to_string(),
+                date: Date(Utc::now().with_nanosecond(0).unwrap()),
+                backend,
+                benchmarks: results,
+            })?;
+            Ok(0)
+        }
+
+        ("bench_published", Some(sub_m)) => {
+            let repo = get_out_repo(false)?;
+            // Remove benchmarks that don't work with a stable compiler.
+            benchmarks.retain(|b| b.supports_stable());
+
+            if sub_m.is_present("WATCH") {
+                let poll_interval = match sub_m.value_of("POLL_INTERVAL") {
+                    Some(secs) => secs.parse::<u64>()
+                        .with_context(|_| format!("invalid poll interval: {}", secs))?,
+                    None => 60 * 60,
+                };
+                return watch_published(&repo, &benchmarks, Duration::from_secs(poll_interval));
+            }
+
+            let id = sub_m.value_of("ID").unwrap();
+            bench_published(&repo, id, id, None, &benchmarks)?;
+            Ok(0)
+        }
+
+        ("process", Some(sub_m)) => {
+            let collector = sub_m.value_of("NAME").or(config.name.as_ref().map(String::as_str));
+            let mut out_repo = get_out_repo(false)?;
+            if let Some(name) = collector {
+                out_repo.set_collector(name);
+            }
+            info!("processing commits");
+            let mut history = outrepo::load_all_commit_data(out_repo.path());
+            process_next_commit(&out_repo, &mut get_cache()?, collector, &benchmarks,
+                                &get_commits()?, sub_m.is_present("ALT"), &mut history, None)?;
+            Ok(0)
+        }
+
+        ("daemon", Some(sub_m)) => {
+            // Also used by `send_home`.
+            if let Some(site_url) = sub_m.value_of("SITE_URL") {
+                env::set_var("SITE_URL", site_url);
+            } else if env::var_os("SITE_URL").is_none() {
+                bail!("--site-url is required, unless $SITE_URL or the configuration sets it");
+            }
+            let poll_interval = match sub_m.value_of("POLL_INTERVAL") {
+                Some(secs) => secs.parse::<u64>()
+                    .with_context(|_| format!("invalid poll interval: {}", secs))?,
+                None => 60,
+            };
+            let collector = sub_m.value_of("NAME").or(config.name.as_ref().map(String::as_str));
+            let mut out_repo = get_out_repo(false)?;
+            if let Some(name) = collector {
+                out_repo.set_collector(name);
+            }
+            daemon(&out_repo, &mut get_cache()?, collector, &benchmarks, sub_m.is_present("ALT"),
+                   Duration::from_secs(poll_interval), &|| Ok(get_commits()?))
+        }
+
         ("profile", Some(sub_m)) => {
             let rustc = sub_m.value_of("RUSTC").unwrap();
-            let cargo = sub_m.value_of("CARGO").unwrap();
-            let build_kinds = build_kinds_from_arg(&sub_m.value_of("BUILDS"))?;
-            let run_kinds = run_kinds_from_arg(&sub_m.value_of("RUNS"))?;
+            let cargo = sub_m.value_of("CARGO");
+            let build_kinds = build_kinds_from_arg(
+                &sub_m.value_of("BUILDS").or(sub_m.value_of("PROFILES")))?;
+            let run_kinds = run_kinds_from_arg(
+                &sub_m.value_of("RUNS").or(sub_m.value_of("SCENARIOS")))?;
             let profiler = Profiler::from_name(sub_m.value_of("PROFILER").unwrap())?;
             let id = sub_m.value_of("ID").unwrap();
+            let diff_id = sub_m.value_of("DIFF");
 
             info!("Profile with {:?}", profiler);
 
-            let rustc_path = PathBuf::from(rustc).canonicalize()?;
-            let cargo_path = PathBuf::from(cargo).canonicalize()?;
-            let compiler = Compiler { rustc: &rustc_path, cargo: &cargo_path, is_nightly: true };
+            let (rustc_path, cargo_path) = resolve_toolchain(rustc, cargo)?;
+            let backend = backend_from_arg(&sub_m.value_of("BACKEND"))?;
+            let linker = linker_from_arg(&sub_m.value_of("LINKER"))?;
+            check_linker(linker)?;
+            let compiler = Compiler { rustc: &rustc_path, cargo: &cargo_path, is_nightly: true,
+                                      target: sub_m.value_of("TARGET"), backend, linker,
+                                      threads: None };
 
             for (i, benchmark) in benchmarks.iter().enumerate() {
+                let _span = logging::span("benchmark", &benchmark.name, Level::Info);
                 let out_dir = get_out_dir();
-                let mut processor = execute::ProfileProcessor::new(profiler, &out_dir, &id);
+                let mut processor = execute::ProfileProcessor::new(profiler, &out_dir, &id,
+                                                                   diff_id);
                 let result = benchmark.measure(&mut processor, &build_kinds, &run_kinds,
-                                               compiler, 1);
+                                               compiler, Iterations::fixed(1));
                 if let Err(ref s) = result {
                     info!("failed to profile {} with {:?}, recorded: {:?}",
                           benchmark.name, profiler, s);
@@ -632,8 +2536,8 @@ fn main_result() -> Result<i32, Error> {
             let benchmark = sub_m.value_of("BENCHMARK").unwrap();
             let out_repo = get_out_repo(false)?;
             for commit in &get_commits()? {
-                if let Ok(mut data) = out_repo.load_commit_data(&commit, "x86_64-unknown-linux-gnu")
-                {
+                let triple = HOST_TRIPLE;
+                if let Ok(mut data) = out_repo.load_commit_data(&commit, triple, Flavor::Plain) {
                     if data.benchmarks.remove(&*benchmark).is_none() {
                         warn!("could not remove {} from {}", benchmark, commit.sha);
                     }
@@ -646,8 +2550,8 @@ fn main_result() -> Result<i32, Error> {
         ("remove_errs", Some(_)) => {
             for commit in &get_commits()? {
                 let out_repo = get_out_repo(false)?;
-                if let Ok(mut data) = out_repo.load_commit_data(&commit, "x86_64-unknown-linux-gnu")
-                {
+                let triple = HOST_TRIPLE;
+                if let Ok(mut data) = out_repo.load_commit_data(&commit, triple, Flavor::Plain) {
                     let benchmarks = data.benchmarks
                         .into_iter()
                         .filter(|&(_, ref v)| v.is_ok())
@@ -659,26 +2563,189 @@ fn main_result() -> Result<i32, Error> {
             Ok(0)
         }
 
+        ("cache", Some(sub_m)) => {
+            let mut cache = get_cache()?;
+            match sub_m.subcommand() {
+                ("prune", Some(sub_m)) => {
+                    let max_size = match sub_m.value_of("MAX_SIZE") {
+                        Some(size) => gib_from_arg(size)?,
+                        None => cache_size,
+                    };
+                    let evicted = cache.prune(max_size, sub_m.is_present("VERIFY"))?;
+                    for &(ref name, ref entry) in &evicted {
+                        println!("evicted {} ({} bytes)", name, entry.bytes);
+                    }
+                    println!("{} files evicted, {} bytes left", evicted.len(), cache.size());
+                }
+                _ => {
+                    for (name, entry) in cache.entries() {
+                        println!("{}  {:>12}  {}  {}",
+                                 entry.last_used, entry.bytes, entry.sha256, name);
+                    }
+                    println!("{} bytes in {:?}", cache.size(), cache_dir);
+                }
+            }
+            Ok(0)
+        }
+
+        ("export_db", Some(sub_m)) => {
+            let url = database_url(sub_m.value_of("DB"))?;
+            let format = db_export::Format::from_name(sub_m.value_of("FORMAT").unwrap_or("csv"))?;
+            let list = |arg| sub_m.value_of(arg)
+                .map(|names: &str| names.split(',').map(String::from).collect());
+            let since = match sub_m.value_of("SINCE") {
+                Some(since) => Some(NaiveDate::parse_from_str(since, "%Y-%m-%d")
+                    .with_context(|_| format!("invalid --since: {}", since))?),
+                None => None,
+            };
+            let filter = db_export::Filter {
+                benchmarks: list("BENCHMARKS"),
+                metrics: list("METRICS"),
+                since,
+            };
+            let output = Path::new(sub_m.value_of("OUTPUT").unwrap());
+            let rows = db_export::export(&url, &filter, format, output)?;
+            println!("exported {} rows to {:?}", rows, output);
+            Ok(0)
+        }
+
+        ("import_db", Some(sub_m)) => {
+            let url = database_url(sub_m.value_of("DB"))?;
+            let options = db_import::Options {
+                replace: sub_m.is_present("REPLACE"),
+                artifact_prefix: sub_m.value_of("ARTIFACT_PREFIX").map(String::from),
+            };
+            let summary = db_import::import(sub_m.value_of("FROM").unwrap(), &url, &options)?;
+            println!("imported {} artifacts, skipped {} already in the database",
+                     summary.imported, summary.skipped);
+            Ok(0)
+        }
+
+        ("flush_spool", Some(sub_m)) => {
+            let uploads = spool::uploads(&get_out_dir());
+            if uploads.len()? > 0 {
+                let uploader = upload::Uploader::from_env()?;
+                uploads.replay(|data| uploader.upload(data))?;
+            }
+            let stores = spool::stores(&get_out_dir());
+            if stores.len()? > 0 {
+                let mut db = db::open(&database_url(sub_m.value_of("DB"))?)?;
+                stores.replay(|data| db.store(&db::Results::from(data.clone())))?;
+            }
+            println!("{} uploads and {} stores left in the queue", uploads.len()?, stores.len()?);
+            Ok(0)
+        }
+
+        ("prune_db", Some(sub_m)) => {
+            let mut db = db::open(&database_url(sub_m.value_of("DB"))?)?;
+            let commits = db.load()?.commits;
+            let (changed, report) = retention::prune(commits, &config.retention, Utc::now());
+            let dry_run = sub_m.is_present("DRY_RUN");
+            println!("{} {} commits:", if dry_run { "would prune" } else { "pruning" },
+                     report.commits);
+            println!("  the per-query self-profile data of {} runs", report.query_data);
+            for (metric, runs) in &report.samples {
+                println!("  the samples of {} of {} runs", metric, runs);
+            }
+            for (metric, values) in &report.values {
+                println!("  {} values of {}", values, metric);
+            }
+            if !dry_run && !changed.is_empty() {
+                db.store(&db::Results { commits: changed, ..db::Results::default() })?;
+            }
+            Ok(0)
+        }
+
+        ("calibrate", Some(sub_m)) => {
+            let runs = match sub_m.value_of("RUNS") {
+                Some(runs) => runs.parse()
+                    .with_context(|_| format!("invalid --runs: {}", runs))?,
+                None => calibrate::DEFAULT_RUNS,
+            };
+            let max_cv = match sub_m.value_of("MAX_CV") {
+                Some(max_cv) => max_cv.parse()
+                    .with_context(|_| format!("invalid --max-cv: {}", max_cv))?,
+                None => calibrate::DEFAULT_MAX_CV,
+            };
+            let baseline = calibrate::measure(runs)?;
+            println!("{} runs: mean {:.3}s, min {:.3}s, max {:.3}s, varying by {:.2}%",
+                     baseline.runs, baseline.mean, baseline.min, baseline.max,
+                     baseline.cv * 100.0);
+            if baseline.cv > max_cv {
+                warn!("the machine is noisy: the times vary by more than {:.2}%",
+                      max_cv * 100.0);
+            }
+            calibrate::save(&baseline, &calibration)?;
+            println!("saved the baseline to {:?}", calibration);
+            Ok(0)
+        }
+
+        ("learn_sensitivity", Some(sub_m)) => {
+            let rust_src = PathBuf::from(sub_m.value_of("RUST_SRC").unwrap_or("rust"));
+            let stat = sub_m.value_of("STAT").unwrap_or(DEFAULT_STAT);
+            let threshold = match sub_m.value_of("THRESHOLD") {
+                Some(threshold) => threshold.parse()
+                    .with_context(|_| format!("invalid --threshold: {}", threshold))?,
+                None => sensitivity::DEFAULT_THRESHOLD,
+            };
+            let history = outrepo::load_all_commit_data(&get_out_dir());
+            let sensitivity = sensitivity::learn(&history, &rust_src, stat, threshold)?;
+            println!("learned from {} commits: {} modules, {} benchmarks",
+                     sensitivity.commits, sensitivity.modules.len(),
+                     sensitivity.benchmarks.len());
+            sensitivity::save(&sensitivity, &sensitivity_path)?;
+            println!("saved the sensitivity matrix to {:?}", sensitivity_path);
+            Ok(0)
+        }
+
+        ("add_benchmark", Some(sub_m)) => {
+            let name = sub_m.value_of("NAME").unwrap();
+            let version = sub_m.value_of("VERSION").unwrap();
+            let path = benchmark_dir.join(name);
+            add_benchmark::add_benchmark(name, version, &path)?;
+            println!("added {} {} as {:?}; check that it builds with `bench_local --include {}`, \
+                      then run `update_manifest`", name, version, path, name);
+            Ok(0)
+        }
+
+        ("update_manifest", Some(_)) => {
+            // The manifest covers the whole suite, regardless of any filters.
+            let all_benchmarks = get_benchmarks(&benchmark_dir, None, None, None)?;
+            let manifest = suite::update_manifest(&all_benchmarks)?;
+            println!("{} is at version {}", suite::MANIFEST_PATH, manifest.version);
+            Ok(0)
+        }
+
         ("test_benchmarks", Some(_)) => {
             if let Some(commit) = get_commits()?.last() {
-                let sysroot = Sysroot::install(commit, "x86_64-unknown-linux-gnu", false, false)
-                    .map_err(SyncFailure::new)?;
+                let sysroot = get_cache()?
+                    .install_sysroot(commit, HOST_TRIPLE, false)?;
                 // filter out servo benchmarks as they simply take too long
                 bench_commit(
                     None,
+                    &[],
                     commit,
+                    None,
                     &sysroot.triple,
+                    Flavor::Plain,
                     &[BuildKind::Check], // no Debug or Opt builds
                     &RunKind::all(),
                     Compiler {
                         rustc: &sysroot.rustc,
                         cargo: &sysroot.cargo,
                         is_nightly: true,
+                        target: None,
+                        backend: CodegenBackend::Llvm,
+                        linker: Linker::Default,
+                        threads: None,
                     },
                     &benchmarks,
-                    1,
+                    None,
+                    Iterations::fixed(1),
                     false,
-                );
+                    Profiler::PerfStat,
+                )?;
+                shutdown::check()?;
             } else {
                 panic!("no commits");
             }
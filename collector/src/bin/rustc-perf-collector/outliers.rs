@@ -0,0 +1,123 @@
+//! Finds results that are far off from the history of their series, and
+//! reruns their benchmarks to tell real changes from noise.
+
+use std::collections::BTreeMap;
+
+use collector::{Benchmark as CollectedBenchmark, CommitData, Outlier, Run};
+
+/// A result is an outlier if it's more than this many standard deviations
+/// away from the mean of its series.
+pub const SIGMA: f64 = 5.0;
+
+/// How many times the benchmark of an outlier is rerun to see whether it's
+/// reproducible.
+pub const RERUNS: usize = 3;
+
+/// How many of the most recent results of a series make up its history.
+const HISTORY: usize = 30;
+
+/// Series with fewer results than this aren't checked.
+const MIN_HISTORY: usize = 10;
+
+/// How many standard deviations `value` is away from the mean of `stat` in
+/// `run` of `benchmark` in `history` (oldest first), if there is enough of
+/// it. Positive if `value` is larger than the mean.
+fn sigmas(history: &[CommitData], benchmark: &str, run: &Run, stat: &str, value: f64)
+          -> Option<f64> {
+    let values = history.iter()
+        .rev()
+        .filter_map(|data| match data.benchmarks.get(benchmark) {
+            Some(&Ok(ref b)) => b.runs.iter().find(|r| *r == run).and_then(|r| r.get_stat(stat)),
+            _ => None,
+        })
+        .take(HISTORY)
+        .collect::<Vec<_>>();
+    if values.len() < MIN_HISTORY {
+        return None;
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let std_dev = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+    // A series that never changed can't tell how much change is noise.
+    if std_dev == 0.0 {
+        return None;
+    }
+    Some((value - mean) / std_dev)
+}
+
+/// The runs of `benchmark` whose `stat` is an outlier, with how many
+/// standard deviations off it is.
+fn find(history: &[CommitData], benchmark: &CollectedBenchmark, stat: &str) -> Vec<(Run, f64)> {
+    benchmark.runs.iter()
+        .filter_map(|run| {
+            let value = run.get_stat(stat)?;
+            let off = sigmas(history, &benchmark.name, run, stat, value)?;
+            if off.abs() > SIGMA {
+                Some((run.clone(), off))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Checks `stat` in all of the `results` against `history`. The benchmark
+/// of every outlier is rerun with `rerun` up to `RERUNS` times, until all of
+/// its outliers are reproduced, i.e. are as far off in the same direction.
+/// Outliers that aren't reproduced are suspected to be noise.
+pub fn check<F>(
+    results: &BTreeMap<String, Result<CollectedBenchmark, String>>,
+    history: &[CommitData],
+    stat: &str,
+    rerun: F,
+) -> Vec<Outlier>
+where
+    F: Fn(&str) -> Option<CollectedBenchmark>,
+{
+    let mut outliers = Vec::new();
+    for (name, result) in results {
+        let benchmark = match *result {
+            Ok(ref benchmark) => benchmark,
+            Err(_) => continue,
+        };
+        let found = find(history, benchmark, stat);
+        if found.is_empty() {
+            continue;
+        }
+
+        let mut reproduced = vec![false; found.len()];
+        for i in 0..RERUNS {
+            info!("{} has {} outliers, rerunning it ({}/{})", name, found.len(), i + 1, RERUNS);
+            let again = match rerun(name) {
+                Some(again) => again,
+                None => break,
+            };
+            for (&(ref run, sigmas_before), done) in found.iter().zip(&mut reproduced) {
+                let value = again.runs.iter().find(|r| *r == run).and_then(|r| r.get_stat(stat));
+                let sigmas_now = value.and_then(|v| sigmas(history, name, run, stat, v));
+                if let Some(sigmas_now) = sigmas_now {
+                    if sigmas_now.abs() > SIGMA && sigmas_now.signum() == sigmas_before.signum() {
+                        *done = true;
+                    }
+                }
+            }
+            if reproduced.iter().all(|&r| r) {
+                break;
+            }
+        }
+
+        for ((run, sigmas), reproduced) in found.into_iter().zip(reproduced) {
+            if !reproduced {
+                warn!("{} {} is suspected to be noise ({:+.1} sigma)", name, run.name(), sigmas);
+            }
+            outliers.push(Outlier {
+                benchmark: name.clone(),
+                run: run.name(),
+                stat: stat.to_string(),
+                sigmas,
+                reproduced,
+            });
+        }
+    }
+    outliers
+}
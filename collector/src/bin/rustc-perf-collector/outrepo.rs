@@ -9,14 +9,65 @@ use std::str;
 use std::thread;
 use std::time::{self, Instant};
 use serde_json;
-use collector::{ArtifactData, CommitData};
+use collector::{ArtifactData, CommitData, Flavor, RuntimeData};
+use collector::api::upload;
 use rust_sysroot::git::Commit as GitCommit;
 use failure::{Error, ResultExt};
+use progress::write_atomically;
+use spool;
+use upload::Uploader;
+
+/// Loads all the commit data that has been recorded in the output directory
+/// at `path`, oldest first. Files that can't be read are skipped.
+pub fn load_all_commit_data(path: &Path) -> Vec<CommitData> {
+    let entries = match fs::read_dir(path.join("times")) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut data = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            name.ends_with(".json") &&
+                !name.starts_with("artifact-") && !name.starts_with("runtime-")
+        })
+        .filter_map(|path| {
+            let file = File::open(&path).ok()?;
+            serde_json::from_reader::<_, CommitData>(file)
+                .map_err(|e| debug!("skipping {}: {}", path.display(), e))
+                .ok()
+        })
+        .collect::<Vec<_>>();
+    data.sort_by_key(|d| d.commit.date);
+    // The shards of a commit collected by several collectors are in files of
+    // their own; they're merged into one.
+    let mut merged: Vec<CommitData> = Vec::with_capacity(data.len());
+    for d in data {
+        let sharded = d.collection.as_ref().map_or(false, |c| c.shard.is_some());
+        let existing = if sharded {
+            merged.iter().position(|m| {
+                m.commit.sha == d.commit.sha && m.triple == d.triple && m.flavor == d.flavor
+            })
+        } else {
+            None
+        };
+        match existing {
+            Some(i) => merged[i].merge_shard(d),
+            None => merged.push(d),
+        }
+    }
+    merged
+}
 
 pub struct Repo {
     path: PathBuf,
     use_remote: bool,
     retries: Vec<String>,
+    /// The name of this collector, if it's one of several sharing the repo.
+    collector: Option<String>,
+    /// Where the results go instead of being committed, if anywhere.
+    uploader: Option<Uploader>,
 }
 
 impl Repo {
@@ -66,6 +117,8 @@ impl Repo {
             path: path,
             use_remote,
             retries: vec![],
+            collector: None,
+            uploader: None,
         };
 
         // Don't nuke random repositories, unless specifically requested.
@@ -84,8 +137,34 @@ impl Repo {
         Ok(result)
     }
 
+    /// Makes the results of commits go into files of this collector's own, so
+    /// that several collectors can share the repo without overwriting each
+    /// other's results.
+    pub fn set_collector(&mut self, name: &str) {
+        self.collector = Some(name.to_string());
+    }
+
+    /// Makes the results be uploaded to the site, rather than committed to
+    /// the repo. They are still written to it, so that interrupted
+    /// collections can be resumed.
+    pub fn set_uploader(&mut self, uploader: Uploader) {
+        self.uploader = Some(uploader);
+    }
+
+    /// Uploads the results that couldn't be uploaded earlier, if uploading.
+    pub fn replay_uploads(&self) -> Result<usize, Error> {
+        match self.uploader {
+            Some(ref uploader) => spool::uploads(&self.path).replay(|data| uploader.upload(data)),
+            None => Ok(0),
+        }
+    }
+
     pub fn success(&self, data: &CommitData) -> Result<(), Error> {
         self.add_commit_data(data)?;
+        if let Some(ref uploader) = self.uploader {
+            return spool::uploads(&self.path)
+                .send(&upload::Data::Commit(data.clone()), |data| uploader.upload(data));
+        }
         self.commit_and_push(&format!("{} - success", data.commit.sha))?;
         Ok(())
     }
@@ -95,10 +174,22 @@ impl Repo {
         info!("creating file {}", filepath.display());
         let mut file = File::create(&filepath)?;
         serde_json::to_writer(&mut file, &data)?;
+        if let Some(ref uploader) = self.uploader {
+            return spool::uploads(&self.path)
+                .send(&upload::Data::Artifact(data.clone()), |data| uploader.upload(data));
+        }
         self.commit_and_push(&format!("{} - success", data.id))?;
         Ok(())
     }
 
+    pub fn add_runtime_data(&self, data: &RuntimeData) -> Result<(), Error> {
+        let filepath = self.times().join(format!("runtime-{}.json", data.id));
+        info!("creating file {}", filepath.display());
+        let mut file = File::create(&filepath)?;
+        serde_json::to_writer(&mut file, &data)?;
+        Ok(())
+    }
+
     fn commit_and_push(&self, message: &str) -> Result<(), Error> {
         self.write_retries()?;
         self.git(&["add", "retries", "times"])?;
@@ -107,10 +198,13 @@ impl Repo {
         if let Err(_) = self.git(&["diff-index", "--quiet", "--cached", "HEAD"]) {
             self.git(&["commit", "-m", message])?;
             if self.use_remote {
+                // Other collectors may have pushed in the meantime; they only
+                // ever touch their own files, so this doesn't conflict.
+                self.git(&["pull", "--rebase"])?;
                 self.git(&["push"])?;
             }
         } else {
-            println!("nothing to commit...");
+            info!("nothing to commit...");
         }
         Ok(())
     }
@@ -125,7 +219,24 @@ impl Repo {
         Ok(data)
     }
 
-    pub fn load_commit_data(&self, commit: &GitCommit, triple: &str) -> Result<CommitData, Error> {
+    /// The file the results of `flavor` of `sha` for `triple` are saved in.
+    fn commit_data_file(&self, sha: &str, triple: &str, flavor: Flavor) -> PathBuf {
+        let mut name = format!("commit-{}-{}", sha, triple);
+        if !flavor.is_plain() {
+            name.push_str(&format!("-{}", flavor.name()));
+        }
+        if let Some(ref collector) = self.collector {
+            name.push_str(&format!("-{}", collector));
+        }
+        self.times().join(format!("{}.json", name))
+    }
+
+    pub fn load_commit_data(&self, commit: &GitCommit, triple: &str, flavor: Flavor)
+                            -> Result<CommitData, Error> {
+        if self.collector.is_some() || !flavor.is_plain() {
+            return self.load_commit_data_file(
+                &self.commit_data_file(&commit.sha, triple, flavor));
+        }
         let filepath = self.times().join(format!(
             "{}-{}-{}.json",
             commit.date.to_rfc3339(),
@@ -135,21 +246,17 @@ impl Repo {
         match self.load_commit_data_file(&filepath) {
             Ok(v) => return Ok(v),
             Err(_) => {
-                self.load_commit_data_file(
-                    &self.times().join(format!("commit-{}-{}.json", commit.sha, triple)))
+                self.load_commit_data_file(&self.commit_data_file(&commit.sha, triple, flavor))
             }
         }
     }
 
     pub fn add_commit_data(&self, data: &CommitData) -> Result<(), Error> {
-        let commit = &data.commit;
-        let filepath = self.times().join(format!(
-            "commit-{}-{}.json",
-            commit.sha, data.triple
-        ));
+        let filepath = self.commit_data_file(&data.commit.sha, &data.triple, data.flavor);
         info!("creating file {}", filepath.display());
-        let mut file = File::create(&filepath)?;
-        serde_json::to_writer(&mut file, &data)?;
+        // The results are saved after every benchmark, so a crash may well
+        // happen while they're written.
+        write_atomically(&filepath, &serde_json::to_vec(&data)?)?;
         Ok(())
     }
 
@@ -211,6 +318,10 @@ impl Repo {
         self.path.join("retries")
     }
 
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
     fn times(&self) -> PathBuf {
         self.path.join("times")
     }
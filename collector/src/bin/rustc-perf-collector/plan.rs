@@ -0,0 +1,70 @@
+//! Prints what the collector would benchmark, for `--dry-run`.
+
+use std::collections::HashMap;
+
+use collector::CommitData;
+
+use execute::PlannedBuild;
+
+/// The most recently recorded wall time of each run, keyed by benchmark and
+/// run name. `history` must be sorted oldest first.
+pub fn durations(history: &[CommitData]) -> HashMap<(String, String), f64> {
+    let mut durations = HashMap::new();
+    for data in history {
+        for (name, result) in &data.benchmarks {
+            if let Ok(ref benchmark) = *result {
+                for run in &benchmark.runs {
+                    if let Some(wall_time) = run.get_stat("wall-time") {
+                        durations.insert((name.clone(), run.name()), wall_time);
+                    }
+                }
+            }
+        }
+    }
+    durations
+}
+
+fn format_duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    if secs >= 60 * 60 {
+        format!("{}h {}m", secs / (60 * 60), secs / 60 % 60)
+    } else if secs >= 60 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// Prints every build in `plan`, with its expected duration according to
+/// `durations`, and the expected total.
+pub fn print_plan(plan: &[PlannedBuild], durations: &HashMap<(String, String), f64>) {
+    let mut total = 0.0;
+    let mut unknown = 0;
+    for build in plan {
+        let key = (build.benchmark.clone(), build.run_name.clone());
+        let estimate = match durations.get(&key) {
+            Some(&duration) => {
+                total += duration;
+                format!("~{}", format_duration(duration))
+            }
+            None => {
+                unknown += 1;
+                String::from("?")
+            }
+        };
+        println!("{} {}{} ({} {}/{}): {}",
+                 build.benchmark, build.run_name,
+                 if build.revert { " revert" } else { "" },
+                 if build.warmup { "warmup" } else { "iteration" },
+                 build.iteration, build.iterations, estimate);
+    }
+
+    println!();
+    println!("{} builds, estimated to take {}", plan.len(), format_duration(total));
+    println!("(not counting the initial build of each benchmark's dependencies{})",
+             if unknown > 0 {
+                 format!(", nor the {} builds without a recorded wall time", unknown)
+             } else {
+                 String::new()
+             });
+}
@@ -0,0 +1,107 @@
+//! Keeps track of the build kinds of each benchmark that have been measured,
+//! so that a collection that is interrupted, e.g. by a crash of the collector
+//! or the machine, resumes where it left off. The results of benchmarks that
+//! are done are kept with the commit's data; this is only about the
+//! benchmarks in progress, whose build kinds can take a long time each.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use collector::{Flavor, Run};
+use failure::Error;
+use serde_json;
+
+use BuildKind;
+
+/// What has been measured of a benchmark so far.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BenchmarkProgress {
+    pub build_kinds: Vec<BuildKind>,
+    pub runs: Vec<Run>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    commit: String,
+    triple: String,
+    #[serde(default)]
+    flavor: Flavor,
+    benchmarks: BTreeMap<String, BenchmarkProgress>,
+}
+
+pub struct Progress {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+impl Progress {
+    /// Loads the progress of the collection of `flavor` of `commit` for
+    /// `triple` from `dir`. The progress of a different collection, which was
+    /// abandoned, is discarded.
+    pub fn load(dir: &Path, commit: &str, triple: &str, flavor: Flavor) -> Progress {
+        let path = dir.join("progress.json");
+        let state = fs::read(&path).ok()
+            .and_then(|contents| serde_json::from_slice::<State>(&contents).ok())
+            .filter(|state| {
+                state.commit == commit && state.triple == triple && state.flavor == flavor
+            })
+            .unwrap_or_else(|| State {
+                commit: commit.to_string(),
+                triple: triple.to_string(),
+                flavor,
+                benchmarks: BTreeMap::new(),
+            });
+        for (benchmark, progress) in &state.benchmarks {
+            info!("resuming {} after {:?}", benchmark, progress.build_kinds);
+        }
+        Progress { path, state: Mutex::new(state) }
+    }
+
+    pub fn get(&self, benchmark: &str) -> BenchmarkProgress {
+        self.state.lock().unwrap().benchmarks.get(benchmark).cloned().unwrap_or_default()
+    }
+
+    /// Records that `build_kind` of `benchmark` has been measured, with all of
+    /// the benchmark's `runs` so far.
+    pub fn record(&self, benchmark: &str, build_kind: BuildKind, runs: &[Run]) {
+        let mut state = self.state.lock().unwrap();
+        {
+            let progress = state.benchmarks.entry(benchmark.to_string()).or_default();
+            progress.build_kinds.push(build_kind);
+            progress.runs = runs.to_vec();
+        }
+        self.save(&state);
+    }
+
+    /// Forgets about `benchmark`, once its results are saved elsewhere.
+    pub fn finish(&self, benchmark: &str) {
+        let mut state = self.state.lock().unwrap();
+        if state.benchmarks.remove(benchmark).is_some() {
+            self.save(&state);
+        }
+    }
+
+    /// Removes the record of the progress, once the collection is done.
+    pub fn clear(&self) {
+        let _ = fs::remove_file(&self.path);
+    }
+
+    fn save(&self, state: &State) {
+        let result = serde_json::to_vec(state).map_err(Error::from)
+            .and_then(|contents| write_atomically(&self.path, &contents));
+        if let Err(e) = result {
+            warn!("failed to save the progress of the collection: {:?}", e);
+        }
+    }
+}
+
+/// Writes `contents` to `path` such that a crash leaves either the old or the
+/// new contents, rather than a partially written file.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
@@ -0,0 +1,93 @@
+//! Finds the releases published on static.rust-lang.org, so that every new
+//! stable, beta and nightly release is benchmarked as it comes out.
+
+use std::fs::File;
+use std::path::Path;
+
+use collector::{ArtifactData, Release};
+use failure::{Error, ResultExt};
+use reqwest;
+use serde_json;
+
+const DIST_URL: &str = "https://static.rust-lang.org/dist";
+
+/// The channels that are watched, in the order they're benchmarked.
+pub const CHANNELS: &[&str] = &["stable", "beta", "nightly"];
+
+/// The release currently published on `channel`, according to its dist
+/// manifest.
+pub fn current(channel: &str) -> Result<Release, Error> {
+    let url = format!("{}/channel-rust-{}.toml", DIST_URL, channel);
+    let mut resp = reqwest::get(&url)?;
+    if !resp.status().is_success() {
+        bail!("failed to fetch {}: {}", url, resp.status());
+    }
+    let manifest = resp.text()?;
+    Ok(parse_manifest(channel, &manifest).with_context(|_| format!("failed to parse {}", url))?)
+}
+
+/// Extracts the date of the release and the version of its rustc from a
+/// dist manifest, e.g. `date = "2019-03-01"` at the top and
+/// `version = "1.34.0-nightly (00aae71f5 2019-02-25)"` under `[pkg.rustc]`.
+fn parse_manifest(channel: &str, manifest: &str) -> Result<Release, Error> {
+    let mut section = "";
+    let mut date = None;
+    let mut version = None;
+    for line in manifest.lines().map(str::trim) {
+        if line.starts_with('[') {
+            section = line;
+            continue;
+        }
+        let mut parts = line.splitn(2, '=').map(str::trim);
+        let (key, value) = match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => (key, value.trim_matches('"')),
+            _ => continue,
+        };
+        match (section, key) {
+            ("", "date") => date = Some(value.to_string()),
+            ("[pkg.rustc]", "version") => version = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    Ok(Release {
+        channel: channel.to_string(),
+        date: date.ok_or_else(|| format_err!("no date"))?,
+        version: version.ok_or_else(|| format_err!("no rustc version"))?,
+    })
+}
+
+/// The id the results of `release` are recorded under: the version number for
+/// stable releases, as `bench_published` has always done, `beta` for the
+/// current beta, and `nightly-YYYY-MM-DD` for nightlies.
+pub fn artifact_id(release: &Release) -> String {
+    match &*release.channel {
+        "stable" => release.version.split_whitespace().next().unwrap_or("").to_string(),
+        "nightly" => format!("nightly-{}", release.date),
+        channel => channel.to_string(),
+    }
+}
+
+/// The rustup toolchain that installs exactly `release`, even once it's no
+/// longer the current release of its channel.
+pub fn toolchain(release: &Release) -> String {
+    match &*release.channel {
+        "stable" => artifact_id(release),
+        channel => format!("{}-{}", channel, release.date),
+    }
+}
+
+/// Whether `release` has yet to be benchmarked into the output directory at
+/// `path`.
+pub fn is_new(path: &Path, release: &Release) -> bool {
+    let file = path.join("times").join(format!("artifact-{}.json", artifact_id(release)));
+    let data = File::open(&file).ok()
+        .and_then(|file| serde_json::from_reader::<_, ArtifactData>(file).ok());
+    match data.map(|data| data.release) {
+        // The beta is recorded under the same id every time; whether it's
+        // the current one depends on when it was released.
+        Some(Some(recorded)) => recorded.date != release.date,
+        // Recorded before releases were, which only matters for the beta.
+        Some(None) => release.channel == "beta",
+        None => true,
+    }
+}
@@ -0,0 +1,158 @@
+//! Prunes the old results in a database, so that it doesn't grow without
+//! bound, by the retention policy in the `[retention]` section of
+//! `collector.toml`, e.g.
+//!
+//!     [retention]
+//!     self-profile-months = 6
+//!     samples-months = 12
+//!
+//!     [retention.metrics]
+//!     faults = { months = 6 }
+//!     cpu-clock = { months = 12, keep = "weekly" }
+//!
+//! Once a commit is older than the given number of (30-day) months, the
+//! per-query data of its self-profiles is dropped, and so are the samples
+//! (the value in each iteration) of its metrics: after the months given for
+//! the metric in `[retention.metrics]`, or else after `samples-months`. The
+//! values of the metrics, i.e. the aggregates the site shows, are kept, unless
+//! the metric's policy says to `keep` those of only the first commit of each
+//! week or month. The self-profiles' totals by category, and the artifacts
+//! other than commits, which are few, are kept whatever their age.
+
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::{DateTime, Datelike, Duration, Utc};
+
+use collector::CommitData;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Policy {
+    /// After how many months the per-query self-profile data is dropped.
+    pub self_profile_months: Option<u32>,
+    /// After how many months the samples of the metrics that aren't in
+    /// `metrics` are dropped.
+    pub samples_months: Option<u32>,
+    /// The policies of the metrics that differ from `samples_months`.
+    pub metrics: BTreeMap<String, MetricPolicy>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct MetricPolicy {
+    /// After how many months the samples of the metric are dropped.
+    pub months: u32,
+    /// Which of the values of the metric are kept after `months`, if not
+    /// all of them.
+    pub keep: Option<Keep>,
+}
+
+/// Which of the old values of a metric are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Keep {
+    Weekly,
+    Monthly,
+}
+
+impl Keep {
+    /// The period of `date` that one commit is kept of.
+    fn period(self, date: DateTime<Utc>) -> (i32, u32) {
+        match self {
+            Keep::Weekly => (date.iso_week().year(), date.iso_week().week()),
+            Keep::Monthly => (date.year(), date.month()),
+        }
+    }
+}
+
+/// What's pruned.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The commits (or rather, their shards) that are changed.
+    pub commits: usize,
+    /// The runs whose per-query self-profile data is dropped.
+    pub query_data: usize,
+    /// The runs whose samples of each metric are dropped.
+    pub samples: BTreeMap<String, usize>,
+    /// How many values of each metric are dropped.
+    pub values: BTreeMap<String, usize>,
+}
+
+fn cutoff(now: DateTime<Utc>, months: u32) -> DateTime<Utc> {
+    now - Duration::days(30 * i64::from(months))
+}
+
+/// Prunes `commits` by `policy`, returning the ones that changed.
+pub fn prune(
+    mut commits: Vec<CommitData>,
+    policy: &Policy,
+    now: DateTime<Utc>,
+) -> (Vec<CommitData>, Report) {
+    commits.sort_by_key(|commit| commit.commit.date);
+
+    // The commits whose values of each downsampled metric are kept: the
+    // first of each period.
+    let kept = policy.metrics.iter().filter_map(|(metric, metric_policy)| {
+        let keep = metric_policy.keep?;
+        let mut periods = HashSet::new();
+        let shas = commits.iter()
+            .filter(|commit| periods.insert(keep.period(commit.commit.date.0)))
+            .map(|commit| commit.commit.sha.clone())
+            .collect::<HashSet<_>>();
+        Some((metric.as_str(), shas))
+    }).collect::<BTreeMap<_, _>>();
+
+    let mut report = Report::default();
+    let mut changed = Vec::new();
+    for mut commit in commits {
+        if commit.is_try() {
+            continue;
+        }
+        let date = commit.commit.date.0;
+        let drop_query_data = policy.self_profile_months
+            .map_or(false, |months| date < cutoff(now, months));
+        let samples_dropped = |metric: &str| {
+            policy.metrics.get(metric).map(|metric_policy| metric_policy.months)
+                .or(policy.samples_months)
+                .map_or(false, |months| date < cutoff(now, months))
+        };
+        let dropped_metrics = kept.iter()
+            .filter(|&(metric, shas)| {
+                samples_dropped(*metric) && !shas.contains(&commit.commit.sha)
+            })
+            .map(|(&metric, _)| metric)
+            .collect::<HashSet<_>>();
+
+        let mut commit_changed = false;
+        for benchmark in commit.benchmarks.values_mut().filter_map(|b| b.as_mut().ok()) {
+            for run in &mut benchmark.runs {
+                if drop_query_data && !run.self_profile.query_data.is_empty() {
+                    run.self_profile.query_data.clear();
+                    report.query_data += 1;
+                    commit_changed = true;
+                }
+                let dropped_samples = run.samples.keys()
+                    .filter(|metric| samples_dropped(metric.as_str()))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for metric in dropped_samples {
+                    run.samples.remove(&metric);
+                    *report.samples.entry(metric).or_insert(0) += 1;
+                    commit_changed = true;
+                }
+                for stat in &run.stats {
+                    if dropped_metrics.contains(stat.name.as_str()) {
+                        *report.values.entry(stat.name.clone()).or_insert(0) += 1;
+                        commit_changed = true;
+                    }
+                }
+                run.stats.retain(|stat| !dropped_metrics.contains(stat.name.as_str()));
+            }
+        }
+        if commit_changed {
+            report.commits += 1;
+            changed.push(commit);
+        }
+    }
+    (changed, report)
+}
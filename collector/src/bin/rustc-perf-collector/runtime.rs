@@ -0,0 +1,130 @@
+//! Runtime benchmarks: small programs that are compiled with the compiler
+//! under test and then executed, to catch regressions in the quality of the
+//! generated code rather than in compile time.
+
+use std::collections::HashMap;
+use std::env;
+use std::f64;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use tempfile::TempDir;
+
+use collector::{RuntimeBenchmark as CollectedRuntimeBenchmark, Stat};
+
+use failure::{Error, ResultExt};
+
+use execute::{command_output, process_perf_stat_output};
+use isolation;
+use Compiler;
+
+pub struct RuntimeBenchmark {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl RuntimeBenchmark {
+    /// Builds the benchmark in release mode and returns the path of the
+    /// resulting executable, which lives in `target_dir`.
+    fn build(&self, compiler: Compiler, target_dir: &Path) -> Result<PathBuf, Error> {
+        let mut cmd = isolation::sandboxed_command(compiler.cargo);
+        cmd.env_clear()
+            .env("PATH", env::var_os("PATH").unwrap_or_default())
+            .env("RUSTC", compiler.rustc)
+            .env("CARGO_TARGET_DIR", target_dir)
+            .current_dir(&self.path)
+            .arg("build")
+            .arg("--release");
+        if !compiler.backend.is_llvm() {
+            cmd.env("RUSTFLAGS", format!("-Zcodegen-backend={}", compiler.backend.name()));
+        }
+        command_output(&mut cmd)
+            .with_context(|_| format!("failed to build runtime benchmark {}", self.name))?;
+
+        Ok(target_dir.join("release").join(format!("{}{}", self.name, env::consts::EXE_SUFFIX)))
+    }
+
+    /// Compiles the benchmark with `compiler`, then runs it `iterations`
+    /// times under `perf stat`. As with the compile-time benchmarks, the
+    /// minimum of each stat across the iterations is recorded.
+    pub fn measure(&self, compiler: Compiler, iterations: usize)
+                   -> Result<CollectedRuntimeBenchmark, Error> {
+        info!("Running runtime benchmark {}", self.name);
+
+        let target_dir = TempDir::new()?;
+        let binary = self.build(compiler, target_dir.path())?;
+
+        let mut stats: HashMap<String, f64> = HashMap::new();
+        for i in 0..iterations {
+            debug!("Runtime benchmark iteration {}/{}", i + 1, iterations);
+
+            // The benchmarks report their results on stderr, so stdout only
+            // contains the output of `perf stat`.
+            let mut cmd = isolation::sandboxed_command("perf");
+            cmd.arg("stat")
+                .arg("-x;")
+                .arg("-e")
+                .arg("instructions:u,cycles:u,branch-misses:u,cache-misses:u,task-clock")
+                .arg("--log-fd")
+                .arg("1")
+                .arg(&binary);
+
+            let start = Instant::now();
+            let output = command_output(&mut cmd)?;
+            let dur = start.elapsed();
+
+            let mut run_stats = process_perf_stat_output(output)
+                .map_err(|e| format_err!("failed to parse perf output of {}: {}", self.name, e))?;
+            run_stats.push(Stat {
+                name: String::from("wall-time"),
+                cnt: dur.as_secs() as f64 + dur.subsec_nanos() as f64 / 1e9,
+            });
+
+            for stat in run_stats {
+                let entry = stats.entry(stat.name).or_insert(f64::INFINITY);
+                *entry = entry.min(stat.cnt);
+            }
+        }
+
+        let mut stats = stats.into_iter()
+            .map(|(name, cnt)| Stat { name, cnt })
+            .collect::<Vec<_>>();
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(CollectedRuntimeBenchmark {
+            name: self.name.clone(),
+            stats,
+        })
+    }
+}
+
+pub fn get_runtime_benchmarks(
+    benchmark_dir: &Path,
+    filter: Option<&str>,
+) -> Result<Vec<RuntimeBenchmark>, Error> {
+    let mut benchmarks = Vec::new();
+    for entry in fs::read_dir(benchmark_dir).context("failed to list runtime benchmarks")? {
+        let entry = entry?;
+        let name = match entry.file_name().into_string() {
+            Ok(s) => s,
+            Err(e) => bail!("non-utf8 benchmark name: {:?}", e),
+        };
+
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        if let Some(filter) = filter {
+            if !name.contains(filter) {
+                debug!("runtime benchmark {} - doesn't match --filter argument, skipping", name);
+                continue;
+            }
+        }
+
+        debug!("runtime benchmark `{}`- registered", name);
+        benchmarks.push(RuntimeBenchmark { name, path: entry.path() });
+    }
+    benchmarks.sort_by_key(|benchmark| benchmark.name.clone());
+    Ok(benchmarks)
+}
@@ -0,0 +1,165 @@
+//! Selects the benchmarks worth running for a change to the compiler, from a
+//! sensitivity matrix: which benchmarks historically moved when which parts
+//! (modules) of the compiler changed. It's learned from the results in the
+//! output repo, and the changes between the commits they're of.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::Command;
+use std::str;
+
+use failure::{Error, ResultExt};
+use serde_json;
+
+use collector::{CommitData, PRIMARY_TRIPLE};
+use execute::command_output;
+
+/// How much a stat has to change, in percent, for a benchmark to count as
+/// moved, unless told otherwise.
+pub const DEFAULT_THRESHOLD: f64 = 1.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sensitivity {
+    /// The stat whose changes were looked at, and how much it had to change.
+    pub stat: String,
+    pub threshold: f64,
+    /// How many pairs of consecutive commits the matrix was learned from.
+    pub commits: usize,
+    /// The benchmarks the matrix knows about. Any other benchmark is always
+    /// selected, as there's no telling what it's sensitive to.
+    pub benchmarks: BTreeSet<String>,
+    /// For every module that changed in one of the commits, the benchmarks
+    /// that moved in (at least) one of them.
+    pub modules: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// The module of the compiler the file at `path` (relative to the root of
+/// the rust repository) is in: the first two components of the path, e.g.
+/// `src/librustc_typeck` or `compiler/rustc_middle`.
+pub fn module_of(path: &str) -> String {
+    path.trim_start_matches("./").split('/').take(2).collect::<Vec<_>>().join("/")
+}
+
+/// The files that changed between the commits `from` and `to` of the rust
+/// checkout at `rust_src`.
+fn changed_files(rust_src: &Path, from: &str, to: &str) -> Result<Vec<String>, Error> {
+    let output = command_output(Command::new("git")
+        .current_dir(rust_src)
+        .arg("diff")
+        .arg("--name-only")
+        .arg(format!("{}..{}", from, to)))?;
+    Ok(str::from_utf8(&output.stdout)?.lines().map(String::from).collect())
+}
+
+/// The benchmarks whose `stat` changed by more than `threshold` percent in
+/// any run from `a` to `b`.
+fn moved(a: &CommitData, b: &CommitData, stat: &str, threshold: f64) -> BTreeSet<String> {
+    let mut moved = BTreeSet::new();
+    for (name, result) in &a.benchmarks {
+        let (a, b) = match (result, b.benchmarks.get(name)) {
+            (&Ok(ref a), Some(&Ok(ref b))) => (a, b),
+            _ => continue,
+        };
+        let changed = a.runs.iter().any(|run| {
+            let a_stat = run.get_stat(stat);
+            let b_stat = b.runs.iter().find(|r| *r == run).and_then(|r| r.get_stat(stat));
+            match (a_stat, b_stat) {
+                (Some(a_stat), Some(b_stat)) if a_stat > 0.0 => {
+                    ((b_stat - a_stat) / a_stat * 100.0).abs() > threshold
+                }
+                _ => false,
+            }
+        });
+        if changed {
+            moved.insert(name.clone());
+        }
+    }
+    moved
+}
+
+/// Learns the sensitivity matrix from `history` (oldest first): the changes
+/// of every pair of consecutive master commits are looked up in the rust
+/// checkout at `rust_src`, and every module they touched is associated with
+/// the benchmarks that moved. Only the plain builds collected on the primary
+/// triple are looked at, as the results of try commits, other flavors and
+/// other machines would move for reasons other than the changes in between.
+/// Pairs whose changes can't be looked up (e.g. because the checkout is older
+/// than the commits) are skipped.
+pub fn learn(history: &[CommitData], rust_src: &Path, stat: &str, threshold: f64)
+             -> Result<Sensitivity, Error> {
+    let history = history.iter()
+        .filter(|d| d.flavor.is_plain() && d.triple == PRIMARY_TRIPLE && !d.is_try())
+        .collect::<Vec<_>>();
+    let mut sensitivity = Sensitivity {
+        stat: stat.to_string(),
+        threshold,
+        commits: 0,
+        benchmarks: BTreeSet::new(),
+        modules: BTreeMap::new(),
+    };
+    for pair in history.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let files = match changed_files(rust_src, &a.commit.sha, &b.commit.sha) {
+            Ok(files) => files,
+            Err(e) => {
+                warn!("skipping {}..{}: {}", a.commit.sha, b.commit.sha, e);
+                continue;
+            }
+        };
+        let moved = moved(a, b, stat, threshold);
+        for file in &files {
+            sensitivity.modules.entry(module_of(file))
+                .or_insert_with(BTreeSet::new)
+                .extend(moved.iter().cloned());
+        }
+        sensitivity.benchmarks.extend(
+            b.benchmarks.iter().filter(|&(_, r)| r.is_ok()).map(|(name, _)| name.clone()));
+        sensitivity.commits += 1;
+    }
+    if sensitivity.commits == 0 {
+        bail!("no pairs of consecutive commits to learn from");
+    }
+    Ok(sensitivity)
+}
+
+impl Sensitivity {
+    /// Whether the benchmark `name` is worth running for a change to
+    /// `changed_files`: if it moved when any of their modules changed, if
+    /// any of the modules never changed before, or if the matrix doesn't
+    /// know about the benchmark. Without any changed files, there's nothing
+    /// to go by, so every benchmark is.
+    pub fn selects(&self, name: &str, changed_files: &[String]) -> bool {
+        if changed_files.is_empty() || !self.benchmarks.contains(name) {
+            return true;
+        }
+        changed_files.iter().any(|file| {
+            match self.modules.get(&module_of(file)) {
+                Some(benchmarks) => benchmarks.contains(name),
+                None => true,
+            }
+        })
+    }
+}
+
+pub fn load(path: &Path) -> Result<Sensitivity, Error> {
+    let file = File::open(path)
+        .with_context(|_| format!("failed to open the sensitivity matrix {:?}", path))?;
+    let sensitivity = serde_json::from_reader(file)
+        .with_context(|_| format!("failed to read the sensitivity matrix {:?}", path))?;
+    Ok(sensitivity)
+}
+
+pub fn save(sensitivity: &Sensitivity, path: &Path) -> Result<(), Error> {
+    fs::write(path, serde_json::to_string_pretty(sensitivity)?)
+        .with_context(|_| format!("failed to write the sensitivity matrix {:?}", path))?;
+    Ok(())
+}
+
+/// The files listed in `path`, one per line, as `git diff --name-only`
+/// prints them.
+pub fn load_changed_files(path: &Path) -> Result<Vec<String>, Error> {
+    let files = fs::read_to_string(path)
+        .with_context(|_| format!("failed to read the changed files {:?}", path))?;
+    Ok(files.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).map(String::from).collect())
+}
@@ -0,0 +1,80 @@
+//! Graceful shutdown on SIGINT and SIGTERM. Once either is received, no new
+//! rustc is started: the builds in progress are finished, the results of the
+//! benchmarks that are done are saved, marked as those of an interrupted
+//! collection, and the collector exits with `EXIT_CODE`. The next collection
+//! of the same commit resumes where this one left off. A second signal kills
+//! the collector right away.
+
+#[cfg(unix)]
+extern crate libc;
+
+use std::cmp;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The exit code of an interrupted collector, as a shell reports a process
+/// killed by SIGINT.
+pub const EXIT_CODE: i32 = 130;
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Fail, Debug)]
+#[fail(display = "interrupted, the results so far are saved")]
+pub struct Interrupted;
+
+#[cfg(unix)]
+extern "C" fn handle(signal: libc::c_int) {
+    const MESSAGE: &str = "shutting down after the current builds, signal again to abort\n";
+    if REQUESTED.swap(true, Ordering::SeqCst) {
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+        }
+    } else {
+        // Logging isn't safe in a signal handler, `write` is.
+        unsafe {
+            libc::write(libc::STDERR_FILENO, MESSAGE.as_ptr() as *const libc::c_void,
+                        MESSAGE.len());
+        }
+    }
+}
+
+/// Handles SIGINT and SIGTERM from now on.
+#[cfg(unix)]
+pub fn install() {
+    unsafe {
+        libc::signal(libc::SIGINT, handle as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, handle as libc::sighandler_t);
+    }
+}
+
+/// On Windows, Ctrl-C still kills the collector right away.
+#[cfg(windows)]
+pub fn install() {}
+
+/// Whether the collector was asked to shut down.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Fails with `Interrupted` if the collector was asked to shut down.
+pub fn check() -> Result<(), Interrupted> {
+    if requested() {
+        Err(Interrupted)
+    } else {
+        Ok(())
+    }
+}
+
+/// Sleeps for `duration`, or until the collector is asked to shut down.
+pub fn sleep(duration: Duration) {
+    let start = Instant::now();
+    while !requested() {
+        let elapsed = start.elapsed();
+        if elapsed >= duration {
+            break;
+        }
+        thread::sleep(cmp::min(duration - elapsed, Duration::from_secs(1)));
+    }
+}
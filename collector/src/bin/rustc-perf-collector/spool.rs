@@ -0,0 +1,110 @@
+//! A durable queue of the results that couldn't be uploaded to the site or
+//! stored in the database, e.g. because the network was down or the database
+//! was restarting, so that a collection isn't lost to it. The results are
+//! queued in files of a directory of the output directory, and sent, oldest
+//! first, before the next results are (or by `flush_spool`). Sending results
+//! again is harmless, so a crash between sending results and removing their
+//! file only means that they're sent twice.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use failure::{Error, ResultExt};
+use serde_json;
+
+use collector::api::upload;
+use collector::stable_hash;
+use progress::write_atomically;
+
+pub struct Spool {
+    dir: PathBuf,
+}
+
+/// The queue of the results to upload to the site, in the output directory
+/// `out_dir`.
+pub fn uploads(out_dir: &Path) -> Spool {
+    Spool::open(&out_dir.join("spool").join("upload"))
+}
+
+/// The queue of the results to store in a database, in the output directory
+/// `out_dir`.
+pub fn stores(out_dir: &Path) -> Spool {
+    Spool::open(&out_dir.join("spool").join("db"))
+}
+
+impl Spool {
+    fn open(dir: &Path) -> Spool {
+        Spool { dir: dir.to_path_buf() }
+    }
+
+    /// Queues `data`.
+    pub fn push(&self, data: &upload::Data) -> Result<(), Error> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|_| format!("failed to create {:?}", self.dir))?;
+        let json = serde_json::to_vec(data)?;
+        // Named so that they sort in the order they were queued.
+        let name = format!("{}-{:016x}.json", Utc::now().format("%Y%m%d%H%M%S%f"),
+                           stable_hash(&json));
+        write_atomically(&self.dir.join(name), &json)
+    }
+
+    /// The files of the queued results, oldest first.
+    fn pending(&self) -> Result<Vec<PathBuf>, Error> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().map_or(false, |e| e == "json") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+
+    pub fn len(&self) -> Result<usize, Error> {
+        Ok(self.pending()?.len())
+    }
+
+    /// Sends the queued results with `send`, oldest first, until one fails.
+    /// Returns how many were sent.
+    pub fn replay<F>(&self, mut send: F) -> Result<usize, Error>
+        where F: FnMut(&upload::Data) -> Result<(), Error>
+    {
+        let mut sent = 0;
+        for path in self.pending()? {
+            let data = match serde_json::from_slice(&fs::read(&path)?) {
+                Ok(data) => data,
+                Err(e) => {
+                    // Set aside, rather than keeping every later result from
+                    // being sent.
+                    warn!("can't read the queued results {:?}, ignoring them: {}", path, e);
+                    fs::rename(&path, path.with_extension("json.invalid"))?;
+                    continue;
+                }
+            };
+            send(&data)?;
+            fs::remove_file(&path)?;
+            sent += 1;
+        }
+        if sent > 0 {
+            info!("sent {} queued results", sent);
+        }
+        Ok(sent)
+    }
+
+    /// Sends the queued results and then `data` with `send`. If that fails,
+    /// `data` is queued, and the error logged rather than returned.
+    pub fn send<F>(&self, data: &upload::Data, mut send: F) -> Result<(), Error>
+        where F: FnMut(&upload::Data) -> Result<(), Error>
+    {
+        if let Err(e) = self.replay(&mut send).and_then(|_| send(data)) {
+            self.push(data)?;
+            warn!("queued the results in {:?}, to be sent later: {:?}", self.dir, e);
+        }
+        Ok(())
+    }
+}
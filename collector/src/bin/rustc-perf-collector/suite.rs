@@ -0,0 +1,199 @@
+//! The benchmark suite manifest, which pins the exact contents of every
+//! benchmark (its files, its dependencies and its patches) under a suite
+//! version. The version is recorded with the results, so that results are
+//! only compared with results from the same benchmarks.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use failure::{Error, ResultExt};
+use serde_json;
+
+use execute::Benchmark;
+
+/// Where the manifest lives, relative to the root of the repository.
+pub const MANIFEST_PATH: &str = "collector/benchmarks/manifest.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Bumped whenever any of the benchmarks changes.
+    pub version: u32,
+    pub benchmarks: BTreeMap<String, LockedBenchmark>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedBenchmark {
+    /// A hash of the names and contents of all files of the benchmark.
+    pub hash: String,
+    /// Every package in the benchmark's `Cargo.lock`, as `$NAME $VERSION`.
+    pub dependencies: Vec<String>,
+    /// The names of the benchmark's patch files.
+    pub patches: Vec<String>,
+}
+
+/// The 64-bit FNV-1a hash. It's trivial to reimplement, and unlike the
+/// hashers in `std` its output is guaranteed to never change.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x100_0000_01b3);
+        }
+    }
+}
+
+/// Collects the paths, relative to `root` and with `/` as the separator, of
+/// all files in `dir`. Symlinks and `target` directories are skipped.
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<(), Error> {
+    for entry in fs::read_dir(dir).with_context(|_| format!("failed to list {:?}", dir))? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            if entry.file_name() != "target" {
+                collect_files(root, &path, files)?;
+            }
+        } else if file_type.is_file() {
+            let relative = path.strip_prefix(root).unwrap()
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            files.push(relative.join("/"));
+        }
+    }
+    Ok(())
+}
+
+fn hash_files(root: &Path) -> Result<String, Error> {
+    let mut files = Vec::new();
+    collect_files(root, root, &mut files)?;
+    files.sort();
+
+    let mut hasher = Fnv::new();
+    for file in &files {
+        let path = root.join(file);
+        let contents = fs::read(&path).with_context(|_| format!("failed to read {:?}", path))?;
+        hasher.write(file.as_bytes());
+        hasher.write(&[0]);
+        hasher.write(&(contents.len() as u64).to_le_bytes());
+        hasher.write(&contents);
+    }
+    Ok(format!("{:016x}", hasher.0))
+}
+
+/// The packages in the `Cargo.lock` at `path`, if there is one.
+fn locked_dependencies(path: &Path) -> Result<Vec<String>, Error> {
+    let lockfile = match fs::read_to_string(path) {
+        Ok(lockfile) => lockfile,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    fn value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+        let rest = line.trim_start_matches(key).trim_start();
+        if line.starts_with(key) && rest.starts_with('=') {
+            Some(rest[1..].trim().trim_matches('"'))
+        } else {
+            None
+        }
+    }
+
+    let mut dependencies = Vec::new();
+    let mut in_package = false;
+    let (mut name, mut version) = (None, None);
+    for line in lockfile.lines().map(str::trim).chain(Some("[end]")) {
+        if line.starts_with('[') {
+            if let (Some(name), Some(version)) = (name.take(), version.take()) {
+                dependencies.push(format!("{} {}", name, version));
+            }
+            in_package = line == "[[package]]";
+        } else if in_package {
+            if let Some(v) = value(line, "name") {
+                name = Some(v);
+            } else if let Some(v) = value(line, "version") {
+                version = Some(v);
+            }
+        }
+    }
+    dependencies.sort();
+    Ok(dependencies)
+}
+
+/// Describes the current contents of `benchmark`.
+pub fn lock(benchmark: &Benchmark) -> Result<LockedBenchmark, Error> {
+    let mut patches = Vec::new();
+    for entry in fs::read_dir(&benchmark.path)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name.ends_with(".patch") {
+            patches.push(name);
+        }
+    }
+    patches.sort();
+
+    Ok(LockedBenchmark {
+        hash: hash_files(&benchmark.path)?,
+        dependencies: locked_dependencies(&benchmark.path.join("Cargo.lock"))?,
+        patches,
+    })
+}
+
+pub fn load_manifest() -> Result<Manifest, Error> {
+    let manifest = fs::read_to_string(MANIFEST_PATH)
+        .with_context(|_| format!("failed to read {}", MANIFEST_PATH))?;
+    Ok(serde_json::from_str(&manifest)
+        .with_context(|_| format!("failed to parse {}", MANIFEST_PATH))?)
+}
+
+/// The suite version of `benchmarks`, or `None` if any of them doesn't match
+/// the manifest, e.g. because it was changed locally.
+pub fn version(benchmarks: &[Benchmark]) -> Option<u32> {
+    let manifest = match load_manifest() {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            warn!("no suite version, as the manifest couldn't be loaded: {}", e);
+            return None;
+        }
+    };
+    for benchmark in benchmarks {
+        let locked = match lock(benchmark) {
+            Ok(locked) => locked,
+            Err(e) => {
+                warn!("no suite version, as {} couldn't be checked: {}", benchmark.name, e);
+                return None;
+            }
+        };
+        if manifest.benchmarks.get(&benchmark.name) != Some(&locked) {
+            warn!("no suite version, as {} doesn't match {}; run `update_manifest` \
+                   if the change is intended", benchmark.name, MANIFEST_PATH);
+            return None;
+        }
+    }
+    Some(manifest.version)
+}
+
+/// Writes a manifest describing `benchmarks`, which must be all of them. The
+/// version is bumped if anything changed since the last manifest. Returns the
+/// new manifest.
+pub fn update_manifest(benchmarks: &[Benchmark]) -> Result<Manifest, Error> {
+    let mut locked = BTreeMap::new();
+    for benchmark in benchmarks {
+        locked.insert(benchmark.name.clone(), lock(benchmark)?);
+    }
+
+    let manifest = match load_manifest() {
+        Ok(ref old) if old.benchmarks == locked => return Ok(old.clone()),
+        Ok(old) => Manifest { version: old.version + 1, benchmarks: locked },
+        Err(_) => Manifest { version: 1, benchmarks: locked },
+    };
+    let mut json = serde_json::to_string_pretty(&manifest)?;
+    json.push('\n');
+    fs::write(MANIFEST_PATH, json)
+        .with_context(|_| format!("failed to write {}", MANIFEST_PATH))?;
+    Ok(manifest)
+}
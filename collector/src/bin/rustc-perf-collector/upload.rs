@@ -0,0 +1,110 @@
+//! Uploads results to the site over HTTP(S), so that collectors don't need
+//! write access to the output repository, or even a clone of it that's kept
+//! in sync.
+
+use std::cmp;
+use std::env;
+use std::thread;
+use std::time::Duration;
+
+use collector::api::upload;
+use collector::stable_hash;
+use failure::{Error, ResultExt};
+use reqwest;
+use serde_json;
+
+/// The most JSON that is sent in one request. The site refuses requests over
+/// 25 kB, and the JSON grows when it's escaped into the request.
+const CHUNK_SIZE: usize = 10_000;
+
+/// How many times the missing chunks are sent before giving up.
+const ATTEMPTS: u32 = 5;
+
+pub struct Uploader {
+    site_url: String,
+    token: String,
+    client: reqwest::Client,
+}
+
+impl Uploader {
+    /// An uploader to the site at `$SITE_URL`, which identifies the collector
+    /// by its token, `$PERF_UPLOAD_TOKEN`.
+    pub fn from_env() -> Result<Uploader, Error> {
+        Ok(Uploader {
+            site_url: env::var("SITE_URL").context("SITE_URL is needed to upload results")?,
+            token: env::var("PERF_UPLOAD_TOKEN")
+                .context("PERF_UPLOAD_TOKEN is needed to upload results")?,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// Uploads `data` in chunks. The id of the upload is derived from the
+    /// data, so uploading the same results again, e.g. after a failure that
+    /// went unnoticed by the site, is harmless.
+    pub fn upload(&self, data: &upload::Data) -> Result<(), Error> {
+        let json = serde_json::to_string(data)?;
+        let id = format!("{:016x}", stable_hash(json.as_bytes()));
+        let chunks = split(&json);
+
+        let mut todo = (0..chunks.len()).collect::<Vec<_>>();
+        for attempt in 1..ATTEMPTS + 1 {
+            let mut missing = Vec::new();
+            for index in todo {
+                let request = upload::Request {
+                    id: id.clone(),
+                    index,
+                    count: chunks.len(),
+                    data: chunks[index].to_string(),
+                };
+                match self.send(&request) {
+                    // The site knows best which chunks it lacks, including
+                    // those that failed earlier.
+                    Ok(response) => {
+                        if response.missing.is_empty() {
+                            return Ok(());
+                        }
+                        missing = response.missing;
+                    }
+                    Err(e) => {
+                        warn!("failed to upload chunk {} of {}: {:?}", index + 1, chunks.len(), e);
+                        if !missing.contains(&index) {
+                            missing.push(index);
+                        }
+                    }
+                }
+            }
+            todo = missing;
+            info!("[{}/{}]: {} chunks left to upload", attempt, ATTEMPTS, todo.len());
+            if attempt < ATTEMPTS {
+                thread::sleep(Duration::from_secs(10));
+            }
+        }
+        bail!("failed to upload the results, {} chunks are missing", todo.len())
+    }
+
+    fn send(&self, request: &upload::Request) -> Result<upload::Response, Error> {
+        let mut resp = self.client.post(&format!("{}/perf/upload", self.site_url))
+            .bearer_auth(&self.token)
+            .json(request)
+            .send()?;
+        if !resp.status().is_success() {
+            bail!("upload failed: {:?}", resp);
+        }
+        Ok(resp.json()?)
+    }
+}
+
+/// Splits `s` into chunks of at most `CHUNK_SIZE` bytes.
+fn split(s: &str) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = s;
+    while !rest.is_empty() {
+        let mut end = cmp::min(CHUNK_SIZE, rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        chunks.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    chunks
+}
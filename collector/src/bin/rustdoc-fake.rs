@@ -0,0 +1,34 @@
+use std::env;
+use std::process::Command;
+
+// Cargo has no way to wrap rustdoc for a single crate the way `cargo rustc`
+// does for rustc, so `RUSTDOC` points here instead. All the actual work is
+// done by rustc-fake, which is told to run the real rustdoc rather than rustc.
+fn main() {
+    let args = env::args_os().skip(1).collect::<Vec<_>>();
+    let rustdoc = env::var_os("RUSTDOC_REAL").unwrap();
+
+    let mut fake_rustc = env::current_exe().unwrap();
+    fake_rustc.pop();
+    fake_rustc.push(format!("rustc-fake{}", env::consts::EXE_SUFFIX));
+
+    let mut cmd = Command::new(fake_rustc);
+    cmd.env("RUSTC_REAL", rustdoc)
+        .args(&args);
+    exec(&mut cmd);
+}
+
+#[cfg(unix)]
+fn exec(cmd: &mut Command) -> ! {
+    use std::os::unix::prelude::*;
+    let error = cmd.exec();
+    panic!("failed to exec: {}", error);
+}
+
+#[cfg(windows)]
+fn exec(cmd: &mut Command) -> ! {
+    // Windows can't replace the current process, so rustdoc-fake waits for
+    // rustc-fake and exits the same way.
+    let status = cmd.status().expect("failed to spawn");
+    ::std::process::exit(status.code().unwrap_or(1));
+}
@@ -0,0 +1,44 @@
+-- The tables of the results, see `db/mod.rs`. `IF NOT EXISTS` adopts the
+-- databases created before there were migrations.
+CREATE TABLE IF NOT EXISTS artifact (
+    id SERIAL PRIMARY KEY,
+    kind TEXT NOT NULL,
+    name TEXT NOT NULL,
+    date TIMESTAMP WITH TIME ZONE,
+    triple TEXT NOT NULL,
+    flavor TEXT NOT NULL,
+    shard TEXT NOT NULL,
+    data JSONB NOT NULL,
+    UNIQUE (kind, name, triple, flavor, shard)
+);
+CREATE INDEX IF NOT EXISTS artifact_date ON artifact (date);
+CREATE TABLE IF NOT EXISTS benchmark (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+CREATE TABLE IF NOT EXISTS metric (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+CREATE TABLE IF NOT EXISTS run (
+    id SERIAL PRIMARY KEY,
+    artifact INTEGER NOT NULL REFERENCES artifact (id) ON DELETE CASCADE,
+    benchmark INTEGER NOT NULL REFERENCES benchmark (id),
+    category TEXT NOT NULL,
+    data JSONB NOT NULL
+);
+CREATE INDEX IF NOT EXISTS run_artifact ON run (artifact, benchmark);
+CREATE INDEX IF NOT EXISTS run_benchmark ON run (benchmark);
+CREATE TABLE IF NOT EXISTS value (
+    run INTEGER NOT NULL REFERENCES run (id) ON DELETE CASCADE,
+    metric INTEGER NOT NULL REFERENCES metric (id),
+    value DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (run, metric)
+);
+CREATE INDEX IF NOT EXISTS value_metric ON value (metric);
+CREATE TABLE IF NOT EXISTS error (
+    artifact INTEGER NOT NULL REFERENCES artifact (id) ON DELETE CASCADE,
+    benchmark INTEGER NOT NULL REFERENCES benchmark (id),
+    message TEXT NOT NULL,
+    PRIMARY KEY (artifact, benchmark)
+);
@@ -0,0 +1,25 @@
+-- What each commit or release is, see `ArtifactMetadata`, so that it can be
+-- queried without digging into the JSON of the artifact. The artifacts stored
+-- before there was metadata get what can be told from them: a try commit has
+-- the placeholder date of 2000-01-01, and a release its date.
+CREATE TABLE artifact_metadata (
+    artifact INTEGER PRIMARY KEY REFERENCES artifact (id) ON DELETE CASCADE,
+    type TEXT NOT NULL,
+    date TIMESTAMP WITH TIME ZONE,
+    pr INTEGER,
+    parent_sha TEXT
+);
+CREATE INDEX artifact_metadata_pr ON artifact_metadata (pr);
+INSERT INTO artifact_metadata (artifact, type, date, pr, parent_sha)
+SELECT id,
+       COALESCE(data->'metadata'->>'type',
+                CASE WHEN date = '2000-01-01T00:00:00Z' THEN 'try' ELSE 'master' END),
+       COALESCE((data->'metadata'->>'date')::TIMESTAMP WITH TIME ZONE,
+                CASE WHEN date = '2000-01-01T00:00:00Z' THEN NULL ELSE date END),
+       (data->'metadata'->>'pr')::INTEGER,
+       data->'metadata'->>'parent_sha'
+FROM artifact WHERE kind = 'commit';
+INSERT INTO artifact_metadata (artifact, type, date)
+SELECT id, 'release', (data->'release'->>'date')::DATE
+FROM artifact WHERE kind = 'artifact' AND data->'release' IS NOT NULL
+    AND data->'release' <> 'null'::JSONB;
@@ -0,0 +1,14 @@
+-- The value of each metric in every iteration of a run, and the summaries of
+-- those samples next to the minimum. The runs stored before there were
+-- samples have none, and no summaries.
+CREATE TABLE sample (
+    run INTEGER NOT NULL REFERENCES run (id) ON DELETE CASCADE,
+    metric INTEGER NOT NULL REFERENCES metric (id),
+    iteration INTEGER NOT NULL,
+    value DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (run, metric, iteration)
+);
+ALTER TABLE value
+    ADD COLUMN median DOUBLE PRECISION,
+    ADD COLUMN mad DOUBLE PRECISION,
+    ADD COLUMN stddev DOUBLE PRECISION;
@@ -0,0 +1,13 @@
+-- A counter of the changes to the artifacts, for loading the ones stored
+-- since the last load: `id`s can be committed out of order, by concurrent
+-- stores, so they can't tell that. Each store takes the next value, whose
+-- row lock makes the stores commit in the counter's order, and stamps its
+-- artifacts with it; a snapshot that sees the counter at some value sees all
+-- the artifacts stamped with it or less, and none stamped with more.
+CREATE TABLE change_seq (
+    seq BIGINT NOT NULL
+);
+INSERT INTO change_seq (seq) SELECT COALESCE(MAX(id), 0) FROM artifact;
+ALTER TABLE artifact ADD COLUMN seq BIGINT NOT NULL DEFAULT 0;
+UPDATE artifact SET seq = id;
+CREATE INDEX artifact_seq ON artifact (seq);
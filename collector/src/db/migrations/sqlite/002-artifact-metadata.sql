@@ -0,0 +1,21 @@
+-- What each commit or release is, see the Postgres migration.
+CREATE TABLE artifact_metadata (
+    artifact INTEGER PRIMARY KEY REFERENCES artifact (id) ON DELETE CASCADE,
+    type TEXT NOT NULL,
+    date TEXT,
+    pr INTEGER,
+    parent_sha TEXT
+);
+CREATE INDEX artifact_metadata_pr ON artifact_metadata (pr);
+INSERT INTO artifact_metadata (artifact, type, date, pr, parent_sha)
+SELECT id,
+       COALESCE(json_extract(data, '$.metadata.type'),
+                CASE WHEN date LIKE '2000-01-01T%' THEN 'try' ELSE 'master' END),
+       COALESCE(json_extract(data, '$.metadata.date'),
+                CASE WHEN date LIKE '2000-01-01T%' THEN NULL ELSE date END),
+       json_extract(data, '$.metadata.pr'),
+       json_extract(data, '$.metadata.parent_sha')
+FROM artifact WHERE kind = 'commit';
+INSERT INTO artifact_metadata (artifact, type, date)
+SELECT id, 'release', json_extract(data, '$.release.date') || 'T00:00:00+00:00'
+FROM artifact WHERE kind = 'artifact' AND json_extract(data, '$.release') IS NOT NULL;
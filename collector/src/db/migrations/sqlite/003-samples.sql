@@ -0,0 +1,11 @@
+-- The samples of the metrics of each run, see the Postgres migration.
+CREATE TABLE sample (
+    run INTEGER NOT NULL REFERENCES run (id) ON DELETE CASCADE,
+    metric INTEGER NOT NULL REFERENCES metric (id),
+    iteration INTEGER NOT NULL,
+    value REAL NOT NULL,
+    PRIMARY KEY (run, metric, iteration)
+);
+ALTER TABLE value ADD COLUMN median REAL;
+ALTER TABLE value ADD COLUMN mad REAL;
+ALTER TABLE value ADD COLUMN stddev REAL;
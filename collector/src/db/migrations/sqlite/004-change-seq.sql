@@ -0,0 +1,10 @@
+-- The counter of the changes to the artifacts, see the Postgres migration.
+-- A replaced artifact's `id` may be reused for its replacement, as `id`s
+-- are only one more than the largest at the time.
+CREATE TABLE change_seq (
+    seq INTEGER NOT NULL
+);
+INSERT INTO change_seq (seq) SELECT COALESCE(MAX(id), 0) FROM artifact;
+ALTER TABLE artifact ADD COLUMN seq INTEGER NOT NULL DEFAULT 0;
+UPDATE artifact SET seq = id;
+CREATE INDEX artifact_seq ON artifact (seq);
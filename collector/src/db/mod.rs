@@ -0,0 +1,403 @@
+//! Databases the results can be kept in, instead of the data repo: Postgres
+//! for the site of the benchmark server, so that several collectors can
+//! upload at once without racing each other's git pushes, and SQLite for
+//! local use, which needs no server at all.
+//!
+//! The results are normalized into tables of artifacts (the commits,
+//! artifacts and runtime benchmark results, with everything about them but
+//! their benchmarks' results), the artifacts' metadata, benchmarks, metrics
+//! (the stats), runs (of a benchmark for an artifact) and the values of the
+//! metrics of each run, with the summaries of their samples, and the samples
+//! themselves (the value in each iteration), plus the errors of the
+//! benchmarks that failed. The conversion between those rows and the results
+//! is shared by all backends, so that they load the same results that were
+//! stored in either.
+//!
+//! The tables are created, and changed, by migrations: SQL scripts in
+//! `migrations/$BACKEND`, which are applied in order when a database is
+//! opened, each in a transaction of its own, up to the last one; a database
+//! that's opened read-only isn't migrated, and must be up to date already.
+//! The `schema_version` table records how many have been applied. A change to
+//! the tables is a new migration for each backend, which converts the
+//! existing data; the earlier migrations are never changed.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use failure::Error;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+use {ArtifactData, ArtifactMetadata, Benchmark, Category, CommitData, RuntimeData, Stat,
+     Summary};
+use api::upload;
+
+pub mod postgres;
+pub mod sqlite;
+
+/// The kinds of artifacts.
+pub const COMMIT: &str = "commit";
+pub const ARTIFACT: &str = "artifact";
+pub const RUNTIME: &str = "runtime";
+
+/// The results as they're stored, before the results of the shards of a
+/// commit are merged.
+#[derive(Debug, Default, Clone)]
+pub struct Results {
+    pub commits: Vec<CommitData>,
+    pub artifacts: Vec<ArtifactData>,
+    pub runtime: Vec<RuntimeData>,
+}
+
+impl Results {
+    pub fn is_empty(&self) -> bool {
+        self.commits.is_empty() && self.artifacts.is_empty() && self.runtime.is_empty()
+    }
+}
+
+impl From<upload::Data> for Results {
+    fn from(data: upload::Data) -> Results {
+        let mut results = Results::default();
+        match data {
+            upload::Data::Commit(data) => results.commits.push(data),
+            upload::Data::Artifact(data) => results.artifacts.push(data),
+        }
+        results
+    }
+}
+
+/// A connection to a database of results.
+pub trait Connection: Send + fmt::Debug {
+    /// All the results in the database.
+    fn load(&mut self) -> Result<Results, Error> {
+        Ok(self.load_after(0)?.0)
+    }
+
+    /// The results of the artifacts stored since the change `after`, with
+    /// the last change, for picking up the results stored since the last
+    /// load. Each store is a change, counted by the database in the order
+    /// they're committed, so a replaced artifact's new results are among
+    /// these; a deleted one isn't noticed.
+    fn load_after(&mut self, after: i64) -> Result<(Results, i64), Error>;
+
+    /// Stores `results`, all or nothing, replacing the results of the same
+    /// commits (and shards, flavors and triples), artifacts and runtime
+    /// benchmarks.
+    fn store(&mut self, results: &Results) -> Result<(), Error>;
+}
+
+/// The prefix of the URLs of SQLite databases, which is followed by the path
+/// of the database file, e.g. `sqlite://results.db`.
+const SQLITE_PREFIX: &str = "sqlite://";
+
+/// The migration that brings a database of `backend` from schema `version`
+/// (the number of migrations applied to it so far) to the next, if any.
+pub fn next_migration(
+    backend: &str,
+    version: i64,
+    migrations: &[&'static str],
+) -> Result<Option<&'static str>, Error> {
+    if version as usize > migrations.len() {
+        bail!("the {} database has schema version {}, but only {} is known; \
+               the collector or site needs updating", backend, version, migrations.len());
+    }
+    let migration = migrations.get(version as usize).cloned();
+    if migration.is_some() {
+        info!("migrating the {} database to schema version {}", backend, version + 1);
+    }
+    Ok(migration)
+}
+
+/// Checks that a database of `backend` with schema `version`, which is read
+/// rather than migrated, has the current schema.
+pub fn check_version(backend: &str, version: i64, migrations: &[&'static str])
+                     -> Result<(), Error> {
+    if version as usize != migrations.len() {
+        bail!("the {} database has schema version {}, not {}; it's only read, so it must be \
+               migrated (or the collector or site updated) first",
+              backend, version, migrations.len());
+    }
+    Ok(())
+}
+
+/// Opens the database at `url`, migrating it to the current schema: a
+/// `postgres://` URL, or `sqlite://` followed by the path of a file.
+pub fn open(url: &str) -> Result<Box<Connection>, Error> {
+    if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        Ok(Box::new(postgres::Postgres::open(url)?))
+    } else if url.starts_with(SQLITE_PREFIX) {
+        Ok(Box::new(sqlite::Sqlite::open(Path::new(&url[SQLITE_PREFIX.len()..]))?))
+    } else {
+        bail!("unsupported database URL, it must start with postgres:// or sqlite://");
+    }
+}
+
+/// Opens the database at `url` like `open`, but only to read it, e.g. a
+/// replica, which is neither migrated nor written to.
+pub fn open_read_only(url: &str) -> Result<Box<Connection>, Error> {
+    if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        Ok(Box::new(postgres::Postgres::open_read_only(url)?))
+    } else if url.starts_with(SQLITE_PREFIX) {
+        let path = Path::new(&url[SQLITE_PREFIX.len()..]);
+        Ok(Box::new(sqlite::Sqlite::open_read_only(path)?))
+    } else {
+        bail!("unsupported database URL, it must start with postgres:// or sqlite://");
+    }
+}
+
+/// What identifies a stored artifact: storing another one with the same key
+/// replaces it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ArtifactKey {
+    pub kind: &'static str,
+    /// The sha of a commit, or the id of an artifact.
+    pub name: String,
+    pub triple: String,
+    pub flavor: String,
+    /// The collector that collected the shard of a commit, if it's one.
+    pub shard: String,
+}
+
+impl ArtifactKey {
+    pub fn of_commit(commit: &CommitData) -> ArtifactKey {
+        ArtifactKey {
+            kind: COMMIT,
+            name: commit.commit.sha.clone(),
+            triple: commit.triple.clone(),
+            flavor: commit.flavor.name().to_string(),
+            shard: commit.collection.as_ref()
+                .and_then(|c| c.shard.as_ref())
+                .map_or(String::new(), |shard| shard.collector.clone()),
+        }
+    }
+
+    pub fn of_artifact(artifact: &ArtifactData) -> ArtifactKey {
+        ArtifactKey {
+            kind: ARTIFACT,
+            name: artifact.id.clone(),
+            triple: String::new(),
+            flavor: String::new(),
+            shard: String::new(),
+        }
+    }
+
+    pub fn of_runtime(runtime: &RuntimeData) -> ArtifactKey {
+        ArtifactKey {
+            kind: RUNTIME,
+            name: runtime.id.clone(),
+            triple: String::new(),
+            flavor: String::new(),
+            shard: String::new(),
+        }
+    }
+
+    /// The keys of all of `results`.
+    pub fn all(results: &Results) -> Vec<ArtifactKey> {
+        results.commits.iter().map(ArtifactKey::of_commit)
+            .chain(results.artifacts.iter().map(ArtifactKey::of_artifact))
+            .chain(results.runtime.iter().map(ArtifactKey::of_runtime))
+            .collect()
+    }
+}
+
+/// An artifact as it's stored: what identifies it, and everything about it
+/// but the results of its benchmarks.
+pub struct Artifact {
+    pub kind: &'static str,
+    /// The sha of a commit, or the id of an artifact.
+    pub name: String,
+    pub date: Option<DateTime<Utc>>,
+    pub triple: String,
+    pub flavor: String,
+    /// The collector that collected the shard of a commit, if it's one.
+    pub shard: String,
+    pub data: Value,
+    /// Also in `data`, but kept in a table of its own to be queried.
+    pub metadata: Option<ArtifactMetadata>,
+}
+
+impl Artifact {
+    fn new(
+        key: ArtifactKey,
+        date: Option<DateTime<Utc>>,
+        data: Value,
+        metadata: Option<ArtifactMetadata>,
+    ) -> Artifact {
+        let ArtifactKey { kind, name, triple, flavor, shard } = key;
+        Artifact { kind, name, date, triple, flavor, shard, data, metadata }
+    }
+}
+
+/// A run of a benchmark, as it's stored.
+pub struct Run<'a> {
+    pub benchmark: &'a str,
+    pub category: &'static str,
+    /// Everything about the run but its stats and their samples.
+    pub data: Value,
+    pub stats: &'a [Stat],
+    pub samples: &'a BTreeMap<String, Vec<f64>>,
+}
+
+impl<'a> Run<'a> {
+    /// The summary of the samples of the stat `metric`, if there are any.
+    pub fn summary(&self, metric: &str) -> Option<Summary> {
+        Summary::of(self.samples.get(metric)?)
+    }
+}
+
+/// The rows an artifact is stored as. The results of the runtime benchmarks
+/// are stored whole with their artifact.
+pub struct Rows<'a> {
+    pub artifact: Artifact,
+    pub runs: Vec<Run<'a>>,
+    /// The benchmarks that failed, with their errors.
+    pub errors: Vec<(&'a str, &'a str)>,
+}
+
+/// Splits `results` into the rows they're stored as.
+pub fn rows(results: &Results) -> Result<Vec<Rows>, Error> {
+    let mut rows = Vec::new();
+    for commit in &results.commits {
+        let artifact = Artifact::new(
+            ArtifactKey::of_commit(commit),
+            Some(commit.commit.date.0),
+            without(commit, "benchmarks")?,
+            commit.metadata.clone(),
+        );
+        rows.push(benchmark_rows(artifact, &commit.benchmarks)?);
+    }
+    for artifact in &results.artifacts {
+        let stored = Artifact::new(
+            ArtifactKey::of_artifact(artifact),
+            None,
+            without(artifact, "benchmarks")?,
+            artifact.metadata(),
+        );
+        rows.push(benchmark_rows(stored, &artifact.benchmarks)?);
+    }
+    for runtime in &results.runtime {
+        let artifact = Artifact::new(
+            ArtifactKey::of_runtime(runtime),
+            Some(runtime.date.0),
+            serde_json::to_value(runtime)?,
+            None,
+        );
+        rows.push(Rows { artifact, runs: Vec::new(), errors: Vec::new() });
+    }
+    Ok(rows)
+}
+
+fn benchmark_rows<'a>(
+    artifact: Artifact,
+    benchmarks: &'a BTreeMap<String, Result<Benchmark, String>>,
+) -> Result<Rows<'a>, Error> {
+    let mut runs = Vec::new();
+    let mut errors = Vec::new();
+    for (name, benchmark) in benchmarks {
+        match *benchmark {
+            Ok(ref benchmark) => {
+                for run in &benchmark.runs {
+                    runs.push(Run {
+                        benchmark: name,
+                        category: benchmark.category.name(),
+                        data: without(without(run, "stats")?, "samples")?,
+                        stats: &run.stats,
+                        samples: &run.samples,
+                    });
+                }
+            }
+            Err(ref error) => errors.push((name.as_str(), error.as_str())),
+        }
+    }
+    Ok(Rows { artifact, runs, errors })
+}
+
+/// `value` as JSON, without its `field`.
+fn without<T: Serialize>(value: &T, field: &str) -> Result<Value, Error> {
+    let mut value = serde_json::to_value(value)?;
+    if let Value::Object(ref mut map) = value {
+        map.remove(field);
+    }
+    Ok(value)
+}
+
+/// `value` with its `field` set to `field_value`, as a `T`.
+fn with<T: DeserializeOwned>(mut value: Value, field: &str, field_value: Value)
+    -> Result<T, Error>
+{
+    if let Value::Object(ref mut map) = value {
+        map.insert(field.to_string(), field_value);
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Puts the results loaded from a database back together, from the rows of
+/// their artifacts, runs and errors, by the database's ids of the artifacts.
+#[derive(Default)]
+pub struct Assembly {
+    artifacts: BTreeMap<i64, (String, Value)>,
+    benchmarks: BTreeMap<i64, BTreeMap<String, Result<Benchmark, String>>>,
+}
+
+impl Assembly {
+    pub fn artifact(&mut self, id: i64, kind: String, data: Value) {
+        self.artifacts.insert(id, (kind, data));
+    }
+
+    pub fn run(
+        &mut self,
+        artifact: i64,
+        benchmark: String,
+        category: &str,
+        data: Value,
+        stats: Vec<Stat>,
+        samples: BTreeMap<String, Vec<f64>>,
+    ) -> Result<(), Error> {
+        let data = with::<Value>(data, "samples", serde_json::to_value(samples)?)?;
+        let run = with(data, "stats", serde_json::to_value(stats)?)?;
+        let category = match Category::from_name(category) {
+            Some(category) => category,
+            None => bail!("unknown category {:?} of {}", category, benchmark),
+        };
+        let entry = self.benchmarks.entry(artifact).or_insert_with(BTreeMap::new)
+            .entry(benchmark.clone())
+            .or_insert_with(|| Ok(Benchmark { runs: Vec::new(), name: benchmark, category }));
+        if let Ok(ref mut benchmark) = *entry {
+            benchmark.runs.push(run);
+        }
+        Ok(())
+    }
+
+    pub fn error(&mut self, artifact: i64, benchmark: String, message: String) {
+        self.benchmarks.entry(artifact).or_insert_with(BTreeMap::new)
+            .insert(benchmark, Err(message));
+    }
+
+    pub fn finish(mut self) -> Result<Results, Error> {
+        let mut results = Results::default();
+        for (id, (kind, data)) in self.artifacts {
+            let benchmarks = self.benchmarks.remove(&id).unwrap_or_default();
+            match kind.as_str() {
+                COMMIT => {
+                    let data: CommitData =
+                        with(data, "benchmarks", serde_json::to_value(benchmarks)?)?;
+                    results.commits.push(data);
+                }
+                ARTIFACT => {
+                    let data: ArtifactData =
+                        with(data, "benchmarks", serde_json::to_value(benchmarks)?)?;
+                    results.artifacts.push(data);
+                }
+                RUNTIME => {
+                    let data: RuntimeData = serde_json::from_value(data)?;
+                    results.runtime.push(data);
+                }
+                _ => bail!("unknown kind of artifact {:?}", kind),
+            }
+        }
+        Ok(results)
+    }
+}
@@ -0,0 +1,237 @@
+//! The Postgres backend, for the site of the benchmark server.
+
+use std::collections::{BTreeMap, HashMap};
+
+use failure::Error;
+use postgres::{self as pg, TlsMode};
+use postgres::transaction::Transaction;
+use serde_json::Value;
+
+use Stat;
+use db::{self, Assembly, Connection, Results};
+
+/// The migrations, see `db/mod.rs`.
+const MIGRATIONS: &[&str] = &[
+    include_str!("migrations/postgres/001-initial.sql"),
+    include_str!("migrations/postgres/002-artifact-metadata.sql"),
+    include_str!("migrations/postgres/003-samples.sql"),
+    include_str!("migrations/postgres/004-change-seq.sql"),
+];
+
+#[derive(Debug)]
+pub struct Postgres {
+    conn: pg::Connection,
+}
+
+impl Postgres {
+    pub fn open(url: &str) -> Result<Postgres, Error> {
+        let conn = pg::Connection::connect(url, TlsMode::None)?;
+        migrate(&conn)?;
+        Ok(Postgres { conn })
+    }
+
+    /// Connects to the database at `url` to read it, e.g. a replica.
+    pub fn open_read_only(url: &str) -> Result<Postgres, Error> {
+        let conn = pg::Connection::connect(url, TlsMode::None)?;
+        conn.batch_execute("SET SESSION CHARACTERISTICS AS TRANSACTION READ ONLY")?;
+        let version: i32 = conn.query("SELECT version FROM schema_version", &[])?
+            .iter()
+            .next()
+            .map_or(0, |row| row.get(0));
+        db::check_version("Postgres", version as i64, MIGRATIONS)?;
+        Ok(Postgres { conn })
+    }
+}
+
+fn migrate(conn: &pg::Connection) -> Result<(), Error> {
+    conn.batch_execute("CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL)")?;
+    loop {
+        let tx = conn.transaction()?;
+        // Sites and collectors that start at the same time migrate in turn.
+        tx.batch_execute("LOCK TABLE schema_version IN EXCLUSIVE MODE")?;
+        let version: i32 = tx.query("SELECT version FROM schema_version", &[])?
+            .iter()
+            .next()
+            .map_or(0, |row| row.get(0));
+        let migration = match db::next_migration("Postgres", version as i64, MIGRATIONS)? {
+            Some(migration) => migration,
+            None => return Ok(()),
+        };
+        tx.batch_execute(migration)?;
+        tx.execute("DELETE FROM schema_version", &[])?;
+        tx.execute("INSERT INTO schema_version (version) VALUES ($1)", &[&(version + 1)])?;
+        tx.commit()?;
+    }
+}
+
+/// The id of the row of `table` (`benchmark` or `metric`) called `name`,
+/// which is inserted if there's none yet.
+fn name_id(
+    tx: &Transaction,
+    ids: &mut HashMap<(&'static str, String), i32>,
+    table: &'static str,
+    name: &str,
+) -> Result<i32, Error> {
+    if let Some(&id) = ids.get(&(table, name.to_string())) {
+        return Ok(id);
+    }
+    // Updating the conflicting row makes it be returned.
+    let statement = tx.prepare_cached(&format!(
+        "INSERT INTO {} (name) VALUES ($1) \
+         ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name RETURNING id",
+        table,
+    ))?;
+    let id: i32 = statement.query(&[&name])?.get(0).get(0);
+    ids.insert((table, name.to_string()), id);
+    Ok(id)
+}
+
+impl Connection for Postgres {
+    fn load_after(&mut self, after: i64) -> Result<(Results, i64), Error> {
+        // All in one snapshot of the database, so that the rows of an
+        // artifact that's being stored are either all loaded or none are.
+        let tx = self.conn.transaction()?;
+        tx.batch_execute("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")?;
+        // The stores that are still in flight have taken later changes.
+        let last: i64 = tx.query("SELECT seq FROM change_seq", &[])?.get(0).get(0);
+
+        let mut assembly = Assembly::default();
+        for row in &tx.query("SELECT id, kind, data FROM artifact WHERE seq > $1", &[&after])? {
+            let id: i32 = row.get(0);
+            assembly.artifact(id as i64, row.get(1), row.get(2));
+        }
+
+        let mut stats = HashMap::<i32, Vec<Stat>>::new();
+        let values = tx.query(
+            "SELECT value.run, metric.name, value.value \
+             FROM value JOIN metric ON metric.id = value.metric \
+             JOIN run ON run.id = value.run JOIN artifact ON artifact.id = run.artifact \
+             WHERE artifact.seq > $1 \
+             ORDER BY value.run, metric.name",
+            &[&after],
+        )?;
+        for row in &values {
+            stats.entry(row.get(0)).or_insert_with(Vec::new)
+                .push(Stat { name: row.get(1), cnt: row.get(2) });
+        }
+
+        let mut samples = HashMap::<i32, BTreeMap<String, Vec<f64>>>::new();
+        let sample_rows = tx.query(
+            "SELECT sample.run, metric.name, sample.value \
+             FROM sample JOIN metric ON metric.id = sample.metric \
+             JOIN run ON run.id = sample.run JOIN artifact ON artifact.id = run.artifact \
+             WHERE artifact.seq > $1 \
+             ORDER BY sample.run, metric.name, sample.iteration",
+            &[&after],
+        )?;
+        for row in &sample_rows {
+            samples.entry(row.get(0)).or_insert_with(BTreeMap::new)
+                .entry(row.get(1)).or_insert_with(Vec::new)
+                .push(row.get(2));
+        }
+
+        let runs = tx.query(
+            "SELECT run.id, run.artifact, benchmark.name, run.category, run.data \
+             FROM run JOIN benchmark ON benchmark.id = run.benchmark \
+             JOIN artifact ON artifact.id = run.artifact WHERE artifact.seq > $1 \
+             ORDER BY run.id",
+            &[&after],
+        )?;
+        for row in &runs {
+            let id: i32 = row.get(0);
+            let artifact: i32 = row.get(1);
+            let category: String = row.get(3);
+            let data: Value = row.get(4);
+            let stats = stats.remove(&id).unwrap_or_default();
+            let samples = samples.remove(&id).unwrap_or_default();
+            assembly.run(artifact as i64, row.get(2), &category, data, stats, samples)?;
+        }
+
+        let errors = tx.query(
+            "SELECT error.artifact, benchmark.name, error.message \
+             FROM error JOIN benchmark ON benchmark.id = error.benchmark \
+             JOIN artifact ON artifact.id = error.artifact WHERE artifact.seq > $1",
+            &[&after],
+        )?;
+        for row in &errors {
+            let artifact: i32 = row.get(0);
+            assembly.error(artifact as i64, row.get(1), row.get(2));
+        }
+        tx.commit()?;
+
+        Ok((assembly.finish()?, last))
+    }
+
+    fn store(&mut self, results: &Results) -> Result<(), Error> {
+        let tx = self.conn.transaction()?;
+        // Locks the counter until the commit, see the migration.
+        let seq: i64 = tx.query("UPDATE change_seq SET seq = seq + 1 RETURNING seq", &[])?
+            .get(0).get(0);
+        let mut ids = HashMap::new();
+        for rows in db::rows(results)? {
+            let artifact = &rows.artifact;
+            tx.execute(
+                "DELETE FROM artifact \
+                 WHERE kind = $1 AND name = $2 AND triple = $3 AND flavor = $4 AND shard = $5",
+                &[&artifact.kind, &artifact.name, &artifact.triple, &artifact.flavor,
+                  &artifact.shard],
+            )?;
+            let artifact_id: i32 = tx.query(
+                "INSERT INTO artifact (kind, name, date, triple, flavor, shard, data, seq) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
+                &[&artifact.kind, &artifact.name, &artifact.date, &artifact.triple,
+                  &artifact.flavor, &artifact.shard, &artifact.data, &seq],
+            )?.get(0).get(0);
+            if let Some(ref metadata) = artifact.metadata {
+                let pr = metadata.pr.map(|pr| pr as i32);
+                tx.execute(
+                    "INSERT INTO artifact_metadata (artifact, type, date, pr, parent_sha) \
+                     VALUES ($1, $2, $3, $4, $5)",
+                    &[&artifact_id, &metadata.kind.name(), &metadata.date.map(|d| d.0), &pr,
+                      &metadata.parent_sha],
+                )?;
+            }
+
+            let insert_run = tx.prepare_cached(
+                "INSERT INTO run (artifact, benchmark, category, data) \
+                 VALUES ($1, $2, $3, $4) RETURNING id",
+            )?;
+            let insert_value = tx.prepare_cached(
+                "INSERT INTO value (run, metric, value, median, mad, stddev) \
+                 VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT DO NOTHING",
+            )?;
+            let insert_sample = tx.prepare_cached(
+                "INSERT INTO sample (run, metric, iteration, value) VALUES ($1, $2, $3, $4)",
+            )?;
+            for run in &rows.runs {
+                let benchmark = name_id(&tx, &mut ids, "benchmark", run.benchmark)?;
+                let run_id: i32 = insert_run.query(
+                    &[&artifact_id, &benchmark, &run.category, &run.data],
+                )?.get(0).get(0);
+                for stat in run.stats {
+                    let metric = name_id(&tx, &mut ids, "metric", &stat.name)?;
+                    let summary = run.summary(&stat.name);
+                    insert_value.execute(&[&run_id, &metric, &stat.cnt,
+                                           &summary.map(|s| s.median), &summary.map(|s| s.mad),
+                                           &summary.map(|s| s.stddev)])?;
+                }
+                for (name, values) in run.samples {
+                    let metric = name_id(&tx, &mut ids, "metric", name)?;
+                    for (iteration, value) in values.iter().enumerate() {
+                        insert_sample.execute(&[&run_id, &metric, &(iteration as i32), value])?;
+                    }
+                }
+            }
+
+            for &(benchmark, message) in &rows.errors {
+                let benchmark = name_id(&tx, &mut ids, "benchmark", benchmark)?;
+                tx.execute(
+                    "INSERT INTO error (artifact, benchmark, message) VALUES ($1, $2, $3)",
+                    &[&artifact_id, &benchmark, &message],
+                )?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+//! The SQLite backend, for benchmarking and running the site locally. Its
+//! tables are those of the Postgres backend, with the JSON and the dates
+//! stored as text.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use failure::{Error, ResultExt};
+use rusqlite::{self, OpenFlags, OptionalExtension, Transaction, TransactionBehavior,
+              NO_PARAMS};
+use rusqlite::types::ToSql;
+use serde_json;
+
+use Stat;
+use db::{self, Assembly, Connection, Results};
+
+/// The migrations, see `db/mod.rs`.
+const MIGRATIONS: &[&str] = &[
+    include_str!("migrations/sqlite/001-initial.sql"),
+    include_str!("migrations/sqlite/002-artifact-metadata.sql"),
+    include_str!("migrations/sqlite/003-samples.sql"),
+    include_str!("migrations/sqlite/004-change-seq.sql"),
+];
+
+#[derive(Debug)]
+pub struct Sqlite {
+    conn: rusqlite::Connection,
+}
+
+impl Sqlite {
+    /// Opens the database in the file at `path`, which is created if it
+    /// doesn't exist.
+    pub fn open(path: &Path) -> Result<Sqlite, Error> {
+        let mut conn = rusqlite::Connection::open(path)
+            .with_context(|_| format!("failed to open the database {:?}", path))?;
+        // SQLite only enforces the references between the tables, and
+        // deletes the rows that refer to a deleted one, if told to.
+        conn.execute_batch("PRAGMA foreign_keys = ON")?;
+        migrate(&mut conn)?;
+        Ok(Sqlite { conn })
+    }
+
+    /// Opens the existing database in the file at `path` to read it.
+    pub fn open_read_only(path: &Path) -> Result<Sqlite, Error> {
+        let conn = rusqlite::Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|_| format!("failed to open the database {:?}", path))?;
+        let version: i64 = conn.query_row("SELECT version FROM schema_version", NO_PARAMS,
+                                          |row| row.get(0))
+            .optional()?
+            .unwrap_or(0);
+        db::check_version("SQLite", version, MIGRATIONS)?;
+        Ok(Sqlite { conn })
+    }
+}
+
+fn migrate(conn: &mut rusqlite::Connection) -> Result<(), Error> {
+    conn.execute_batch("CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL)")?;
+    loop {
+        // Collectors and sites that start at the same time migrate in turn.
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let version: i64 = tx.query_row("SELECT version FROM schema_version", NO_PARAMS,
+                                        |row| row.get(0))
+            .optional()?
+            .unwrap_or(0);
+        let migration = match db::next_migration("SQLite", version, MIGRATIONS)? {
+            Some(migration) => migration,
+            None => return Ok(()),
+        };
+        tx.execute_batch(migration)?;
+        tx.execute("DELETE FROM schema_version", NO_PARAMS)?;
+        tx.execute("INSERT INTO schema_version (version) VALUES (?1)", &[version + 1])?;
+        tx.commit()?;
+    }
+}
+
+/// The id of the row of `table` (`benchmark` or `metric`) called `name`,
+/// which is inserted if there's none yet.
+fn name_id(
+    tx: &Transaction,
+    ids: &mut HashMap<(&'static str, String), i64>,
+    table: &'static str,
+    name: &str,
+) -> Result<i64, Error> {
+    if let Some(&id) = ids.get(&(table, name.to_string())) {
+        return Ok(id);
+    }
+    tx.prepare_cached(&format!("INSERT OR IGNORE INTO {} (name) VALUES (?1)", table))?
+        .execute(&[name])?;
+    let id = tx.prepare_cached(&format!("SELECT id FROM {} WHERE name = ?1", table))?
+        .query_row(&[name], |row| row.get(0))?;
+    ids.insert((table, name.to_string()), id);
+    Ok(id)
+}
+
+impl Connection for Sqlite {
+    fn load_after(&mut self, after: i64) -> Result<(Results, i64), Error> {
+        // All in one snapshot of the database, so that the rows of an
+        // artifact that's being stored are either all loaded or none are.
+        let tx = self.conn.transaction()?;
+        let last: i64 = tx.query_row("SELECT seq FROM change_seq", NO_PARAMS, |row| row.get(0))?;
+
+        let mut assembly = Assembly::default();
+        {
+            let mut statement = tx.prepare("SELECT id, kind, data FROM artifact WHERE seq > ?1")?;
+            let mut rows = statement.query(&[&after])?;
+            while let Some(row) = rows.next() {
+                let row = row?;
+                let data: String = row.get(2);
+                assembly.artifact(row.get(0), row.get(1), serde_json::from_str(&data)?);
+            }
+        }
+
+        let mut stats = HashMap::<i64, Vec<Stat>>::new();
+        {
+            let mut statement = tx.prepare(
+                "SELECT value.run, metric.name, value.value \
+                 FROM value JOIN metric ON metric.id = value.metric \
+                 JOIN run ON run.id = value.run JOIN artifact ON artifact.id = run.artifact \
+                 WHERE artifact.seq > ?1 \
+                 ORDER BY value.run, metric.name",
+            )?;
+            let mut rows = statement.query(&[&after])?;
+            while let Some(row) = rows.next() {
+                let row = row?;
+                stats.entry(row.get(0)).or_insert_with(Vec::new)
+                    .push(Stat { name: row.get(1), cnt: row.get(2) });
+            }
+        }
+
+        let mut samples = HashMap::<i64, BTreeMap<String, Vec<f64>>>::new();
+        {
+            let mut statement = tx.prepare(
+                "SELECT sample.run, metric.name, sample.value \
+                 FROM sample JOIN metric ON metric.id = sample.metric \
+                 JOIN run ON run.id = sample.run JOIN artifact ON artifact.id = run.artifact \
+                 WHERE artifact.seq > ?1 \
+                 ORDER BY sample.run, metric.name, sample.iteration",
+            )?;
+            let mut rows = statement.query(&[&after])?;
+            while let Some(row) = rows.next() {
+                let row = row?;
+                samples.entry(row.get(0)).or_insert_with(BTreeMap::new)
+                    .entry(row.get(1)).or_insert_with(Vec::new)
+                    .push(row.get(2));
+            }
+        }
+
+        {
+            let mut statement = tx.prepare(
+                "SELECT run.id, run.artifact, benchmark.name, run.category, run.data \
+                 FROM run JOIN benchmark ON benchmark.id = run.benchmark \
+                 JOIN artifact ON artifact.id = run.artifact WHERE artifact.seq > ?1 \
+                 ORDER BY run.id",
+            )?;
+            let mut rows = statement.query(&[&after])?;
+            while let Some(row) = rows.next() {
+                let row = row?;
+                let id: i64 = row.get(0);
+                let category: String = row.get(3);
+                let data: String = row.get(4);
+                let stats = stats.remove(&id).unwrap_or_default();
+                let samples = samples.remove(&id).unwrap_or_default();
+                assembly.run(row.get(1), row.get(2), &category, serde_json::from_str(&data)?,
+                             stats, samples)?;
+            }
+        }
+
+        {
+            let mut statement = tx.prepare(
+                "SELECT error.artifact, benchmark.name, error.message \
+                 FROM error JOIN benchmark ON benchmark.id = error.benchmark \
+                 JOIN artifact ON artifact.id = error.artifact WHERE artifact.seq > ?1",
+            )?;
+            let mut rows = statement.query(&[&after])?;
+            while let Some(row) = rows.next() {
+                let row = row?;
+                assembly.error(row.get(0), row.get(1), row.get(2));
+            }
+        }
+
+        tx.commit()?;
+
+        Ok((assembly.finish()?, last))
+    }
+
+    fn store(&mut self, results: &Results) -> Result<(), Error> {
+        // Taking the next change takes the database's write lock, so the
+        // stores commit in the order of their changes.
+        let tx = self.conn.transaction()?;
+        tx.execute("UPDATE change_seq SET seq = seq + 1", NO_PARAMS)?;
+        let seq: i64 = tx.query_row("SELECT seq FROM change_seq", NO_PARAMS, |row| row.get(0))?;
+        let mut ids = HashMap::new();
+        for rows in db::rows(results)? {
+            let artifact = &rows.artifact;
+            tx.execute(
+                "DELETE FROM artifact \
+                 WHERE kind = ?1 AND name = ?2 AND triple = ?3 AND flavor = ?4 AND shard = ?5",
+                &[&artifact.kind as &ToSql, &artifact.name, &artifact.triple, &artifact.flavor,
+                  &artifact.shard],
+            )?;
+            let date = artifact.date.as_ref().map(DateTime::<Utc>::to_rfc3339);
+            tx.execute(
+                "INSERT INTO artifact (kind, name, date, triple, flavor, shard, data, seq) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                &[&artifact.kind as &ToSql, &artifact.name, &date, &artifact.triple,
+                  &artifact.flavor, &artifact.shard, &artifact.data.to_string(), &seq],
+            )?;
+            let artifact_id = tx.last_insert_rowid();
+            if let Some(ref metadata) = artifact.metadata {
+                let date = metadata.date.map(|d| d.0.to_rfc3339());
+                tx.execute(
+                    "INSERT INTO artifact_metadata (artifact, type, date, pr, parent_sha) \
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    &[&artifact_id as &ToSql, &metadata.kind.name(), &date, &metadata.pr,
+                      &metadata.parent_sha],
+                )?;
+            }
+
+            for run in &rows.runs {
+                let benchmark = name_id(&tx, &mut ids, "benchmark", run.benchmark)?;
+                tx.prepare_cached(
+                    "INSERT INTO run (artifact, benchmark, category, data) \
+                     VALUES (?1, ?2, ?3, ?4)",
+                )?.execute(&[&artifact_id as &ToSql, &benchmark, &run.category,
+                             &run.data.to_string()])?;
+                let run_id = tx.last_insert_rowid();
+                let mut insert_value = tx.prepare_cached(
+                    "INSERT OR IGNORE INTO value (run, metric, value, median, mad, stddev) \
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )?;
+                for stat in run.stats {
+                    let metric = name_id(&tx, &mut ids, "metric", &stat.name)?;
+                    let summary = run.summary(&stat.name);
+                    insert_value.execute(&[&run_id as &ToSql, &metric, &stat.cnt,
+                                           &summary.map(|s| s.median), &summary.map(|s| s.mad),
+                                           &summary.map(|s| s.stddev)])?;
+                }
+                let mut insert_sample = tx.prepare_cached(
+                    "INSERT INTO sample (run, metric, iteration, value) VALUES (?1, ?2, ?3, ?4)",
+                )?;
+                for (name, values) in run.samples {
+                    let metric = name_id(&tx, &mut ids, "metric", name)?;
+                    for (iteration, value) in values.iter().enumerate() {
+                        insert_sample.execute(
+                            &[&run_id as &ToSql, &metric, &(iteration as i64), value],
+                        )?;
+                    }
+                }
+            }
+
+            for &(benchmark, message) in &rows.errors {
+                let benchmark = name_id(&tx, &mut ids, "benchmark", benchmark)?;
+                tx.execute(
+                    "INSERT INTO error (artifact, benchmark, message) VALUES (?1, ?2, ?3)",
+                    &[&artifact_id as &ToSql, &benchmark, &message],
+                )?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
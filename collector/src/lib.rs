@@ -1,12 +1,17 @@
 extern crate chrono;
 #[macro_use]
+extern crate failure;
+#[macro_use]
 extern crate log;
+extern crate postgres;
+extern crate rusqlite;
 extern crate semver;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
 
-use std::cmp::{Ord, Ordering, PartialOrd};
+use std::cmp::{self, Ord, Ordering, PartialOrd};
 use std::collections::BTreeMap;
 use std::fmt;
 use std::ops::{Add, Sub};
@@ -21,8 +26,17 @@ use chrono::naive::NaiveDate;
 use serde::{Deserialize, Serialize};
 
 pub mod api;
+pub mod db;
 pub mod self_profile;
 
+/// The stat that comparisons and graphs use unless another one is requested.
+/// Instruction counts are far less noisy than any of the time-based stats.
+pub const DEFAULT_STAT: &str = "instructions:u";
+
+/// The triple of the machines whose results make up the graphs and
+/// comparisons. Those of other machines can't be compared with them.
+pub const PRIMARY_TRIPLE: &str = "x86_64-unknown-linux-gnu";
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Commit {
     pub sha: String,
@@ -126,6 +140,17 @@ impl Patch {
         }
         Ok(())
     }
+
+    pub fn revert(&self, dir: &Path) -> Result<(), String> {
+        debug!("reverting {} in {:?}", self.name, dir);
+        let mut cmd = process::Command::new("patch");
+        cmd.current_dir(dir).args(&["-RNp1", "-i"]).arg(&self.path);
+        cmd.stdout(Stdio::null());
+        if cmd.status().map(|s| !s.success()).unwrap_or(false) {
+            return Err(format!("could not execute {:?}.", cmd));
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Deserialize, Serialize)]
@@ -184,6 +209,52 @@ impl BenchmarkState {
 pub struct Benchmark {
     pub runs: Vec<Run>,
     pub name: String,
+    /// The category of the benchmark, from its `perf-config.json`.
+    #[serde(default)]
+    pub category: Category,
+}
+
+/// What kind of code a benchmark is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    /// Real-world code.
+    Primary,
+    /// Artificial stress tests and regression tests for specific issues.
+    Secondary,
+    /// Real-world code that also builds with stable compilers.
+    Stable,
+}
+
+impl Default for Category {
+    fn default() -> Category {
+        Category::Primary
+    }
+}
+
+impl Category {
+    pub fn from_name(name: &str) -> Option<Category> {
+        match name {
+            "primary" => Some(Category::Primary),
+            "secondary" => Some(Category::Secondary),
+            "stable" => Some(Category::Stable),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Category::Primary => "primary",
+            Category::Secondary => "secondary",
+            Category::Stable => "stable",
+        }
+    }
+
+    /// Whether the benchmark is real-world code, whose results are the
+    /// headline numbers, rather than a stress test.
+    pub fn is_real_world(&self) -> bool {
+        *self != Category::Secondary
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
@@ -192,20 +263,260 @@ pub struct Stat {
     pub cnt: f64,
 }
 
+/// The codegen backend used by rustc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodegenBackend {
+    Llvm,
+    Cranelift,
+}
+
+impl Default for CodegenBackend {
+    fn default() -> Self {
+        CodegenBackend::Llvm
+    }
+}
+
+impl CodegenBackend {
+    pub fn from_name(name: &str) -> Option<CodegenBackend> {
+        match name {
+            "llvm" => Some(CodegenBackend::Llvm),
+            "cranelift" => Some(CodegenBackend::Cranelift),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            CodegenBackend::Llvm => "llvm",
+            CodegenBackend::Cranelift => "cranelift",
+        }
+    }
+
+    pub fn is_llvm(&self) -> bool {
+        *self == CodegenBackend::Llvm
+    }
+}
+
+/// The linker the benchmarks are linked with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Linker {
+    /// Whatever rustc uses for the target, usually the system linker via `cc`.
+    Default,
+    Lld,
+    Mold,
+}
+
+impl Default for Linker {
+    fn default() -> Self {
+        Linker::Default
+    }
+}
+
+impl Linker {
+    pub fn from_name(name: &str) -> Option<Linker> {
+        match name {
+            "default" => Some(Linker::Default),
+            "lld" => Some(Linker::Lld),
+            "mold" => Some(Linker::Mold),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Linker::Default => "default",
+            Linker::Lld => "lld",
+            Linker::Mold => "mold",
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Linker::Default
+    }
+}
+
+/// How the target directory a benchmark is built in is prepared for the
+/// measured builds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetDirPolicy {
+    /// The dependencies are built once per build kind, and each iteration
+    /// starts from a copy of that directory, which is in the page cache. The
+    /// scenarios of an iteration share the directory.
+    Reuse,
+    /// Each iteration starts from scratch: the dependencies are built again,
+    /// unmeasured, and the directory is then evicted from the page cache, so
+    /// that the measured builds read it from disk.
+    Wipe,
+    /// Like `Reuse`, but on a tmpfs, so that the builds don't touch the disk.
+    Tmpfs,
+}
+
+impl Default for TargetDirPolicy {
+    fn default() -> Self {
+        TargetDirPolicy::Reuse
+    }
+}
+
+impl TargetDirPolicy {
+    pub fn from_name(name: &str) -> Option<TargetDirPolicy> {
+        match name {
+            "reuse" => Some(TargetDirPolicy::Reuse),
+            "wipe" => Some(TargetDirPolicy::Wipe),
+            "tmpfs" => Some(TargetDirPolicy::Tmpfs),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            TargetDirPolicy::Reuse => "reuse",
+            TargetDirPolicy::Wipe => "wipe",
+            TargetDirPolicy::Tmpfs => "tmpfs",
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == TargetDirPolicy::Reuse
+    }
+}
+
+/// How the benchmarked compiler was built, for commits that are built in
+/// more than one way, e.g. to find out how much each optimization of the
+/// build pipeline buys. The results of each flavor of a commit are kept
+/// apart, so that they can be compared with each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Flavor {
+    /// The build that is shipped.
+    Plain,
+    /// The alternative build (`rustc-builds-alt`), e.g. with the compiler's
+    /// debug assertions or LLVM assertions enabled, or against a newer LLVM
+    /// that is yet to land.
+    Alt,
+    /// Built with profile-guided optimization.
+    Pgo,
+    /// Post-link optimized with BOLT (which implies PGO).
+    Bolt,
+    /// Built with (thin) LTO across the compiler's crates.
+    Lto,
+}
+
+impl Default for Flavor {
+    fn default() -> Self {
+        Flavor::Plain
+    }
+}
+
+impl Flavor {
+    pub fn from_name(name: &str) -> Option<Flavor> {
+        match name {
+            "plain" => Some(Flavor::Plain),
+            "alt" => Some(Flavor::Alt),
+            "pgo" => Some(Flavor::Pgo),
+            "bolt" => Some(Flavor::Bolt),
+            "lto" => Some(Flavor::Lto),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Flavor::Plain => "plain",
+            Flavor::Alt => "alt",
+            Flavor::Pgo => "pgo",
+            Flavor::Bolt => "bolt",
+            Flavor::Lto => "lto",
+        }
+    }
+
+    pub fn is_plain(&self) -> bool {
+        *self == Flavor::Plain
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Run {
+    /// The minimum of each stat over the iterations.
     pub stats: Vec<Stat>,
+    /// The value of each stat in every iteration, in the order they were
+    /// run. Empty for the results collected before these were recorded.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub samples: BTreeMap<String, Vec<f64>>,
     pub self_profile: self_profile::SelfProfile,
     #[serde(default)]
     pub check: bool,
     pub release: bool,
+    #[serde(default)]
+    pub doc: bool,
+    /// Whether this is an optimized build with full debuginfo (`release` is
+    /// also set).
+    #[serde(default)]
+    pub debuginfo: bool,
+    /// Whether this is a debug build with LTO.
+    #[serde(default)]
+    pub lto: bool,
+    /// The target triple the benchmark was built for, if it was a cross
+    /// build.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default, skip_serializing_if = "CodegenBackend::is_llvm")]
+    pub backend: CodegenBackend,
+    #[serde(default, skip_serializing_if = "Linker::is_default")]
+    pub linker: Linker,
+    /// The number of frontend threads (`-Zthreads`), if set explicitly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads: Option<u32>,
+    /// How many times the benchmark was run to get these stats, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iterations: Option<usize>,
+    /// How many unmeasured runs of the benchmark preceded the measured ones,
+    /// if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warmup: Option<usize>,
     pub state: BenchmarkState,
+    /// How the memory layout of the compiler was controlled.
+    #[serde(default, skip_serializing_if = "LayoutControl::is_default")]
+    pub layout: LayoutControl,
+    #[serde(default, skip_serializing_if = "TargetDirPolicy::is_default")]
+    pub target_dir: TargetDirPolicy,
+}
+
+/// How the things that change the memory layout of the measured compiler
+/// from one run to the next were controlled.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LayoutControl {
+    #[serde(default)]
+    pub aslr_disabled: bool,
+    /// The length of the path of the directory the benchmark was built in,
+    /// if it was fixed.
+    #[serde(default)]
+    pub path_length: Option<usize>,
+    /// Whether the size of the environment was randomized for every run, to
+    /// estimate how much the results depend on layout.
+    #[serde(default)]
+    pub randomized_environment: bool,
+}
+
+impl LayoutControl {
+    pub fn is_default(&self) -> bool {
+        *self == LayoutControl::default()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RunId {
     check: bool,
     release: bool,
+    doc: bool,
+    debuginfo: bool,
+    lto: bool,
+    target: Option<String>,
+    backend: CodegenBackend,
+    linker: Linker,
+    threads: Option<u32>,
     state: BenchmarkState,
 }
 
@@ -213,18 +524,50 @@ impl RunId {
     pub fn name(&self) -> String {
         self.to_string()
     }
+
+    /// The build kind, e.g. `check` or `opt`.
+    pub fn profile(&self) -> &'static str {
+        profile(self.release, self.debuginfo, self.check, self.doc, self.lto)
+    }
+}
+
+fn profile(release: bool, debuginfo: bool, check: bool, doc: bool, lto: bool) -> &'static str {
+    if release && debuginfo {
+        "opt-debuginfo"
+    } else if release {
+        "opt"
+    } else if check {
+        "check"
+    } else if doc {
+        "doc"
+    } else if lto {
+        "debug-lto"
+    } else {
+        "debug"
+    }
 }
 
 impl fmt::Display for RunId {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let opt = if self.release {
-            "-opt"
-        } else if self.check {
-            "-check"
-        } else {
-            ""
-        };
-        write!(f, "{}{}", self.state.name(), opt)
+        write!(f, "{}", self.state.name())?;
+        // Debug builds are the default, which is left out.
+        match self.profile() {
+            "debug" => {}
+            profile => write!(f, "-{}", profile)?,
+        }
+        if !self.backend.is_llvm() {
+            write!(f, "+{}", self.backend.name())?;
+        }
+        if !self.linker.is_default() {
+            write!(f, "+{}", self.linker.name())?;
+        }
+        if let Some(threads) = self.threads {
+            write!(f, "-t{}", threads)?;
+        }
+        if let Some(ref target) = self.target {
+            write!(f, "@{}", target)?;
+        }
+        Ok(())
     }
 }
 
@@ -232,6 +575,13 @@ impl PartialEq for Run {
     fn eq(&self, other: &Self) -> bool {
         self.release == other.release &&
         self.check == other.check &&
+        self.doc == other.doc &&
+        self.debuginfo == other.debuginfo &&
+        self.lto == other.lto &&
+        self.target == other.target &&
+        self.backend == other.backend &&
+        self.linker == other.linker &&
+        self.threads == other.threads &&
         self.state == other.state
     }
 }
@@ -240,6 +590,13 @@ impl PartialEq<RunId> for Run {
     fn eq(&self, other: &RunId) -> bool {
         self.release == other.release &&
         self.check == other.check &&
+        self.doc == other.doc &&
+        self.debuginfo == other.debuginfo &&
+        self.lto == other.lto &&
+        self.target == other.target &&
+        self.backend == other.backend &&
+        self.linker == other.linker &&
+        self.threads == other.threads &&
         self.state == other.state
     }
 }
@@ -274,6 +631,13 @@ impl Run {
         RunId {
             check: self.check,
             release: self.release,
+            doc: self.doc,
+            debuginfo: self.debuginfo,
+            lto: self.lto,
+            target: self.target.clone(),
+            backend: self.backend,
+            linker: self.linker,
+            threads: self.threads,
             state: state,
         }
     }
@@ -282,9 +646,59 @@ impl Run {
         self.id().name()
     }
 
+    /// The build kind, e.g. `check` or `opt`.
+    pub fn profile(&self) -> &'static str {
+        profile(self.release, self.debuginfo, self.check, self.doc, self.lto)
+    }
+
     pub fn get_stat(&self, stat: &str) -> Option<f64> {
         self.stats.iter().find(|s| s.name == stat).map(|s| s.cnt)
     }
+
+    /// The summary of the samples of `stat`, if they were recorded.
+    pub fn summary(&self, stat: &str) -> Option<Summary> {
+        Summary::of(self.samples.get(stat)?)
+    }
+}
+
+/// How the samples of a stat are spread, for telling whether a change in its
+/// minimum is more than noise.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub median: f64,
+    /// The median absolute deviation from the median.
+    pub mad: f64,
+    /// The sample standard deviation, which is 0 for a single sample.
+    pub stddev: f64,
+}
+
+impl Summary {
+    pub fn of(samples: &[f64]) -> Option<Summary> {
+        if samples.is_empty() {
+            return None;
+        }
+        let middle = median(samples.to_vec());
+        let mad = median(samples.iter().map(|v| (v - middle).abs()).collect());
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = if samples.len() > 1 {
+            samples.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+        Some(Summary { median: middle, mad, stddev: variance.sqrt() })
+    }
+}
+
+/// The median of the non-empty `values`.
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -292,6 +706,182 @@ pub struct ArtifactData {
     pub id: String,
     // String in Result is the output of the command that failed
     pub benchmarks: BTreeMap<String, Result<Benchmark, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<Collection>,
+    /// See `CommitData::artifact_sizes`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub artifact_sizes: BTreeMap<String, u64>,
+    /// See `CommitData::llvm_version`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub llvm_version: Option<String>,
+    /// The release the artifact was published as, if it was benchmarked as
+    /// one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release: Option<Release>,
+    /// See `CommitData::ices`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ices: BTreeMap<String, String>,
+}
+
+/// A release published on static.rust-lang.org.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Release {
+    /// `stable`, `beta` or `nightly`.
+    pub channel: String,
+    /// The date it was published, as YYYY-MM-DD.
+    pub date: String,
+    /// The version of its rustc, e.g. `1.34.0-beta.1 (e1c6d0057 2019-02-22)`.
+    pub version: String,
+}
+
+impl ArtifactData {
+    /// The metadata of the artifact, if it's a release; the others are
+    /// compilers benchmarked locally.
+    pub fn metadata(&self) -> Option<ArtifactMetadata> {
+        let release = self.release.as_ref()?;
+        Some(ArtifactMetadata {
+            kind: ArtifactType::Release,
+            date: NaiveDate::parse_from_str(&release.date, "%Y-%m-%d").ok()
+                .map(|date| Date::ymd_hms(date.year(), date.month(), date.day(), 0, 0, 0)),
+            pr: None,
+            parent_sha: None,
+        })
+    }
+}
+
+/// A result that was far off from the history of its series, and whose
+/// benchmark was rerun to check whether it was noise.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Outlier {
+    pub benchmark: String,
+    pub run: String,
+    pub stat: String,
+    /// How many standard deviations the result was away from the mean of the
+    /// series.
+    pub sigmas: f64,
+    /// Whether a rerun was as far off. If not, the result is suspected to be
+    /// noise.
+    pub reproduced: bool,
+}
+
+impl CommitData {
+    /// Whether this is a try build rather than a merge. The results recorded
+    /// before there was metadata only tell by their date.
+    pub fn is_try(&self) -> bool {
+        match self.metadata {
+            Some(ref metadata) => metadata.kind == ArtifactType::Try,
+            None => self.commit.is_try(),
+        }
+    }
+
+    /// Adds the results of another shard of the same commit to `self`.
+    pub fn merge_shard(&mut self, other: CommitData) {
+        if self.shards.is_empty() {
+            self.shards.extend(self.collection.clone());
+        }
+        self.shards.extend(other.collection);
+        self.benchmarks.extend(other.benchmarks);
+        self.collected_by.extend(other.collected_by);
+        self.outliers.extend(other.outliers);
+        self.ices.extend(other.ices);
+    }
+
+    /// Whether all of the results are in: every shard, if the commit was
+    /// sharded, has finished collecting.
+    pub fn is_complete(&self) -> bool {
+        let shard_count = self.collection.as_ref()
+            .and_then(|c| c.shard.as_ref())
+            .map(|s| s.count);
+        match shard_count {
+            Some(count) => (0..count).all(|index| {
+                self.shards.iter().chain(self.collection.as_ref()).any(|c| {
+                    c.duration.is_some() && c.shard.as_ref().map_or(false, |s| {
+                        s.index == index && s.count == count
+                    })
+                })
+            }),
+            None => self.collection.as_ref().map_or(true, |c| c.duration.is_some()),
+        }
+    }
+}
+
+/// The part of the benchmarks one of several collectors working on the same
+/// commit is responsible for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Shard {
+    /// The name the collector registered with the site under.
+    pub collector: String,
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Shard {
+    /// Whether `benchmark` belongs to this shard. Benchmarks are assigned by
+    /// a hash of their name, so that the assignment doesn't depend on which
+    /// benchmarks each collector has.
+    pub fn contains(&self, benchmark: &str) -> bool {
+        stable_hash(benchmark.as_bytes()) % self.count as u64 == self.index as u64
+    }
+}
+
+/// A hash of `data` that, unlike those of `DefaultHasher`, is the same on
+/// every machine and with every version of Rust (FNV-1a).
+pub fn stable_hash(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &byte in data {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// The lines of rustc's output that start an internal compiler error.
+const ICE_MARKERS: &[&str] = &[
+    "error: internal compiler error",
+    "thread 'rustc' panicked",
+    "note: the compiler unexpectedly panicked",
+];
+
+/// At most this many lines of an ICE are kept, as the query stack of some is
+/// very long.
+const MAX_ICE_LINES: usize = 50;
+
+/// The internal compiler error reported in `output` (that of a failed
+/// build), if rustc crashed rather than rejecting the code: from the first
+/// line that reports it up to the end of the query stack.
+pub fn ice_message(output: &str) -> Option<String> {
+    let lines = output.lines().collect::<Vec<_>>();
+    let start = lines.iter()
+        .position(|line| ICE_MARKERS.iter().any(|marker| line.contains(marker)))?;
+    let end = lines[start..].iter()
+        .position(|line| line.starts_with("end of query stack"))
+        .map_or(lines.len(), |i| start + i + 1);
+    let end = cmp::min(end, start + MAX_ICE_LINES);
+    Some(lines[start..end].join("\n"))
+}
+
+/// The stats gathered by executing a runtime benchmark.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RuntimeBenchmark {
+    pub name: String,
+    pub stats: Vec<Stat>,
+}
+
+impl RuntimeBenchmark {
+    pub fn get_stat(&self, stat: &str) -> Option<f64> {
+        self.stats.iter().find(|s| s.name == stat).map(|s| s.cnt)
+    }
+}
+
+/// The results of the runtime benchmarks, compiled with the artifact `id`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RuntimeData {
+    pub id: String,
+    pub date: Date,
+    #[serde(default, skip_serializing_if = "CodegenBackend::is_llvm")]
+    pub backend: CodegenBackend,
+    // String in Result is the output of the command that failed
+    pub benchmarks: BTreeMap<String, Result<RuntimeBenchmark, String>>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -300,6 +890,209 @@ pub struct CommitData {
     // String in Result is the output of the command that failed
     pub benchmarks: BTreeMap<String, Result<Benchmark, String>>,
     pub triple: String,
+    #[serde(default, skip_serializing_if = "Flavor::is_plain")]
+    pub flavor: Flavor,
+    /// The LLVM version the benchmarked compiler was built against, as
+    /// reported by `rustc -vV`, e.g. `8.0`. `None` if unknown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub llvm_version: Option<String>,
+    /// How long, in seconds, each rustbuild step took when CI built this
+    /// commit. Empty if CI didn't publish its metrics.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub bootstrap: BTreeMap<String, f64>,
+    /// The state of the machine when the benchmarks were run, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<Environment>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<Collection>,
+    /// The sizes, in bytes, of the benchmarked compiler's `librustc_driver`,
+    /// `libstd` and whole sysroot. Empty if they weren't measured.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub artifact_sizes: BTreeMap<String, u64>,
+    /// The name of the collector that produced each benchmark's results.
+    /// Empty if the results weren't collected by a named collector.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub collected_by: BTreeMap<String, String>,
+    /// The collections of every shard, once the results of several collectors
+    /// have been merged with `merge_shard`. Empty otherwise.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shards: Vec<Collection>,
+    /// The results that were far off from the history of their series.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outliers: Vec<Outlier>,
+    /// The internal compiler errors (see `ice_message`) the benchmarks that
+    /// failed ran into, by benchmark.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ices: BTreeMap<String, String>,
+    /// What the commit is, if it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ArtifactMetadata>,
+}
+
+/// What kind of build of the compiler was benchmarked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactType {
+    /// A merge into master by bors.
+    Master,
+    /// A `@bors try` build of a PR.
+    Try,
+    /// A published release.
+    Release,
+}
+
+impl ArtifactType {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ArtifactType::Master => "master",
+            ArtifactType::Try => "try",
+            ArtifactType::Release => "release",
+        }
+    }
+}
+
+/// What's known about a benchmarked artifact besides its sha or id, which
+/// used to be guessed from those (e.g. try commits by their placeholder date).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArtifactMetadata {
+    #[serde(rename = "type")]
+    pub kind: ArtifactType,
+    /// When the commit was merged, if known. The `Commit::date` of a try
+    /// commit is a placeholder, which sorts it before all master commits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<Date>,
+    /// The PR that was merged, or that the try build is of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pr: Option<u32>,
+    /// The master commit this one comes after: the previous merge, or the
+    /// one a try build is based on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_sha: Option<String>,
+}
+
+impl ArtifactMetadata {
+    /// The metadata of a bors merge with the commit message `summary`, made
+    /// after the merge `parent_sha`.
+    pub fn master(date: Date, summary: &str, parent_sha: Option<String>) -> ArtifactMetadata {
+        ArtifactMetadata {
+            kind: ArtifactType::Master,
+            date: Some(date),
+            pr: merged_pr(summary),
+            parent_sha,
+        }
+    }
+}
+
+/// The PR that bors merged, from the first line of the merge's message, e.g.
+/// `Auto merge of #58361 - ...` or `Rollup merge of #58361 - ...`.
+fn merged_pr(summary: &str) -> Option<u32> {
+    let number = ["Auto merge of #", "Rollup merge of #"].iter()
+        .filter(|prefix| summary.starts_with(*prefix))
+        .map(|prefix| &summary[prefix.len()..])
+        .next()?;
+    let end = number.find(|c: char| !c.is_digit(10)).unwrap_or(number.len());
+    number[..end].parse().ok()
+}
+
+/// The machine and software that collected a set of results. Results are
+/// only comparable with others from the same machine. Each field is `None`
+/// if it couldn't be determined.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Collection {
+    pub hostname: Option<String>,
+    /// The OS, as in `std::env::consts::OS` (e.g. `linux` or `macos`),
+    /// followed by its version if the kernel's doesn't say which it is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<String>,
+    /// The machine's architecture, as in `std::env::consts::ARCH` (e.g.
+    /// `x86_64` or `aarch64`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
+    pub kernel: Option<String>,
+    pub cpu_model: Option<String>,
+    pub microcode: Option<String>,
+    /// Total memory, in kB.
+    pub memory: Option<u64>,
+    pub glibc: Option<String>,
+    /// The LLVM version of the benchmarked compiler.
+    pub llvm_version: Option<String>,
+    /// The rustc-perf commit the collector was built from.
+    pub collector_sha: Option<String>,
+    /// The version of the benchmark suite, if the benchmarks matched its
+    /// manifest. Results from different versions can't be compared.
+    #[serde(default)]
+    pub suite_version: Option<u32>,
+    /// How long, in seconds, collecting the results took. A collection that
+    /// was resumed includes the time spent before it was interrupted. `None`
+    /// in the results of a try build that are pushed while it's collected.
+    #[serde(default)]
+    pub duration: Option<f64>,
+    /// Whether the collector was shut down before all the benchmarks were
+    /// done. The collection is resumed by the next run of the collector.
+    #[serde(default)]
+    pub interrupted: bool,
+    /// How long, in seconds, each benchmark took, including its failed
+    /// attempts.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub benchmark_durations: BTreeMap<String, f64>,
+    /// The shard of the benchmarks that was collected, if the collection was
+    /// split across several collectors.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard: Option<Shard>,
+}
+
+impl Collection {
+    /// Describes how the machine (as opposed to the compiler) differs between
+    /// `self` and `other`, e.g. `kernel: 4.15.0 vs. 4.18.0`. Fields unknown for
+    /// either are not compared.
+    pub fn machine_differences(&self, other: &Collection) -> Vec<String> {
+        fn compare<T: PartialEq + fmt::Display>(
+            differences: &mut Vec<String>,
+            name: &str,
+            a: &Option<T>,
+            b: &Option<T>,
+        ) {
+            if let (&Some(ref a), &Some(ref b)) = (a, b) {
+                if a != b {
+                    differences.push(format!("{}: {} vs. {}", name, a, b));
+                }
+            }
+        }
+
+        let mut differences = Vec::new();
+        compare(&mut differences, "hostname", &self.hostname, &other.hostname);
+        compare(&mut differences, "OS", &self.os, &other.os);
+        compare(&mut differences, "architecture", &self.arch, &other.arch);
+        compare(&mut differences, "kernel", &self.kernel, &other.kernel);
+        compare(&mut differences, "CPU", &self.cpu_model, &other.cpu_model);
+        compare(&mut differences, "microcode", &self.microcode, &other.microcode);
+        compare(&mut differences, "memory (kB)", &self.memory, &other.memory);
+        compare(&mut differences, "glibc", &self.glibc, &other.glibc);
+        differences
+    }
+}
+
+/// Machine settings that affect how stable the measurements are. Each is
+/// `None` if it couldn't be determined (e.g. not on Linux).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Environment {
+    pub turbo_boost: Option<bool>,
+    pub cpu_governor: Option<String>,
+    pub smt: Option<bool>,
+    pub aslr: Option<bool>,
+    /// Average current frequency over all cores, in MHz.
+    pub cpu_mhz: Option<f64>,
+    /// Hottest thermal zone, in degrees Celsius.
+    pub max_temperature: Option<f64>,
+    /// Why the results are likely to be noisy; empty if they shouldn't be.
+    #[serde(default)]
+    pub issues: Vec<String>,
+}
+
+impl Environment {
+    pub fn is_noisy(&self) -> bool {
+        !self.issues.is_empty()
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
@@ -313,9 +1106,24 @@ pub enum Bound {
     // sha, unverified
     Commit(String),
     Date(NaiveDate),
+    // the id of an artifact that isn't a commit, e.g. the release `1.34.0`;
+    // written as `artifact:<id>` if the id alone would be taken for a commit
+    // or a date
+    Artifact(String),
     None,
 }
 
+/// The prefix that makes a bound the id of an artifact, whatever it looks like.
+const ARTIFACT_PREFIX: &str = "artifact:";
+
+fn is_hex(s: &str) -> bool {
+    s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn is_artifact_id(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+}
+
 impl Serialize for Bound {
     fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
     where
@@ -324,6 +1132,13 @@ impl Serialize for Bound {
         let s = match *self {
             Bound::Commit(ref s) => s.clone(),
             Bound::Date(ref date) => date.format("%Y-%m-%d").to_string(),
+            Bound::Artifact(ref id) => {
+                if is_hex(id) || id.parse::<NaiveDate>().is_ok() {
+                    format!("{}{}", ARTIFACT_PREFIX, id)
+                } else {
+                    id.clone()
+                }
+            }
             Bound::None => String::new(),
         };
         serializer.serialize_str(&s)
@@ -348,23 +1163,28 @@ impl<'de> Deserialize<'de> for Bound {
                     return Ok(Bound::None);
                 }
 
-                let bound = value
-                    .parse::<NaiveDate>()
-                    .map(|d| Bound::Date(d))
-                    .unwrap_or(Bound::Commit(value.to_string()));
-                if let Bound::Commit(ref sha) = bound {
-                    if sha.len() != 40 {
-                        return Err(serde::de::Error::invalid_value(
-                            serde::de::Unexpected::Str(value),
-                            &self,
-                        ));
+                if value.starts_with(ARTIFACT_PREFIX) {
+                    let id = &value[ARTIFACT_PREFIX.len()..];
+                    if is_artifact_id(id) {
+                        return Ok(Bound::Artifact(id.to_string()));
+                    }
+                } else if let Ok(date) = value.parse::<NaiveDate>() {
+                    return Ok(Bound::Date(date));
+                } else if is_hex(value) {
+                    // Anything else that's all hex digits is most likely a
+                    // mistyped or abbreviated hash, rather than an artifact.
+                    if value.len() == 40 {
+                        return Ok(Bound::Commit(value.to_string()));
                     }
+                } else if is_artifact_id(value) {
+                    return Ok(Bound::Artifact(value.to_string()));
                 }
-                Ok(bound)
+                Err(serde::de::Error::invalid_value(serde::de::Unexpected::Str(value), &self))
             }
 
             fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
-                f.write_str("either a YYYY-mm-dd date or a 40 character long git commit hash")
+                f.write_str("a YYYY-mm-dd date, a 40 character long git commit hash or the id \
+                             of an artifact, e.g. 1.34.0 or artifact:<id>")
             }
         }
 
@@ -499,7 +1319,8 @@ pub fn version_supports_incremental(version_str: &str) -> bool {
     if let Some(version) = version_str.parse::<semver::Version>().ok() {
         version >= semver::Version::new(1, 24, 0)
     } else {
-        assert!(version_str == "beta" || version_str.starts_with("master"));
+        assert!(version_str == "beta" || version_str.starts_with("master") ||
+                version_str.starts_with("nightly-"));
         true
     }
 }
@@ -1,6 +1,10 @@
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct SelfProfile {
     pub category_data: Vec<Category>,
+    /// Per-query data, summarized with measureme's `summarize` from the
+    /// files `-Zself-profile` writes. Empty if `summarize` isn't available.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub query_data: Vec<QueryData>,
     // This field is intentionally private as for perf it should not be read.
     compilation_options: Options,
 }
@@ -18,6 +22,38 @@ pub struct Category {
     pub query_hits: f32,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QueryData {
+    /// Query name, e.g. typeck_tables_of
+    pub label: String,
+    /// Number of times the query was executed or its result was looked up
+    pub invocations: u64,
+    /// Number of lookups that found the result in the in-memory cache
+    pub cache_hits: u64,
+    /// Time spent loading results from the incremental cache, in
+    /// milliseconds; non-zero if results were reused from a previous session
+    pub incremental_load_time_ms: f64,
+    /// Time spent in the query (or activity) itself, excluding the queries
+    /// it called, in milliseconds
+    #[serde(default)]
+    pub self_time_ms: f64,
+}
+
+impl QueryData {
+    pub fn cache_misses(&self) -> u64 {
+        self.invocations.saturating_sub(self.cache_hits)
+    }
+
+    /// Percentage of lookups that were cache hits
+    pub fn hit_rate(&self) -> f64 {
+        if self.invocations == 0 {
+            0.0
+        } else {
+            self.cache_hits as f64 / self.invocations as f64 * 100.0
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 struct Options {
     optimization_level: String,
@@ -0,0 +1,107 @@
+// Copyright 2016 The rustc-perf Project Developers. See the COPYRIGHT
+// file at the top-level directory.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Tells significant changes of the results from noise, by how much the series
+//! they're part of changed from one master commit to the next in the past.
+//! Some benchmarks barely change unless the compiler does, while others move
+//! by a few percent on every commit, so no single cutoff fits all of them.
+
+use collector::Run;
+
+use load::{CommitData, InputData};
+use util::Interpolate;
+
+/// How many of the master commits before a comparison make up the history of
+/// its series.
+const HISTORY: usize = 100;
+
+/// Series with fewer changes than this in their history fall back to
+/// `DEFAULT_THRESHOLD`.
+const MIN_HISTORY: usize = 10;
+
+/// How many standard deviations of the past changes of its series a change has
+/// to be to be significant.
+const SIGMAS: f64 = 3.0;
+
+/// The smallest threshold, in percent, so that a series that hardly ever
+/// changed doesn't make every tiny change significant.
+const MIN_THRESHOLD: f64 = 0.1;
+
+/// The threshold, in percent, of series without enough history.
+const DEFAULT_THRESHOLD: f64 = 1.0;
+
+/// How a series changed from one commit to the next in its history.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Noise {
+    /// The number of changes.
+    pub changes: usize,
+    /// The mean and standard deviation of the changes, in percent.
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+impl Noise {
+    /// The noise of `stat` in `run` of `benchmark` in `history` (oldest
+    /// first), if there's enough of it.
+    pub fn of(history: &[&CommitData], benchmark: &str, run: &Run, stat: &str) -> Option<Noise> {
+        let values = history.iter()
+            .filter_map(|data| match data.benchmarks.get(benchmark) {
+                Some(&Ok(ref b)) => {
+                    b.runs.iter().find(|r| *r == run).and_then(|r| r.get_stat(stat))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let changes = values.windows(2)
+            .filter(|w| w[0] > 0.0)
+            .map(|w| (w[1] - w[0]) / w[0] * 100.0)
+            .collect::<Vec<_>>();
+        if changes.len() < MIN_HISTORY {
+            return None;
+        }
+        let n = changes.len() as f64;
+        let mean = changes.iter().sum::<f64>() / n;
+        let variance = changes.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        Some(Noise { changes: changes.len(), mean, stddev: variance.sqrt() })
+    }
+
+    /// The smallest change, in percent, that's significant.
+    pub fn threshold(&self) -> f64 {
+        (SIGMAS * self.stddev).max(MIN_THRESHOLD)
+    }
+}
+
+/// The history to measure the noise of a comparison with `base` against: the
+/// `HISTORY` master commits up to `base`, if it's one, or else the latest ones,
+/// oldest first.
+pub fn history<'a>(data: &'a InputData, base: &str) -> Vec<&'a CommitData> {
+    let mut commits = data.data(Interpolate::No).values()
+        .filter(|cd| !cd.is_try())
+        .collect::<Vec<_>>();
+    if let Some(pos) = commits.iter().position(|cd| cd.commit.sha == base) {
+        commits.truncate(pos + 1);
+    }
+    let skip = commits.len().saturating_sub(HISTORY);
+    commits.split_off(skip)
+}
+
+/// The smallest change, in percent, from `a` to `b`, the results of a run of
+/// `benchmark`, that's significant. That's told by the noise of the series in
+/// `history`, if there's enough of it, or else by `DEFAULT_THRESHOLD` and the
+/// spread of the samples of `a` and `b`, if they were recorded.
+pub fn threshold(history: &[&CommitData], benchmark: &str, a: &Run, b: &Run, stat: &str) -> f64 {
+    if let Some(noise) = Noise::of(history, benchmark, a, stat) {
+        return noise.threshold();
+    }
+    let samples = match (a.summary(stat), b.summary(stat), a.get_stat(stat)) {
+        (Some(a), Some(b), Some(value)) if value > 0.0 => 2.0 * (a.mad + b.mad) / value * 100.0,
+        _ => 0.0,
+    };
+    DEFAULT_THRESHOLD.max(samples)
+}
@@ -47,6 +47,10 @@ impl List {
 
 pub type ServerResult<T> = StdResult<T, String>;
 
+pub fn default_stat() -> String {
+    String::from(::collector::DEFAULT_STAT)
+}
+
 pub mod info {
     use collector::Date;
     use std::collections::BTreeSet;
@@ -59,8 +63,12 @@ pub mod info {
         /// Sorted list of statistic names known
         pub stats: BTreeSet<String>,
 
-        /// Chronologically last loaded run date.
-        pub as_of: Date,
+        /// The triples results were collected on, which `days` can compare
+        /// the results of.
+        pub triples: BTreeSet<String>,
+
+        /// Chronologically last loaded run date, if any runs were loaded.
+        pub as_of: Option<Date>,
     }
 }
 
@@ -97,6 +105,7 @@ pub mod data {
         pub end: Bound,
 
         /// Which statistic to return data for
+        #[serde(default = "::api::default_stat")]
         pub stat: String,
     }
 
@@ -113,6 +122,7 @@ pub mod graph {
     pub struct Request {
         pub start: Bound,
         pub end: Bound,
+        #[serde(default = "::api::default_stat")]
         pub stat: String,
         pub absolute: bool,
     }
@@ -137,6 +147,66 @@ pub mod graph {
     }
 }
 
+pub mod graphs {
+    use collector::Bound;
+
+    /// The series of a stat over a range of commits, filtered and reduced on
+    /// the server rather than in the browser.
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Request {
+        pub start: Bound,
+        pub end: Bound,
+        #[serde(default = "::api::default_stat")]
+        pub stat: String,
+        /// The benchmarks to include, or all of them if empty.
+        #[serde(default)]
+        pub benchmarks: Vec<String>,
+        /// The build kinds to include (e.g. `check`, `debug`, `opt`), or all of
+        /// them if empty.
+        #[serde(default)]
+        pub profiles: Vec<String>,
+        /// The scenarios to include (e.g. `clean`, `baseline incremental`),
+        /// or all of them if empty.
+        #[serde(default)]
+        pub scenarios: Vec<String>,
+        /// Each point is the average of the value and the ones of this many
+        /// commits before it, minus one.
+        #[serde(default)]
+        pub rolling_average: Option<usize>,
+        /// The most points a series is downsampled to, by averaging runs of
+        /// consecutive commits.
+        #[serde(default)]
+        pub max_points: Option<usize>,
+        /// The values themselves, rather than their change in percent from
+        /// the first one.
+        #[serde(default)]
+        pub absolute: bool,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Point {
+        /// The date of the commit, in milliseconds since the epoch.
+        pub x: u64,
+        pub y: f64,
+        /// The commit, or the last of those that were averaged.
+        pub commit: String,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Series {
+        pub benchmark: String,
+        pub profile: String,
+        pub scenario: String,
+        pub points: Vec<Point>,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Response {
+        /// By benchmark, profile and scenario.
+        pub series: Vec<Series>,
+    }
+}
+
 pub mod days {
     use server::DateData;
     use collector::Bound;
@@ -146,13 +216,29 @@ pub mod days {
         pub start: Bound,
         pub end: Bound,
 
+        #[serde(default = "::api::default_stat")]
         pub stat: String,
+
+        /// The triple whose results are compared, if not the primary one.
+        /// Only commits are benchmarked there, so `start` and `end` can't be
+        /// artifacts.
+        #[serde(default)]
+        pub triple: Option<String>,
     }
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct Response {
         pub a: DateData,
         pub b: DateData,
+        /// How the machines that collected `a` and `b` differ; if they do,
+        /// the comparison may not be meaningful.
+        #[serde(default)]
+        pub machine_differences: Vec<String>,
+        /// Whether `b` is a try commit, and the results of its parent `a`
+        /// were reused from when it was benchmarked as a master commit,
+        /// rather than collected along with `b`'s.
+        #[serde(default)]
+        pub reused_base: bool,
     }
 }
 
@@ -162,6 +248,7 @@ pub mod nll_dashboard {
     #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
     pub struct Request {
         pub commit: Bound,
+        #[serde(default = "::api::default_stat")]
         pub stat: String,
     }
 
@@ -189,8 +276,328 @@ pub mod nll_dashboard {
     }
 }
 
+pub mod self_profile {
+    use collector::Bound;
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Request {
+        pub base_commit: Bound,
+        pub new_commit: Bound,
+        pub benchmark: String,
+        /// The name of the run, e.g. `clean-check`.
+        pub run_name: String,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct CategoryDelta {
+        pub category: String,
+        pub base_time_ms: Option<u64>,
+        pub new_time_ms: Option<u64>,
+        pub base_query_count: Option<u64>,
+        pub new_query_count: Option<u64>,
+        pub base_query_hits: Option<f32>,
+        pub new_query_hits: Option<f32>,
+    }
+
+    impl CategoryDelta {
+        pub fn time_delta_ms(&self) -> i64 {
+            self.new_time_ms.unwrap_or(0) as i64 - self.base_time_ms.unwrap_or(0) as i64
+        }
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct QueryDelta {
+        pub query: String,
+        pub base_invocations: Option<u64>,
+        pub new_invocations: Option<u64>,
+        pub base_cache_hits: Option<u64>,
+        pub new_cache_hits: Option<u64>,
+        pub base_incremental_load_time_ms: Option<f64>,
+        pub new_incremental_load_time_ms: Option<f64>,
+    }
+
+    impl QueryDelta {
+        pub fn cache_misses_delta(&self) -> i64 {
+            let misses = |invocations: Option<u64>, hits: Option<u64>| {
+                invocations.unwrap_or(0).saturating_sub(hits.unwrap_or(0)) as i64
+            };
+            misses(self.new_invocations, self.new_cache_hits)
+                - misses(self.base_invocations, self.base_cache_hits)
+        }
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Response {
+        pub base_commit: String,
+        pub new_commit: String,
+        /// Sorted by the absolute change in time, largest first.
+        pub categories: Vec<CategoryDelta>,
+        /// Sorted by the change in cache misses, most new misses first. Empty
+        /// unless `summarize` was available when both runs were collected.
+        pub queries: Vec<QueryDelta>,
+    }
+}
+
+pub mod samples {
+    use collector::{Bound, Summary};
+
+    /// The raw values of a stat of each run of a benchmark at a commit.
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Request {
+        pub commit: Bound,
+        pub benchmark: String,
+        #[serde(default = "::api::default_stat")]
+        pub stat: String,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct RunSamples {
+        /// The name of the run, e.g. `clean-check`.
+        pub run_name: String,
+        /// The value in each iteration, in the order they were run. Empty if
+        /// the samples weren't recorded.
+        pub samples: Vec<f64>,
+        pub summary: Option<Summary>,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Response {
+        pub commit: String,
+        pub runs: Vec<RunSamples>,
+    }
+}
+
+pub mod compare {
+    use collector::Bound;
+
+    /// Compares the results of the artifact `b` with those of `a`, each a
+    /// commit or another artifact, e.g. a release.
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Request {
+        pub a: Bound,
+        pub b: Bound,
+        #[serde(default = "::api::default_stat")]
+        pub stat: String,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Significance {
+        Improvement,
+        Regression,
+        /// The change is within the noise of its series.
+        Insignificant,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Change {
+        pub benchmark: String,
+        /// The name of the run, e.g. `clean-check`.
+        pub run_name: String,
+        pub a: f64,
+        pub b: f64,
+        /// The change from `a` to `b`, in percent.
+        pub percent: f64,
+        /// The smallest change of the series that's significant, in percent.
+        pub threshold: f64,
+        pub significance: Significance,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Summary {
+        /// The number of runs that both commits have results for.
+        pub runs: usize,
+        pub improvements: usize,
+        pub regressions: usize,
+        /// The geometric mean of the changes of all runs, in percent.
+        pub geomean_percent: f64,
+        /// The largest significant improvement and regression, if any.
+        pub largest_improvement: Option<Change>,
+        pub largest_regression: Option<Change>,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Response {
+        pub a: String,
+        pub b: String,
+        /// The changes of the runs, largest first.
+        pub changes: Vec<Change>,
+        pub summary: Summary,
+        /// See `days::Response::machine_differences`.
+        pub machine_differences: Vec<String>,
+    }
+}
+
+pub mod bootstrap {
+    use collector::Bound;
+    use std::collections::HashMap;
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Request {
+        pub start: Bound,
+        pub end: Bound,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Response {
+        /// The commits in the range, oldest first.
+        pub commits: Vec<String>,
+        /// For each rustbuild step, how long it took in seconds at each of
+        /// `commits`, if known.
+        pub by_step: HashMap<String, Vec<Option<f64>>>,
+    }
+}
+
+pub mod artifact_sizes {
+    use collector::Bound;
+    use std::collections::HashMap;
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Request {
+        pub start: Bound,
+        pub end: Bound,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Response {
+        /// The published releases, oldest first, followed by the commits in
+        /// the range. Only artifacts whose sizes were measured are included.
+        pub artifacts: Vec<String>,
+        /// For each component (`librustc_driver`, `libstd` and `sysroot`),
+        /// its size in bytes in each of `artifacts`, if known.
+        pub by_component: HashMap<String, Vec<Option<u64>>>,
+    }
+}
+
+pub mod collection_times {
+    use collector::Bound;
+    use std::collections::HashMap;
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Request {
+        pub start: Bound,
+        pub end: Bound,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Response {
+        /// The commits in the range whose collection was timed, oldest first.
+        pub commits: Vec<String>,
+        /// How long, in seconds, collecting each of `commits` took.
+        pub totals: Vec<f64>,
+        /// For each benchmark, how long it took in seconds at each of
+        /// `commits`, if known.
+        pub by_benchmark: HashMap<String, Vec<Option<f64>>>,
+    }
+}
+
+pub mod flavors {
+    use collector::Flavor;
+
+    /// Compares `flavor` of `commit` with its plain build. The response is
+    /// that of `days`, with the plain build as `a`.
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Request {
+        pub commit: String,
+        pub flavor: Flavor,
+        #[serde(default = "::api::default_stat")]
+        pub stat: String,
+    }
+
+    pub mod summary {
+        use collector::Flavor;
+
+        /// Summarizes how every flavor of `commit` that was benchmarked
+        /// compares with its plain build, over the real-world benchmarks.
+        #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+        pub struct Request {
+            pub commit: String,
+            #[serde(default = "::api::default_stat")]
+            pub stat: String,
+        }
+
+        #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+        pub struct FlavorSummary {
+            pub flavor: Flavor,
+            /// The number of runs that both builds have results for.
+            pub runs: usize,
+            /// The geometric mean of the ratios of the stat of the flavor to
+            /// that of the plain build, e.g. 0.9 for a flavor that's 10%
+            /// faster.
+            pub geomean_ratio: f64,
+            /// How many of the runs got better (lower) and worse (higher).
+            pub improved: usize,
+            pub regressed: usize,
+            /// How the machines that benchmarked the two builds differ, see
+            /// `days::Response`.
+            pub machine_differences: Vec<String>,
+        }
+
+        #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+        pub struct Response {
+            pub commit: String,
+            pub flavors: Vec<FlavorSummary>,
+        }
+    }
+
+    pub mod history {
+        use collector::{Date, Flavor};
+        use super::summary::FlavorSummary;
+
+        /// Tracks how `flavor` compares with the plain build over time, e.g.
+        /// what the compiler's debug assertions cost, over the real-world
+        /// benchmarks.
+        #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+        pub struct Request {
+            pub flavor: Flavor,
+            #[serde(default = "::api::default_stat")]
+            pub stat: String,
+        }
+
+        #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+        pub struct Point {
+            pub commit: String,
+            pub date: Date,
+            pub summary: FlavorSummary,
+        }
+
+        /// The commits that both builds have results for, oldest first.
+        #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+        pub struct Response {
+            pub flavor: Flavor,
+            pub points: Vec<Point>,
+        }
+    }
+}
+
+pub mod runtime {
+    /// Compares the runtime benchmarks compiled by two artifacts.
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Request {
+        /// The ids passed to `collector bench_runtime_local`.
+        pub base_id: String,
+        pub new_id: String,
+        #[serde(default = "::api::default_stat")]
+        pub stat: String,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct BenchmarkDelta {
+        pub benchmark: String,
+        pub base: Option<f64>,
+        pub new: Option<f64>,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Response {
+        pub base_id: String,
+        pub new_id: String,
+        pub benchmarks: Vec<BenchmarkDelta>,
+    }
+}
+
 pub mod status {
-    use collector::Commit;
+    use collector::{Commit, Outlier};
     use load::{MissingReason, CurrentState};
 
     #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -198,11 +605,19 @@ pub mod status {
         pub name: String,
         pub success: bool,
         pub error: Option<String>,
+        /// The internal compiler error the benchmark ran into, if it failed
+        /// with one.
+        pub ice: Option<String>,
+        /// The results that were far off from the history of their series,
+        /// and whether a rerun reproduced them.
+        pub outliers: Vec<Outlier>,
     }
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct Response {
-        pub last_commit: Commit,
+        /// The commit the benchmarks' status is that of, unless there are no
+        /// commits yet.
+        pub last_commit: Option<Commit>,
         pub benchmarks: Vec<BenchmarkStatus>,
         pub missing: Vec<(Commit, MissingReason)>,
         pub current: Option<CurrentState>,
@@ -237,8 +652,15 @@ pub mod github {
 
     #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
     pub struct Issue {
+        /// The number of the issue or PR, which the try commits queued before
+        /// it was recorded lack.
+        #[serde(default)]
+        pub number: Option<u32>,
         pub comments_url: String,
         pub repository_url: String,
+        /// The description of the issue or PR.
+        #[serde(default)]
+        pub body: Option<String>,
     }
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,6 +37,28 @@ pub fn execute_command(working_dir: &Path, args: &[&str]) -> Result<(), Error> {
     }
 }
 
+/// Commits the changes to `paths` and pushes them, after rebasing them onto
+/// any that were pushed in the meantime. Does nothing if there are no
+/// changes.
+pub fn commit_and_push(repo_path: &str, paths: &[&str], message: &str) -> Result<(), Error> {
+    let working_dir = Path::new(repo_path);
+    let mut add = vec!["add"];
+    add.extend(paths);
+    execute_command(working_dir, &add)?;
+    let unchanged = Command::new(GIT)
+        .current_dir(working_dir)
+        .args(&["diff", "--cached", "--quiet"])
+        .status()?
+        .success();
+    if unchanged {
+        return Ok(());
+    }
+    execute_command(working_dir, &["commit", "-m", message])?;
+    execute_command(working_dir, &[GIT_PULL, "--rebase"])?;
+    execute_command(working_dir, &["push"])?;
+    Ok(())
+}
+
 fn checkout_master(working_dir: &Path) -> Result<(), Error> {
     execute_command(working_dir, &[GIT_CHECKOUT, BRANCH])
 }
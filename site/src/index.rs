@@ -0,0 +1,72 @@
+// Copyright 2016 The rustc-perf Project Developers. See the COPYRIGHT
+// file at the top-level directory.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The results in the database, kept up to date by ingesting the artifacts
+//! stored since they were last read, rather than reading all of them again.
+//! The site polls the database for those, whoever stored them (an upload, or
+//! a collector with `--db`), and swaps in the data built from the updated
+//! results, so that the graphs show new results minutes after they're
+//! collected, without a restart.
+
+use std::collections::HashSet;
+use std::env;
+use std::time::Duration;
+
+use failure::Error;
+
+use collector::db::{ArtifactKey, Connection, Results};
+
+/// How often the database is polled by default, in seconds.
+const DEFAULT_POLL_INTERVAL: u64 = 60;
+
+/// How often the database is polled, `$INDEX_POLL_INTERVAL` seconds.
+pub fn poll_interval() -> Duration {
+    let secs = env::var("INDEX_POLL_INTERVAL").ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(DEFAULT_POLL_INTERVAL);
+    Duration::from_secs(secs)
+}
+
+#[derive(Debug)]
+pub struct Index {
+    results: Results,
+    /// The last change to the database that was ingested.
+    last: i64,
+}
+
+impl Index {
+    /// The index of all the results in `db`.
+    pub fn load(db: &mut Connection) -> Result<Index, Error> {
+        let (results, last) = db.load_after(0)?;
+        Ok(Index { results, last })
+    }
+
+    pub fn results(&self) -> &Results {
+        &self.results
+    }
+
+    /// Ingests the artifacts stored in `db` since the last update, which
+    /// replace the ones of the same key. Returns how many there were.
+    pub fn update(&mut self, db: &mut Connection) -> Result<usize, Error> {
+        let (new, last) = db.load_after(self.last)?;
+        self.last = last;
+        if new.is_empty() {
+            return Ok(0);
+        }
+        let keys = ArtifactKey::all(&new).into_iter().collect::<HashSet<_>>();
+        let results = &mut self.results;
+        results.commits.retain(|c| !keys.contains(&ArtifactKey::of_commit(c)));
+        results.artifacts.retain(|a| !keys.contains(&ArtifactKey::of_artifact(a)));
+        results.runtime.retain(|r| !keys.contains(&ArtifactKey::of_runtime(r)));
+        results.commits.extend(new.commits);
+        results.artifacts.extend(new.artifacts);
+        results.runtime.extend(new.runtime);
+        Ok(keys.len())
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright 2016 The rustc-perf Project Developers. See the COPYRIGHT
+// file at the top-level directory.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Interned names of benchmarks, metrics and artifacts. The in-memory index
+//! refers to them by small typed ids rather than by the same strings over and
+//! over, which makes its keys cheap to store, hash and compare.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+/// An id handed out by an `Interner`.
+pub trait Idx: Copy + Eq + Hash + Ord + fmt::Debug {
+    fn new(index: usize) -> Self;
+    fn index(self) -> usize;
+}
+
+macro_rules! idx {
+    ($($(#[$attr:meta])* $name:ident;)*) => {$(
+        $(#[$attr])*
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(u32);
+
+        impl Idx for $name {
+            fn new(index: usize) -> $name {
+                assert!(index <= u32::max_value() as usize, "too many names to intern");
+                $name(index as u32)
+            }
+
+            fn index(self) -> usize {
+                self.0 as usize
+            }
+        }
+    )*}
+}
+
+idx! {
+    /// A benchmark, e.g. `syn`.
+    BenchmarkIdx;
+    /// A statistic, e.g. `instructions:u`.
+    MetricIdx;
+    /// A commit or a release, by its sha or name.
+    ArtifactIdx;
+}
+
+/// The names interned so far, with ids handed out in the order the names were
+/// first seen.
+pub struct Interner<I> {
+    names: Vec<Box<str>>,
+    ids: HashMap<Box<str>, I>,
+}
+
+impl<I: Idx> Interner<I> {
+    pub fn new() -> Interner<I> {
+        Interner {
+            names: Vec::new(),
+            ids: HashMap::new(),
+        }
+    }
+
+    /// The id of `name`, which is interned if it hasn't been yet.
+    pub fn intern(&mut self, name: &str) -> I {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = I::new(self.names.len());
+        self.names.push(name.into());
+        self.ids.insert(name.into(), id);
+        id
+    }
+
+    /// The id of `name`, if it has been interned.
+    pub fn get(&self, name: &str) -> Option<I> {
+        self.ids.get(name).cloned()
+    }
+
+    pub fn name(&self, id: I) -> &str {
+        &self.names[id.index()]
+    }
+}
+
+impl<I: Idx> Default for Interner<I> {
+    fn default() -> Interner<I> {
+        Interner::new()
+    }
+}
+
+impl<I> fmt::Debug for Interner<I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Interner({} names)", self.names.len())
+    }
+}
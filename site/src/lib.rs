@@ -43,7 +43,11 @@ pub struct CommandFailed {
 
 mod git;
 
+pub mod analysis;
 pub mod api;
+pub mod index;
+pub mod intern;
 pub mod load;
+pub mod snapshot;
 pub mod util;
 pub mod server;
@@ -9,9 +9,11 @@
 
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::{self, File};
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::io::Read;
 use std::env;
+use std::sync::Arc;
+use std::time::Instant;
 
 use serde_json;
 use antidote::Mutex;
@@ -25,12 +27,19 @@ use toml;
 use util;
 use util::Interpolate;
 use git;
-use collector::Date;
+use index::Index;
+use intern::{ArtifactIdx, BenchmarkIdx, Idx, Interner, MetricIdx};
+use collector::{Date, Flavor, Shard};
+use collector::db::{self, Connection, Results};
 
-pub use collector::{RunId, Benchmark, CommitData, Commit, ArtifactData, Patch, Run, Stat};
+pub use collector::{RunId, Benchmark, CommitData, Commit, ArtifactData, Patch, Run, RuntimeData,
+                    Stat};
 use api::github;
 use collector;
 
+/// See `InputData::triple_data` for the results of the other triples.
+pub use collector::PRIMARY_TRIPLE;
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub enum MissingReason {
     /// This commmit has not yet been benchmarked
@@ -61,7 +70,7 @@ pub enum InterpolationSource {
 
 #[derive(Debug)]
 pub struct Interpolation {
-    pub benchmark: String,
+    pub benchmark: BenchmarkIdx,
     pub run: Option<RunId>,
     pub from: InterpolationSource,
 }
@@ -78,6 +87,11 @@ pub struct TryCommit {
     pub sha: String,
     pub parent_sha: String,
     pub issue: github::Issue,
+    /// Whether the parent already had results when the try commit was
+    /// queued. Those are then compared against, rather than the parent being
+    /// benchmarked again.
+    #[serde(default)]
+    pub parent_reused: bool,
 }
 
 impl TryCommit {
@@ -90,6 +104,19 @@ impl TryCommit {
 pub struct Persistent {
     pub try_commits: Vec<TryCommit>,
     pub current: Option<CurrentState>,
+    /// When each collector that registered with a name last asked for work.
+    #[serde(default)]
+    pub collectors: BTreeMap<String, Date>,
+    /// The shard each active collector was assigned, see `register_collector`.
+    #[serde(default)]
+    pub shard_indices: BTreeMap<String, usize>,
+}
+
+/// An upload of which some chunks are still missing.
+pub struct PartialUpload {
+    pub chunks: Vec<Option<String>>,
+    /// When the last chunk came in, so that abandoned uploads can be dropped.
+    pub updated: Instant,
 }
 
 lazy_static! {
@@ -111,6 +138,8 @@ impl Persistent {
         let p = Persistent::load_().unwrap_or_else(|| Persistent {
             try_commits: Vec::new(),
             current: None,
+            collectors: BTreeMap::new(),
+            shard_indices: BTreeMap::new(),
         });
         p.write().unwrap();
         p
@@ -128,6 +157,10 @@ impl Persistent {
 pub struct Keys {
     pub github: Option<String>,
     pub secret: Option<String>,
+    /// The token each collector that uploads its results authenticates
+    /// with, by the collector's name.
+    #[serde(default)]
+    pub collectors: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -144,10 +177,11 @@ pub struct InputData {
     /// All known statistics gathered for crates
     pub stats_list: BTreeSet<String>,
 
-    /// The last date that was seen while loading files. The DateTime variant is
-    /// used here since the date may or may not contain a time. Since the
-    /// timezone is not important, it isn't stored, hence the Naive variant.
-    pub last_date: Date,
+    /// The last date that was seen while loading files, if there are any
+    /// commits at all. The DateTime variant is used here since the date may or
+    /// may not contain a time. Since the timezone is not important, it isn't
+    /// stored, hence the Naive variant.
+    pub last_date: Option<Date>,
 
     /// `data_real` is as-is, `data` has been interpolated.
     data_real: BTreeMap<Commit, CommitData>,
@@ -156,20 +190,158 @@ pub struct InputData {
     /// The benchmarks we interpolated for a given commit.
     ///
     /// Not all commits are in this map.
-    pub interpolated: HashMap<String, Vec<Interpolation>>,
+    pub interpolated: HashMap<ArtifactIdx, Vec<Interpolation>>,
 
     /// The list of commits in the `data` map.
     pub data_commits: Vec<Commit>,
-    /// A map from commit to index in the `commits` vector.
-    pub commit_map: HashMap<Commit, usize>,
+    /// The shas of the commits in `data_commits`, whose ids are their
+    /// indices in it.
+    pub artifacts: Interner<ArtifactIdx>,
+    pub benchmarks: Interner<BenchmarkIdx>,
+    pub metrics: Interner<MetricIdx>,
+    /// The values of the statistics in `data`, by commit, benchmark and
+    /// metric, as the index of the run in the benchmark's runs and its value,
+    /// for looking series up without searching the runs' statistics by name.
+    pub values: Vec<HashMap<(BenchmarkIdx, MetricIdx), Vec<(usize, f64)>>>,
 
     pub artifact_data: BTreeMap<String, ArtifactData>,
 
+    /// The results of the flavors of commits other than the plain one,
+    /// keyed by sha and flavor. They aren't part of `data`, so they don't
+    /// show up in the graphs of the commits.
+    pub flavor_data: BTreeMap<(String, Flavor), CommitData>,
+
+    /// The results collected on machines other than the `PRIMARY_TRIPLE`
+    /// ones, keyed by sha, triple and flavor. They can't be compared with the
+    /// primary results, so they're kept apart just like other flavors.
+    pub triple_data: BTreeMap<(String, String, Flavor), CommitData>,
+
+    /// The results of the runtime benchmarks, keyed by the id of the artifact
+    /// that compiled them.
+    pub runtime_data: BTreeMap<String, RuntimeData>,
+
     pub commits: Vec<GitCommit>,
 
     pub persistent: Mutex<Persistent>,
 
+    /// The uploads that are still incomplete, by collector and upload id, as
+    /// the ids are only unique per collector.
+    pub uploads: Mutex<HashMap<(String, String), PartialUpload>>,
+
     pub config: Config,
+
+    /// The database the results were loaded from, if they weren't read from
+    /// the data repo.
+    pub db: Option<Arc<Database>>,
+
+    /// Whether the results are a snapshot of the database (see `snapshot`),
+    /// which takes no uploads and queues no work.
+    pub read_only: bool,
+}
+
+/// A database the results are kept in, and the index of them, which the data
+/// built from the index shares as it's updated.
+pub struct Database {
+    pub conn: Mutex<Box<Connection>>,
+    pub index: Mutex<Index>,
+}
+
+/// Reads the results in the data repo at `repo_loc`, which is cloned first if
+/// it doesn't exist.
+pub fn read_results(repo_loc: &Path) -> Result<Results, Error> {
+    let mut results = Results::default();
+    let mut skipped = 0;
+
+    if !repo_loc.exists() {
+        // If the repository doesn't yet exist, simplify clone it to the given location.
+        info!(
+            "cloning repository into {}, since it doesn't exist before",
+            repo_loc.display()
+        );
+        git::execute_command(
+            &env::current_dir()?,
+            &[
+                "clone",
+                "https://github.com/rust-lang-nursery/rustc-timing.git",
+                repo_loc.to_str().unwrap(),
+            ],
+        )?;
+    }
+
+    // Read all files from repo_loc/processed
+    let mut file_count = 0;
+    for entry in fs::read_dir(repo_loc.join("times"))? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            continue;
+        }
+        file_count += 1;
+
+        let filename = entry.file_name();
+        let filename = filename.to_str().unwrap();
+        let mut file = File::open(entry.path())?;
+        let mut file_contents = String::new();
+        // Skip files whose size is 0.
+        if file.read_to_string(&mut file_contents)? == 0 {
+            warn!("Skipping empty file: {}", filename);
+            skipped += 1;
+            continue;
+        }
+
+        if filename.starts_with("artifact-") {
+            let contents: ArtifactData = match serde_json::from_str(&file_contents) {
+                Ok(j) => j,
+                Err(err) => {
+                    error!("Failed to parse JSON for {}: {:?}", filename, err);
+                    skipped += 1;
+                    continue;
+                }
+            };
+            if contents.benchmarks.is_empty() {
+                warn!("empty benchmarks hash for {}", filename);
+                skipped += 1;
+                continue;
+            }
+
+            results.artifacts.push(contents);
+        } else if filename.starts_with("runtime-") {
+            let contents: RuntimeData = match serde_json::from_str(&file_contents) {
+                Ok(j) => j,
+                Err(err) => {
+                    error!("Failed to parse JSON for {}: {:?}", filename, err);
+                    skipped += 1;
+                    continue;
+                }
+            };
+            if contents.benchmarks.is_empty() {
+                warn!("empty benchmarks hash for {}", filename);
+                skipped += 1;
+                continue;
+            }
+
+            results.runtime.push(contents);
+        } else {
+            let contents: CommitData = match serde_json::from_str(&file_contents) {
+                Ok(json) => json,
+                Err(err) => {
+                    error!("Failed to parse JSON for {}: {:?}", filename, err);
+                    skipped += 1;
+                    continue;
+                }
+            };
+            if contents.benchmarks.is_empty() {
+                warn!("empty benchmarks hash for {}", filename);
+                skipped += 1;
+                continue;
+            }
+
+            results.commits.push(contents);
+        }
+    }
+
+    info!("{} total files", file_count);
+    info!("{} skipped files", skipped);
+    Ok(results)
 }
 
 impl InputData {
@@ -182,84 +354,118 @@ impl InputData {
 
     /// Initialize `InputData from the file system.
     pub fn from_fs(repo_loc: &str) -> Result<InputData, Error> {
-        let repo_loc = PathBuf::from(repo_loc);
-        let mut skipped = 0;
-        let mut artifact_data = BTreeMap::new();
-        let mut data = BTreeMap::new();
+        let results = read_results(Path::new(repo_loc))?;
+        InputData::from_results(results, None, None)
+    }
 
-        if !repo_loc.exists() {
-            // If the repository doesn't yet exist, simplify clone it to the given location.
-            info!(
-                "cloning repository into {}, since it doesn't exist before",
-                repo_loc.display()
-            );
-            git::execute_command(
-                &env::current_dir()?,
-                &[
-                    "clone",
-                    "https://github.com/rust-lang-nursery/rustc-timing.git",
-                    repo_loc.to_str().unwrap(),
-                ],
-            )?;
+    /// Initialize `InputData` from the database at `url` (see `db::open`),
+    /// which the results that are uploaded are then stored in. An empty
+    /// database is first filled with the results in the data repo at
+    /// `repo_loc`, if given.
+    pub fn from_db(url: &str, repo_loc: Option<&str>) -> Result<InputData, Error> {
+        let mut conn = db::open(url)?;
+        let mut index = Index::load(&mut *conn)?;
+        match repo_loc {
+            Some(repo_loc) if index.results().is_empty() => {
+                info!("importing the results in {} into the empty database", repo_loc);
+                conn.store(&read_results(Path::new(repo_loc))?)?;
+                index.update(&mut *conn)?;
+            }
+            _ => {}
         }
+        let db = Database { conn: Mutex::new(conn), index: Mutex::new(index) };
+        InputData::from_database(Arc::new(db), None)
+    }
 
-        // Read all files from repo_loc/processed
-        let mut file_count = 0;
-        for entry in fs::read_dir(repo_loc.join("times"))? {
-            let entry = entry?;
-            if entry.file_type()?.is_dir() {
-                continue;
-            }
-            file_count += 1;
-
-            let filename = entry.file_name();
-            let filename = filename.to_str().unwrap();
-            let mut file = File::open(entry.path())?;
-            let mut file_contents = String::new();
-            // Skip files whose size is 0.
-            if file.read_to_string(&mut file_contents)? == 0 {
-                warn!("Skipping empty file: {}", filename);
-                skipped += 1;
-                continue;
-            }
+    fn from_database(
+        db: Arc<Database>,
+        commits: Option<Vec<GitCommit>>,
+    ) -> Result<InputData, Error> {
+        let results = db.index.lock().results().clone();
+        InputData::from_results(results, Some(db), commits)
+    }
+
+    /// The data with the results stored in the database since it was loaded,
+    /// if there are any.
+    pub fn update(&self) -> Result<Option<InputData>, Error> {
+        let db = match self.db {
+            Some(ref db) => db,
+            None => return Ok(None),
+        };
+        let ingested = db.index.lock().update(&mut **db.conn.lock())?;
+        if ingested == 0 {
+            return Ok(None);
+        }
+        info!("ingested {} artifacts stored in the database", ingested);
+        // The commits of rust.git are only fetched again once there are
+        // results of a commit newer than the ones that were fetched, rather
+        // than on every update.
+        let newest = self.commits.iter().map(|c| c.date).max();
+        let stale = db.index.lock().results().commits.iter()
+            .any(|cd| !cd.is_try() && newest.map_or(true, |newest| cd.commit.date.0 > newest));
+        let commits = if stale { None } else { Some(self.commits.clone()) };
+        InputData::from_database(db.clone(), commits).map(Some)
+    }
 
-            if filename.starts_with("artifact-") {
-                let contents: ArtifactData = match serde_json::from_str(&file_contents) {
-                    Ok(j) => j,
-                    Err(err) => {
-                        error!("Failed to parse JSON for {}: {:?}", filename, err);
-                        skipped += 1;
+    /// Initialize `InputData` from the results in a snapshot of the database
+    /// (see `snapshot`), read-only.
+    pub fn from_snapshot(results: Results) -> Result<InputData, Error> {
+        let mut data = InputData::from_results(results, None, None)?;
+        data.read_only = true;
+        Ok(data)
+    }
+
+    fn from_results(
+        results: Results,
+        db: Option<Arc<Database>>,
+        commits: Option<Vec<GitCommit>>,
+    ) -> Result<InputData, Error> {
+        let artifact_data = results.artifacts.into_iter()
+            .map(|artifact| (artifact.id.clone(), artifact))
+            .collect();
+        let runtime_data = results.runtime.into_iter()
+            .map(|runtime| (runtime.id.clone(), runtime))
+            .collect();
+        let mut flavor_data = BTreeMap::new();
+        let mut triple_data = BTreeMap::new();
+        let mut data = BTreeMap::new();
+
+        for contents in results.commits {
+            // Each collector that worked on a sharded commit has a file of
+            // its own, and the results are merged.
+            let sharded = contents.collection.as_ref().map_or(false, |c| c.shard.is_some());
+            if contents.triple != PRIMARY_TRIPLE {
+                let key = (contents.commit.sha.clone(), contents.triple.clone(),
+                           contents.flavor);
+                if sharded {
+                    if let Some(existing) = triple_data.get_mut(&key) {
+                        existing.merge_shard(contents);
                         continue;
                     }
-                };
-                if contents.benchmarks.is_empty() {
-                    warn!("empty benchmarks hash for {}", filename);
-                    skipped += 1;
-                    continue;
                 }
-
-                artifact_data.insert(contents.id.clone(), contents);
-            } else {
-                let contents: CommitData = match serde_json::from_str(&file_contents) {
-                    Ok(json) => json,
-                    Err(err) => {
-                        error!("Failed to parse JSON for {}: {:?}", filename, err);
-                        skipped += 1;
+                triple_data.insert(key, contents);
+                continue;
+            }
+            if !contents.flavor.is_plain() {
+                let key = (contents.commit.sha.clone(), contents.flavor);
+                if sharded {
+                    if let Some(existing) = flavor_data.get_mut(&key) {
+                        existing.merge_shard(contents);
                         continue;
                     }
-                };
-                if contents.benchmarks.is_empty() {
-                    warn!("empty benchmarks hash for {}", filename);
-                    skipped += 1;
+                }
+                flavor_data.insert(key, contents);
+                continue;
+            }
+            if sharded {
+                if let Some(existing) = data.get_mut(&contents.commit) {
+                    existing.merge_shard(contents);
                     continue;
                 }
-
-                data.insert(contents.commit.clone(), contents);
             }
+            data.insert(contents.commit.clone(), contents);
         }
 
-        info!("{} total files", file_count);
-        info!("{} skipped files", skipped);
         info!("{} measured", data.len());
 
         let config = if let Ok(s) = fs::read_to_string("site-config.toml") {
@@ -271,13 +477,21 @@ impl InputData {
             }
         };
 
-        InputData::new(data, artifact_data, config)
+        InputData::new(data, artifact_data, flavor_data, triple_data, runtime_data, config, db,
+                       commits)
     }
 
+    /// Builds the data from the results, with the commits of rust.git in
+    /// `commits`, which are fetched if not given.
     pub fn new(
         data: BTreeMap<Commit, CommitData>,
         artifact_data: BTreeMap<String, ArtifactData>,
+        flavor_data: BTreeMap<(String, Flavor), CommitData>,
+        triple_data: BTreeMap<(String, String, Flavor), CommitData>,
+        runtime_data: BTreeMap<String, RuntimeData>,
         config: Config,
+        db: Option<Arc<Database>>,
+        commits: Option<Vec<GitCommit>>,
     ) -> Result<InputData, Error> {
         let mut last_date = None;
         let mut crate_list = BTreeSet::new();
@@ -303,17 +517,24 @@ impl InputData {
             }
         }
 
-        let last_date = last_date.expect("No dates found");
-        println!("Updating rust.git clone...");
-        let commits = rust_sysroot::get_commits(rust_sysroot::EPOCH_COMMIT, "master").map_err(SyncFailure::new)?;
-        println!("Update of rust.git complete");
+        let commits = match commits {
+            Some(commits) => commits,
+            None => {
+                println!("Updating rust.git clone...");
+                let commits = rust_sysroot::get_commits(rust_sysroot::EPOCH_COMMIT, "master")
+                    .map_err(SyncFailure::new)?;
+                println!("Update of rust.git complete");
+                commits
+            }
+        };
 
         let data_commits = data.keys().cloned().collect::<Vec<_>>();
 
-        let mut commit_map = HashMap::with_capacity(data_commits.len());
-        for (idx, commit) in data_commits.iter().enumerate() {
-            commit_map.insert(commit.clone(), idx);
+        let mut artifacts = Interner::new();
+        for commit in &data_commits {
+            artifacts.intern(&commit.sha);
         }
+        let mut benchmarks = Interner::new();
 
         eprintln!("Starting interpolation...");
         let mut latest_section_start = ::std::time::Instant::now();
@@ -419,7 +640,7 @@ impl InputData {
                 // We do not interpolate try commits today
                 // because we don't track their parents so it's
                 // difficult to add that data in.
-                if commit.is_try() {
+                if cd.is_try() {
                     continue;
                 }
 
@@ -427,7 +648,8 @@ impl InputData {
                     commit: &commit,
                     data: &data_real,
                     commits: &data_commits,
-                    commit_map: &commit_map,
+                    artifacts: &artifacts,
+                    benchmarks: &mut benchmarks,
                     interpolated: &mut interpolated,
                     last_seen_commit: &last_commit,
                     next_seen_commit: &next_commit,
@@ -442,13 +664,10 @@ impl InputData {
                 // benchmark did not run successfully at this commit
                 // or benchmark did not attempt to run at this commit
                 if entry.is_err() {
-                    let runs = fill_benchmark_data(benchmark_name, &mut assoc);
+                    let benchmark = fill_benchmark_data(benchmark_name, &mut assoc);
                     // If we couldn't do this then do nothing
-                    if let Some(runs) = runs {
-                        *entry = Ok(Benchmark {
-                            name: benchmark_name.to_owned(),
-                            runs: runs,
-                        });
+                    if let Some(benchmark) = benchmark {
+                        *entry = Ok(benchmark);
                     }
                 }
 
@@ -481,22 +700,83 @@ impl InputData {
         eprintln!("Interpolation of {} commits complete in {:?}", interpolated.len(), start.elapsed());
         let data = data_next;
 
+        let mut metrics = Interner::new();
+        let values = data.values().map(|cd| {
+            let mut values = HashMap::new();
+            for benchmark in cd.benchmarks.values().filter_map(|b| b.as_ref().ok()) {
+                let benchmark_idx = benchmarks.intern(&benchmark.name);
+                for (run_idx, run) in benchmark.runs.iter().enumerate() {
+                    for stat in &run.stats {
+                        values.entry((benchmark_idx, metrics.intern(&stat.name)))
+                            .or_insert_with(Vec::new)
+                            .push((run_idx, stat.cnt));
+                    }
+                }
+            }
+            values
+        }).collect();
+
         Ok(InputData {
             crate_list: crate_list,
             stats_list: stats_list,
             data_commits: data_commits,
-            commit_map: commit_map,
+            artifacts,
+            benchmarks,
+            metrics,
+            values,
             interpolated,
             last_date: last_date,
             data_real: data_real,
             data: data,
             artifact_data,
+            flavor_data,
+            triple_data,
+            runtime_data,
             commits,
             persistent: Mutex::new(Persistent::load()),
+            uploads: Mutex::new(HashMap::new()),
             config,
+            db,
+            read_only: false,
         })
     }
 
+    /// Records that the collector called `name` is asking for work, and
+    /// assigns it its shard among the collectors that have done so recently.
+    /// Collectors that stopped asking are assumed to be gone, and their
+    /// benchmarks are spread over the others.
+    pub fn register_collector(&self, name: &str) -> Shard {
+        let mut persistent = self.persistent.lock();
+        let now = Utc::now();
+        persistent.collectors.insert(name.to_string(), Date(now));
+        let active = persistent.collectors.iter()
+            .filter(|&(_, seen)| now.signed_duration_since(seen.0) < Duration::hours(24))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        // The collectors keep their shards, so that they don't start over on
+        // another one whenever a collector joins. Only when collectors leave,
+        // those with a shard that's now out of range move to a free one.
+        let count = active.len();
+        let mut indices = persistent.shard_indices.iter()
+            .filter(|&(name, &index)| active.contains(name) && index < count)
+            .map(|(name, &index)| (name.clone(), index))
+            .collect::<BTreeMap<_, _>>();
+        for name in &active {
+            if !indices.contains_key(name) {
+                let free = (0..count).find(|i| !indices.values().any(|v| v == i)).unwrap();
+                indices.insert(name.clone(), free);
+            }
+        }
+        let shard = Shard {
+            collector: name.to_string(),
+            index: indices[name],
+            count,
+        };
+        persistent.shard_indices = indices;
+        persistent.write().expect("successful encode");
+        shard
+    }
+
     pub fn missing_commits(&self) -> Result<Vec<(Commit, MissingReason)>, Error> {
         let known_benchmarks = self.data.values()
             .rev()
@@ -529,7 +809,7 @@ impl InputData {
         missing.reverse();
 
         let mut commits = self.persistent.lock().try_commits.iter()
-            .flat_map(|TryCommit { sha, parent_sha, .. }| {
+            .flat_map(|TryCommit { sha, parent_sha, parent_reused, .. }| {
                 let mut ret = Vec::new();
                 ret.push(
                     (
@@ -537,17 +817,24 @@ impl InputData {
                         MissingReason::TryCommit,
                     )
                 );
-                if let Some(commit) = self.commits.iter().find(|c| c.sha == *parent_sha) {
-                    ret.push((
-                        Commit { sha: commit.sha.clone(), date: Date(commit.date.clone()) },
-                        MissingReason::TryParent
-                    ));
-                } else {
-                    warn!("could not find parent_sha {:?}", parent_sha);
+                // A reused parent is only benchmarked again if it's missing
+                // benchmarks, like any other master commit.
+                if !*parent_reused {
+                    if let Some(commit) = self.commits.iter().find(|c| c.sha == *parent_sha) {
+                        ret.push((
+                            Commit { sha: commit.sha.clone(), date: Date(commit.date.clone()) },
+                            MissingReason::TryParent
+                        ));
+                    } else {
+                        warn!("could not find parent_sha {:?}", parent_sha);
+                    }
                 }
                 ret
             })
-            .filter(|c| !have.contains_key(&c.0.sha)) // we may have not updated the try-commits file
+            // We may have not updated the try-commits file. The results of try
+            // commits are also pushed while they're being collected, before
+            // the duration of the collection is known; those aren't done yet.
+            .filter(|c| have.get(&c.0.sha).map_or(true, |cd| !cd.is_complete()))
             .chain(missing)
             .collect::<Vec<_>>();
 
@@ -575,8 +862,9 @@ struct AssociatedData<'a> {
     commit: &'a Commit,
     data: &'a BTreeMap<Commit, CommitData>,
     commits: &'a [Commit],
-    commit_map: &'a HashMap<Commit, usize>,
-    interpolated: &'a mut HashMap<String, Vec<Interpolation>>,
+    artifacts: &'a Interner<ArtifactIdx>,
+    benchmarks: &'a mut Interner<BenchmarkIdx>,
+    interpolated: &'a mut HashMap<ArtifactIdx, Vec<Interpolation>>,
 
     // By benchmark name
     last_seen_commit: &'a [HashMap<String, Commit>],
@@ -591,7 +879,9 @@ struct AssociatedData<'a> {
 // This function can assume that the benchmark exists and is restricted to filling in runs within
 // the benchmark.
 fn fill_benchmark_runs(benchmark: &mut Benchmark, missing_runs: Vec<&RunId>, data: &mut AssociatedData) {
-    let commit_idx = data.commit_map[data.commit];
+    let artifact = data.artifacts.get(&data.commit.sha).unwrap();
+    let commit_idx = artifact.index();
+    let benchmark_idx = data.benchmarks.intern(&benchmark.name);
     for missing_run in missing_runs {
         let time_start = ::std::time::Instant::now();
         let start = data.last_seen_run[commit_idx]
@@ -605,7 +895,7 @@ fn fill_benchmark_runs(benchmark: &mut Benchmark, missing_runs: Vec<&RunId>, dat
         assert_ne!(start_commit.as_ref(), Some(data.commit));
         assert_ne!(end_commit.as_ref(), Some(data.commit));
 
-        let mut interpolations = data.interpolated.entry(data.commit.sha.clone()).or_insert_with(Vec::new);
+        let mut interpolations = data.interpolated.entry(artifact).or_insert_with(Vec::new);
         let run = match (start, end) {
             (Some(srun), Some(erun)) => {
                 let distance = (commit_idx - srun.0 - 1) + (erun.0 - commit_idx - 1);
@@ -614,7 +904,7 @@ fn fill_benchmark_runs(benchmark: &mut Benchmark, missing_runs: Vec<&RunId>, dat
                 let mut interpolated_run = srun.1.clone();
                 interpolated_run.stats = interpolated_stats;
                 interpolations.push(Interpolation {
-                    benchmark: benchmark.name.clone(),
+                    benchmark: benchmark_idx,
                     run: Some(missing_run.clone()),
                     from: InterpolationSource::Middle(
                         start_commit.unwrap(),
@@ -625,7 +915,7 @@ fn fill_benchmark_runs(benchmark: &mut Benchmark, missing_runs: Vec<&RunId>, dat
             }
             (Some(srun), None) => {
                 interpolations.push(Interpolation {
-                    benchmark: benchmark.name.clone(),
+                    benchmark: benchmark_idx,
                     run: Some(missing_run.clone()),
                     from: InterpolationSource::First(
                         start_commit.unwrap(),
@@ -635,7 +925,7 @@ fn fill_benchmark_runs(benchmark: &mut Benchmark, missing_runs: Vec<&RunId>, dat
             }
             (None, Some(erun)) => {
                 interpolations.push(Interpolation {
-                    benchmark: benchmark.name.clone(),
+                    benchmark: benchmark_idx,
                     run: Some(missing_run.clone()),
                     from: InterpolationSource::Last(
                         end_commit.unwrap(),
@@ -652,10 +942,11 @@ fn fill_benchmark_runs(benchmark: &mut Benchmark, missing_runs: Vec<&RunId>, dat
     }
 }
 
-fn fill_benchmark_data(benchmark_name: &str, data: &mut AssociatedData) -> Option<Vec<Run>> {
-    let commit_idx = data.commit_map[data.commit];
-    let interpolation_entry =
-        data.interpolated.entry(data.commit.sha.clone()).or_insert_with(Vec::new);
+fn fill_benchmark_data(benchmark_name: &str, data: &mut AssociatedData) -> Option<Benchmark> {
+    let artifact = data.artifacts.get(&data.commit.sha).unwrap();
+    let commit_idx = artifact.index();
+    let benchmark_idx = data.benchmarks.intern(benchmark_name);
+    let interpolation_entry = data.interpolated.entry(artifact).or_insert_with(Vec::new);
 
     let start = if let Some(needle) = data.last_seen_commit[commit_idx].get(benchmark_name) {
         let commit = needle.clone();
@@ -680,8 +971,9 @@ fn fill_benchmark_data(benchmark_name: &str, data: &mut AssociatedData) -> Optio
         // This code ignores the case where a run is
         // absent in start or end. This is handled later.
         (Some(start), Some(end)) => {
-            let distance = data.commit_map[&end.0] - data.commit_map[&start.0];
-            let from_start = commit_idx - data.commit_map[&start.0];
+            let start_idx = data.artifacts.get(&start.0.sha).unwrap().index();
+            let distance = data.artifacts.get(&end.0.sha).unwrap().index() - start_idx;
+            let from_start = commit_idx - start_idx;
             let start_runs = &start.1.runs;
             let end_runs = &end.1.runs;
 
@@ -701,11 +993,15 @@ fn fill_benchmark_data(benchmark_name: &str, data: &mut AssociatedData) -> Optio
 
             interpolation_entry.push(
                 Interpolation {
-                    benchmark: benchmark_name.to_owned(),
+                    benchmark: benchmark_idx,
                     run: None,
                     from: InterpolationSource::Middle(start.0, end.0),
                 });
-            return Some(interpolated_runs);
+            return Some(Benchmark {
+                name: benchmark_name.to_owned(),
+                runs: interpolated_runs,
+                category: end.1.category,
+            });
         }
 
         // This hole is unbounded to the right, so
@@ -714,11 +1010,11 @@ fn fill_benchmark_data(benchmark_name: &str, data: &mut AssociatedData) -> Optio
         (Some(start), None) => {
             interpolation_entry.push(
                 Interpolation {
-                    benchmark: benchmark_name.to_owned(),
+                    benchmark: benchmark_idx,
                     run: None,
                     from: InterpolationSource::Last(start.0),
                 });
-            return Some(start.1.runs);
+            return Some(start.1);
         }
 
         // This hole is unbounded to the left, so
@@ -727,11 +1023,11 @@ fn fill_benchmark_data(benchmark_name: &str, data: &mut AssociatedData) -> Optio
         (None, Some(end)) => {
             interpolation_entry.push(
                 Interpolation {
-                    benchmark: benchmark_name.to_owned(),
+                    benchmark: benchmark_idx,
                     run: None,
                     from: InterpolationSource::First(end.0),
                 });
-            return Some(end.1.runs);
+            return Some(end.1);
         }
 
         // No data for this benchmark was found to
@@ -11,12 +11,23 @@ extern crate env_logger;
 extern crate site;
 
 use site::{load, server, util};
+use site::snapshot::Snapshot;
 use std::env;
 
 fn main() {
     env_logger::init();
 
-    let data = load::InputData::from_fs(&util::get_repo_path().unwrap()).unwrap();
+    let data = match util::get_database_url() {
+        Some(url) => match Snapshot::from_env(&url) {
+            Some(snapshot) => load::InputData::from_snapshot(snapshot.load().unwrap()).unwrap(),
+            // The data repo, if given, fills an empty database.
+            None => {
+                let repo_path = util::get_repo_path().ok();
+                load::InputData::from_db(&url, repo_path.as_ref().map(String::as_str)).unwrap()
+            }
+        },
+        None => load::InputData::from_fs(&util::get_repo_path().unwrap()).unwrap(),
+    };
 
     let port = env::var("PORT")
         .ok()
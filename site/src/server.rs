@@ -8,15 +8,18 @@
 // except according to those terms.
 
 use std::str;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::sync::Arc;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::path::Path;
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
 use std::cmp::Ordering;
 use std::borrow::Cow;
+use std::mem;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use serde::Serialize;
 use serde::de::DeserializeOwned;
@@ -29,55 +32,124 @@ use hyper::header::{AcceptEncoding, CacheControl, CacheDirective, Encoding};
 use hyper::header::{ContentEncoding, ContentLength, ContentType, Authorization, Bearer};
 use hyper::mime;
 use hyper::server::{Http, Request, Response, Service};
-use url::Url;
+use url::{form_urlencoded, Url};
 use flate2::Compression;
 use flate2::write::GzEncoder;
 use semver::Version;
 use failure::Error;
-use ring::{hmac, digest};
+use ring::{constant_time, hmac, digest};
 use hex;
 use regex::Regex;
 use reqwest;
 use reqwest::header::USER_AGENT;
 
+use analysis;
 use git;
 use util::{self, get_repo_path, Interpolate};
-pub use api::{self, github, status, nll_dashboard, dashboard, data, days, graph, info, CommitResponse, ServerResult};
-use collector::{Date, Run, version_supports_incremental};
-use collector::api::collected;
-use load::{Config, CommitData, InputData, TryCommit};
-use antidote::RwLock;
+use index;
+use intern::Idx;
+pub use api::{self, artifact_sizes, bootstrap, collection_times, github, status, nll_dashboard,
+              compare, dashboard, data, days, flavors, graph, graphs, info, runtime,
+              samples, self_profile, CommitResponse, ServerResult};
+use collector::{ArtifactMetadata, ArtifactType, Benchmark, Bound, Category, Collection, Date,
+                Flavor, Run, Shard, DEFAULT_STAT, ice_message, version_supports_incremental};
+use collector::api::{collected, next_commit, upload};
+use load::{Config, CommitData, InputData, MissingReason, PartialUpload, RuntimeData, TryCommit,
+           PRIMARY_TRIPLE};
+use collector::db::Results;
+use antidote::{Mutex, RwLock};
 use load::CurrentState;
+use snapshot::Snapshot;
 
 header! { (HubSignature, "X-Hub-Signature") => [String] }
 
 static INTERPOLATED_COLOR: &str = "#fcb0f1";
 
+/// The endpoints that store results or hand out work, which a site serving a
+/// snapshot doesn't take.
+const WRITE_PATHS: &[&str] = &[
+    "/perf/next_commit",
+    "/perf/onpush",
+    "/perf/collected",
+    "/perf/upload",
+    "/perf/github-hook",
+];
+
 /// Data associated with a specific date
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DateData {
     pub date: Date,
     pub commit: String,
     pub data: HashMap<String, Vec<(String, Run, f64)>>,
+    /// The machine that collected `data`, if recorded.
+    #[serde(default)]
+    pub collection: Option<Collection>,
+    /// The category of each benchmark (without its build kind suffix), so
+    /// that stress tests can be told apart from real-world code.
+    #[serde(default)]
+    pub categories: HashMap<String, Category>,
+    /// What the commit is, if recorded, e.g. the PR it merged.
+    #[serde(default)]
+    pub metadata: Option<ArtifactMetadata>,
 }
 
 impl DateData {
     pub fn for_day(commit: &CommitData, stat: &str) -> DateData {
+        DateData::with_values(commit, stat, |benchmark| {
+            benchmark.runs.iter().map(|run| run.get_stat(stat)).collect()
+        })
+    }
+
+    /// Like `for_day`, for a commit of the interpolated data, whose values
+    /// are looked up in the index rather than searched for by name.
+    pub fn for_interpolated_day(data: &InputData, commit: &CommitData, stat: &str) -> DateData {
+        let values = data.artifacts.get(&commit.commit.sha)
+            .map(|artifact| &data.values[artifact.index()]);
+        let metric = data.metrics.get(stat);
+        DateData::with_values(commit, stat, |benchmark| {
+            let mut run_values = vec![None; benchmark.runs.len()];
+            let key = data.benchmarks.get(&benchmark.name).and_then(|b| Some((b, metric?)));
+            if let Some(values) = key.and_then(|key| values?.get(&key)) {
+                for &(run_idx, value) in values {
+                    run_values[run_idx] = Some(value);
+                }
+            }
+            run_values
+        })
+    }
+
+    /// The `DateData` of `commit`, given the values of `stat` of the runs of
+    /// each of its benchmarks.
+    fn with_values<F>(commit: &CommitData, stat: &str, mut run_values: F) -> DateData
+        where F: FnMut(&Benchmark) -> Vec<Option<f64>>
+    {
         let benchmarks = commit.benchmarks.values().filter_map(|v| v.as_ref().ok());
-        let mut out = HashMap::with_capacity(commit.benchmarks.len() * 3);
+        let mut out = HashMap::with_capacity(commit.benchmarks.len() * 4);
+        let mut categories = HashMap::with_capacity(commit.benchmarks.len());
         for benchmark in benchmarks {
+            categories.insert(benchmark.name.clone(), benchmark.category);
+            let run_values = run_values(benchmark);
             let mut runs_check = Vec::with_capacity(benchmark.runs.len() / 3);
             let mut runs_opt = Vec::with_capacity(benchmark.runs.len() / 3);
             let mut runs_debug = Vec::with_capacity(benchmark.runs.len() / 3);
-            for run in &benchmark.runs {
-                let v = if run.release {
+            let mut runs_doc = Vec::new();
+            let mut runs_opt_debuginfo = Vec::new();
+            let mut runs_debug_lto = Vec::new();
+            for (run, value) in benchmark.runs.iter().zip(run_values) {
+                let v = if run.release && run.debuginfo {
+                    &mut runs_opt_debuginfo
+                } else if run.release {
                     &mut runs_opt
                 } else if run.check {
                     &mut runs_check
+                } else if run.doc {
+                    &mut runs_doc
+                } else if run.lto {
+                    &mut runs_debug_lto
                 } else {
                     &mut runs_debug
                 };
-                if let Some(mut value) = run.get_stat(stat) {
+                if let Some(mut value) = value {
                     if stat == "cpu-clock" {
                         // convert to seconds; perf records it in milliseconds
                         value /= 1000.0;
@@ -94,12 +166,24 @@ impl DateData {
             if !runs_debug.is_empty() {
                 out.insert(benchmark.name.clone() + "-debug", runs_debug);
             }
+            if !runs_doc.is_empty() {
+                out.insert(benchmark.name.clone() + "-doc", runs_doc);
+            }
+            if !runs_opt_debuginfo.is_empty() {
+                out.insert(benchmark.name.clone() + "-opt-debuginfo", runs_opt_debuginfo);
+            }
+            if !runs_debug_lto.is_empty() {
+                out.insert(benchmark.name.clone() + "-debug-lto", runs_debug_lto);
+            }
         }
 
         DateData {
             date: commit.commit.date,
             commit: commit.commit.sha.clone(),
             data: out,
+            collection: commit.collection.clone(),
+            categories,
+            metadata: commit.metadata.clone(),
         }
     }
 }
@@ -135,23 +219,35 @@ pub fn handle_nll_dashboard(
 }
 
 pub fn handle_info(data: &InputData) -> info::Response {
+    let mut triples = data.triple_data.keys()
+        .map(|&(_, ref triple, _)| triple.clone())
+        .collect::<BTreeSet<_>>();
+    triples.insert(PRIMARY_TRIPLE.to_string());
     info::Response {
         crates: data.crate_list.clone(),
         stats: data.stats_list.clone(),
+        triples,
         as_of: data.last_date,
     }
 }
 
-fn average(v: &[f64]) -> f64 {
-    (v.iter().sum::<f64>() / v.len() as f64 * 10.0).round() / 10.0
+/// The geometric mean of `v`, rounded like `round`; NaN if `v` is empty.
+fn geometric_mean(v: &[f64]) -> f64 {
+    round((v.iter().map(|v| v.ln()).sum::<f64>() / v.len() as f64).exp())
 }
 
 fn round(v: f64) -> f64 {
     (v * 10.0).round() / 10.0
 }
 
-pub fn handle_dashboard(data: &InputData) -> dashboard::Response {
-    let mut versions = data.artifact_data.keys().cloned().collect::<Vec<_>>();
+/// The ids of the published stable releases, oldest first, and the beta
+/// last. Nightlies are left out; they are too many to show next to each
+/// other.
+fn sorted_versions(data: &InputData) -> Vec<String> {
+    let mut versions = data.artifact_data.keys()
+        .filter(|id| *id == "beta" || id.parse::<Version>().is_ok())
+        .cloned()
+        .collect::<Vec<_>>();
     versions.sort_by(|a, b| {
         match (a.parse::<Version>().ok(), b.parse::<Version>().ok()) {
             (Some(a), Some(b)) => a.cmp(&b),
@@ -166,121 +262,105 @@ pub fn handle_dashboard(data: &InputData) -> dashboard::Response {
             }
         }
     });
+    versions
+}
 
-    versions.push(format!("master: {}", &data.data(Interpolate::Yes).keys().last().unwrap().sha[0..8]));
-
-    let mut check_clean_average = Vec::new();
-    let mut check_base_incr_average = Vec::new();
-    let mut check_clean_incr_average = Vec::new();
-    let mut check_println_incr_average = Vec::new();
-    let mut debug_clean_average = Vec::new();
-    let mut debug_base_incr_average = Vec::new();
-    let mut debug_clean_incr_average = Vec::new();
-    let mut debug_println_incr_average = Vec::new();
-    let mut opt_clean_average = Vec::new();
-    let mut opt_base_incr_average = Vec::new();
-    let mut opt_clean_incr_average = Vec::new();
-    let mut opt_println_incr_average = Vec::new();
-
-    let benchmark_names = data.artifact_data["beta"].benchmarks.keys()
-        .filter(|key| data.artifact_data["beta"].benchmarks[*key].is_ok())
+/// Summarizes the compile times of every stable release, the beta and the
+/// latest master commit: for each profile and scenario, the geometric mean of
+/// the wall times of the benchmarks that all of them have results for, so that
+/// each is measured on the same code.
+pub fn handle_dashboard(data: &InputData) -> dashboard::Response {
+    let mut versions = sorted_versions(data).into_iter()
+        .map(|id| {
+            let benchmarks = &data.artifact_data[&id].benchmarks;
+            (id, benchmarks)
+        })
         .collect::<Vec<_>>();
-    for version in &versions {
-        let mut check_clean_points = Vec::new();
-        let mut check_base_incr_points = Vec::new();
-        let mut check_clean_incr_points = Vec::new();
-        let mut check_println_incr_points = Vec::new();
-        let mut debug_clean_points = Vec::new();
-        let mut debug_base_incr_points = Vec::new();
-        let mut debug_clean_incr_points = Vec::new();
-        let mut debug_println_incr_points = Vec::new();
-        let mut opt_clean_points = Vec::new();
-        let mut opt_base_incr_points = Vec::new();
-        let mut opt_clean_incr_points = Vec::new();
-        let mut opt_println_incr_points = Vec::new();
-
-        let mut benches = if version.starts_with("master") {
-            let data = data.data(Interpolate::Yes).values().last().unwrap();
-            let benches = data.benchmarks.iter()
-                .filter(|(name, _)| benchmark_names.contains(name)).collect::<Vec<_>>();
-            assert_eq!(benches.len(), benchmark_names.len());
-            benches
-        } else {
-            data.artifact_data[version].benchmarks.iter().collect::<Vec<_>>()
-        };
-        for (_, bench) in benches {
-            let bench = match bench {
-                Ok(v) => v,
-                Err(_) => continue,
-            };
+    if let Some((commit, cd)) = data.data(Interpolate::Yes).iter().last() {
+        versions.push((format!("master: {}", &commit.sha[0..8]), &cd.benchmarks));
+    }
 
-            macro_rules! extend {
-                ($v:ident, $r: ident, $cond: expr) => {
-                    let run = bench.runs.iter().find(|$r| $cond);
-                    if let Some(stat) = run.and_then(|r| r.get_stat("wall-time")) {
-                        $v.push(stat);
-                    }
-                }
+    let benchmark_names = versions.iter()
+        .map(|&(_, benchmarks)| {
+            benchmarks.iter()
+                .filter(|&(_, result)| result.is_ok())
+                .map(|(name, _)| name)
+                .collect::<BTreeSet<_>>()
+        })
+        .fold(None, |common: Option<BTreeSet<&String>>, names| Some(match common {
+            Some(common) => common.intersection(&names).cloned().collect(),
+            None => names,
+        }))
+        .unwrap_or_default();
+
+    // The extra build kinds (debuginfo and LTO) aren't part of the dashboard.
+    fn is_check(r: &Run) -> bool { r.check }
+    fn is_debug(r: &Run) -> bool { !r.check && !r.release && !r.doc && !r.lto }
+    fn is_opt(r: &Run) -> bool { r.release && !r.debuginfo }
+    let profiles: [fn(&Run) -> bool; 3] = [is_check, is_debug, is_opt];
+    let scenarios: [fn(&Run) -> bool; 4] =
+        [Run::is_clean, Run::is_base_incr, Run::is_clean_incr, Run::is_println_incr];
+
+    let mut means = vec![vec![Vec::new(); scenarios.len()]; profiles.len()];
+    for &(ref version, benchmarks) in &versions {
+        let incremental = version_supports_incremental(version);
+        for (profile, profile_means) in profiles.iter().zip(&mut means) {
+            for (s, scenario) in scenarios.iter().enumerate() {
+                // Only the first scenario, a clean build, isn't incremental.
+                let times = if s == 0 || incremental {
+                    benchmark_names.iter()
+                        .filter_map(|&name| {
+                            let benchmark = benchmarks[name].as_ref().ok()?;
+                            benchmark.runs.iter()
+                                .find(|r| profile(r) && scenario(r))?
+                                .get_stat("wall-time")
+                        })
+                        .collect::<Vec<_>>()
+                } else {
+                    Vec::new()
+                };
+                profile_means[s].push(geometric_mean(&times));
             }
-
-            extend!(check_clean_points, r, r.is_clean() && r.check);
-            extend!(debug_clean_points, r, r.is_clean() && !r.check && !r.release);
-            extend!(opt_clean_points, r, r.is_clean() && r.release);
-            if version_supports_incremental(version) {
-                extend!(check_base_incr_points, r, r.is_base_incr() && r.check);
-                extend!(check_clean_incr_points, r, r.is_clean_incr() && r.check);
-                extend!(check_println_incr_points, r, r.is_println_incr() && r.check);
-                extend!(debug_base_incr_points, r, r.is_base_incr() && !r.check && !r.release);
-                extend!(debug_clean_incr_points, r, r.is_clean_incr() && !r.check && !r.release);
-                extend!(debug_println_incr_points, r, r.is_println_incr() && !r.check && !r.release);
-                extend!(opt_base_incr_points, r, r.is_base_incr() && r.release);
-                extend!(opt_clean_incr_points, r, r.is_clean_incr() && r.release);
-                extend!(opt_println_incr_points, r, r.is_println_incr() && r.release);
-            }
-        }
-
-        check_clean_average.push(average(&check_clean_points));
-        check_base_incr_average.push(average(&check_base_incr_points));
-        check_clean_incr_average.push(average(&check_clean_incr_points));
-        check_println_incr_average.push(average(&check_println_incr_points));
-        debug_clean_average.push(average(&debug_clean_points));
-        debug_base_incr_average.push(average(&debug_base_incr_points));
-        debug_clean_incr_average.push(average(&debug_clean_incr_points));
-        debug_println_incr_average.push(average(&debug_println_incr_points));
-        opt_clean_average.push(average(&opt_clean_points));
-        opt_base_incr_average.push(average(&opt_base_incr_points));
-        opt_clean_incr_average.push(average(&opt_clean_incr_points));
-        opt_println_incr_average.push(average(&opt_println_incr_points));
+        }
     }
 
+    // The beta is labelled with the release it was, if known.
+    let versions = versions.into_iter()
+        .map(|(id, _)| match data.artifact_data.get(&id).and_then(|a| a.release.as_ref()) {
+            Some(release) if id == "beta" => {
+                let version = release.version.split_whitespace().next().unwrap_or("");
+                format!("beta: {}", version)
+            }
+            _ => id,
+        })
+        .collect();
+
+    let mut cases = means.into_iter().map(|means| {
+        let mut means = means.into_iter();
+        // In the order of `scenarios`.
+        dashboard::Cases {
+            clean_averages: means.next().unwrap(),
+            base_incr_averages: means.next().unwrap(),
+            clean_incr_averages: means.next().unwrap(),
+            println_incr_averages: means.next().unwrap(),
+        }
+    });
+    // In the order of `profiles`.
     dashboard::Response {
         versions,
-        check: dashboard::Cases {
-            clean_averages: check_clean_average,
-            base_incr_averages: check_base_incr_average,
-            clean_incr_averages: check_clean_incr_average,
-            println_incr_averages: check_println_incr_average,
-        },
-        debug: dashboard::Cases {
-            clean_averages: debug_clean_average,
-            base_incr_averages: debug_base_incr_average,
-            clean_incr_averages: debug_clean_incr_average,
-            println_incr_averages: debug_println_incr_average,
-        },
-        opt: dashboard::Cases {
-            clean_averages: opt_clean_average,
-            base_incr_averages: opt_base_incr_average,
-            clean_incr_averages: opt_clean_incr_average,
-            println_incr_averages: opt_println_incr_average,
-        },
+        check: cases.next().unwrap(),
+        debug: cases.next().unwrap(),
+        opt: cases.next().unwrap(),
     }
 }
 
 pub fn handle_status_page(data: &InputData) -> status::Response {
-    let last_commit = data.data(Interpolate::No).iter().last().unwrap();
+    let last_commit = data.data(Interpolate::No).iter().last();
 
-    let mut benchmark_state = last_commit.1.benchmarks.iter()
-        .map(|(name, res)| {
+    // Without any commits yet, there's nothing to report but what's missing.
+    let mut benchmark_state = last_commit.into_iter()
+        .flat_map(|(_, cd)| cd.benchmarks.iter().map(move |b| (cd, b)))
+        .map(|(cd, (name, res))| {
             let error = res.as_ref().err().cloned();
             let mut msg = String::new();
             if let Some(error) = error.as_ref() {
@@ -292,29 +372,137 @@ pub fn handle_status_page(data: &InputData) -> status::Response {
 
                 msg.push_str(&log);
             }
+            // Results collected before ICEs were recorded separately only
+            // have them in the error.
+            let ice = cd.ices.get(name).cloned()
+                .or_else(|| error.as_ref().and_then(|_| ice_message(&msg)));
+            let outliers = cd.outliers.iter()
+                .filter(|o| o.benchmark == *name)
+                .cloned()
+                .collect();
             status::BenchmarkStatus {
                 name: name.clone(),
                 success: res.is_ok(),
                 error: error.as_ref().map(|_| msg),
+                ice,
+                outliers,
             }
         }).collect::<Vec<_>>();
 
-    benchmark_state.sort_by_key(|s| s.error.is_some());
+    benchmark_state.sort_by_key(|s| (s.error.is_some(), s.ice.is_some()));
     benchmark_state.reverse();
 
     let missing = data.missing_commits().unwrap();
     let current = data.persistent.lock().current.clone();
 
     status::Response {
-        last_commit: last_commit.0.clone(),
+        last_commit: last_commit.map(|(commit, _)| commit.clone()),
         benchmarks: benchmark_state,
         missing,
         current: current,
     }
 }
 
-pub fn handle_next_commit(data: &InputData) -> Option<String> {
-    data.missing_commits().ok().and_then(|c| c.into_iter().next()).map(|c| c.0.sha)
+/// How many of the latest commits the sensitivity of the benchmarks is
+/// judged by.
+const SENSITIVITY_COMMITS: usize = 30;
+
+/// The order to run the benchmarks of the try commit `sha` in: those named in
+/// the description of its PR first, then the others by how much their results
+/// usually change from one commit to the next. The benchmarks most likely to
+/// show a difference are thus done first.
+fn benchmark_priority(data: &InputData, sha: &str) -> Vec<String> {
+    let description = data.persistent.lock().try_commits.iter()
+        .find(|c| c.sha == sha)
+        .and_then(|c| c.issue.body.clone())
+        .unwrap_or_default();
+    let words = description
+        .split(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_' || c == '.'))
+        .collect::<BTreeSet<_>>();
+
+    let commits = data.data(Interpolate::No).values()
+        .rev()
+        .take(SENSITIVITY_COMMITS + 1)
+        .collect::<Vec<_>>();
+    let mut changes: HashMap<&str, Vec<f64>> = HashMap::new();
+    for pair in commits.windows(2) {
+        let (new, old) = (pair[0], pair[1]);
+        for (name, result) in &new.benchmarks {
+            let (new_benchmark, old_benchmark) = match (result, old.benchmarks.get(name)) {
+                (&Ok(ref new), Some(&Ok(ref old))) => (new, old),
+                _ => continue,
+            };
+            // A benchmark is as sensitive as its most sensitive run.
+            let change = new_benchmark.runs.iter()
+                .filter_map(|run| {
+                    let old_run = old_benchmark.runs.iter().find(|r| r.id() == run.id())?;
+                    let (a, b) = (old_run.get_stat(DEFAULT_STAT)?, run.get_stat(DEFAULT_STAT)?);
+                    if a > 0.0 { Some(((b - a) / a).abs()) } else { None }
+                })
+                .fold(0.0, f64::max);
+            changes.entry(name).or_insert_with(Vec::new).push(change);
+        }
+    }
+    let sensitivity = |name: &str| {
+        changes.get(name).map_or(0.0, |c| c.iter().sum::<f64>() / c.len() as f64)
+    };
+
+    let mut priority = data.crate_list.iter().cloned().collect::<Vec<_>>();
+    priority.sort_by(|a, b| {
+        words.contains(b.as_str()).cmp(&words.contains(a.as_str()))
+            .then_with(|| sensitivity(b).partial_cmp(&sensitivity(a)).unwrap_or(Ordering::Equal))
+    });
+    priority
+}
+
+/// Whether the collection of `shard` of the commit `sha` has finished.
+fn shard_done(data: &InputData, sha: &str, shard: &Shard) -> bool {
+    data.data(Interpolate::No).values().filter(|cd| cd.commit.sha == sha).any(|cd| {
+        cd.shards.iter().chain(cd.collection.as_ref()).any(|c| {
+            c.duration.is_some() && c.shard.as_ref().map_or(false, |s| {
+                s.index == shard.index && s.count == shard.count
+            })
+        })
+    })
+}
+
+/// The commit a collector should benchmark next. A named collector is also
+/// assigned its shard of the benchmarks, and is only given commits whose
+/// shard it hasn't collected yet.
+pub fn handle_next_commit(collector: Option<&str>, data: &InputData) -> next_commit::Response {
+    let shard = collector.map(|name| data.register_collector(name));
+    let next = data.missing_commits().ok().and_then(|commits| {
+        commits.into_iter().find(|&(ref commit, _)| {
+            shard.as_ref().map_or(true, |shard| !shard_done(data, &commit.sha, shard))
+        })
+    });
+    let priority = match next {
+        Some((ref commit, MissingReason::TryCommit)) => benchmark_priority(data, &commit.sha),
+        _ => Vec::new(),
+    };
+    let metadata = next.as_ref().and_then(|&(ref commit, _)| commit_metadata(data, &commit.sha));
+    next_commit::Response {
+        commit: next.map(|c| c.0.sha),
+        priority,
+        shard,
+        metadata,
+    }
+}
+
+/// The metadata of the try commit or bors merge `sha`, if it's known.
+fn commit_metadata(data: &InputData, sha: &str) -> Option<ArtifactMetadata> {
+    if let Some(try_commit) = data.persistent.lock().try_commits.iter().find(|c| c.sha == sha) {
+        return Some(ArtifactMetadata {
+            kind: ArtifactType::Try,
+            date: None,
+            pr: try_commit.issue.number,
+            parent_sha: Some(try_commit.parent_sha.clone()),
+        });
+    }
+    let index = data.commits.iter().position(|c| c.sha == sha)?;
+    let commit = &data.commits[index];
+    let parent = index.checked_sub(1).map(|i| data.commits[i].sha.clone());
+    Some(ArtifactMetadata::master(Date(commit.date), &commit.summary, parent))
 }
 
 pub fn handle_graph(body: graph::Request, data: &InputData) -> ServerResult<graph::Response> {
@@ -327,8 +515,8 @@ pub fn handle_graph(body: graph::Request, data: &InputData) -> ServerResult<grap
         data,
     )?.0;
 
-    // crate list * 3 because we have check, debug, and opt variants.
-    let mut result: HashMap<_, HashMap<Cow<str>, _>> = HashMap::with_capacity(data.crate_list.len() * 3);
+    // crate list * 4 because we have check, debug, opt, and doc variants.
+    let mut result: HashMap<_, HashMap<Cow<str>, _>> = HashMap::with_capacity(data.crate_list.len() * 4);
     let elements = out.len();
     let mut last_commit = None;
     let mut initial_debug_base_compile = None;
@@ -366,9 +554,11 @@ pub fn handle_graph(body: graph::Request, data: &InputData) -> ServerResult<grap
                     y: if body.absolute { value } else { percent },
                     x: date_data.date.0.timestamp() as u64 * 1000, // all dates are since 1970
                     color: {
-                        data.interpolated.get(&commit)
+                        data.artifacts.get(&commit)
+                            .and_then(|artifact| data.interpolated.get(&artifact))
                             .map(|c| c.iter().any(|interpolation| {
-                                if !bench_name.starts_with(&interpolation.benchmark) {
+                                let benchmark = data.benchmarks.name(interpolation.benchmark);
+                                if !bench_name.starts_with(benchmark) {
                                     return false;
                                 }
                                 if let Some(run_name) = &interpolation.run {
@@ -382,12 +572,18 @@ pub fn handle_graph(body: graph::Request, data: &InputData) -> ServerResult<grap
                     }
                 });
             }
+            // The doc runs have no println patch, so rustdoc isn't
+            // summarized.
             if base_compile && is_println_incr {
                 for (_, run, value) in runs {
                     // TODO: Come up with a way to summarize non-standard patches
                     if run.state.is_patch() && !run.is_println_incr() {
                         continue;
                     }
+                    // Nor are the extra build kinds summarized.
+                    if run.debuginfo || run.lto {
+                        continue;
+                    }
                     summary_points
                         .entry((run.release, run.check, run.state.erase_path()))
                         .or_insert_with(Vec::new)
@@ -439,7 +635,8 @@ pub fn handle_graph(body: graph::Request, data: &InputData) -> ServerResult<grap
                 y: if body.absolute { value } else { percent },
                 x: date_data.date.0.timestamp() as u64 * 1000, // all dates are since 1970
                 color: {
-                    data.interpolated.get(&commit)
+                    data.artifacts.get(&commit)
+                        .and_then(|artifact| data.interpolated.get(&artifact))
                         .map(|c| c.iter().any(|interpolation| {
                             if let Some(run) = &interpolation.run {
                                 *run.name() == (state.name() + appendix)
@@ -457,7 +654,9 @@ pub fn handle_graph(body: graph::Request, data: &InputData) -> ServerResult<grap
 
     let mut maxes = HashMap::with_capacity(result.len());
     for (ref crate_name, ref benchmarks) in &result {
-        let name = crate_name.replace("-check", "").replace("-debug", "").replace("-opt", "");
+        let name = crate_name.replace("-opt-debuginfo", "").replace("-debug-lto", "")
+            .replace("-check", "").replace("-debug", "")
+            .replace("-opt", "").replace("-doc", "");
         let mut max = 0.0f32;
         for points in benchmarks.values() {
             for point in points {
@@ -478,6 +677,93 @@ pub fn handle_graph(body: graph::Request, data: &InputData) -> ServerResult<grap
     })
 }
 
+pub fn handle_graphs(body: graphs::Request, data: &InputData) -> ServerResult<graphs::Response> {
+    let metric = data.metrics.get(&body.stat)
+        .ok_or_else(|| format!("unknown stat {:?}", body.stat))?;
+    let wanted = |names: &[String], name: &str| {
+        names.is_empty() || names.iter().any(|n| n == name)
+    };
+    let range = util::data_range(&data, &body.start, &body.end, Interpolate::Yes)?;
+
+    // The values of each series, looked up in the index.
+    let mut series = BTreeMap::<(&str, &str, Cow<str>), Vec<graphs::Point>>::new();
+    for (commit, cd) in range {
+        let values = match data.artifacts.get(&commit.sha) {
+            Some(artifact) => &data.values[artifact.index()],
+            None => continue,
+        };
+        for (&(benchmark, _), runs) in values.iter().filter(|&(&(_, m), _)| m == metric) {
+            let name = data.benchmarks.name(benchmark);
+            if !wanted(&body.benchmarks, name) {
+                continue;
+            }
+            let benchmark_runs = match cd.benchmarks.get(name) {
+                Some(&Ok(ref benchmark)) => &benchmark.runs,
+                _ => continue,
+            };
+            for &(run_idx, value) in runs {
+                let run = &benchmark_runs[run_idx];
+                let scenario = run.state.name();
+                if !wanted(&body.profiles, run.profile()) || !wanted(&body.scenarios, &scenario) {
+                    continue;
+                }
+                series.entry((name, run.profile(), scenario)).or_insert_with(Vec::new)
+                    .push(graphs::Point {
+                        x: commit.date.0.timestamp() as u64 * 1000,
+                        y: value,
+                        commit: commit.sha.clone(),
+                    });
+            }
+        }
+    }
+
+    let series = series.into_iter().map(|((benchmark, profile, scenario), mut points)| {
+        match body.rolling_average {
+            Some(window) if window > 1 => {
+                let values = points.iter().map(|p| p.y).collect::<Vec<_>>();
+                for (i, point) in points.iter_mut().enumerate() {
+                    let window = &values[(i + 1).saturating_sub(window)..i + 1];
+                    point.y = window.iter().sum::<f64>() / window.len() as f64;
+                }
+            }
+            _ => {}
+        }
+        if !body.absolute {
+            let first = points.first().map_or(0.0, |p| p.y);
+            for point in &mut points {
+                point.y = if first == 0.0 { 0.0 } else { (point.y - first) / first * 100.0 };
+            }
+        }
+        if let Some(max_points) = body.max_points {
+            points = downsample(points, max_points);
+        }
+        graphs::Series {
+            benchmark: benchmark.to_string(),
+            profile: profile.to_string(),
+            scenario: scenario.into_owned(),
+            points,
+        }
+    }).collect();
+    Ok(graphs::Response { series })
+}
+
+/// Averages runs of consecutive `points` so that there are at most
+/// `max_points` of them, each at the date of the last in its run.
+fn downsample(points: Vec<graphs::Point>, max_points: usize) -> Vec<graphs::Point> {
+    if max_points == 0 || points.len() <= max_points {
+        return points;
+    }
+    let bucket = (points.len() + max_points - 1) / max_points;
+    points.chunks(bucket).map(|chunk| {
+        let last = chunk.last().unwrap();
+        graphs::Point {
+            x: last.x,
+            y: chunk.iter().map(|p| p.y).sum::<f64>() / chunk.len() as f64,
+            commit: last.commit.clone(),
+        }
+    }).collect()
+}
+
 fn handle_data(body: data::Request, data: &InputData) -> ServerResult<data::Response> {
     debug!(
         "handle_data: start = {:?}, end = {:?}",
@@ -487,7 +773,7 @@ fn handle_data(body: data::Request, data: &InputData) -> ServerResult<data::Resp
     let mut result = range
         .into_iter()
         .map(|(_, day)| day)
-        .map(|day| DateData::for_day(day, &body.stat))
+        .map(|day| DateData::for_interpolated_day(data, day, &body.stat))
         .collect::<Vec<_>>();
 
     if result.is_empty() {
@@ -512,11 +798,466 @@ fn handle_data(body: data::Request, data: &InputData) -> ServerResult<data::Resp
 }
 
 pub fn handle_days(body: days::Request, data: &InputData) -> ServerResult<days::Response> {
-    let a = util::find_commit(data, &body.start, true, Interpolate::No)?;
-    let b = util::find_commit(data, &body.end, false, Interpolate::No)?;
+    if let Some(ref triple) = body.triple {
+        if triple != PRIMARY_TRIPLE {
+            let a = util::find_triple_commit(data, triple, &body.start, true)?;
+            let b = util::find_triple_commit(data, triple, &body.end, false)?;
+            return Ok(days::Response {
+                a: DateData::for_day(a, &body.stat),
+                b: DateData::for_day(b, &body.stat),
+                machine_differences: differences(a, b),
+                reused_base: false,
+            });
+        }
+    }
+    let a = util::find_artifact(data, &body.start, true)?;
+    let b = util::find_artifact(data, &body.end, false)?;
+    let reused_base = data.persistent.lock().try_commits.iter().any(|c| {
+        c.sha == b.commit.sha && c.parent_sha == a.commit.sha && c.parent_reused
+    });
+    Ok(days::Response {
+        a: DateData::for_day(&a, &body.stat),
+        b: DateData::for_day(&b, &body.stat),
+        machine_differences: differences(&a, &b),
+        reused_base,
+    })
+}
+
+/// Compares a flavor of a commit, e.g. its alternative build against a
+/// newer LLVM, with its plain build.
+pub fn handle_flavors(body: flavors::Request, data: &InputData) -> ServerResult<days::Response> {
+    let plain = data.data(Interpolate::No).values()
+        .find(|cd| cd.commit.sha == body.commit)
+        .ok_or_else(|| format!("no results for {}", body.commit))?;
+    let flavored = data.flavor_data.get(&(body.commit.clone(), body.flavor))
+        .ok_or_else(|| {
+            format!("no results for the {} build of {}", body.flavor.name(), body.commit)
+        })?;
     Ok(days::Response {
-        a: DateData::for_day(a.1, &body.stat),
-        b: DateData::for_day(b.1, &body.stat),
+        a: DateData::for_day(plain, &body.stat),
+        b: DateData::for_day(flavored, &body.stat),
+        machine_differences: differences(plain, flavored),
+        reused_base: false,
+    })
+}
+
+/// Summarizes how much each flavor of a commit, e.g. a PGO or BOLT build,
+/// buys over its plain build across the real-world benchmarks of the suite.
+/// Stress tests are left out, as they'd skew the summary.
+pub fn handle_flavor_summary(body: flavors::summary::Request, data: &InputData)
+                             -> ServerResult<flavors::summary::Response> {
+    let plain = data.data(Interpolate::No).values()
+        .find(|cd| cd.commit.sha == body.commit)
+        .ok_or_else(|| format!("no results for {}", body.commit))?;
+    let flavors = data.flavor_data.iter()
+        .filter(|&(&(ref sha, _), _)| *sha == body.commit)
+        .map(|(&(_, flavor), flavored)| summarize_flavor(plain, flavor, flavored, &body.stat))
+        .collect();
+    Ok(flavors::summary::Response {
+        commit: body.commit.clone(),
+        flavors,
+    })
+}
+
+/// Tracks how a flavor of the commits, e.g. their alternative build with
+/// debug assertions, compares with their plain build, commit by commit.
+pub fn handle_flavor_history(body: flavors::history::Request, data: &InputData)
+                             -> ServerResult<flavors::history::Response> {
+    let plain = data.data(Interpolate::No).values()
+        .map(|cd| (cd.commit.sha.as_str(), cd))
+        .collect::<HashMap<_, _>>();
+    let mut points = data.flavor_data.iter()
+        .filter(|&(&(_, flavor), _)| flavor == body.flavor)
+        .filter_map(|(&(ref sha, flavor), flavored)| {
+            let plain = plain.get(sha.as_str())?;
+            Some(flavors::history::Point {
+                commit: sha.clone(),
+                date: plain.commit.date,
+                summary: summarize_flavor(plain, flavor, flavored, &body.stat),
+            })
+        })
+        .collect::<Vec<_>>();
+    points.sort_by_key(|point| point.date);
+    Ok(flavors::history::Response {
+        flavor: body.flavor,
+        points,
+    })
+}
+
+/// Compares the results of `flavor` of a commit with those of its `plain`
+/// build, over the real-world benchmarks.
+fn summarize_flavor(plain: &CommitData, flavor: Flavor, flavored: &CommitData, stat: &str)
+                    -> flavors::summary::FlavorSummary {
+    let mut ratios = Vec::new();
+    for (name, result) in &plain.benchmarks {
+        let (a, b) = match (result, flavored.benchmarks.get(name)) {
+            (&Ok(ref a), Some(&Ok(ref b))) if a.category.is_real_world() => (a, b),
+            _ => continue,
+        };
+        for run in &a.runs {
+            let a_stat = run.get_stat(stat);
+            let b_stat = b.runs.iter().find(|r| *r == run).and_then(|r| r.get_stat(stat));
+            if let (Some(a_stat), Some(b_stat)) = (a_stat, b_stat) {
+                if a_stat > 0.0 && b_stat > 0.0 {
+                    ratios.push(b_stat / a_stat);
+                }
+            }
+        }
+    }
+    let geomean_ratio = if ratios.is_empty() {
+        1.0
+    } else {
+        (ratios.iter().map(|r| r.ln()).sum::<f64>() / ratios.len() as f64).exp()
+    };
+    flavors::summary::FlavorSummary {
+        flavor,
+        runs: ratios.len(),
+        geomean_ratio,
+        improved: ratios.iter().filter(|&&r| r < 1.0).count(),
+        regressed: ratios.iter().filter(|&&r| r > 1.0).count(),
+        machine_differences: differences(plain, flavored),
+    }
+}
+
+/// How the collections of `a` and `b` differ, other than in the compiler
+/// they benchmarked.
+fn differences(a: &CommitData, b: &CommitData) -> Vec<String> {
+    let mut machine_differences = match (&a.collection, &b.collection) {
+        (&Some(ref a_collection), &Some(ref b_collection)) => {
+            let mut differences = Vec::new();
+            if let (Some(a_version), Some(b_version)) =
+                (a_collection.suite_version, b_collection.suite_version) {
+                if a_version != b_version {
+                    differences.push(format!(
+                        "benchmark suite: version {} vs. {}; the benchmarks may have \
+                         changed in between, so their results may not be comparable",
+                        a_version, b_version
+                    ));
+                }
+            }
+            differences.extend(a_collection.machine_differences(b_collection));
+            differences
+        }
+        _ => Vec::new(),
+    };
+    // The benchmarks of sharded commits may have been collected by different
+    // machines on either side.
+    let moved = a.collected_by.iter()
+        .filter(|&(benchmark, a_collector)| {
+            b.collected_by.get(benchmark).map_or(false, |b_collector| a_collector != b_collector)
+        })
+        .count();
+    if moved > 0 {
+        machine_differences.push(
+            format!("{} benchmarks were collected by different collectors", moved));
+    }
+    // Not a difference of the machines, but one that easily explains a
+    // change in the results.
+    if let (&Some(ref a_llvm), &Some(ref b_llvm)) = (&a.llvm_version, &b.llvm_version) {
+        if a_llvm != b_llvm {
+            machine_differences.push(format!("LLVM: {} vs. {}", a_llvm, b_llvm));
+        }
+    }
+    machine_differences
+}
+
+pub fn handle_self_profile(
+    body: self_profile::Request,
+    data: &InputData,
+) -> ServerResult<self_profile::Response> {
+    let find_run = |bound: &Bound| -> ServerResult<(String, Run)> {
+        let (commit, cd) = util::find_commit(data, bound, false, Interpolate::No)?;
+        let benchmark = cd.benchmarks.get(&body.benchmark)
+            .ok_or_else(|| format!("no data for {} at {}", body.benchmark, commit.sha))?
+            .as_ref()
+            .map_err(|_| format!("{} failed at {}", body.benchmark, commit.sha))?;
+        let run = benchmark.runs.iter().find(|r| r.name() == body.run_name)
+            .ok_or_else(|| format!("no {} run for {} at {}",
+                body.run_name, body.benchmark, commit.sha))?;
+        Ok((commit.sha.clone(), run.clone()))
+    };
+    let (base_commit, base_run) = find_run(&body.base_commit)?;
+    let (new_commit, new_run) = find_run(&body.new_commit)?;
+
+    let mut categories: Vec<self_profile::CategoryDelta> = Vec::new();
+    for category in &base_run.self_profile.category_data {
+        categories.push(self_profile::CategoryDelta {
+            category: category.name.clone(),
+            base_time_ms: Some(category.time_ms),
+            new_time_ms: None,
+            base_query_count: Some(category.query_count),
+            new_query_count: None,
+            base_query_hits: Some(category.query_hits),
+            new_query_hits: None,
+        });
+    }
+    for category in &new_run.self_profile.category_data {
+        if let Some(delta) = categories.iter_mut().find(|d| d.category == category.name) {
+            delta.new_time_ms = Some(category.time_ms);
+            delta.new_query_count = Some(category.query_count);
+            delta.new_query_hits = Some(category.query_hits);
+            continue;
+        }
+        categories.push(self_profile::CategoryDelta {
+            category: category.name.clone(),
+            base_time_ms: None,
+            new_time_ms: Some(category.time_ms),
+            base_query_count: None,
+            new_query_count: Some(category.query_count),
+            base_query_hits: None,
+            new_query_hits: Some(category.query_hits),
+        });
+    }
+    categories.sort_by_key(|d| (-d.time_delta_ms().abs(), d.category.clone()));
+
+    let mut queries: Vec<self_profile::QueryDelta> = Vec::new();
+    for query in &base_run.self_profile.query_data {
+        queries.push(self_profile::QueryDelta {
+            query: query.label.clone(),
+            base_invocations: Some(query.invocations),
+            new_invocations: None,
+            base_cache_hits: Some(query.cache_hits),
+            new_cache_hits: None,
+            base_incremental_load_time_ms: Some(query.incremental_load_time_ms),
+            new_incremental_load_time_ms: None,
+        });
+    }
+    for query in &new_run.self_profile.query_data {
+        if let Some(delta) = queries.iter_mut().find(|d| d.query == query.label) {
+            delta.new_invocations = Some(query.invocations);
+            delta.new_cache_hits = Some(query.cache_hits);
+            delta.new_incremental_load_time_ms = Some(query.incremental_load_time_ms);
+            continue;
+        }
+        queries.push(self_profile::QueryDelta {
+            query: query.label.clone(),
+            base_invocations: None,
+            new_invocations: Some(query.invocations),
+            base_cache_hits: None,
+            new_cache_hits: Some(query.cache_hits),
+            base_incremental_load_time_ms: None,
+            new_incremental_load_time_ms: Some(query.incremental_load_time_ms),
+        });
+    }
+    queries.sort_by_key(|d| (-d.cache_misses_delta(), d.query.clone()));
+
+    Ok(self_profile::Response {
+        base_commit,
+        new_commit,
+        categories,
+        queries,
+    })
+}
+
+pub fn handle_bootstrap(body: bootstrap::Request, data: &InputData)
+                        -> ServerResult<bootstrap::Response> {
+    let range = util::data_range(&data, &body.start, &body.end, Interpolate::No)?;
+    let range = range.into_iter()
+        .filter(|(_, cd)| !cd.bootstrap.is_empty())
+        .collect::<Vec<_>>();
+
+    let mut by_step = HashMap::new();
+    for (idx, (_, cd)) in range.iter().enumerate() {
+        for (step, &duration) in &cd.bootstrap {
+            by_step.entry(step.clone())
+                .or_insert_with(|| vec![None; range.len()])[idx] = Some(duration);
+        }
+    }
+
+    Ok(bootstrap::Response {
+        commits: range.iter().map(|(commit, _)| commit.sha.clone()).collect(),
+        by_step,
+    })
+}
+
+pub fn handle_collection_times(body: collection_times::Request, data: &InputData)
+                               -> ServerResult<collection_times::Response> {
+    let range = util::data_range(&data, &body.start, &body.end, Interpolate::No)?;
+    let range = range.into_iter()
+        .filter_map(|(commit, cd)| {
+            let collection = cd.collection.as_ref()?;
+            collection.duration.map(|duration| (commit, duration, collection))
+        })
+        .collect::<Vec<_>>();
+
+    let mut by_benchmark = HashMap::new();
+    for (idx, &(_, _, collection)) in range.iter().enumerate() {
+        for (benchmark, &duration) in &collection.benchmark_durations {
+            by_benchmark.entry(benchmark.clone())
+                .or_insert_with(|| vec![None; range.len()])[idx] = Some(duration);
+        }
+    }
+
+    Ok(collection_times::Response {
+        commits: range.iter().map(|&(commit, _, _)| commit.sha.clone()).collect(),
+        totals: range.iter().map(|&(_, duration, _)| duration).collect(),
+        by_benchmark,
+    })
+}
+
+pub fn handle_artifact_sizes(body: artifact_sizes::Request, data: &InputData)
+                             -> ServerResult<artifact_sizes::Response> {
+    let mut artifacts = Vec::new();
+    let mut all_sizes = Vec::new();
+    for version in sorted_versions(data) {
+        let sizes = &data.artifact_data[&version].artifact_sizes;
+        if !sizes.is_empty() {
+            artifacts.push(version);
+            all_sizes.push(sizes);
+        }
+    }
+    let range = util::data_range(&data, &body.start, &body.end, Interpolate::No)?;
+    for &(commit, cd) in &range {
+        if !cd.artifact_sizes.is_empty() {
+            artifacts.push(commit.sha.clone());
+            all_sizes.push(&cd.artifact_sizes);
+        }
+    }
+
+    let mut by_component = HashMap::new();
+    for (idx, sizes) in all_sizes.iter().enumerate() {
+        for (component, &size) in sizes.iter() {
+            by_component.entry(component.clone())
+                .or_insert_with(|| vec![None; all_sizes.len()])[idx] = Some(size);
+        }
+    }
+
+    Ok(artifact_sizes::Response {
+        artifacts,
+        by_component,
+    })
+}
+
+pub fn handle_samples(
+    body: samples::Request,
+    data: &InputData,
+) -> ServerResult<samples::Response> {
+    let (commit, cd) = util::find_commit(data, &body.commit, false, Interpolate::No)?;
+    let benchmark = cd.benchmarks.get(&body.benchmark)
+        .ok_or_else(|| format!("no data for {} at {}", body.benchmark, commit.sha))?
+        .as_ref()
+        .map_err(|_| format!("{} failed at {}", body.benchmark, commit.sha))?;
+    let runs = benchmark.runs.iter()
+        .filter(|run| run.get_stat(&body.stat).is_some())
+        .map(|run| samples::RunSamples {
+            run_name: run.name(),
+            samples: run.samples.get(&body.stat).cloned().unwrap_or_default(),
+            summary: run.summary(&body.stat),
+        })
+        .collect();
+    Ok(samples::Response { commit: commit.sha.clone(), runs })
+}
+
+/// Compares the results of two artifacts run by run, classifying each change
+/// by whether it's more than the noise of its series (see `analysis`).
+pub fn handle_compare(body: compare::Request, data: &InputData) -> ServerResult<compare::Response> {
+    let a = util::find_artifact(data, &body.a, true)?;
+    let b = util::find_artifact(data, &body.b, false)?;
+    let machine_differences = differences(&a, &b);
+    let history = analysis::history(data, &a.commit.sha);
+
+    let mut changes = Vec::new();
+    for (name, result) in &a.benchmarks {
+        let (a_benchmark, b_benchmark) = match (result, b.benchmarks.get(name)) {
+            (&Ok(ref a), Some(&Ok(ref b))) => (a, b),
+            _ => continue,
+        };
+        for a_run in &a_benchmark.runs {
+            let b_run = match b_benchmark.runs.iter().find(|r| *r == a_run) {
+                Some(b_run) => b_run,
+                None => continue,
+            };
+            let values = (a_run.get_stat(&body.stat), b_run.get_stat(&body.stat));
+            let (a_value, b_value) = match values {
+                (Some(a), Some(b)) if a > 0.0 => (a, b),
+                _ => continue,
+            };
+            let percent = (b_value - a_value) / a_value * 100.0;
+            let threshold = analysis::threshold(&history, name, a_run, b_run, &body.stat);
+            let significance = if percent.abs() < threshold {
+                compare::Significance::Insignificant
+            } else if percent < 0.0 {
+                compare::Significance::Improvement
+            } else {
+                compare::Significance::Regression
+            };
+            changes.push(compare::Change {
+                benchmark: name.clone(),
+                run_name: a_run.name(),
+                a: a_value,
+                b: b_value,
+                percent,
+                threshold,
+                significance,
+            });
+        }
+    }
+    changes.sort_by(|x, y| {
+        y.percent.abs().partial_cmp(&x.percent.abs()).unwrap_or(Ordering::Equal)
+    });
+
+    let summary = summarize_changes(&changes);
+    Ok(compare::Response {
+        a: a.commit.sha.clone(),
+        b: b.commit.sha.clone(),
+        changes,
+        summary,
+        machine_differences,
+    })
+}
+
+/// Summarizes `changes`, which are sorted by size, largest first.
+fn summarize_changes(changes: &[compare::Change]) -> compare::Summary {
+    let largest = |significance: compare::Significance| {
+        changes.iter().find(|c| c.significance == significance).cloned()
+    };
+    let ratios = changes.iter()
+        .map(|c| c.b / c.a)
+        .filter(|&r| r > 0.0)
+        .collect::<Vec<_>>();
+    let geomean_ratio = if ratios.is_empty() {
+        1.0
+    } else {
+        (ratios.iter().map(|r| r.ln()).sum::<f64>() / ratios.len() as f64).exp()
+    };
+    compare::Summary {
+        runs: changes.len(),
+        improvements: changes.iter()
+            .filter(|c| c.significance == compare::Significance::Improvement)
+            .count(),
+        regressions: changes.iter()
+            .filter(|c| c.significance == compare::Significance::Regression)
+            .count(),
+        geomean_percent: (geomean_ratio - 1.0) * 100.0,
+        largest_improvement: largest(compare::Significance::Improvement),
+        largest_regression: largest(compare::Significance::Regression),
+    }
+}
+
+pub fn handle_runtime(body: runtime::Request, data: &InputData) -> ServerResult<runtime::Response> {
+    let base = data.runtime_data.get(&body.base_id)
+        .ok_or_else(|| format!("no runtime data for {}", body.base_id))?;
+    let new = data.runtime_data.get(&body.new_id)
+        .ok_or_else(|| format!("no runtime data for {}", body.new_id))?;
+
+    let get_stat = |data: &RuntimeData, benchmark: &str| {
+        data.benchmarks.get(benchmark)
+            .and_then(|b| b.as_ref().ok())
+            .and_then(|b| b.get_stat(&body.stat))
+    };
+    let names = base.benchmarks.keys().chain(new.benchmarks.keys()).collect::<BTreeSet<_>>();
+    let benchmarks = names.into_iter()
+        .map(|name| runtime::BenchmarkDelta {
+            benchmark: name.clone(),
+            base: get_stat(base, name.as_str()),
+            new: get_stat(new, name.as_str()),
+        })
+        .collect();
+
+    Ok(runtime::Response {
+        base_id: body.base_id.clone(),
+        new_id: body.new_id.clone(),
+        benchmarks,
     })
 }
 
@@ -598,28 +1339,180 @@ pub fn handle_github(request: github::Request, data: &InputData) -> ServerResult
                         commit_response.sha, commit_response.parents.len()))?;
                 return Ok(github::Response);
             }
+            // The parent (the merge base with master) is usually benchmarked
+            // already. Its results are then reused, which halves the time
+            // until those of the try commit can be compared.
+            let parent_sha = &commit_response.parents[0].sha;
+            let parent_reused = data.data(Interpolate::No).values()
+                .any(|cd| cd.commit.sha == *parent_sha && cd.is_complete());
             {
                 let mut persistent = data.persistent.lock();
                 if !persistent.try_commits.iter().any(|c| c.sha() == &commit_response.sha) {
                     persistent.try_commits.push(TryCommit {
                         sha: commit_response.sha.clone(),
-                        parent_sha: commit_response.parents[0].sha.clone(),
+                        parent_sha: parent_sha.clone(),
                         issue: request.issue.clone(),
+                        parent_reused,
                     });
                 }
                 persistent.write().expect("successful encode");
             }
+            let parent = if parent_reused {
+                format!("{} (reusing its existing results)", parent_sha)
+            } else {
+                parent_sha.clone()
+            };
             post_comment(&data.config, &request.issue,
                 &format!("Success: Queued {} with parent {}, [comparison URL]({}).",
-                    commit_response.sha, commit_response.parents[0].sha,
+                    commit_response.sha, parent,
                     format!("https://perf.rust-lang.org/compare.html?start={}&end={}",
-                        commit_response.parents[0].sha, commit_response.sha)))?;
+                        parent_sha, commit_response.sha)))?;
         }
     }
 
     Ok(github::Response)
 }
 
+/// The most chunks an upload may consist of.
+const MAX_UPLOAD_CHUNKS: usize = 10_000;
+
+/// How long, in seconds, an incomplete upload is kept after its last chunk
+/// came in.
+const UPLOAD_TIMEOUT: u64 = 60 * 60;
+
+lazy_static! {
+    /// Held while an upload is written to the data repo and pushed, so that
+    /// uploads don't race each other in the repo, but chunks can still come
+    /// in meanwhile.
+    static ref STORE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Whether `name` can safely be part of a filename in the data repo.
+fn is_safe_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// Compares `token` to the `expected` one, in constant time.
+fn token_matches(token: &str, expected: &str) -> bool {
+    constant_time::verify_slices_are_equal(token.as_bytes(), expected.as_bytes()).is_ok()
+}
+
+/// Stores the chunk of results uploaded by `collector`, and once all of the
+/// chunks are in, stores the results in the database, or else commits them to
+/// the data repo. The site reloads the data when the push is reported back
+/// to it.
+pub fn handle_upload(
+    collector: &str,
+    body: upload::Request,
+    data: &InputData,
+) -> ServerResult<upload::Response> {
+    if body.index >= body.count || body.count > MAX_UPLOAD_CHUNKS {
+        return Err(format!("invalid chunk {} of {}", body.index, body.count));
+    }
+    let json = {
+        let mut uploads = data.uploads.lock();
+        let now = Instant::now();
+        uploads.retain(|&(ref collector, ref id), upload| {
+            let expired = now.duration_since(upload.updated) > Duration::from_secs(UPLOAD_TIMEOUT);
+            if expired {
+                warn!("dropping the incomplete upload {} from {}", id, collector);
+            }
+            !expired
+        });
+        let key = (collector.to_string(), body.id.clone());
+        let missing = {
+            let upload = uploads.entry(key.clone()).or_insert_with(|| PartialUpload {
+                chunks: vec![None; body.count],
+                updated: now,
+            });
+            if upload.chunks.len() != body.count {
+                return Err(format!("upload {} has {} chunks, not {}",
+                                   body.id, upload.chunks.len(), body.count));
+            }
+            upload.chunks[body.index] = Some(body.data);
+            upload.updated = now;
+            upload.chunks.iter().enumerate()
+                .filter(|&(_, chunk)| chunk.is_none())
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>()
+        };
+        if !missing.is_empty() {
+            return Ok(upload::Response { missing });
+        }
+
+        uploads.remove(&key).unwrap().chunks.into_iter()
+            .map(|chunk| chunk.unwrap())
+            .collect::<String>()
+    };
+    // The upload is dropped on failure, so the collector starts over.
+    store_upload(collector, &json, data).map_err(|e| {
+        error!("failed to store upload {} from {}: {:?}", body.id, collector, e);
+        format!("failed to store the results: {}", e)
+    })?;
+    Ok(upload::Response { missing: Vec::new() })
+}
+
+fn store_upload(collector: &str, json: &str, input: &InputData) -> Result<(), Error> {
+    let upload = serde_json::from_str::<upload::Data>(json)?;
+    if let upload::Data::Commit(ref data) = upload {
+        if let Some(shard) = data.collection.as_ref().and_then(|c| c.shard.as_ref()) {
+            if shard.collector != collector {
+                bail!("{} can't upload the results of {}", collector, shard.collector);
+            }
+        }
+    }
+    if let Some(ref db) = input.db {
+        let mut results = Results::default();
+        match upload {
+            upload::Data::Commit(data) => results.commits.push(data),
+            upload::Data::Artifact(data) => results.artifacts.push(data),
+        }
+        info!("storing the results uploaded by {} in the database", collector);
+        return db.conn.lock().store(&results);
+    }
+
+    let (filename, contents, message) = match upload {
+        upload::Data::Commit(data) => {
+            if !is_safe_name(&data.commit.sha) || !is_safe_name(&data.triple) {
+                bail!("invalid commit {} or triple {}", data.commit.sha, data.triple);
+            }
+            let sharded = data.collection.as_ref().map_or(false, |c| c.shard.is_some());
+            let flavor = if data.flavor.is_plain() {
+                String::new()
+            } else {
+                format!("-{}", data.flavor.name())
+            };
+            // Like the collectors themselves, the results of each shard are
+            // kept apart.
+            let filename = if sharded {
+                format!("commit-{}-{}{}-{}.json", data.commit.sha, data.triple, flavor, collector)
+            } else {
+                format!("commit-{}-{}{}.json", data.commit.sha, data.triple, flavor)
+            };
+            let message = format!("{} - success (uploaded by {})", data.commit.sha, collector);
+            (filename, serde_json::to_string(&data)?, message)
+        }
+        upload::Data::Artifact(data) => {
+            if !is_safe_name(&data.id) {
+                bail!("invalid artifact id {}", data.id);
+            }
+            let message = format!("{} - success (uploaded by {})", data.id, collector);
+            (format!("artifact-{}.json", data.id), serde_json::to_string(&data)?, message)
+        }
+    };
+    if !is_safe_name(collector) {
+        bail!("invalid collector name {}", collector);
+    }
+    let repo_path = get_repo_path()?;
+    let path = format!("times/{}", filename);
+    let _lock = STORE_LOCK.lock();
+    info!("storing {} uploaded by {}", path, collector);
+    fs::write(Path::new(&repo_path).join(&path), contents)?;
+    git::commit_and_push(&repo_path, &[&path], &message)?;
+    Ok(())
+}
+
 pub fn handle_collected(body: collected::Request, data: &InputData) -> ServerResult<collected::Response> {
     let mut persistent = data.persistent.lock();
     {
@@ -677,6 +1570,82 @@ struct Server {
     data: Arc<RwLock<InputData>>,
     pool: CpuPool,
     updating: Arc<AtomicBool>,
+    /// Whether results were stored in the database since the data was last
+    /// reloaded.
+    reload_pending: Arc<AtomicBool>,
+}
+
+/// Loads the data again: with the results stored in the database since, if
+/// there is one, from a new snapshot of it, if that's served, or else from the
+/// data repo, which is updated first.
+fn reload_data(rwlock: &RwLock<InputData>) -> Result<(), Error> {
+    let new_data = match util::get_database_url() {
+        // The snapshot that's served is kept if the database can't be read.
+        Some(url) => match Snapshot::from_env(&url) {
+            Some(snapshot) => {
+                info!("refreshing the snapshot of the database...");
+                InputData::from_snapshot(snapshot.refresh()?)?
+            }
+            // Only the results stored since the last update are read.
+            None => match rwlock.read().update()? {
+                Some(new_data) => new_data,
+                None => {
+                    debug!("no new results in the database");
+                    return Ok(());
+                }
+            },
+        },
+        None => {
+            let repo_path = get_repo_path()?;
+
+            git::update_repo(&repo_path)?;
+
+            info!("updating from filesystem...");
+            InputData::from_fs(&repo_path)?
+        }
+    };
+    debug!("last date = {:?}", new_data.last_date);
+
+    // Retrieve the stored InputData from the request.
+    let mut data = rwlock.write();
+
+    // Uploads that are under way carry on with the new data.
+    *new_data.uploads.lock() = mem::replace(&mut *data.uploads.lock(), HashMap::new());
+
+    // Write the new data back into the request
+    *data = new_data;
+    Ok(())
+}
+
+/// Reloads the data in the background, after results were stored in the
+/// database. Results stored while a reload is under way are picked up by
+/// another one right after.
+fn spawn_reload(
+    pool: &CpuPool,
+    rwlock: Arc<RwLock<InputData>>,
+    updating: Arc<AtomicBool>,
+    pending: Arc<AtomicBool>,
+) {
+    pending.store(true, AtomicOrdering::SeqCst);
+    if updating.compare_and_swap(false, true, AtomicOrdering::SeqCst) {
+        return;
+    }
+    pool.spawn_fn(move || {
+        loop {
+            while pending.swap(false, AtomicOrdering::SeqCst) {
+                if let Err(e) = reload_data(&rwlock) {
+                    error!("failed to reload the data: {:?}", e);
+                }
+            }
+            updating.store(false, AtomicOrdering::SeqCst);
+            // Results may have been stored between the last check and now.
+            if !pending.load(AtomicOrdering::SeqCst) ||
+                updating.compare_and_swap(false, true, AtomicOrdering::SeqCst) {
+                break;
+            }
+        }
+        Ok::<(), ()>(())
+    }).forget();
 }
 
 macro_rules! check_http_method {
@@ -722,7 +1691,7 @@ impl Server {
     fn check_auth(&self, req: &Request) -> bool {
         if let Some(auth) = req.headers().get::<Authorization<Bearer>>() {
             let data = self.data.read();
-            if auth.0.token == *data.config.keys.secret.as_ref().unwrap() {
+            if token_matches(&auth.0.token, data.config.keys.secret.as_ref().unwrap()) {
                 return true;
             }
         }
@@ -760,6 +1729,36 @@ impl Server {
         self.handle_post(req, handler)
     }
 
+    /// Handles an upload, from a collector that authenticates with the token
+    /// it was given in the site config.
+    fn handle_upload_post(&self, req: Request) -> <Server as Service>::Future {
+        let collector = req.headers().get::<Authorization<Bearer>>().and_then(|auth| {
+            let data = self.data.read();
+            data.config.keys.collectors.iter()
+                .find(|&(_, token)| token_matches(&auth.0.token, token))
+                .map(|(name, _)| name.clone())
+        });
+        match collector {
+            Some(collector) => {
+                let pool = self.pool.clone();
+                let rwlock = self.data.clone();
+                let updating = self.updating.clone();
+                let pending = self.reload_pending.clone();
+                self.handle_post(req, move |body, data| {
+                    let response = handle_upload(&collector, body, data)?;
+                    // Results stored in the database aren't pushed anywhere,
+                    // so no push hook reloads the data.
+                    if response.missing.is_empty() && data.db.is_some() {
+                        spawn_reload(&pool, rwlock, updating, pending);
+                    }
+                    Ok(response)
+                })
+            }
+            None => Box::new(futures::future::ok(
+                Response::new().with_status(StatusCode::Unauthorized))),
+        }
+    }
+
     fn handle_post<'de, F, D, S>(&self, req: Request, handler: F) -> <Server as Service>::Future
     where
         F: FnOnce(D, &InputData) -> ServerResult<S> + Send + 'static,
@@ -872,19 +1871,7 @@ impl Server {
         let rwlock = self.data.clone();
         let updating = self.updating.clone();
         let response = self.pool.spawn_fn(move || -> Result<serde_json::Value, Error> {
-            let repo_path = get_repo_path()?;
-
-            git::update_repo(&repo_path)?;
-
-            info!("updating from filesystem...");
-            let new_data = InputData::from_fs(&repo_path)?;
-            debug!("last date = {:?}", new_data.last_date);
-
-            // Retrieve the stored InputData from the request.
-            let mut data = rwlock.write();
-
-            // Write the new data back into the request
-            *data = new_data;
+            reload_data(&rwlock)?;
 
             updating.store(false, AtomicOrdering::Release);
 
@@ -949,14 +1936,40 @@ impl Service for Server {
             }));
         }
 
+        if WRITE_PATHS.contains(&req.path()) && self.data.read().read_only {
+            return Box::new(futures::future::ok(
+                Response::new()
+                    .with_header(ContentType(mime::TEXT_PLAIN_UTF_8))
+                    .with_status(StatusCode::ServiceUnavailable)
+                    .with_body("this site serves a read-only snapshot of the results"),
+            ));
+        }
+
         match req.path() {
             "/perf/info" => self.handle_get(&req, handle_info),
             "/perf/dashboard" => self.handle_get(&req, handle_dashboard),
             "/perf/graph" => self.handle_post(req, handle_graph),
+            "/perf/graphs" => self.handle_post(req, handle_graphs),
             "/perf/get" => self.handle_post(req, handle_days),
+            "/perf/compare" => self.handle_post(req, handle_compare),
             "/perf/nll_dashboard" => self.handle_post(req, handle_nll_dashboard),
+            "/perf/self_profile" => self.handle_post(req, handle_self_profile),
+            "/perf/samples" => self.handle_post(req, handle_samples),
+            "/perf/runtime" => self.handle_post(req, handle_runtime),
+            "/perf/flavors" => self.handle_post(req, handle_flavors),
+            "/perf/flavor_summary" => self.handle_post(req, handle_flavor_summary),
+            "/perf/flavor_history" => self.handle_post(req, handle_flavor_history),
+            "/perf/bootstrap" => self.handle_post(req, handle_bootstrap),
+            "/perf/artifact_sizes" => self.handle_post(req, handle_artifact_sizes),
+            "/perf/collection_times" => self.handle_post(req, handle_collection_times),
             "/perf/status_page" => self.handle_get(&req, handle_status_page),
-            "/perf/next_commit" => self.handle_get(&req, handle_next_commit),
+            "/perf/next_commit" => self.handle_get_req(&req, |req, data| {
+                let query = req.query().unwrap_or_default().as_bytes();
+                let collector = form_urlencoded::parse(query)
+                    .find(|&(ref key, _)| *key == "collector")
+                    .map(|(_, value)| value.into_owned());
+                handle_next_commit(collector.as_ref().map(String::as_str), data)
+            }),
             "/perf/pr_commit" => self.handle_get_req(&req, |req, _data| {
                 let res = req.query()
                     .unwrap_or_default()
@@ -978,6 +1991,7 @@ impl Service for Server {
             }),
             "/perf/onpush" => self.handle_push(req),
             "/perf/collected" => self.handle_auth_post(req, handle_collected),
+            "/perf/upload" => self.handle_upload_post(req),
             "/perf/github-hook" => self.handle_github_auth_post(req, handle_github),
             _ => Box::new(futures::future::ok(
                 Response::new()
@@ -1007,7 +2021,21 @@ pub fn start(data: InputData, port: u16) {
         data: Arc::new(RwLock::new(data)),
         pool: CpuPool::new_num_cpus(),
         updating: Arc::new(AtomicBool::new(false)),
+        reload_pending: Arc::new(AtomicBool::new(false)),
+    });
+    // The results stored in the database by others (e.g. collectors with
+    // `--db`) are picked up by polling it.
+    let interval = util::get_database_url().map(|url| {
+        Snapshot::from_env(&url).map_or_else(index::poll_interval, |s| s.interval())
     });
+    if let Some(interval) = interval {
+        let server = server.clone();
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            spawn_reload(&server.pool, server.data.clone(), server.updating.clone(),
+                         server.reload_pending.clone());
+        });
+    }
     let mut server_address: SocketAddr = "0.0.0.0:2346".parse().unwrap();
     server_address.set_port(port);
     let server = Http::new().bind(&server_address, move || Ok(server.clone()));
@@ -0,0 +1,88 @@
+// Copyright 2016 The rustc-perf Project Developers. See the COPYRIGHT
+// file at the top-level directory.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The read-only mode of the site, for a public instance that keeps serving
+//! while the primary database is being migrated or is busy storing uploads.
+//! The site then reads the primary (or a replica of it) every so often, and
+//! copies its results into a SQLite snapshot, which it serves from until the
+//! next copy succeeds, and which it starts from if the primary can't be read
+//! at the time.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use failure::{Error, ResultExt};
+
+use collector::db::{self, Results};
+
+/// How often the snapshot is refreshed by default, in seconds.
+const DEFAULT_INTERVAL: u64 = 10 * 60;
+
+pub struct Snapshot {
+    /// The database the results are copied from.
+    url: String,
+    /// The SQLite file the results are copied into.
+    path: PathBuf,
+}
+
+impl Snapshot {
+    /// The snapshot given by `$SNAPSHOT_PATH` of the database at `url`, if the
+    /// site is to serve one.
+    pub fn from_env(url: &str) -> Option<Snapshot> {
+        let path = env::var_os("SNAPSHOT_PATH")?;
+        Some(Snapshot { url: url.to_string(), path: PathBuf::from(path) })
+    }
+
+    /// How often the snapshot is refreshed, `$SNAPSHOT_INTERVAL` seconds.
+    pub fn interval(&self) -> Duration {
+        let secs = env::var("SNAPSHOT_INTERVAL").ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(DEFAULT_INTERVAL);
+        Duration::from_secs(secs)
+    }
+
+    /// Copies the results in the database into a new snapshot, which replaces
+    /// the previous one once it's complete, and returns them.
+    pub fn refresh(&self) -> Result<Results, Error> {
+        let results = db::open_read_only(&self.url)
+            .and_then(|mut db| db.load())
+            .context("failed to read the database")?;
+        let partial = self.path.with_extension("partial");
+        if partial.exists() {
+            fs::remove_file(&partial)
+                .with_context(|_| format!("failed to remove {:?}", partial))?;
+        }
+        db::open(&sqlite_url(&partial))?.store(&results)?;
+        fs::rename(&partial, &self.path)
+            .with_context(|_| format!("failed to replace {:?}", self.path))?;
+        info!("refreshed the snapshot {:?}", self.path);
+        Ok(results)
+    }
+
+    /// The results in a new snapshot, or if the database can't be read, in
+    /// the previous one.
+    pub fn load(&self) -> Result<Results, Error> {
+        match self.refresh() {
+            Ok(results) => Ok(results),
+            Err(e) => {
+                if !self.path.exists() {
+                    return Err(e);
+                }
+                warn!("serving the previous snapshot {:?}: {:?}", self.path, e);
+                db::open_read_only(&sqlite_url(&self.path))?.load()
+            }
+        }
+    }
+}
+
+fn sqlite_url(path: &Path) -> String {
+    format!("sqlite://{}", path.display())
+}
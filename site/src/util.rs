@@ -8,12 +8,14 @@
 // except according to those terms.
 
 use std::env;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 
-use load::{Commit, CommitData, InputData};
-use collector::Bound;
+use load::{ArtifactData, Commit, CommitData, InputData, PRIMARY_TRIPLE};
+use collector::{Bound, Date, Flavor};
 use failure::Error;
 
-use chrono::Duration;
+use chrono::{Duration, Utc};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Interpolate {
@@ -27,7 +29,8 @@ pub fn find_commit<'a>(
     left: bool,
     interpolate: Interpolate,
 ) -> Result<(&'a Commit, &'a CommitData), String> {
-    let last_month = data.last_date.0.naive_utc().date() - Duration::days(30);
+    // Without a last date there are no commits to find either.
+    let last_month = data.last_date.map(|date| date.0.naive_utc().date() - Duration::days(30));
     for (commit, cd) in data.data(interpolate) {
         let found = match *idx {
             Bound::Commit(ref sha) => commit.sha == *sha,
@@ -40,9 +43,12 @@ pub fn find_commit<'a>(
                     commit.date.0.naive_utc().date() == date.succ()
                 }
             }
+            Bound::Artifact(_) => false,
             Bound::None => {
                 if left {
-                    last_month <= commit.date.0.naive_utc().date()
+                    last_month.map_or(false, |last_month| {
+                        last_month <= commit.date.0.naive_utc().date()
+                    })
                 } else {
                     // all the data
                     false
@@ -67,6 +73,78 @@ pub fn find_commit<'a>(
     ))
 }
 
+/// Like `find_commit`, but also finds the artifacts that aren't commits, e.g.
+/// releases, by their id, so that any two artifacts can be compared.
+pub fn find_artifact<'a>(
+    data: &'a InputData,
+    idx: &Bound,
+    left: bool,
+) -> Result<Cow<'a, CommitData>, String> {
+    match *idx {
+        Bound::Artifact(ref id) => {
+            let artifact = data.artifact_data.get(id)
+                .ok_or_else(|| format!("no results for artifact {}", id))?;
+            Ok(Cow::Owned(artifact_commit_data(data, artifact)))
+        }
+        _ => find_commit(data, idx, left, Interpolate::No).map(|(_, cd)| Cow::Borrowed(cd)),
+    }
+}
+
+/// Like `find_commit`, but among the plain results collected on `triple`,
+/// which isn't the primary one. Only commits are benchmarked there, so
+/// artifacts aren't found.
+pub fn find_triple_commit<'a>(
+    data: &'a InputData,
+    triple: &str,
+    idx: &Bound,
+    left: bool,
+) -> Result<&'a CommitData, String> {
+    let mut commits = data.triple_data.iter()
+        .filter(|&(&(_, ref t, flavor), _)| t == triple && flavor == Flavor::Plain)
+        .map(|(_, cd)| cd)
+        .collect::<Vec<_>>();
+    commits.sort_by_key(|cd| cd.commit.date);
+    let found = match *idx {
+        Bound::Commit(ref sha) => commits.into_iter().find(|cd| cd.commit.sha == *sha),
+        Bound::Date(ref date) if left => {
+            commits.into_iter().find(|cd| cd.commit.date.0.naive_utc().date() >= *date)
+        }
+        Bound::Date(ref date) => {
+            commits.into_iter().rev().find(|cd| cd.commit.date.0.naive_utc().date() <= *date)
+        }
+        Bound::None if left => commits.into_iter().next(),
+        Bound::None => commits.into_iter().last(),
+        Bound::Artifact(_) => None,
+    };
+    found.ok_or_else(|| format!("no results on {} for bound {:?}", triple, idx))
+}
+
+/// The results of `artifact` as those of a commit, dated by its release, if
+/// it's one, or else by the last results, or now if there are none.
+fn artifact_commit_data(data: &InputData, artifact: &ArtifactData) -> CommitData {
+    let metadata = artifact.metadata();
+    CommitData {
+        commit: Commit {
+            sha: artifact.id.clone(),
+            date: metadata.as_ref().and_then(|m| m.date).or(data.last_date)
+                .unwrap_or_else(|| Date(Utc::now())),
+        },
+        benchmarks: artifact.benchmarks.clone(),
+        triple: PRIMARY_TRIPLE.to_string(),
+        flavor: Flavor::Plain,
+        llvm_version: artifact.llvm_version.clone(),
+        bootstrap: BTreeMap::new(),
+        environment: None,
+        collection: artifact.collection.clone(),
+        artifact_sizes: artifact.artifact_sizes.clone(),
+        collected_by: BTreeMap::new(),
+        shards: Vec::new(),
+        outliers: Vec::new(),
+        ices: artifact.ices.clone(),
+        metadata,
+    }
+}
+
 pub fn data_range<'a>(
     data: &'a InputData,
     a: &Bound,
@@ -101,4 +179,10 @@ pub fn get_repo_path() -> Result<String, Error> {
     }
 }
 
+/// The URL of the database the results are kept in (`$DATABASE_URL`), if
+/// they aren't kept in the data repo.
+pub fn get_database_url() -> Option<String> {
+    env::var("DATABASE_URL").ok()
+}
+
 pub use collector::{null_means_nan, round_float};
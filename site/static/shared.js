@@ -94,8 +94,9 @@ function make_settings(callback) {
                 list.innerHTML = phases_html;
                 list.value = 'instructions:u';
             }
-            document.getElementById("as-of").innerHTML =
-                "Updated as of: " + (new Date(data.as_of)).toLocaleString();
+            document.getElementById("as-of").innerHTML = data.as_of ?
+                "Updated as of: " + (new Date(data.as_of)).toLocaleString() :
+                "No results yet";
             callback();
         });
     }, function(err) {